@@ -0,0 +1,72 @@
+//! Criterion benchmarks of the sync hot paths on synthetic calendars.
+//!
+//! Run with `cargo bench --features test-util`, `BENCH_EVENTS` changes the calendar size.
+
+use std::hint::black_box;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use icalendar::Calendar;
+use nextcloud_ics_sync::{SyncOptions, calculate_diff, nextcloud, test_util};
+
+fn event_count() -> usize {
+    std::env::var("BENCH_EVENTS")
+        .ok()
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(10_000)
+}
+
+fn ics(c: &mut Criterion) {
+    let count = event_count();
+    let source_text = test_util::synthetic_calendar(count, 2, false).to_string();
+    let mut group = c.benchmark_group(format!("ics/{}", count));
+
+    group.bench_function("parse", |b| {
+        b.iter(|| {
+            black_box(&source_text)
+                .parse::<Calendar>()
+                .expect("synthetic calendar should parse")
+        })
+    });
+    group.bench_function("serialize", |b| {
+        b.iter(|| test_util::synthetic_calendar(count, 2, false).to_string())
+    });
+    group.bench_function("extract events", |b| {
+        b.iter(|| {
+            nextcloud::api::extract_events(test_util::synthetic_calendar(count, 2, false), true)
+        })
+    });
+    group.finish();
+}
+
+fn diff(c: &mut Criterion) {
+    let count = event_count();
+    let source_text = test_util::synthetic_calendar(count, 2, false).to_string();
+    let nextcloud_text = test_util::synthetic_calendar(count, 1, true).to_string();
+    let source_events =
+        nextcloud::api::extract_events(source_text.parse().expect("should parse"), true);
+    let unchanged_events =
+        nextcloud::api::extract_events(source_text.parse().expect("should parse"), true);
+    let changed_events =
+        nextcloud::api::extract_events(nextcloud_text.parse().expect("should parse"), false);
+    let options = SyncOptions::default();
+    let mut group = c.benchmark_group(format!("diff/{}", count));
+
+    group.bench_function("all unchanged", |b| {
+        b.iter(|| calculate_diff(&source_events, &unchanged_events, &options))
+    });
+    group.bench_function("all changed", |b| {
+        b.iter(|| calculate_diff(&source_events, &changed_events, &options))
+    });
+    group.bench_function("empty target", |b| {
+        b.iter(|| calculate_diff(&source_events, &Default::default(), &options))
+    });
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    // Each iteration handles the whole calendar, so fewer samples are enough
+    config = Criterion::default().sample_size(10);
+    targets = ics, diff
+}
+criterion_main!(benches);
@@ -0,0 +1,129 @@
+use std::{future::Future, sync::OnceLock};
+
+use anyhow::Result;
+use icalendar::Calendar;
+use tokio::runtime::Runtime;
+
+use crate::{
+    SyncOptions, SyncReport,
+    http::Client,
+    nextcloud::{
+        self,
+        api::{CalendarObject, TimeRange, Transfer},
+    },
+    source::CalendarSource,
+};
+
+/// Runs the blocking versions of the main API, for programs that don't use Tokio.
+///
+/// Shared by all calls, so the connections of a [`Client`] are reused. The functions of this
+/// module must not be called from within an async runtime.
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    RUNTIME
+        .get_or_init(|| {
+            tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .thread_name("nextcloud-ics-sync")
+                .build()
+                .expect("Failed to start the runtime of the blocking API")
+        })
+        .block_on(future)
+}
+
+/// See [`crate::sync_calendar`].
+pub fn sync_calendar(
+    client: &Client,
+    nextcloud_username: &str,
+    nextcloud_password: &str,
+    nextcloud_calendar_url: &str,
+    source_calendar: Calendar,
+    nextcloud_objects: Vec<CalendarObject>,
+    options: &SyncOptions,
+) -> Result<SyncReport> {
+    block_on(crate::sync_calendar(
+        client,
+        nextcloud_username,
+        nextcloud_password,
+        nextcloud_calendar_url,
+        source_calendar,
+        nextcloud_objects,
+        options,
+    ))
+}
+
+/// See [`crate::delete_synced_events`].
+pub fn delete_synced_events(
+    client: &Client,
+    nextcloud_objects: Vec<CalendarObject>,
+    nextcloud_calendar_url: &str,
+    username: &str,
+    password: &str,
+) -> Result<()> {
+    block_on(crate::delete_synced_events(
+        client,
+        nextcloud_objects,
+        nextcloud_calendar_url,
+        username,
+        password,
+    ))
+}
+
+/// See [`crate::migrate_synced_events`].
+pub fn migrate_synced_events(
+    client: &Client,
+    nextcloud_objects: Vec<CalendarObject>,
+    nextcloud_calendar_url: &str,
+    destination_calendar_url: &str,
+    username: &str,
+    password: &str,
+    transfer: Transfer,
+) -> Result<usize> {
+    block_on(crate::migrate_synced_events(
+        client,
+        nextcloud_objects,
+        nextcloud_calendar_url,
+        destination_calendar_url,
+        username,
+        password,
+        transfer,
+    ))
+}
+
+/// See [`nextcloud::api::get_calendar_ids`].
+pub fn get_calendar_ids(
+    client: &Client,
+    nextcloud_url: &str,
+    username: &str,
+    password: &str,
+) -> Result<Vec<String>> {
+    block_on(nextcloud::api::get_calendar_ids(
+        client,
+        nextcloud_url,
+        username,
+        password,
+    ))
+}
+
+/// See [`nextcloud::api::get_calendar_objects`].
+pub fn get_calendar_objects(
+    client: &Client,
+    calendar_url: &str,
+    username: &str,
+    password: &str,
+    time_range: TimeRange,
+) -> Result<Vec<CalendarObject>> {
+    block_on(nextcloud::api::get_calendar_objects(
+        client,
+        calendar_url,
+        username,
+        password,
+        time_range,
+    ))
+}
+
+/// See [`CalendarSource::fetch_calendar`].
+pub fn fetch_calendar(client: &Client, source: &dyn CalendarSource) -> Result<Calendar> {
+    block_on(source.fetch_calendar(client))
+}
@@ -0,0 +1,268 @@
+use std::{collections::HashMap, str::FromStr};
+
+use anyhow::{Result, bail};
+use icalendar::{Calendar, CalendarComponent, Component, Event, Property};
+use log::debug;
+
+/// Provider specific workarounds applied to source feeds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompatMode {
+    Outlook,
+    Google,
+}
+
+impl FromStr for CompatMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "outlook" | "exchange" => Ok(Self::Outlook),
+            "google" => Ok(Self::Google),
+            other => bail!("Unknown compatibility mode: {}", other),
+        }
+    }
+}
+
+/// Windows timezone IDs as used by Exchange/Outlook mapped to their IANA equivalents.
+pub const WINDOWS_TIMEZONES: &[(&str, &str)] = &[
+    ("Dateline Standard Time", "Etc/GMT+12"),
+    ("UTC-11", "Etc/GMT+11"),
+    ("Hawaiian Standard Time", "Pacific/Honolulu"),
+    ("Alaskan Standard Time", "America/Anchorage"),
+    ("Pacific Standard Time", "America/Los_Angeles"),
+    ("US Mountain Standard Time", "America/Phoenix"),
+    ("Mountain Standard Time", "America/Denver"),
+    ("Central America Standard Time", "America/Guatemala"),
+    ("Central Standard Time", "America/Chicago"),
+    ("Central Standard Time (Mexico)", "America/Mexico_City"),
+    ("Canada Central Standard Time", "America/Regina"),
+    ("SA Pacific Standard Time", "America/Bogota"),
+    ("Eastern Standard Time", "America/New_York"),
+    ("US Eastern Standard Time", "America/Indianapolis"),
+    ("Atlantic Standard Time", "America/Halifax"),
+    ("SA Western Standard Time", "America/La_Paz"),
+    ("Newfoundland Standard Time", "America/St_Johns"),
+    ("E. South America Standard Time", "America/Sao_Paulo"),
+    ("Argentina Standard Time", "America/Buenos_Aires"),
+    ("UTC", "Etc/UTC"),
+    ("GMT Standard Time", "Europe/London"),
+    ("Greenwich Standard Time", "Atlantic/Reykjavik"),
+    ("W. Europe Standard Time", "Europe/Berlin"),
+    ("Central Europe Standard Time", "Europe/Budapest"),
+    ("Romance Standard Time", "Europe/Paris"),
+    ("Central European Standard Time", "Europe/Warsaw"),
+    ("W. Central Africa Standard Time", "Africa/Lagos"),
+    ("GTB Standard Time", "Europe/Bucharest"),
+    ("E. Europe Standard Time", "Europe/Chisinau"),
+    ("FLE Standard Time", "Europe/Kiev"),
+    ("Israel Standard Time", "Asia/Jerusalem"),
+    ("South Africa Standard Time", "Africa/Johannesburg"),
+    ("Egypt Standard Time", "Africa/Cairo"),
+    ("Turkey Standard Time", "Europe/Istanbul"),
+    ("Russian Standard Time", "Europe/Moscow"),
+    ("Arab Standard Time", "Asia/Riyadh"),
+    ("Arabian Standard Time", "Asia/Dubai"),
+    ("Iran Standard Time", "Asia/Tehran"),
+    ("Pakistan Standard Time", "Asia/Karachi"),
+    ("India Standard Time", "Asia/Calcutta"),
+    ("Nepal Standard Time", "Asia/Katmandu"),
+    ("Bangladesh Standard Time", "Asia/Dhaka"),
+    ("SE Asia Standard Time", "Asia/Bangkok"),
+    ("China Standard Time", "Asia/Shanghai"),
+    ("Singapore Standard Time", "Asia/Singapore"),
+    ("Taipei Standard Time", "Asia/Taipei"),
+    ("Tokyo Standard Time", "Asia/Tokyo"),
+    ("Korea Standard Time", "Asia/Seoul"),
+    ("AUS Central Standard Time", "Australia/Darwin"),
+    ("E. Australia Standard Time", "Australia/Brisbane"),
+    ("AUS Eastern Standard Time", "Australia/Sydney"),
+    ("Tasmania Standard Time", "Australia/Hobart"),
+    ("New Zealand Standard Time", "Pacific/Auckland"),
+];
+
+pub fn windows_to_iana(tzid: &str) -> Option<&'static str> {
+    WINDOWS_TIMEZONES
+        .iter()
+        .find(|(windows, _)| *windows == tzid)
+        .map(|(_, iana)| *iana)
+}
+
+/// Applies all text level workarounds of the given modes before the feed is parsed.
+pub fn preprocess(ics_text: &str, modes: &[CompatMode]) -> String {
+    let mut text = ics_text.to_string();
+    for mode in modes {
+        text = match mode {
+            CompatMode::Outlook => outlook_quirks(&text),
+            CompatMode::Google => text,
+        };
+    }
+    text
+}
+
+/// Applies all workarounds of the given modes that operate on the parsed calendar.
+pub fn postprocess(calendar: Calendar, modes: &[CompatMode]) -> Calendar {
+    modes.iter().fold(calendar, |calendar, mode| match mode {
+        CompatMode::Google => google_quirks(calendar),
+        CompatMode::Outlook => calendar,
+    })
+}
+
+// Google keeps cancelled occurrences of recurring events as separate VEVENTs sharing the
+// UID of the series. Fold them into EXDATEs of the series so only one event per UID remains.
+fn google_quirks(mut calendar: Calendar) -> Calendar {
+    let mut cancelled: HashMap<String, Vec<Property>> = HashMap::new();
+
+    calendar.components.retain(|component| match component {
+        CalendarComponent::Event(event) if is_cancelled_instance(event) => {
+            if let (Some(uid), Some(recurrence_id)) =
+                (event.get_uid(), event.properties().get("RECURRENCE-ID"))
+            {
+                debug!("Dropping cancelled instance of UID: {}", uid);
+                let mut exdate = Property::new("EXDATE", recurrence_id.value());
+                for param in recurrence_id.params().values() {
+                    exdate.append_parameter(param.clone());
+                }
+                cancelled.entry(uid.to_string()).or_default().push(exdate);
+            }
+            false
+        }
+        _ => true,
+    });
+
+    for component in calendar.components.iter_mut() {
+        if let CalendarComponent::Event(event) = component {
+            if event.get_recurrence_id().is_none()
+                && let Some(exdates) = event.get_uid().and_then(|uid| cancelled.remove(uid))
+            {
+                for exdate in exdates {
+                    event.append_multi_property(exdate);
+                }
+            }
+            collapse_exdates(event);
+        }
+    }
+
+    calendar
+}
+
+fn is_cancelled_instance(event: &Event) -> bool {
+    event.get_recurrence_id().is_some() && event.property_value("STATUS") == Some("CANCELLED")
+}
+
+// Merge all EXDATE lines sharing the same parameters into one deduplicated line
+fn collapse_exdates(event: &mut Event) {
+    let Some(exdates) = event.multi_properties().get("EXDATE").cloned() else {
+        return;
+    };
+
+    let mut collapsed: Vec<(Property, Vec<String>)> = Vec::new();
+    for exdate in exdates {
+        let values = exdate.value().split(',').map(str::to_string);
+        match collapsed
+            .iter_mut()
+            .find(|(existing, _)| existing.params() == exdate.params())
+        {
+            Some((_, existing_values)) => existing_values.extend(values),
+            None => collapsed.push((exdate.clone(), values.collect())),
+        }
+    }
+
+    event.remove_multi_property("EXDATE");
+    for (template, mut values) in collapsed {
+        values.sort();
+        values.dedup();
+        let mut exdate = Property::new("EXDATE", values.join(","));
+        for param in template.params().values() {
+            exdate.append_parameter(param.clone());
+        }
+        event.append_multi_property(exdate);
+    }
+}
+
+// Outlook occasionally wraps long lines without the leading whitespace RFC 5545 requires
+// and names timezones by their Windows IDs.
+fn outlook_quirks(ics_text: &str) -> String {
+    let normalized = ics_text.replace("\r\r\n", "\r\n").replace("\r\n", "\n");
+    let mut lines: Vec<String> = Vec::new();
+
+    for line in normalized.split('\n') {
+        match lines.last_mut() {
+            Some(previous) if is_unfolded_continuation(line) => {
+                debug!("Joining unfolded continuation line: {}", line);
+                previous.push_str(line);
+            }
+            _ => lines.push(replace_windows_tzid(line)),
+        }
+    }
+
+    lines.join("\r\n")
+}
+
+fn is_unfolded_continuation(line: &str) -> bool {
+    if line.is_empty() || line.starts_with([' ', '\t']) {
+        return false;
+    }
+    // A content line starts with a property name followed by parameters or the value
+    let name_end = line.find([':', ';']).unwrap_or(line.len());
+    let name = &line[..name_end];
+    name_end == line.len()
+        || name.is_empty()
+        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+// Maps the TZID parameters of a content line and the value of a VTIMEZONE's TZID property,
+// leaving the values of other properties alone even if they contain `TZID=`
+fn replace_windows_tzid(line: &str) -> String {
+    let name_end = line.find([';', ':']).unwrap_or(line.len());
+    let name = &line[..name_end];
+    let mut result = name.to_string();
+    let mut rest = &line[name_end..];
+
+    while let Some(parameters) = rest.strip_prefix(';') {
+        let end = parameter_end(parameters);
+        let parameter = &parameters[..end];
+        result.push(';');
+        match parameter.split_once('=') {
+            Some((key, value)) if key.eq_ignore_ascii_case("TZID") => {
+                match map_windows_tzid(value.trim_matches('"')) {
+                    Some(iana) => result.push_str(&format!("{}={}", key, iana)),
+                    None => result.push_str(parameter),
+                }
+            }
+            _ => result.push_str(parameter),
+        }
+        rest = &parameters[end..];
+    }
+
+    let value_tzid = rest
+        .strip_prefix(':')
+        .filter(|_| name.eq_ignore_ascii_case("TZID"));
+    match value_tzid.and_then(map_windows_tzid) {
+        Some(iana) => {
+            result.push(':');
+            result.push_str(iana);
+        }
+        None => result.push_str(rest),
+    }
+    result
+}
+
+fn map_windows_tzid(tzid: &str) -> Option<&'static str> {
+    let iana = windows_to_iana(tzid)?;
+    debug!("Mapping Windows timezone '{}' to '{}'", tzid, iana);
+    Some(iana)
+}
+
+/// Length of the parameter at the start of `parameters`, which ends at the next `;` or `:`
+/// outside of quotes.
+fn parameter_end(parameters: &str) -> usize {
+    let mut quoted = false;
+    for (index, c) in parameters.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' | ':' if !quoted => return index,
+            _ => {}
+        }
+    }
+    parameters.len()
+}
@@ -1,15 +1,95 @@
-use anyhow::{Context, Result};
-use std::env;
+use anyhow::{Context, Result, bail};
+use chrono::TimeDelta;
+use log::warn;
+use reqwest::Url;
+use std::{
+    collections::HashMap,
+    env, fs,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    process::Stdio,
+    sync::Mutex,
+    time::Duration,
+};
+
+use crate::{
+    Deletions, UploadBatching,
+    compat::CompatMode,
+    digest::{DEFAULT_DIGEST_DAYS, DigestOptions},
+    freebusy::{AvailabilityOptions, DEFAULT_AVAILABILITY_DAYS},
+    geo::GeoOptions,
+    hooks::Hooks,
+    http::HttpConfig,
+    journal::DEFAULT_JOURNAL_KEEP,
+    lock,
+    nextcloud::api::CalendarMetadata,
+    notify::{
+        MatrixNotifier, NextcloudNotifier, Notifiers, Severity, TELEGRAM_API_URL, TalkNotifier,
+        TelegramNotifier,
+    },
+    oauth::OAuthConfig,
+    ownership::PropertyOwnership,
+    paths,
+    routing::{CalendarRoutes, Route},
+    shell,
+    source::{CsvMapping, JsonMapping, SourceCache, SourceType},
+    state::StateLocations,
+    tasks::TaskRouting,
+    timezone::{FloatingTimes, TimezoneOptions},
+    transform::{self, EventLimits, Transforms, XPropertyRules},
+    urls,
+    window::SyncWindow,
+};
 
 #[derive(Debug)]
 pub struct Config {
+    pub source_type: SourceType,
+    pub json_mapping: Option<JsonMapping>,
+    pub csv_mapping: Option<CsvMapping>,
     pub ics_url: String,
+    /// Mirrors of the source, tried in order when `ics_url` fails or is empty.
+    pub ics_fallback_urls: Vec<String>,
     pub ics_username: Option<String>,
     pub ics_password: Option<String>,
+    pub ics_bearer_token: Option<String>,
+    pub ics_headers: Vec<(String, String)>,
+    pub ics_oauth: Option<OAuthConfig>,
+    pub ics_compat: Vec<CompatMode>,
     pub nextcloud_url: String,
     pub nextcloud_calendar_url: String,
     pub nextcloud_username: String,
     pub nextcloud_password: String,
+    pub hooks: Hooks,
+    pub script_path: Option<PathBuf>,
+    pub sync_window: Option<SyncWindow>,
+    pub journal_dir: PathBuf,
+    /// Number of recent runs whose journals are kept.
+    pub journal_keep: usize,
+    pub deletions: Deletions,
+    pub archive_calendar_url: Option<String>,
+    pub transforms: Transforms,
+    pub geo: Option<GeoOptions>,
+    pub import_batching: UploadBatching,
+    pub relink_renamed_uids: bool,
+    pub dedupe_events: bool,
+    pub max_calendar_items: Option<usize>,
+    pub check_quota: bool,
+    pub verify_uploads: bool,
+    pub lock_stale_after: Duration,
+    pub timestamp_tolerance: Duration,
+    pub timezones: TimezoneOptions,
+    pub expand_recurrences: bool,
+    pub detect_series: bool,
+    pub task_routing: Option<TaskRouting>,
+    pub calendar_routes: CalendarRoutes,
+    pub additional_calendar_urls: Vec<String>,
+    pub notifiers: Notifiers,
+    pub ownership: PropertyOwnership,
+    pub sync_back_properties: Vec<String>,
+    pub sync_back_file: PathBuf,
+    pub calendar_metadata: CalendarMetadata,
+    pub source_cache: SourceCache,
+    pub use_source_cache_on_failure: bool,
     // pub calendar_id: String,
 }
 
@@ -19,30 +99,300 @@ impl Config {
         let nextcloud_url = load_nextcloud_url()?;
 
         let nextcloud_username = load_nextcloud_username()?;
-        let calendar_id = load_calendar_id()?;
+        let nextcloud_calendar_url =
+            load_nextcloud_calendar_url(&nextcloud_url, &nextcloud_username)?;
+
+        let source_type = load_source_type()?;
+        let ics_url = load_ics_url()?;
+        let sync_window = load_sync_window()?;
+        let deletions = load_deletions()?;
+        let archive_calendar_url = load_env_var("ARCHIVE_CALENDAR_ID")
+            .ok()
+            .map(|archive_id| calendar_url(&nextcloud_url, &nextcloud_username, &archive_id));
+        let expand_recurrences = load_env_var("EXPAND_RECURRENCES")
+            .map(|expand| parse_bool("EXPAND_RECURRENCES", &expand))
+            .unwrap_or(Ok(false))?;
+        if expand_recurrences && sync_window.is_none_or(|window| window.future_days.is_none()) {
+            bail!("EXPAND_RECURRENCES requires SYNC_WINDOW_FUTURE_DAYS to bound the series");
+        }
+        let task_routing = load_task_routing(&nextcloud_url, &nextcloud_username)?;
+        let calendar_routes = load_calendar_routes(&nextcloud_url, &nextcloud_username)?;
+        let additional_calendar_urls =
+            load_additional_calendar_urls(&nextcloud_url, &nextcloud_username)?;
+        if let Some(calendar_url) = additional_calendar_urls.iter().find(|calendar_url| {
+            **calendar_url == nextcloud_calendar_url
+                || calendar_routes
+                    .calendar_urls()
+                    .contains(&calendar_url.as_str())
+        }) {
+            bail!(
+                "ADDITIONAL_CALENDAR_IDS contains {}, which is already synced through \
+                 CALENDAR_ID or CALENDAR_ROUTES",
+                calendar_url
+            );
+        }
+        let nextcloud_password = load_nextcloud_password()?;
+        let notifiers = load_notifiers(&nextcloud_url, &nextcloud_username, &nextcloud_password)?;
+        let detect_series = load_env_var("DETECT_SERIES")
+            .map(|detect| parse_bool("DETECT_SERIES", &detect))
+            .unwrap_or(Ok(false))?;
+        if expand_recurrences && detect_series {
+            bail!("EXPAND_RECURRENCES and DETECT_SERIES undo each other, enable only one");
+        }
+        if deletions == Deletions::WindowOnly && sync_window.is_none() {
+            bail!(
+                "DELETIONS=window-only requires SYNC_WINDOW_PAST_DAYS or SYNC_WINDOW_FUTURE_DAYS"
+            );
+        }
 
         Ok(Self {
-            ics_url: load_ics_url()?,
+            source_type,
+            json_mapping: match source_type {
+                SourceType::JsonApi => Some(load_json_mapping()?),
+                _ => None,
+            },
+            csv_mapping: match source_type {
+                SourceType::Csv => Some(load_csv_mapping()?),
+                _ => None,
+            },
+            source_cache: SourceCache {
+                path: load_env_var("SOURCE_CACHE_PATH")
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|_| SourceCache::path_for(&ics_url)),
+                max_age: load_env_var("SOURCE_CACHE_MAX_AGE_SECS")
+                    .map(|secs| {
+                        secs.parse()
+                            .map(Duration::from_secs)
+                            .context("SOURCE_CACHE_MAX_AGE_SECS must be a number of seconds")
+                    })
+                    .unwrap_or(Ok(SourceCache::DEFAULT_MAX_AGE))?,
+            },
+            use_source_cache_on_failure: load_env_var("USE_SOURCE_CACHE_ON_FAILURE")
+                .map(|use_cache| parse_bool("USE_SOURCE_CACHE_ON_FAILURE", &use_cache))
+                .unwrap_or(Ok(false))?,
+            ics_url,
+            ics_fallback_urls: load_env_var("ICS_FALLBACK_URLS")
+                .map(|urls| {
+                    urls.split(',')
+                        .map(str::trim)
+                        .filter(|url| !url.is_empty())
+                        .map(urls::source_url)
+                        .collect()
+                })
+                .unwrap_or_default(),
             ics_username: load_ics_username().ok(),
             ics_password: load_ics_password().ok(),
+            ics_bearer_token: load_ics_bearer_token().ok(),
+            ics_headers: load_ics_headers(),
+            ics_oauth: load_ics_oauth()?,
+            ics_compat: load_ics_compat()?,
             nextcloud_url: nextcloud_url.clone(),
-            nextcloud_calendar_url: format!(
-                "{}/remote.php/dav/calendars/{}/{}/",
-                nextcloud_url, nextcloud_username, calendar_id
-            ),
-            nextcloud_username: nextcloud_username,
-            nextcloud_password: load_nextcloud_password()?,
+            nextcloud_calendar_url,
+            nextcloud_username,
+            nextcloud_password,
+            hooks: load_hooks(),
+            script_path: load_env_var("SCRIPT_PATH").ok().map(PathBuf::from),
+            sync_window,
+            journal_dir: load_journal_dir(),
+            journal_keep: load_env_var("SYNC_JOURNAL_KEEP")
+                .map(|keep| {
+                    keep.parse()
+                        .with_context(|| format!("Invalid SYNC_JOURNAL_KEEP: {}", keep))
+                })
+                .unwrap_or(Ok(DEFAULT_JOURNAL_KEEP))?,
+            deletions,
+            archive_calendar_url,
+            transforms: load_transforms()?,
+            geo: load_geo(),
+            import_batching: load_import_batching()?,
+            relink_renamed_uids: load_env_var("RELINK_RENAMED_UIDS")
+                .map(|relink| parse_bool("RELINK_RENAMED_UIDS", &relink))
+                .unwrap_or(Ok(false))?,
+            dedupe_events: load_env_var("DEDUPE_EVENTS")
+                .map(|dedupe| parse_bool("DEDUPE_EVENTS", &dedupe))
+                .unwrap_or(Ok(false))?,
+            max_calendar_items: load_env_var("MAX_CALENDAR_ITEMS")
+                .ok()
+                .map(|max| {
+                    max.parse()
+                        .with_context(|| format!("Invalid MAX_CALENDAR_ITEMS: {}", max))
+                })
+                .transpose()?,
+            timezones: load_timezones()?,
+            expand_recurrences,
+            detect_series,
+            task_routing,
+            calendar_routes,
+            additional_calendar_urls,
+            notifiers,
+            ownership: PropertyOwnership {
+                owned: load_property_names("SYNC_OWNED_PROPERTIES"),
+                // Synced back properties are kept as they are in existing copies
+                target_owned: load_property_names("TARGET_OWNED_PROPERTIES")
+                    .into_iter()
+                    .chain(load_property_names("SYNC_BACK_PROPERTIES"))
+                    .collect(),
+            },
+            sync_back_properties: load_property_names("SYNC_BACK_PROPERTIES"),
+            sync_back_file: load_sync_back_file(),
+            calendar_metadata: load_calendar_metadata(),
+            check_quota: load_env_var("CHECK_QUOTA")
+                .map(|check| parse_bool("CHECK_QUOTA", &check))
+                .unwrap_or(Ok(false))?,
+            lock_stale_after: load_lock_stale_after()?,
+            timestamp_tolerance: load_env_var("LAST_MODIFIED_TOLERANCE_SECS")
+                .map(|secs| {
+                    secs.parse()
+                        .map(Duration::from_secs)
+                        .context("LAST_MODIFIED_TOLERANCE_SECS must be a number of seconds")
+                })
+                .unwrap_or(Ok(Duration::ZERO))?,
+            verify_uploads: load_env_var("VERIFY_UPLOADS")
+                .map(|verify| parse_bool("VERIFY_UPLOADS", &verify))
+                .unwrap_or(Ok(false))?,
             // calendar_id: calendar_id,
         })
     }
 }
 
+pub fn calendar_url(nextcloud_url: &str, username: &str, calendar_id: &str) -> String {
+    format!(
+        "{}/remote.php/dav/calendars/{}/{}/",
+        nextcloud_url, username, calendar_id
+    )
+}
+
+/// Credentials, which can also be read from the file named by `<KEY>_FILE`, as used for Docker
+/// and Kubernetes secrets, or from the output of the command in `<KEY>_CMD`.
+const SECRET_KEYS: &[&str] = &[
+    "NEXTCLOUD_PASSWORD",
+    "ICS_PASSWORD",
+    "ICS_BEARER_TOKEN",
+    "ICS_OAUTH_CLIENT_SECRET",
+    "NOTIFY_MATRIX_ACCESS_TOKEN",
+    "NOTIFY_TELEGRAM_BOT_TOKEN",
+    "DIGEST_SMTP_URL",
+];
+
 fn load_env_var(env_var_key: &str) -> Result<String> {
-    env::var(env_var_key).with_context(|| format!("{} environment variable not set", env_var_key))
+    let value = env::var(env_var_key).ok();
+    if !SECRET_KEYS.contains(&env_var_key) {
+        return value.with_context(|| format!("{} environment variable not set", env_var_key));
+    }
+
+    let file_key = format!("{}_FILE", env_var_key);
+    let command_key = format!("{}_CMD", env_var_key);
+    let file_path = env::var(&file_key).ok();
+    let command = env::var(&command_key).ok();
+    let set: Vec<&str> = [
+        (env_var_key, value.is_some()),
+        (file_key.as_str(), file_path.is_some()),
+        (command_key.as_str(), command.is_some()),
+    ]
+    .into_iter()
+    .filter_map(|(key, is_set)| is_set.then_some(key))
+    .collect();
+    if set.len() > 1 {
+        // Optional settings ignore the error, so make sure it is seen
+        warn!("Only one of {} can be set", set.join(", "));
+        bail!("Only one of {} can be set", set.join(", "));
+    }
+
+    if let Some(value) = value {
+        return Ok(value);
+    }
+
+    if let Some(path) = file_path {
+        return match fs::read_to_string(&path) {
+            Ok(content) => Ok(content.trim_end_matches(['\r', '\n']).to_string()),
+            Err(e) => {
+                // Optional settings ignore the error, so make sure it is seen
+                warn!("Failed to read {} from {}: {}", file_key, path, e);
+                Err(e).with_context(|| format!("Failed to read {}. Path: {}", file_key, path))
+            }
+        };
+    }
+
+    let Some(command) = command else {
+        bail!("{} environment variable not set", env_var_key);
+    };
+    run_secret_command(&command).inspect_err(|e| warn!("{} failed: {:#}", command_key, e))
+}
+
+/// Output of the commands of `_CMD` variables, so a password manager asks only once.
+static SECRET_COMMANDS: Mutex<Option<HashMap<String, String>>> = Mutex::new(None);
+
+/// The first line of the output of `command`, e.g. `pass show nextcloud/app`.
+fn run_secret_command(command: &str) -> Result<String> {
+    let mut outputs = SECRET_COMMANDS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(output) = outputs.get_or_insert_default().get(command) {
+        return Ok(output.clone());
+    }
+
+    // The terminal stays connected, so the command can ask for a passphrase
+    let output = shell::command(command)
+        .as_std_mut()
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .with_context(|| format!("Failed to run `{}`", command))?;
+    if !output.status.success() {
+        bail!("`{}` exited with {}", command, output.status);
+    }
+    let secret = String::from_utf8(output.stdout)
+        .with_context(|| format!("`{}` printed invalid UTF-8", command))?
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    if secret.is_empty() {
+        bail!("`{}` printed nothing", command);
+    }
+
+    outputs
+        .get_or_insert_default()
+        .insert(command.to_string(), secret.clone());
+    Ok(secret)
+}
+
+pub fn load_source_type() -> Result<SourceType> {
+    load_env_var("SOURCE_TYPE")
+        .map(|source_type| source_type.parse())
+        .unwrap_or(Ok(SourceType::Ics))
+}
+
+pub fn load_json_mapping() -> Result<JsonMapping> {
+    Ok(JsonMapping {
+        events: load_env_var("JSON_EVENTS_PATH").unwrap_or_else(|_| ".[]".to_string()),
+        uid: load_env_var("JSON_UID_PATH").ok(),
+        summary: load_env_var("JSON_SUMMARY_PATH")?,
+        start: load_env_var("JSON_START_PATH")?,
+        end: load_env_var("JSON_END_PATH").ok(),
+        location: load_env_var("JSON_LOCATION_PATH").ok(),
+        description: load_env_var("JSON_DESCRIPTION_PATH").ok(),
+        last_modified: load_env_var("JSON_LAST_MODIFIED_PATH").ok(),
+    })
+}
+
+pub fn load_csv_mapping() -> Result<CsvMapping> {
+    Ok(CsvMapping {
+        summary: load_env_var("CSV_SUMMARY_COLUMN")?,
+        start: load_env_var("CSV_START_COLUMN")?,
+        end: load_env_var("CSV_END_COLUMN").ok(),
+        location: load_env_var("CSV_LOCATION_COLUMN").ok(),
+        description: load_env_var("CSV_DESCRIPTION_COLUMN").ok(),
+        timezone: load_env_var("CSV_TIMEZONE_COLUMN").ok(),
+        uid_template: load_env_var("CSV_UID_TEMPLATE").ok(),
+        date_format: load_env_var("CSV_DATE_FORMAT").ok(),
+        delimiter: load_env_var("CSV_DELIMITER")
+            .ok()
+            .and_then(|delimiter| delimiter.bytes().next())
+            .unwrap_or(b','),
+    })
 }
 
 pub fn load_ics_url() -> Result<String> {
-    load_env_var("ICS_URL")
+    load_env_var("ICS_URL").map(|url| urls::source_url(&url))
 }
 
 pub fn load_ics_username() -> Result<String> {
@@ -53,8 +403,114 @@ pub fn load_ics_password() -> Result<String> {
     load_env_var("ICS_PASSWORD")
 }
 
+pub fn load_ics_bearer_token() -> Result<String> {
+    load_env_var("ICS_BEARER_TOKEN")
+}
+
+// Collect `ICS_HEADER_<NAME>` variables, e.g. `ICS_HEADER_X_API_KEY` becomes `X-API-KEY`
+pub fn load_ics_headers() -> Vec<(String, String)> {
+    env::vars()
+        .filter_map(|(key, value)| {
+            key.strip_prefix("ICS_HEADER_")
+                .filter(|name| !name.is_empty())
+                .map(|name| (name.replace('_', "-"), value))
+        })
+        .collect()
+}
+
+pub fn load_journal_dir() -> PathBuf {
+    load_env_var("SYNC_JOURNAL_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| paths::state_dir().join("journal"))
+}
+
+/// The state locations, without requiring the rest of the configuration.
+pub fn load_state_locations() -> StateLocations {
+    StateLocations {
+        journal_dir: load_journal_dir(),
+        oauth_token: load_oauth_token_cache(),
+        sync_back: load_sync_back_file(),
+    }
+}
+
+fn load_sync_back_file() -> PathBuf {
+    load_env_var("SYNC_BACK_FILE")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| paths::state_dir().join("sync-back.ics"))
+}
+
+fn load_oauth_token_cache() -> PathBuf {
+    load_env_var("ICS_OAUTH_TOKEN_CACHE")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| paths::state_dir().join("oauth-token.json"))
+}
+
+// OAuth is only enabled when `ICS_OAUTH_TOKEN_URL` is set
+pub fn load_ics_oauth() -> Result<Option<OAuthConfig>> {
+    let Ok(token_url) = load_env_var("ICS_OAUTH_TOKEN_URL") else {
+        return Ok(None);
+    };
+
+    Ok(Some(OAuthConfig {
+        token_url,
+        auth_url: load_env_var("ICS_OAUTH_AUTH_URL").ok(),
+        client_id: load_env_var("ICS_OAUTH_CLIENT_ID")?,
+        client_secret: load_env_var("ICS_OAUTH_CLIENT_SECRET").ok(),
+        scope: load_env_var("ICS_OAUTH_SCOPE").ok(),
+        redirect_uri: load_env_var("ICS_OAUTH_REDIRECT_URI")
+            .unwrap_or_else(|_| "http://localhost".to_string()),
+        token_cache: load_oauth_token_cache(),
+    }))
+}
+
+pub fn load_ics_compat() -> Result<Vec<CompatMode>> {
+    load_env_var("ICS_COMPAT")
+        .map(|modes| {
+            modes
+                .split(',')
+                .filter(|mode| !mode.trim().is_empty())
+                .map(str::parse)
+                .collect()
+        })
+        .unwrap_or_else(|_| Ok(Vec::new()))
+}
+
+/// The target calendar, given by `NEXTCLOUD_CALENDAR_URL` or built from `CALENDAR_ID`.
+pub fn load_nextcloud_calendar_url(nextcloud_url: &str, username: &str) -> Result<String> {
+    let calendar_id = load_calendar_id().ok();
+    let Ok(url) = load_env_var("NEXTCLOUD_CALENDAR_URL") else {
+        let calendar_id = calendar_id
+            .context("CALENDAR_ID or NEXTCLOUD_CALENDAR_URL environment variable not set")?;
+        return Ok(calendar_url(nextcloud_url, username, &calendar_id));
+    };
+
+    let url = urls::calendar_collection_url(&url)?;
+    let origin = |url: &str| Url::parse(url).map(|url| url.origin());
+    // The Nextcloud credentials must not be sent anywhere else
+    if origin(&url)? != origin(nextcloud_url)? {
+        bail!(
+            "NEXTCLOUD_CALENDAR_URL {} is not on the Nextcloud instance {}",
+            url,
+            nextcloud_url
+        );
+    }
+    if let Some(calendar_id) = calendar_id {
+        let built_url = calendar_url(nextcloud_url, username, &calendar_id);
+        if built_url != url {
+            bail!(
+                "CALENDAR_ID {} ({}) and NEXTCLOUD_CALENDAR_URL {} are different calendars, set \
+                 only one of them",
+                calendar_id,
+                built_url,
+                url
+            );
+        }
+    }
+    Ok(url)
+}
+
 pub fn load_calendar_id() -> Result<String> {
-    load_env_var("CALENDAR_ID")
+    load_env_var("CALENDAR_ID").map(|id| urls::calendar_id(&id))
 }
 
 pub fn load_nextcloud_username() -> Result<String> {
@@ -65,6 +521,548 @@ pub fn load_nextcloud_password() -> Result<String> {
     load_env_var("NEXTCLOUD_PASSWORD")
 }
 
+/// The `NEXTCLOUD_PASSWORD_FILE`, if the password is read from it.
+pub fn load_nextcloud_password_file() -> Option<PathBuf> {
+    if env::var("NEXTCLOUD_PASSWORD").is_ok() {
+        return None;
+    }
+    env::var("NEXTCLOUD_PASSWORD_FILE").ok().map(PathBuf::from)
+}
+
 pub fn load_nextcloud_url() -> Result<String> {
-    load_env_var("NEXTCLOUD_URL")
+    urls::nextcloud_url(&load_env_var("NEXTCLOUD_URL")?)
+}
+
+pub fn load_hooks() -> Hooks {
+    Hooks {
+        pre_sync: load_env_var("HOOK_PRE_SYNC").ok(),
+        post_sync: load_env_var("HOOK_POST_SYNC").ok(),
+        on_event_upload: load_env_var("HOOK_ON_EVENT_UPLOAD").ok(),
+        on_event_delete: load_env_var("HOOK_ON_EVENT_DELETE").ok(),
+        translate_summary: load_env_var("HOOK_TRANSLATE_SUMMARY").ok(),
+    }
+}
+
+// The window is only enabled when at least one of its bounds is set
+pub fn load_sync_window() -> Result<Option<SyncWindow>> {
+    let load_days = |key: &str| -> Result<Option<u64>> {
+        load_env_var(key)
+            .ok()
+            .map(|days| {
+                days.parse()
+                    .with_context(|| format!("{} must be a number of days", key))
+            })
+            .transpose()
+    };
+
+    let window = SyncWindow {
+        past_days: load_days("SYNC_WINDOW_PAST_DAYS")?,
+        future_days: load_days("SYNC_WINDOW_FUTURE_DAYS")?,
+    };
+    Ok((window.past_days.is_some() || window.future_days.is_some()).then_some(window))
+}
+
+// Routing is only enabled when `TASK_PATTERNS` is set
+pub fn load_task_routing(nextcloud_url: &str, username: &str) -> Result<Option<TaskRouting>> {
+    let Ok(patterns) = load_env_var("TASK_PATTERNS") else {
+        return Ok(None);
+    };
+    let tasks_calendar_id = load_env_var("TASKS_CALENDAR_ID")
+        .context("TASK_PATTERNS requires TASKS_CALENDAR_ID, the ID of a tasks list")?;
+
+    Ok(Some(TaskRouting {
+        patterns: patterns
+            .split(',')
+            .map(str::trim)
+            .filter(|pattern| !pattern.is_empty())
+            .map(str::to_string)
+            .collect(),
+        tasks_calendar_url: calendar_url(nextcloud_url, username, &tasks_calendar_id),
+        mode: load_env_var("TASK_ROUTING")
+            .map(|mode| mode.parse())
+            .unwrap_or(Ok(Default::default()))?,
+    }))
+}
+
+pub fn load_notifiers(nextcloud_url: &str, username: &str, password: &str) -> Result<Notifiers> {
+    let mut notifiers = Notifiers::default();
+    if let Ok(recipient) = load_env_var("NOTIFY_NEXTCLOUD_USER") {
+        let notifier = NextcloudNotifier {
+            nextcloud_url: nextcloud_url.to_string(),
+            username: username.to_string(),
+            password: password.to_string(),
+            recipient,
+        };
+        notifiers.push(notifier, load_severities("NOTIFY_NEXTCLOUD_SEVERITIES")?);
+    }
+    if let Ok(room_token) = load_env_var("NOTIFY_TALK_ROOM") {
+        let notifier = TalkNotifier {
+            nextcloud_url: nextcloud_url.to_string(),
+            username: username.to_string(),
+            password: password.to_string(),
+            room_token,
+        };
+        notifiers.push(notifier, load_severities("NOTIFY_TALK_SEVERITIES")?);
+    }
+    if let Ok(room_id) = load_env_var("NOTIFY_MATRIX_ROOM") {
+        let notifier = MatrixNotifier {
+            homeserver_url: load_env_var("NOTIFY_MATRIX_HOMESERVER")
+                .context("NOTIFY_MATRIX_ROOM requires NOTIFY_MATRIX_HOMESERVER")?,
+            access_token: load_env_var("NOTIFY_MATRIX_ACCESS_TOKEN")
+                .context("NOTIFY_MATRIX_ROOM requires NOTIFY_MATRIX_ACCESS_TOKEN")?,
+            room_id,
+        };
+        notifiers.push(notifier, load_severities("NOTIFY_MATRIX_SEVERITIES")?);
+    }
+    if let Ok(chat_id) = load_env_var("NOTIFY_TELEGRAM_CHAT_ID") {
+        let notifier = TelegramNotifier {
+            api_url: load_env_var("NOTIFY_TELEGRAM_API_URL")
+                .unwrap_or_else(|_| TELEGRAM_API_URL.to_string()),
+            bot_token: load_env_var("NOTIFY_TELEGRAM_BOT_TOKEN")
+                .context("NOTIFY_TELEGRAM_CHAT_ID requires NOTIFY_TELEGRAM_BOT_TOKEN")?,
+            chat_id,
+        };
+        notifiers.push(notifier, load_severities("NOTIFY_TELEGRAM_SEVERITIES")?);
+    }
+    Ok(notifiers)
+}
+
+// All severities unless the backend is limited, e.g. to `failure`
+fn load_severities(key: &str) -> Result<Vec<Severity>> {
+    let Ok(severities) = load_env_var(key) else {
+        return Ok(Severity::ALL.to_vec());
+    };
+    severities
+        .split(',')
+        .filter(|severity| !severity.trim().is_empty())
+        .map(|severity| severity.parse().with_context(|| format!("Invalid {}", key)))
+        .collect()
+}
+
+pub fn load_digest() -> Result<DigestOptions> {
+    Ok(DigestOptions {
+        days: load_env_var("DIGEST_DAYS")
+            .map(|days| days.parse().context("DIGEST_DAYS must be a number of days"))
+            .unwrap_or(Ok(DEFAULT_DIGEST_DAYS))?,
+        smtp_url: load_env_var("DIGEST_SMTP_URL").ok(),
+        from: load_env_var("DIGEST_FROM").ok(),
+        to: load_env_var("DIGEST_TO")
+            .map(|to| {
+                to.split(',')
+                    .map(str::trim)
+                    .filter(|address| !address.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default(),
+    })
+}
+
+pub fn load_lock_stale_after() -> Result<Duration> {
+    load_env_var("SYNC_LOCK_STALE_SECS")
+        .map(|secs| {
+            secs.parse()
+                .map(Duration::from_secs)
+                .context("SYNC_LOCK_STALE_SECS must be a number of seconds")
+        })
+        .unwrap_or(Ok(lock::DEFAULT_STALE_AFTER))
+}
+
+pub fn load_availability(nextcloud_url: &str, username: &str) -> Result<AvailabilityOptions> {
+    let attendees: Vec<String> = load_env_var("AVAILABILITY_ATTENDEES")
+        .context("AVAILABILITY_ATTENDEES environment variable not set")?
+        .split(',')
+        .map(str::trim)
+        .filter(|address| !address.is_empty())
+        .map(str::to_string)
+        .collect();
+    if attendees.is_empty() {
+        bail!("AVAILABILITY_ATTENDEES contains no email addresses");
+    }
+    let calendar_id = load_env_var("AVAILABILITY_CALENDAR_ID")
+        .context("AVAILABILITY_CALENDAR_ID environment variable not set")?;
+
+    Ok(AvailabilityOptions {
+        attendees,
+        organizer: load_env_var("AVAILABILITY_ORGANIZER")
+            .context("AVAILABILITY_ORGANIZER environment variable not set")?,
+        calendar_url: calendar_url(nextcloud_url, username, &urls::calendar_id(&calendar_id)),
+        days: load_env_var("AVAILABILITY_DAYS")
+            .map(|days| {
+                days.parse()
+                    .context("AVAILABILITY_DAYS must be a number of days")
+            })
+            .unwrap_or(Ok(DEFAULT_AVAILABILITY_DAYS))?,
+        summary: load_env_var("AVAILABILITY_SUMMARY").unwrap_or_else(|_| "Busy".to_string()),
+    })
+}
+
+pub fn load_deletions() -> Result<Deletions> {
+    load_env_var("DELETIONS")
+        .map(|deletions| deletions.parse())
+        .unwrap_or(Ok(Deletions::default()))
+}
+
+pub fn load_transforms() -> Result<Transforms> {
+    Ok(Transforms {
+        scheduling: load_env_var("SCHEDULING_PROPERTIES")
+            .map(|policy| policy.parse())
+            .unwrap_or(Ok(Default::default()))?,
+        attachments: load_env_var("ATTACHMENTS")
+            .map(|policy| policy.parse())
+            .unwrap_or(Ok(Default::default()))?,
+        max_attachment_bytes: load_env_var("ATTACHMENT_MAX_BYTES")
+            .ok()
+            .map(|bytes| {
+                bytes
+                    .parse()
+                    .context("ATTACHMENT_MAX_BYTES must be a number of bytes")
+            })
+            .transpose()?,
+        transliteration: load_env_var("TRANSLITERATE")
+            .ok()
+            .map(|transliteration| transliteration.parse())
+            .transpose()?,
+        x_properties: load_x_property_rules()?,
+        busy_shadow: load_env_var("BUSY_SHADOW").ok(),
+        annotation: load_env_var("DESCRIPTION_ANNOTATION").ok(),
+        participants: load_env_var("PARTICIPANT_FILTER")
+            .map(|addresses| {
+                addresses
+                    .split(',')
+                    .map(|address| address.trim().to_string())
+                    .filter(|address| !address.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default(),
+        busy_hours: load_env_var("BUSY_HOURS")
+            .ok()
+            .map(|hours| hours.parse())
+            .transpose()?,
+        min_duration: load_duration("MIN_DURATION")?,
+        max_duration: load_duration("MAX_DURATION")?,
+        normalize_end: load_env_var("NORMALIZE_DTEND")
+            .map(|normalize| parse_bool("NORMALIZE_DTEND", &normalize))
+            .unwrap_or(Ok(false))?,
+        limits: EventLimits {
+            max_text_bytes: load_limit("EVENT_MAX_TEXT_BYTES")?,
+            max_exdates: load_limit("EVENT_MAX_EXDATES")?,
+            max_event_bytes: load_limit("EVENT_MAX_BYTES")?,
+            policy: load_env_var("EVENT_LIMIT_POLICY")
+                .map(|policy| policy.parse())
+                .unwrap_or(Ok(Default::default()))?,
+        },
+    })
+}
+
+// RFC 5545 durations like `PT1M` or `P2W`
+fn load_duration(key: &str) -> Result<Option<TimeDelta>> {
+    load_env_var(key)
+        .ok()
+        .map(|duration| {
+            transform::parse_duration(&duration).with_context(|| {
+                format!(
+                    "{} must be a duration like PT15M or P2W, got: {}",
+                    key, duration
+                )
+            })
+        })
+        .transpose()
+}
+
+fn load_x_property_rules() -> Result<XPropertyRules> {
+    let list = |key: &str| -> Vec<String> {
+        load_env_var(key)
+            .map(|names| {
+                names
+                    .split(',')
+                    .map(|name| name.trim().to_string())
+                    .filter(|name| !name.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let mut rules = XPropertyRules {
+        keep: list("X_PROPERTIES_KEEP"),
+        drop: list("X_PROPERTIES_DROP"),
+        rename: Vec::new(),
+    };
+    if let Ok(renames) = load_env_var("X_PROPERTIES_RENAME") {
+        rules.rename = renames
+            .split(',')
+            .filter(|rename| !rename.trim().is_empty())
+            .map(|rename| {
+                rename
+                    .split_once('=')
+                    .map(|(from, to)| (from.trim().to_string(), to.trim().to_string()))
+                    .with_context(|| {
+                        format!(
+                            "Invalid X_PROPERTIES_RENAME entry, expected FROM=TO: {}",
+                            rename
+                        )
+                    })
+            })
+            .collect::<Result<_>>()?;
+    }
+    Ok(rules)
+}
+
+fn load_limit(key: &str) -> Result<Option<usize>> {
+    load_env_var(key)
+        .ok()
+        .map(|limit| {
+            limit
+                .parse()
+                .with_context(|| format!("{} must be a number", key))
+        })
+        .transpose()
+}
+
+// Enrichment is only enabled when a geocoder or a map URL is set
+pub fn load_geo() -> Option<GeoOptions> {
+    let geocoder_url = load_env_var("GEOCODER_URL").ok();
+    let map_url = load_env_var("MAP_URL_TEMPLATE").ok();
+    if geocoder_url.is_none() && map_url.is_none() {
+        return None;
+    }
+
+    Some(GeoOptions {
+        geocoder_url,
+        cache_path: load_env_var("GEOCODER_CACHE")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| paths::cache_dir().join("geocode-cache.json")),
+        map_url,
+    })
+}
+
+pub fn load_timezones() -> Result<TimezoneOptions> {
+    let aliases = load_env_var("TZID_ALIASES")
+        .map(|aliases| {
+            aliases
+                .split(',')
+                .filter(|alias| !alias.trim().is_empty())
+                .map(|alias| {
+                    alias
+                        .split_once('=')
+                        .map(|(tzid, iana)| (tzid.trim().to_string(), iana.trim().to_string()))
+                        .with_context(|| {
+                            format!("Invalid TZID alias, expected TZID=IANA: {}", alias)
+                        })
+                })
+                .collect::<Result<_>>()
+        })
+        .unwrap_or(Ok(Default::default()))?;
+
+    Ok(TimezoneOptions {
+        validation: load_env_var("TZID_VALIDATION")
+            .map(|validation| validation.parse())
+            .unwrap_or(Ok(Default::default()))?,
+        aliases,
+        floating: match load_env_var("FLOATING_TIMES")
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase()
+            .as_str()
+        {
+            "" | "keep" => FloatingTimes::Keep,
+            "anchor" => {
+                FloatingTimes::Anchor(load_env_var("FLOATING_TIMEZONE").context(
+                    "FLOATING_TIMES=anchor requires FLOATING_TIMEZONE, e.g. Europe/Berlin",
+                )?)
+            }
+            "utc" => FloatingTimes::Utc,
+            other => bail!("Unknown floating time policy: {}", other),
+        },
+    })
+}
+
+pub fn load_redact_secrets() -> Result<bool> {
+    load_env_var("REDACT_SECRETS")
+        .map(|redact| parse_bool("REDACT_SECRETS", &redact))
+        .unwrap_or(Ok(true))
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "true" | "1" | "yes" => Ok(true),
+        "false" | "0" | "no" => Ok(false),
+        other => bail!("{} must be true or false, got: {}", key, other),
+    }
+}
+
+// Semicolon separated, as the patterns may contain commas: `summary:^Exam=exams; category:Lecture=uni`
+fn load_calendar_routes(nextcloud_url: &str, username: &str) -> Result<CalendarRoutes> {
+    let Ok(routes) = load_env_var("CALENDAR_ROUTES") else {
+        return Ok(CalendarRoutes::default());
+    };
+    let routes = routes
+        .split(';')
+        .map(str::trim)
+        .filter(|route| !route.is_empty())
+        .map(|route| {
+            let (condition, calendar_id) = route.rsplit_once('=').with_context(|| {
+                format!(
+                    "Invalid CALENDAR_ROUTES entry, expected <condition>=<calendar id>: {}",
+                    route
+                )
+            })?;
+            Ok(Route {
+                condition: condition.parse()?,
+                calendar_url: calendar_url(nextcloud_url, username, calendar_id.trim()),
+            })
+        })
+        .collect::<Result<_>>()?;
+    Ok(CalendarRoutes { routes })
+}
+
+// Comma separated calendar IDs like `family,shared_by_bob`
+fn load_additional_calendar_urls(nextcloud_url: &str, username: &str) -> Result<Vec<String>> {
+    let Ok(calendar_ids) = load_env_var("ADDITIONAL_CALENDAR_IDS") else {
+        return Ok(Vec::new());
+    };
+    let mut calendar_urls = Vec::new();
+    for calendar_id in calendar_ids.split(',').filter(|id| !id.trim().is_empty()) {
+        let calendar_url = calendar_url(nextcloud_url, username, &urls::calendar_id(calendar_id));
+        if !calendar_urls.contains(&calendar_url) {
+            calendar_urls.push(calendar_url);
+        }
+    }
+    Ok(calendar_urls)
+}
+
+// Comma separated property or component names like `SUMMARY,VALARM`
+fn load_property_names(key: &str) -> Vec<String> {
+    load_env_var(key)
+        .map(|names| {
+            names
+                .split(',')
+                .map(|name| name.trim().to_uppercase())
+                .filter(|name| !name.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn load_import_batching() -> Result<UploadBatching> {
+    Ok(UploadBatching {
+        size: load_env_var("IMPORT_BATCH_SIZE")
+            .map(|size| size.parse().context("IMPORT_BATCH_SIZE must be a number"))
+            .unwrap_or(Ok(50))?,
+        pause: load_env_var("IMPORT_BATCH_PAUSE_SECS")
+            .map(|secs| {
+                secs.parse()
+                    .map(Duration::from_secs)
+                    .context("IMPORT_BATCH_PAUSE_SECS must be a number of seconds")
+            })
+            .unwrap_or(Ok(Duration::from_secs(10)))?,
+    })
+}
+
+pub fn load_http_config() -> Result<HttpConfig> {
+    let load_secs = |key: &str, default: Duration| -> Result<Duration> {
+        load_env_var(key)
+            .map(|secs| {
+                secs.parse()
+                    .map(Duration::from_secs)
+                    .with_context(|| format!("{} must be a number of seconds", key))
+            })
+            .unwrap_or(Ok(default))
+    };
+    let defaults = HttpConfig::default();
+
+    Ok(HttpConfig {
+        timeout: load_secs("HTTP_TIMEOUT_SECS", defaults.timeout)?,
+        connect_timeout: load_secs("HTTP_CONNECT_TIMEOUT_SECS", defaults.connect_timeout)?,
+        retries: load_env_var("HTTP_RETRIES")
+            .map(|retries| retries.parse().context("HTTP_RETRIES must be a number"))
+            .unwrap_or(Ok(defaults.retries))?,
+        user_agent: load_env_var("HTTP_USER_AGENT").unwrap_or(defaults.user_agent),
+        request_ids: load_env_var("HTTP_REQUEST_IDS")
+            .map(|request_ids| parse_bool("HTTP_REQUEST_IDS", &request_ids))
+            .unwrap_or(Ok(defaults.request_ids))?,
+        digest_auth_urls: load_digest_auth_urls()?,
+        client_certificate: match (
+            load_env_var("TLS_CLIENT_CERT_PATH"),
+            load_env_var("TLS_CLIENT_KEY_PATH"),
+        ) {
+            (Ok(cert), Ok(key)) => Some((PathBuf::from(cert), PathBuf::from(key))),
+            (Err(_), Err(_)) => None,
+            _ => bail!("TLS_CLIENT_CERT_PATH and TLS_CLIENT_KEY_PATH have to be set together"),
+        },
+        resolve: load_resolve()?,
+        unix_sockets: match load_env_var("NEXTCLOUD_UNIX_SOCKET") {
+            Ok(socket_path) => vec![(
+                load_nextcloud_url().context("NEXTCLOUD_UNIX_SOCKET requires NEXTCLOUD_URL")?,
+                PathBuf::from(socket_path),
+            )],
+            Err(_) => Vec::new(),
+        },
+        ip_family: load_env_var("HTTP_IP_FAMILY")
+            .map(|family| family.parse())
+            .unwrap_or(Ok(defaults.ip_family))?,
+        dns_cache_ttl: load_secs("HTTP_DNS_CACHE_SECS", defaults.dns_cache_ttl)?,
+        trace_body_bytes: defaults.trace_body_bytes,
+    })
+}
+
+// Comma separated `host=address`, the address may carry a port like `10.0.0.5:8443`
+fn load_resolve() -> Result<Vec<(String, SocketAddr)>> {
+    let Ok(resolve) = load_env_var("HTTP_RESOLVE") else {
+        return Ok(Vec::new());
+    };
+    resolve
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (host, address) = entry.split_once('=').with_context(|| {
+                format!(
+                    "Invalid HTTP_RESOLVE entry, expected host=address: {}",
+                    entry
+                )
+            })?;
+            let address = address.trim();
+            let address = address
+                .parse::<SocketAddr>()
+                .or_else(|_| address.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 0)))
+                .with_context(|| format!("Invalid address in HTTP_RESOLVE: {}", address))?;
+            Ok((host.trim().to_string(), address))
+        })
+        .collect()
+}
+
+// The endpoints whose auth scheme is `digest`, `basic` is the default
+fn load_digest_auth_urls() -> Result<Vec<String>> {
+    let mut urls = Vec::new();
+    for (scheme_key, url_key) in [
+        ("ICS_AUTH_SCHEME", "ICS_URL"),
+        ("NEXTCLOUD_AUTH_SCHEME", "NEXTCLOUD_URL"),
+    ] {
+        match load_env_var(scheme_key).map(|scheme| scheme.trim().to_lowercase()) {
+            Ok(scheme) if scheme == "digest" => urls.push(
+                load_env_var(url_key)
+                    .with_context(|| format!("{} requires {}", scheme_key, url_key))?,
+            ),
+            Ok(scheme) if scheme != "basic" => {
+                bail!(
+                    "Invalid {}: {} (expected basic or digest)",
+                    scheme_key,
+                    scheme
+                )
+            }
+            _ => {}
+        }
+    }
+    Ok(urls)
+}
+
+fn load_calendar_metadata() -> CalendarMetadata {
+    let defaults = CalendarMetadata::default();
+    CalendarMetadata {
+        prodid: load_env_var("UPLOAD_PRODID").unwrap_or(defaults.prodid),
+        calscale: load_env_var("UPLOAD_CALSCALE")
+            .map(|calscale| calscale.to_uppercase())
+            .unwrap_or(defaults.calscale),
+        calendar_name: load_env_var("UPLOAD_CALENDAR_NAME").ok(),
+    }
 }
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use icalendar::{Component, Event};
+
+use crate::{explain, window};
+
+/// An event left out because another copy of it is synced or already in the calendar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Duplicate {
+    pub uid: String,
+    pub kept_uid: String,
+}
+
+type Key = (String, Option<DateTime<Utc>>, Option<DateTime<Utc>>);
+
+/// What identifies the same real-world event in different feeds, which assign different
+/// UIDs: the summary ignoring case and spacing, and the start and end.
+fn key(event: &Event) -> Option<Key> {
+    let summary = event
+        .get_summary()?
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    Some((
+        summary,
+        event.get_start().and_then(window::to_utc),
+        event.get_end().and_then(window::to_utc),
+    ))
+}
+
+/// How much detail an event carries, the richer of two copies is kept.
+fn richness(event: &Event) -> (usize, usize) {
+    let values = explain::property_values(event);
+    (
+        values.len() + event.components().len(),
+        values.values().map(String::len).sum(),
+    )
+}
+
+/// Removes source events duplicating another source event or an event that was put into the
+/// Nextcloud calendar by other means, like an invitation.
+///
+/// Of duplicates within the source the richest copy is kept. Events already in the calendar
+/// always win, the sync never changes events it didn't create.
+pub fn remove_duplicates(
+    source_events: &mut HashMap<String, Event>,
+    nextcloud_events: &HashMap<String, Event>,
+) -> Vec<Duplicate> {
+    let mut by_key: HashMap<Key, Vec<&String>> = HashMap::new();
+    for (uid, event) in source_events.iter() {
+        if let Some(key) = key(event) {
+            by_key.entry(key).or_default().push(uid);
+        }
+    }
+    let unsynced: HashMap<Key, &String> = nextcloud_events
+        .iter()
+        .filter(|(uid, event)| {
+            event.property_value("X-SYNCED").is_none() && !source_events.contains_key(*uid)
+        })
+        .filter_map(|(uid, event)| Some((key(event)?, uid)))
+        .collect();
+
+    let mut duplicates = Vec::new();
+    for (key, mut uids) in by_key {
+        // Sorted by UID first, so the choice between equally rich copies is stable
+        uids.sort();
+        uids.sort_by_key(|uid| std::cmp::Reverse(richness(&source_events[*uid])));
+        let kept_uid = match unsynced.get(&key) {
+            Some(nextcloud_uid) => *nextcloud_uid,
+            None => uids.remove(0),
+        };
+        duplicates.extend(uids.into_iter().map(|uid| Duplicate {
+            uid: uid.clone(),
+            kept_uid: kept_uid.clone(),
+        }));
+    }
+
+    for duplicate in &duplicates {
+        source_events.remove(&duplicate.uid);
+    }
+    duplicates.sort_by(|a, b| a.uid.cmp(&b.uid));
+    duplicates
+}
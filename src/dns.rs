@@ -0,0 +1,119 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, bail};
+use log::debug;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+
+/// Which addresses of a host are connected to.
+///
+/// Connections race the address families (happy eyeballs), starting with the family of the
+/// first address, so preferring a family only changes which one is tried first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IpFamily {
+    /// In the order returned by the system resolver
+    #[default]
+    Auto,
+    PreferIpv4,
+    PreferIpv6,
+    Ipv4Only,
+    Ipv6Only,
+}
+
+impl FromStr for IpFamily {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "prefer-ipv4" => Ok(Self::PreferIpv4),
+            "prefer-ipv6" => Ok(Self::PreferIpv6),
+            "ipv4" => Ok(Self::Ipv4Only),
+            "ipv6" => Ok(Self::Ipv6Only),
+            _ => bail!(
+                "Invalid IP family: {} (expected auto, prefer-ipv4, prefer-ipv6, ipv4 or ipv6)",
+                s
+            ),
+        }
+    }
+}
+
+impl IpFamily {
+    /// Filters and orders resolved addresses.
+    pub fn apply(self, mut addresses: Vec<SocketAddr>) -> Vec<SocketAddr> {
+        match self {
+            Self::Auto => {}
+            Self::PreferIpv4 => addresses.sort_by_key(|address| !address.is_ipv4()),
+            Self::PreferIpv6 => addresses.sort_by_key(|address| !address.is_ipv6()),
+            Self::Ipv4Only => addresses.retain(|address| address.is_ipv4()),
+            Self::Ipv6Only => addresses.retain(|address| address.is_ipv6()),
+        }
+        addresses
+    }
+}
+
+/// Addresses by host name, with the time they were resolved.
+type Cache = HashMap<String, (Instant, Vec<SocketAddr>)>;
+
+/// Resolves host names with the system resolver, applying the [`IpFamily`] and keeping the
+/// results for `cache_ttl`.
+#[derive(Clone)]
+pub struct Resolver {
+    family: IpFamily,
+    cache_ttl: Duration,
+    cache: Arc<Mutex<Cache>>,
+}
+
+impl Resolver {
+    pub fn new(family: IpFamily, cache_ttl: Duration) -> Self {
+        Self {
+            family,
+            cache_ttl,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    fn cached(&self, host: &str) -> Option<Vec<SocketAddr>> {
+        let cache = self.cache.lock().expect("DNS cache lock poisoned");
+        let (resolved_at, addresses) = cache.get(host)?;
+        (resolved_at.elapsed() < self.cache_ttl).then(|| addresses.clone())
+    }
+}
+
+impl Resolve for Resolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolver = self.clone();
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            if let Some(addresses) = resolver.cached(&host) {
+                return Ok(Box::new(addresses.into_iter()) as Addrs);
+            }
+
+            let resolved: Vec<SocketAddr> =
+                tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            let addresses = resolver.family.apply(resolved.clone());
+            debug!("Resolved {} to {:?}", host, addresses);
+            if addresses.is_empty() {
+                return Err(format!(
+                    "{} has no {:?} address, it resolved to {:?}",
+                    host, resolver.family, resolved
+                )
+                .into());
+            }
+
+            if !resolver.cache_ttl.is_zero() {
+                resolver
+                    .cache
+                    .lock()
+                    .expect("DNS cache lock poisoned")
+                    .insert(host, (Instant::now(), addresses.clone()));
+            }
+            Ok(Box::new(addresses.into_iter()) as Addrs)
+        })
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::BTreeMap;
+
+use icalendar::{Component, Event, Property};
+
+/// Describes why an event is uploaded, listing the properties differing from the existing copy.
+pub fn explain_upload(uid: &str, source_event: &Event, existing_event: Option<&Event>) -> String {
+    let Some(existing_event) = existing_event else {
+        return format!("UPLOAD {}: not in the Nextcloud calendar yet", uid);
+    };
+
+    let reason = match (
+        source_event.property_value("LAST-MODIFIED"),
+        existing_event.property_value("LAST-MODIFIED"),
+    ) {
+        (Some(new), Some(old)) => format!("LAST-MODIFIED changed from {} to {}", old, new),
+        (None, _) => "source event has no LAST-MODIFIED, so it is always uploaded".to_string(),
+        (_, None) => "existing copy has no LAST-MODIFIED".to_string(),
+    };
+
+    let mut explanation = format!("UPLOAD {}: {}", uid, reason);
+    let changes = property_changes(source_event, existing_event);
+    if changes.is_empty() {
+        explanation.push_str("\n    no property differs");
+    }
+    for (name, old, new) in changes {
+        explanation.push_str(&format!(
+            "\n    {}: {} -> {}",
+            name,
+            old.as_deref().unwrap_or("<missing>"),
+            new.as_deref().unwrap_or("<missing>")
+        ));
+    }
+    explanation
+}
+
+/// The stored and the new event as a unified diff of their ICS lines, sorted by property so
+/// only real differences show up.
+pub fn unified_diff(existing_event: &Event, source_event: &Event) -> String {
+    let old = content_lines(existing_event);
+    let new = content_lines(source_event);
+
+    let mut diff = format!(
+        "--- nextcloud\n+++ source\n@@ -1,{} +1,{} @@",
+        old.len(),
+        new.len()
+    );
+    for (tag, line) in diff_lines(&old, &new) {
+        diff.push('\n');
+        diff.push(tag);
+        diff.push_str(line);
+    }
+    diff
+}
+
+/// Why an event that exists in the calendar is not uploaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// Both copies have the same LAST-MODIFIED.
+    Unchanged,
+    /// The LAST-MODIFIED differ by at most `LAST_MODIFIED_TOLERANCE_SECS`, the content is the
+    /// same.
+    WithinTolerance,
+    /// The properties owned by the source are the same, the others belong to the calendar.
+    OwnedUnchanged,
+    /// An earlier attempt of the resumed run already uploaded it.
+    JournalConfirmed,
+}
+
+pub fn explain_skip(
+    uid: &str,
+    source_event: &Event,
+    existing_event: Option<&Event>,
+    reason: SkipReason,
+) -> String {
+    let last_modified = |event: Option<&Event>| {
+        event
+            .and_then(|event| event.property_value("LAST-MODIFIED"))
+            .unwrap_or("<missing>")
+            .to_string()
+    };
+    let reason = match reason {
+        SkipReason::Unchanged => format!(
+            "LAST-MODIFIED {} is unchanged",
+            last_modified(Some(source_event))
+        ),
+        SkipReason::WithinTolerance => format!(
+            "LAST-MODIFIED {} is within the tolerance of {} and the content is the same",
+            last_modified(Some(source_event)),
+            last_modified(existing_event)
+        ),
+        SkipReason::OwnedUnchanged => {
+            "the properties owned by the source are unchanged".to_string()
+        }
+        SkipReason::JournalConfirmed => {
+            "already uploaded by an earlier attempt of this run".to_string()
+        }
+    };
+    format!("SKIP {}: {}", uid, reason)
+}
+
+pub fn explain_delete(uid: &str) -> String {
+    format!("DELETE {}: synced event is no longer in the source", uid)
+}
+
+/// Properties whose values or parameters differ, as `(name, old, new)`.
+pub(crate) fn property_changes(
+    source_event: &Event,
+    existing_event: &Event,
+) -> Vec<(String, Option<String>, Option<String>)> {
+    let new = property_values(source_event);
+    let old = property_values(existing_event);
+
+    let mut names: Vec<&String> = new.keys().chain(old.keys()).collect();
+    names.sort();
+    names.dedup();
+
+    names
+        .into_iter()
+        // DTSTAMP is regenerated on every export and says nothing about the event
+        .filter(|name| name.as_str() != "DTSTAMP")
+        .filter(|name| new.get(*name) != old.get(*name))
+        .map(|name| (name.clone(), old.get(name).cloned(), new.get(name).cloned()))
+        .collect()
+}
+
+pub(crate) fn property_values(event: &impl Component) -> BTreeMap<String, String> {
+    let mut values: BTreeMap<String, String> = event
+        .properties()
+        .iter()
+        .map(|(name, property)| (name.clone(), format_property(property)))
+        .collect();
+
+    for (name, properties) in event.multi_properties() {
+        let joined = properties
+            .iter()
+            .map(format_property)
+            .collect::<Vec<_>>()
+            .join(", ");
+        values.insert(name.clone(), joined);
+    }
+    values
+}
+
+fn format_property(property: &Property) -> String {
+    // Sorted, the parameters are kept in a map without a stable order
+    let mut params: Vec<String> = property
+        .params()
+        .iter()
+        .map(|(key, param)| format!(";{}={}", key, param.value()))
+        .collect();
+    params.sort();
+    format!("{}{}", property.value(), params.concat())
+}
+
+/// The unfolded content lines of an event as they are serialized, escaping included.
+fn content_lines(event: &Event) -> Vec<String> {
+    let properties = event
+        .properties()
+        .values()
+        .chain(event.multi_properties().values().flatten())
+        // DTSTAMP is regenerated on every export and says nothing about the event
+        .filter(|property| property.key() != "DTSTAMP");
+
+    let mut lines: Vec<String> = properties
+        .filter_map(|property| {
+            let line: String = property.clone().try_into().ok()?;
+            Some(line.replace("\r\n ", "").trim_end().to_string())
+        })
+        .collect();
+    lines.sort();
+    lines
+}
+
+/// Lines of both sides tagged with ' ', '-' or '+', based on their longest common subsequence.
+fn diff_lines<'a>(old: &'a [String], new: &'a [String]) -> Vec<(char, &'a str)> {
+    // common[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push((' ', old[i].as_str()));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(('-', old[i].as_str()));
+            i += 1;
+        } else {
+            lines.push(('+', new[j].as_str()));
+            j += 1;
+        }
+    }
+    lines
+}
@@ -0,0 +1,208 @@
+use std::fmt;
+
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Days, NaiveDateTime, Utc};
+use icalendar::{Calendar, CalendarComponent, Component, Event, EventLike, EventStatus};
+use log::warn;
+
+use crate::{
+    nextcloud::{api::TimeRange, utils::stable_hash},
+    transform,
+};
+
+pub const DEFAULT_AVAILABILITY_DAYS: u64 = 14;
+
+/// The FBTYPE of a period, free periods are left out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusyType {
+    Busy,
+    Tentative,
+    Unavailable,
+}
+
+impl fmt::Display for BusyType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Busy => "BUSY",
+            Self::Tentative => "BUSY-TENTATIVE",
+            Self::Unavailable => "BUSY-UNAVAILABLE",
+        })
+    }
+}
+
+/// A `FREEBUSY` period of a VFREEBUSY component.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BusyPeriod {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub kind: BusyType,
+}
+
+/// The busy periods of the VFREEBUSY components in an iCalendar object, sorted by start.
+pub fn busy_periods(calendar_data: &str) -> Result<Vec<BusyPeriod>> {
+    let calendar = calendar_data
+        .parse::<Calendar>()
+        .map_err(|e: String| anyhow!(e))
+        .context("Failed to parse free/busy data")?;
+
+    let mut periods = Vec::new();
+    for component in &calendar.components {
+        let CalendarComponent::Other(freebusy) = component else {
+            continue;
+        };
+        if freebusy.component_kind() != "VFREEBUSY" {
+            continue;
+        }
+        for property in freebusy
+            .multi_properties()
+            .get("FREEBUSY")
+            .into_iter()
+            .flatten()
+        {
+            let kind = match property
+                .params()
+                .get("FBTYPE")
+                .map(|fbtype| fbtype.value().to_uppercase())
+                .as_deref()
+            {
+                Some("FREE") => continue,
+                Some("BUSY-TENTATIVE") => BusyType::Tentative,
+                Some("BUSY-UNAVAILABLE") => BusyType::Unavailable,
+                _ => BusyType::Busy,
+            };
+            for period in property.value().split(',') {
+                match parse_period(period) {
+                    Some((start, end)) => periods.push(BusyPeriod { start, end, kind }),
+                    None => warn!("Skipping invalid FREEBUSY period: {}", period),
+                }
+            }
+        }
+    }
+    periods.sort_by_key(|period| period.start);
+    Ok(periods)
+}
+
+/// An RFC 5545 period in UTC, `<start>/<end>` or `<start>/<duration>`.
+fn parse_period(period: &str) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let (start, end) = period.trim().split_once('/')?;
+    let start = parse_utc(start)?;
+    let end = match transform::parse_duration(end) {
+        Some(duration) => start.checked_add_signed(duration)?,
+        None => parse_utc(end)?,
+    };
+    (start < end).then_some((start, end))
+}
+
+fn parse_utc(value: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(value.strip_suffix('Z')?, "%Y%m%dT%H%M%S")
+        .ok()
+        .map(|date_time| date_time.and_utc())
+}
+
+/// Settings of the `mirror-availability` command, which publishes the combined busy times of
+/// a group into a calendar.
+#[derive(Debug, Clone)]
+pub struct AvailabilityOptions {
+    /// Email addresses of the users whose busy times are combined.
+    pub attendees: Vec<String>,
+    /// Email address of the Nextcloud account, the server only answers its own requests.
+    pub organizer: String,
+    pub calendar_url: String,
+    pub days: u64,
+    pub summary: String,
+}
+
+impl AvailabilityOptions {
+    /// Today and the next `days` days, whole days so the blocks don't change between runs.
+    pub fn time_range(&self, now: DateTime<Utc>) -> TimeRange {
+        let start = now.date_naive().and_time(Default::default()).and_utc();
+        TimeRange {
+            start: Some(start),
+            end: start.checked_add_days(Days::new(self.days)),
+        }
+    }
+}
+
+/// A time in which at least one member of the group is busy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BusyBlock {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// The busy members, sorted.
+    pub attendees: Vec<String>,
+    /// Everyone is only tentatively busy.
+    pub tentative: bool,
+}
+
+/// Merges the overlapping and adjacent busy periods of all attendees into blocks.
+pub fn combine(busy_times: &[(String, Vec<BusyPeriod>)]) -> Vec<BusyBlock> {
+    let mut periods: Vec<(&str, &BusyPeriod)> = busy_times
+        .iter()
+        .flat_map(|(attendee, periods)| periods.iter().map(|period| (attendee.as_str(), period)))
+        .collect();
+    periods.sort_by_key(|(_, period)| period.start);
+
+    let mut blocks: Vec<BusyBlock> = Vec::new();
+    for (attendee, period) in periods {
+        let tentative = period.kind == BusyType::Tentative;
+        match blocks.last_mut() {
+            Some(block) if period.start <= block.end => {
+                block.end = block.end.max(period.end);
+                block.tentative &= tentative;
+                if !block.attendees.iter().any(|a| a == attendee) {
+                    block.attendees.push(attendee.to_string());
+                }
+            }
+            _ => blocks.push(BusyBlock {
+                start: period.start,
+                end: period.end,
+                attendees: vec![attendee.to_string()],
+                tentative,
+            }),
+        }
+    }
+    for block in &mut blocks {
+        block.attendees.sort();
+    }
+    blocks
+}
+
+/// The blocks as events to sync into the availability calendar.
+///
+/// The UID is derived from the whole content, so a changed block replaces the old one and an
+/// unchanged one is skipped.
+pub fn availability_calendar(blocks: &[BusyBlock], summary: &str) -> Calendar {
+    blocks
+        .iter()
+        .map(|block| {
+            let description = format!("Busy: {}", block.attendees.join(", "));
+            let uid = format!(
+                "availability-{}",
+                stable_hash(&format!(
+                    "{}/{}/{}/{}",
+                    block.start.to_rfc3339(),
+                    block.end.to_rfc3339(),
+                    block.tentative,
+                    description
+                ))
+            );
+
+            let mut event = Event::new();
+            event
+                .uid(&uid)
+                .summary(summary)
+                .description(&description)
+                .starts(block.start)
+                .ends(block.end)
+                .status(if block.tentative {
+                    EventStatus::Tentative
+                } else {
+                    EventStatus::Confirmed
+                })
+                .add_property("TRANSP", "OPAQUE");
+            // Only changes together with the UID
+            event.last_modified(block.start);
+            event.done()
+        })
+        .collect()
+}
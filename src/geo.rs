@@ -0,0 +1,180 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use anyhow::{Context, Result};
+use futures::future::BoxFuture;
+use icalendar::{Calendar, CalendarComponent, Component, EventLike};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use urlencoding::encode;
+
+use crate::http::{self, Client};
+
+/// Resolves a free text LOCATION into coordinates.
+pub trait Geocoder: Send + Sync {
+    /// Returns `(latitude, longitude)`, or `None` if the location is unknown.
+    fn geocode<'a>(
+        &'a self,
+        client: &'a Client,
+        location: &'a str,
+    ) -> BoxFuture<'a, Result<Option<(f64, f64)>>>;
+}
+
+/// A Nominatim compatible search API, `{query}` in the URL is replaced by the location.
+pub struct NominatimGeocoder {
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct NominatimPlace {
+    lat: String,
+    lon: String,
+}
+
+impl Geocoder for NominatimGeocoder {
+    fn geocode<'a>(
+        &'a self,
+        client: &'a Client,
+        location: &'a str,
+    ) -> BoxFuture<'a, Result<Option<(f64, f64)>>> {
+        Box::pin(async move {
+            let url = self.url.replace("{query}", &encode(location));
+            let response = http::send(client, client.get(&url))
+                .await?
+                .error_for_status()
+                .with_context(|| format!("Geocoding request failed. URL: {}", url))?;
+            let places: Vec<NominatimPlace> = response
+                .json()
+                .await
+                .context("Failed to parse geocoding response")?;
+
+            Ok(places
+                .first()
+                .and_then(|place| Some((place.lat.parse().ok()?, place.lon.parse().ok()?))))
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GeoOptions {
+    pub geocoder_url: Option<String>,
+    pub cache_path: PathBuf,
+    /// Appended to DESCRIPTION, `{location}` is replaced by the URL encoded LOCATION.
+    pub map_url: Option<String>,
+}
+
+/// Adds GEO properties and map links to events with a LOCATION.
+pub struct GeoEnricher {
+    geocoder: Option<Box<dyn Geocoder>>,
+    map_url: Option<String>,
+    cache_path: Option<PathBuf>,
+    cache: HashMap<String, Option<(f64, f64)>>,
+}
+
+impl GeoEnricher {
+    pub fn new(geocoder: Option<Box<dyn Geocoder>>, map_url: Option<String>) -> Self {
+        Self {
+            geocoder,
+            map_url,
+            cache_path: None,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Builds the enricher from the configuration, lookups are cached in `cache_path`.
+    pub fn from_options(options: &GeoOptions) -> Self {
+        let geocoder = options
+            .geocoder_url
+            .as_ref()
+            .map(|url| Box::new(NominatimGeocoder { url: url.clone() }) as Box<dyn Geocoder>);
+        let mut enricher = Self::new(geocoder, options.map_url.clone());
+        enricher.cache = fs::read_to_string(&options.cache_path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<Vec<CacheEntry>>(&content).ok())
+            .map(|entries| entries.into_iter().map(|e| (e.location, e.geo)).collect())
+            .unwrap_or_default();
+        enricher.cache_path = Some(options.cache_path.clone());
+        enricher
+    }
+
+    pub async fn enrich(&mut self, client: &Client, mut calendar: Calendar) -> Result<Calendar> {
+        for component in &mut calendar.components {
+            let CalendarComponent::Event(event) = component else {
+                continue;
+            };
+            let Some(location) = event.get_location().map(str::to_string) else {
+                continue;
+            };
+
+            if event.property_value("GEO").is_none()
+                && let Some((lat, lon)) = self.lookup(client, &location).await
+            {
+                event.add_property("GEO", format!("{};{}", lat, lon));
+            }
+
+            if let Some(map_url) = &self.map_url {
+                let link = map_url.replace("{location}", &encode(&location));
+                let description = match event.get_description() {
+                    Some(description) if description.contains(&link) => continue,
+                    Some(description) => format!("{}\n\n{}", description, link),
+                    None => link,
+                };
+                event.description(&description);
+            }
+        }
+
+        self.save_cache();
+        Ok(calendar)
+    }
+
+    async fn lookup(&mut self, client: &Client, location: &str) -> Option<(f64, f64)> {
+        if let Some(geo) = self.cache.get(location) {
+            return *geo;
+        }
+        let geocoder = self.geocoder.as_ref()?;
+
+        debug!("Geocoding location: {}", location);
+        match geocoder.geocode(client, location).await {
+            Ok(geo) => {
+                self.cache.insert(location.to_string(), geo);
+                geo
+            }
+            // Not cached, so the lookup is retried on the next sync
+            Err(e) => {
+                warn!("Failed to geocode {}: {:#}", location, e);
+                None
+            }
+        }
+    }
+
+    fn save_cache(&self) {
+        let Some(path) = &self.cache_path else {
+            return;
+        };
+        let entries: Vec<CacheEntry> = self
+            .cache
+            .iter()
+            .map(|(location, geo)| CacheEntry {
+                location: location.clone(),
+                geo: *geo,
+            })
+            .collect();
+
+        let result = serde_json::to_string_pretty(&entries)
+            .map_err(anyhow::Error::from)
+            .and_then(|content| {
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                fs::write(path, content).map_err(anyhow::Error::from)
+            });
+        if let Err(e) = result {
+            warn!("Failed to save geocoding cache {}: {:#}", path.display(), e);
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    location: String,
+    geo: Option<(f64, f64)>,
+}
@@ -0,0 +1,153 @@
+use std::process::Stdio;
+
+use anyhow::{Context, Result, bail};
+use icalendar::{Calendar, CalendarComponent, Component, Event, EventLike};
+use log::{debug, warn};
+use serde_json::{Value, json};
+use tokio::io::AsyncWriteExt;
+
+use crate::shell;
+
+/// Shell commands run at certain points of a sync.
+///
+/// Hook data is passed both as `NIS_*` environment variables and as a JSON object on stdin.
+#[derive(Debug, Clone, Default)]
+pub struct Hooks {
+    pub pre_sync: Option<String>,
+    pub post_sync: Option<String>,
+    pub on_event_upload: Option<String>,
+    pub on_event_delete: Option<String>,
+    /// Its output replaces the SUMMARY of each source event, e.g. to translate titles.
+    pub translate_summary: Option<String>,
+}
+
+impl Hooks {
+    /// Runs the `pre_sync` hook. A failing hook aborts the sync.
+    pub async fn pre_sync(&self) -> Result<()> {
+        match &self.pre_sync {
+            Some(command) => run(command, "pre_sync", json!({})).await,
+            None => Ok(()),
+        }
+    }
+
+    pub async fn post_sync(&self, success: bool, uploaded: usize, deleted: usize) {
+        if let Some(command) = &self.post_sync {
+            let data = json!({
+                "success": success,
+                "uploaded": uploaded,
+                "deleted": deleted,
+            });
+            warn_on_failure(run(command, "post_sync", data).await);
+        }
+    }
+
+    pub async fn on_event_upload(&self, event: &Event) {
+        if let Some(command) = &self.on_event_upload {
+            warn_on_failure(run(command, "on_event_upload", event_data(event)).await);
+        }
+    }
+
+    pub async fn on_event_delete(&self, uid: &str, event: Option<&Event>) {
+        if let Some(command) = &self.on_event_delete {
+            let data = event
+                .map(event_data)
+                .unwrap_or_else(|| json!({ "uid": uid }));
+            warn_on_failure(run(command, "on_event_delete", data).await);
+        }
+    }
+
+    /// Runs the `translate_summary` hook for every event, keeping the original SUMMARY if
+    /// it fails or prints nothing.
+    pub async fn translate_summaries(&self, mut calendar: Calendar) -> Calendar {
+        let Some(command) = &self.translate_summary else {
+            return calendar;
+        };
+
+        for component in &mut calendar.components {
+            let CalendarComponent::Event(event) = component else {
+                continue;
+            };
+            if event.get_summary().is_none() {
+                continue;
+            }
+
+            match execute(command, "translate_summary", event_data(event), true).await {
+                Ok(output) if !output.trim().is_empty() => {
+                    event.summary(output.trim());
+                }
+                Ok(_) => {}
+                Err(e) => warn!("{:#}", e),
+            }
+        }
+        calendar
+    }
+}
+
+fn warn_on_failure(result: Result<()>) {
+    if let Err(e) = result {
+        warn!("{:#}", e);
+    }
+}
+
+fn event_data(event: &Event) -> Value {
+    json!({
+        "uid": event.get_uid(),
+        "summary": event.get_summary(),
+        "start": event.property_value("DTSTART"),
+        "end": event.property_value("DTEND"),
+        "location": event.get_location(),
+        "description": event.get_description(),
+    })
+}
+
+async fn run(command: &str, hook: &str, data: Value) -> Result<()> {
+    execute(command, hook, data, false).await.map(|_| ())
+}
+
+/// Runs a hook, returning its stdout if `capture_stdout` is set.
+async fn execute(
+    command: &str,
+    hook: &str,
+    mut data: Value,
+    capture_stdout: bool,
+) -> Result<String> {
+    debug!("Running {} hook: {}", hook, command);
+
+    data["hook"] = json!(hook);
+
+    let mut process = shell::command(command);
+    process.env("NIS_HOOK", hook).stdin(Stdio::piped());
+    if capture_stdout {
+        process.stdout(Stdio::piped());
+    }
+
+    if let Value::Object(fields) = &data {
+        for (key, value) in fields {
+            let value = match value {
+                Value::String(s) => s.clone(),
+                Value::Null => continue,
+                other => other.to_string(),
+            };
+            process.env(format!("NIS_{}", key.to_uppercase()), value);
+        }
+    }
+
+    let mut child = process
+        .spawn()
+        .with_context(|| format!("Failed to run {} hook: {}", hook, command))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // The hook may not read stdin at all, so a closed pipe is not an error
+        let _ = stdin.write_all(data.to_string().as_bytes()).await;
+    }
+
+    let output = child
+        .wait_with_output()
+        .await
+        .with_context(|| format!("Failed to wait for {} hook: {}", hook, command))?;
+
+    if !output.status.success() {
+        bail!("{} hook failed with {}: {}", hook, output.status, command);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,125 @@
+use std::str::FromStr;
+
+use anyhow::{Context, Result, bail};
+use chrono::{Datelike, Local, NaiveDateTime, NaiveTime, Timelike, Weekday};
+use icalendar::{CalendarDateTime, Component, DatePerhapsTime, Event};
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+const MINUTES_PER_WEEK: u32 = 7 * MINUTES_PER_DAY;
+
+/// Recurring weekly hours like `Mon-Fri 07:00-20:00`, as minutes since Monday 00:00.
+///
+/// Times are wall-clock times: UTC event times are compared in the local timezone, times
+/// with a TZID as they are written.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WeeklyHours {
+    ranges: Vec<(u32, u32)>,
+}
+
+impl FromStr for WeeklyHours {
+    type Err = anyhow::Error;
+
+    /// Comma separated windows of a day or day range and an optional time range, e.g.
+    /// `Mon-Fri 07:00-20:00, Sat 09:00-13:00, Sun`. A window ending before it starts runs
+    /// past midnight.
+    fn from_str(s: &str) -> Result<Self> {
+        let mut ranges = Vec::new();
+        for window in s.split(',').map(str::trim).filter(|w| !w.is_empty()) {
+            let (days, times) = window.split_once(' ').unwrap_or((window, "00:00-24:00"));
+            let (start, end) = times
+                .trim()
+                .split_once('-')
+                .with_context(|| format!("Invalid time range in busy hours: {}", window))?;
+            let (start, end) = (parse_minute(start)?, parse_minute(end)?);
+            let length = (end + MINUTES_PER_DAY - start - 1) % MINUTES_PER_DAY + 1;
+
+            for day in parse_days(days)? {
+                let start = day.num_days_from_monday() * MINUTES_PER_DAY + start;
+                ranges.push((start, start + length));
+            }
+        }
+        if ranges.is_empty() {
+            bail!("Busy hours are empty: {}", s);
+        }
+        Ok(Self { ranges })
+    }
+}
+
+impl WeeklyHours {
+    /// Whether the event overlaps the hours, events without a duration whether they start
+    /// within them. Events without a start are kept.
+    pub fn overlaps(&self, event: &Event) -> bool {
+        let Some(start) = event.get_start() else {
+            return true;
+        };
+        let all_day = matches!(start, DatePerhapsTime::Date(_));
+        let start = wall_clock(start);
+        let duration = match event.get_end().map(wall_clock) {
+            Some(end) if end > start => (end - start).num_minutes(),
+            _ if all_day => MINUTES_PER_DAY.into(),
+            _ => 0,
+        }
+        .min(MINUTES_PER_WEEK.into());
+
+        let start = i64::from(
+            start.weekday().num_days_from_monday() * MINUTES_PER_DAY
+                + start.hour() * 60
+                + start.minute(),
+        );
+        let end = start + duration;
+        let week = i64::from(MINUTES_PER_WEEK);
+        // Shifted by a week to catch events and windows running past Sunday midnight
+        self.ranges.iter().any(|&(range_start, range_end)| {
+            [-week, 0, week].into_iter().any(|offset| {
+                let (range_start, range_end) = (
+                    i64::from(range_start) + offset,
+                    i64::from(range_end) + offset,
+                );
+                match duration {
+                    0 => range_start <= start && start < range_end,
+                    _ => start < range_end && range_start < end,
+                }
+            })
+        })
+    }
+}
+
+fn parse_days(days: &str) -> Result<Vec<Weekday>> {
+    let parse = |day: &str| {
+        day.trim()
+            .parse::<Weekday>()
+            .map_err(|_| anyhow::anyhow!("Invalid weekday in busy hours: {}", day))
+    };
+    let Some((first, last)) = days.split_once('-') else {
+        return Ok(vec![parse(days)?]);
+    };
+    let (mut day, last) = (parse(first)?, parse(last)?);
+    let mut days = vec![day];
+    while day != last {
+        day = day.succ();
+        days.push(day);
+    }
+    Ok(days)
+}
+
+// Minutes since midnight, `24:00` is the end of the day
+fn parse_minute(time: &str) -> Result<u32> {
+    let time = time.trim();
+    if time == "24:00" {
+        return Ok(MINUTES_PER_DAY);
+    }
+    let time = NaiveTime::parse_from_str(time, "%H:%M")
+        .with_context(|| format!("Invalid time in busy hours, expected HH:MM: {}", time))?;
+    Ok(time.hour() * 60 + time.minute())
+}
+
+fn wall_clock(date: DatePerhapsTime) -> NaiveDateTime {
+    match date {
+        DatePerhapsTime::DateTime(CalendarDateTime::Utc(date_time)) => {
+            date_time.with_timezone(&Local).naive_local()
+        }
+        DatePerhapsTime::DateTime(CalendarDateTime::Floating(date_time))
+        | DatePerhapsTime::DateTime(CalendarDateTime::WithTimezone { date_time, .. }) => date_time,
+        DatePerhapsTime::Date(date) => date.and_time(NaiveTime::MIN),
+    }
+}
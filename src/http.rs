@@ -0,0 +1,560 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use digest_auth::{AuthContext, HttpMethod, WwwAuthenticateHeader};
+use futures::future::BoxFuture;
+use log::{debug, trace, warn};
+use reqwest::{
+    ClientBuilder, Identity, IntoUrl, Method, Request, RequestBuilder, Response,
+    ResponseBuilderExt, StatusCode, Url,
+    header::{AUTHORIZATION, HeaderValue, WWW_AUTHENTICATE},
+};
+use uuid::Uuid;
+
+use crate::{
+    dns::{IpFamily, Resolver},
+    recording,
+};
+
+// Doubled after every failed attempt
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+pub const DEFAULT_USER_AGENT: &str = concat!("nextcloud-ics-sync/", env!("CARGO_PKG_VERSION"));
+
+/// Settings shared by all HTTP requests, to the source as well as to Nextcloud.
+#[derive(Debug, Clone)]
+pub struct HttpConfig {
+    pub timeout: Duration,
+    pub connect_timeout: Duration,
+    /// How often a request failing with a network error or a temporary server error
+    /// (429, 502, 503, 504) is repeated.
+    pub retries: u32,
+    pub user_agent: String,
+    /// Send a random `X-Request-Id` with every request, so it can be found in server logs.
+    pub request_ids: bool,
+    /// Servers that only accept Digest authentication, given by any of their URLs.
+    pub digest_auth_urls: Vec<String>,
+    /// PEM certificate and PKCS#8 key presented to servers that ask for a client certificate.
+    pub client_certificate: Option<(PathBuf, PathBuf)>,
+    /// Host names resolved to these addresses instead of asking DNS, a port of `0` keeps
+    /// the port of the URL.
+    pub resolve: Vec<(String, SocketAddr)>,
+    /// Servers, given by any of their URLs, that are connected to through a Unix socket.
+    pub unix_sockets: Vec<(String, PathBuf)>,
+    pub ip_family: IpFamily,
+    /// How long resolved addresses are reused, `0` asks the system resolver every time.
+    pub dns_cache_ttl: Duration,
+    /// Log every request with its status and timing at trace level, including the first
+    /// bytes of the request and response bodies with secret fields redacted.
+    pub trace_body_bytes: Option<usize>,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(60),
+            connect_timeout: Duration::from_secs(10),
+            retries: 2,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            request_ids: false,
+            digest_auth_urls: Vec::new(),
+            client_certificate: None,
+            resolve: Vec::new(),
+            unix_sockets: Vec::new(),
+            ip_family: IpFamily::Auto,
+            dns_cache_ttl: Duration::ZERO,
+            trace_body_bytes: None,
+        }
+    }
+}
+
+/// The HTTP client of all requests, to the source as well as to Nextcloud, with the retry,
+/// request ID, Digest authentication and tracing settings of its [`HttpConfig`].
+///
+/// Clones share the connections and the Digest challenges. [`Client::new`] sends every
+/// request once with the defaults of reqwest, [`init`] applies an [`HttpConfig`].
+#[derive(Clone, Default)]
+pub struct Client {
+    client: reqwest::Client,
+    settings: Arc<Settings>,
+    transport: Option<Arc<dyn HttpTransport>>,
+}
+
+#[derive(Default)]
+struct Settings {
+    retries: u32,
+    request_ids: bool,
+    trace_body_bytes: Option<usize>,
+    /// Origins answered with Digest instead of Basic auth, with the last challenge of each.
+    digest_origins: Mutex<HashMap<String, Option<WwwAuthenticateHeader>>>,
+    /// Clients for origins that are reached through a Unix socket instead of TCP.
+    socket_clients: HashMap<String, reqwest::Client>,
+}
+
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Client")
+            .field("retries", &self.settings.retries)
+            .field("request_ids", &self.settings.request_ids)
+            .field("transport", &self.transport.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl From<reqwest::Client> for Client {
+    fn from(client: reqwest::Client) -> Self {
+        Self {
+            client,
+            settings: Arc::default(),
+            transport: None,
+        }
+    }
+}
+
+impl Client {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.client.get(url)
+    }
+
+    pub fn post<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.client.post(url)
+    }
+
+    pub fn put<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.client.put(url)
+    }
+
+    pub fn request<U: IntoUrl>(&self, method: Method, url: U) -> RequestBuilder {
+        self.client.request(method, url)
+    }
+
+    /// Sends the requests of this client through `transport` instead of reqwest.
+    pub fn with_transport(mut self, transport: Arc<dyn HttpTransport>) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    /// Answers the Digest challenges of the server of `url` with the credentials of
+    /// requests built with `basic_auth`, which are never sent to it in plain text.
+    pub fn use_digest_auth(&self, url: &str) -> Result<()> {
+        let origin = origin(url)?;
+        self.digest_origins().entry(origin).or_default();
+        Ok(())
+    }
+
+    /// The reqwest client for an origin, which differs for origins served over a Unix
+    /// socket.
+    pub(crate) fn client_for(&self, origin: &str) -> reqwest::Client {
+        self.settings
+            .socket_clients
+            .get(origin)
+            .unwrap_or(&self.client)
+            .clone()
+    }
+
+    fn digest_origins(&self) -> MutexGuard<'_, HashMap<String, Option<WwwAuthenticateHeader>>> {
+        self.settings
+            .digest_origins
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A request handed to an [`HttpTransport`], with the whole body in memory.
+pub type HttpRequest = ::http::Request<Vec<u8>>;
+pub type HttpResponse = ::http::Response<Vec<u8>>;
+
+/// Sends the requests instead of the reqwest client, e.g. another HTTP client or a mock.
+///
+/// Retries, Digest authentication, request IDs and tracing still apply, the transport only
+/// replaces the connection. Client settings like timeouts, proxies and TLS
+/// are up to the transport.
+pub trait HttpTransport: Send + Sync {
+    fn execute(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse>>;
+}
+
+/// Builds the client used for all requests.
+pub fn init(config: &HttpConfig) -> Result<Client> {
+    let mut digest_origins = HashMap::new();
+    for url in &config.digest_auth_urls {
+        digest_origins.insert(origin(url)?, None);
+    }
+
+    let mut socket_clients = HashMap::new();
+    for (url, socket_path) in &config.unix_sockets {
+        socket_clients.insert(
+            origin(url)?,
+            unix_socket(client_builder(config)?, socket_path)?
+                .build()
+                .context("Failed to build HTTP client")?,
+        );
+    }
+
+    Ok(Client {
+        client: client_builder(config)?
+            .build()
+            .context("Failed to build HTTP client")?,
+        settings: Arc::new(Settings {
+            retries: config.retries,
+            request_ids: config.request_ids,
+            trace_body_bytes: config.trace_body_bytes,
+            digest_origins: Mutex::new(digest_origins),
+            socket_clients,
+        }),
+        transport: None,
+    })
+}
+
+fn client_builder(config: &HttpConfig) -> Result<ClientBuilder> {
+    let mut builder = reqwest::Client::builder()
+        .user_agent(&config.user_agent)
+        .timeout(config.timeout)
+        .connect_timeout(config.connect_timeout);
+    if let Some((cert_path, key_path)) = &config.client_certificate {
+        builder = builder.identity(load_identity(cert_path, key_path)?);
+    }
+    if config.ip_family != IpFamily::Auto || !config.dns_cache_ttl.is_zero() {
+        builder = builder.dns_resolver(Arc::new(Resolver::new(
+            config.ip_family,
+            config.dns_cache_ttl,
+        )));
+    }
+    for (host, address) in &config.resolve {
+        builder = builder.resolve(host, *address);
+    }
+    Ok(builder)
+}
+
+#[cfg(unix)]
+fn unix_socket(builder: ClientBuilder, socket_path: &Path) -> Result<ClientBuilder> {
+    Ok(builder.unix_socket(socket_path))
+}
+
+#[cfg(not(unix))]
+fn unix_socket(_builder: ClientBuilder, _socket_path: &Path) -> Result<ClientBuilder> {
+    anyhow::bail!("Unix sockets are not supported on this platform")
+}
+
+fn origin(url: &str) -> Result<String> {
+    Ok(Url::parse(url)
+        .with_context(|| format!("Invalid URL: {}", url))?
+        .origin()
+        .ascii_serialization())
+}
+
+fn load_identity(cert_path: &Path, key_path: &Path) -> Result<Identity> {
+    let cert = fs::read(cert_path).with_context(|| {
+        format!(
+            "Failed to read client certificate. Path: {}",
+            cert_path.display()
+        )
+    })?;
+    let key = fs::read(key_path)
+        .with_context(|| format!("Failed to read client key. Path: {}", key_path.display()))?;
+    Identity::from_pkcs8_pem(&cert, &key).context(
+        "Invalid client certificate or key, the key has to be unencrypted PKCS#8 PEM \
+         (`openssl pkcs8 -topk8 -nocrypt`)",
+    )
+}
+
+/// A WebDAV request authenticated with the Nextcloud credentials.
+pub(crate) fn dav_request(
+    client: &Client,
+    method: &str,
+    url: &str,
+    username: &str,
+    password: &str,
+) -> RequestBuilder {
+    let method = Method::from_bytes(method.as_bytes()).expect("DAV method names are valid");
+    client
+        .request(method, url)
+        .basic_auth(username, Some(password))
+}
+
+/// Sends a request, retrying temporary failures.
+///
+/// Requests with a streamed body can't be repeated and are sent once. Retries keep the
+/// request ID of the first attempt.
+pub(crate) async fn send(client: &Client, request: RequestBuilder) -> Result<Response> {
+    let (_, request) = request.build_split();
+    let mut request = request?;
+    let origin = request.url().origin().ascii_serialization();
+    if client.digest_origins().contains_key(&origin)
+        && let Some(credentials) = basic_credentials(&request)
+    {
+        request.headers_mut().remove(AUTHORIZATION);
+        return send_digest(client, request, &origin, credentials).await;
+    }
+    send_with_retries(client, request).await
+}
+
+async fn send_with_retries(client: &Client, mut request: Request) -> Result<Response> {
+    if client.settings.request_ids {
+        request.headers_mut().insert(
+            "X-Request-Id",
+            HeaderValue::from_str(&Uuid::new_v4().to_string())?,
+        );
+    }
+
+    let retries = client.settings.retries;
+    let mut backoff = INITIAL_BACKOFF;
+
+    for attempt in 0..retries {
+        let Some(retry) = request.try_clone() else {
+            break;
+        };
+
+        match send_once(client, retry).await {
+            Ok(response) if !is_temporary(response.status()) => return Ok(response),
+            Ok(response) => warn!(
+                "Request to {} failed with status {}, retrying in {:?} ({}/{})",
+                request.url(),
+                response.status(),
+                backoff,
+                attempt + 1,
+                retries
+            ),
+            Err(e) => warn!(
+                "Request failed: {:#}, retrying in {:?} ({}/{})",
+                e,
+                backoff,
+                attempt + 1,
+                retries
+            ),
+        }
+
+        tokio::time::sleep(backoff).await;
+        backoff *= 2;
+    }
+
+    send_once(client, request).await
+}
+
+/// The username and password of a Basic Authorization header.
+fn basic_credentials(request: &Request) -> Option<(String, String)> {
+    let encoded = request
+        .headers()
+        .get(AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(BASE64.decode(encoded).ok()?).ok()?;
+    let (username, password) = decoded.split_once(':')?;
+    Some((username.to_string(), password.to_string()))
+}
+
+/// Sends the request with a response to the last challenge of the server, or without
+/// credentials if there is none yet, and answers a new challenge once.
+async fn send_digest(
+    client: &Client,
+    request: Request,
+    origin: &str,
+    (username, password): (String, String),
+) -> Result<Response> {
+    let retry = request
+        .try_clone()
+        .context("Digest authentication needs a request body that can be repeated")?;
+
+    let response = send_with_retries(
+        client,
+        authorize(client, request, origin, &username, &password)?,
+    )
+    .await?;
+    if response.status() != StatusCode::UNAUTHORIZED {
+        return Ok(response);
+    }
+    let Some(challenge) = response
+        .headers()
+        .get_all(WWW_AUTHENTICATE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .find(|value| value.starts_with("Digest "))
+    else {
+        return Ok(response);
+    };
+
+    debug!("Answering Digest challenge of {}", origin);
+    let challenge = digest_auth::parse(challenge).context("Invalid Digest challenge")?;
+    client
+        .digest_origins()
+        .insert(origin.to_string(), Some(challenge));
+    send_with_retries(
+        client,
+        authorize(client, retry, origin, &username, &password)?,
+    )
+    .await
+}
+
+/// Adds the Digest Authorization header for the last challenge of the origin, if any.
+fn authorize(
+    client: &Client,
+    mut request: Request,
+    origin: &str,
+    username: &str,
+    password: &str,
+) -> Result<Request> {
+    let mut origins = client.digest_origins();
+    let Some(Some(challenge)) = origins.get_mut(origin) else {
+        return Ok(request);
+    };
+
+    let uri = match request.url().query() {
+        Some(query) => format!("{}?{}", request.url().path(), query),
+        None => request.url().path().to_string(),
+    };
+    let body = request.body().and_then(|body| body.as_bytes());
+    let context = AuthContext::new_with_method(
+        username,
+        password,
+        uri,
+        body,
+        HttpMethod::from(request.method().as_str()),
+    );
+    let answer = challenge
+        .respond(&context)
+        .context("Failed to answer the Digest challenge")?;
+    request.headers_mut().insert(
+        AUTHORIZATION,
+        HeaderValue::from_str(&answer.to_header_string())?,
+    );
+    Ok(request)
+}
+
+async fn send_once(client: &Client, request: Request) -> Result<Response> {
+    let Some(max_body_bytes) = client.settings.trace_body_bytes else {
+        return execute(client, request).await;
+    };
+
+    let method = request.method().clone();
+    let url = request.url().clone();
+    let request_headers = recording::scrub_headers(request.headers());
+    let request_body = request
+        .body()
+        .and_then(|body| body.as_bytes())
+        .map(|bytes| String::from_utf8_lossy(bytes).into_owned());
+
+    let start = Instant::now();
+    let result = execute(client, request).await;
+    let elapsed = start.elapsed();
+    let response = match result {
+        Ok(response) => response,
+        Err(e) => {
+            trace!("{} {} failed after {:?}: {:#}", method, url, elapsed, e);
+            return Err(e);
+        }
+    };
+
+    trace!(
+        "{} {} -> {} in {:?}",
+        method,
+        url,
+        response.status(),
+        elapsed
+    );
+    trace!(
+        "Request headers: {}",
+        request_headers
+            .iter()
+            .map(|(name, value)| format!("{}: {}", name, value))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    if max_body_bytes == 0 {
+        return Ok(response);
+    }
+
+    if let Some(body) = request_body {
+        trace!("Request body:\n{}", truncate(&body, max_body_bytes));
+    }
+    // The body is read for the log, so the response is rebuilt around it
+    let url = response.url().clone();
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = response.bytes().await?;
+    trace!(
+        "Response body:\n{}",
+        truncate(&String::from_utf8_lossy(&body), max_body_bytes)
+    );
+
+    let mut response = ::http::Response::new(body);
+    *response.status_mut() = status;
+    *response.headers_mut() = headers;
+    into_response(response, url)
+}
+
+/// Sends a request with the client, or the [`HttpTransport`] if one is set.
+async fn execute(client: &Client, request: Request) -> Result<Response> {
+    let Some(transport) = &client.transport else {
+        let origin = request.url().origin().ascii_serialization();
+        return Ok(client.client_for(&origin).execute(request).await?);
+    };
+
+    let body = match request.body() {
+        Some(body) => body
+            .as_bytes()
+            .context("Streamed request bodies can't be sent through an HTTP transport")?
+            .to_vec(),
+        None => Vec::new(),
+    };
+    let mut http_request = ::http::Request::new(body);
+    *http_request.method_mut() = request.method().clone();
+    *http_request.uri_mut() = request.url().as_str().parse()?;
+    *http_request.headers_mut() = request.headers().clone();
+
+    into_response(
+        transport.execute(http_request).await?,
+        request.url().clone(),
+    )
+}
+
+/// Converts a response built by hand, which keeps the URL it was received from unlike
+/// `Response::from`.
+fn into_response<T: Into<reqwest::Body>>(
+    response: ::http::Response<T>,
+    url: Url,
+) -> Result<Response> {
+    let (parts, body) = response.into_parts();
+    let mut builder = ::http::Response::builder()
+        .status(parts.status)
+        .version(parts.version)
+        .url(url);
+    if let Some(headers) = builder.headers_mut() {
+        *headers = parts.headers;
+    }
+    Ok(builder.body(body)?.into())
+}
+
+fn truncate(body: &str, max_bytes: usize) -> String {
+    let body = recording::scrub_body(body);
+    if body.len() <= max_bytes {
+        return body;
+    }
+
+    let mut end = max_bytes;
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}... ({} bytes total)", &body[..end], body.len())
+}
+
+fn is_temporary(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
@@ -1,12 +1,19 @@
 use anyhow::{Context, Result, anyhow, bail};
 use icalendar::Calendar;
-use reqwest::{Client, Response};
+use reqwest::Response;
 
-async fn fetch_ics_data(
+use crate::{
+    compat::{self, CompatMode},
+    http::{self, Client},
+};
+
+pub(crate) async fn fetch_ics_data(
     client: &Client,
     url: &str,
     username: Option<String>,
     password: Option<String>,
+    bearer_token: Option<String>,
+    headers: &[(String, String)],
 ) -> Result<Response> {
     let mut request_builder = client.get(url);
 
@@ -14,8 +21,15 @@ async fn fetch_ics_data(
         request_builder = request_builder.basic_auth(ics_username, password);
     }
 
-    let response = request_builder
-        .send()
+    if let Some(token) = bearer_token {
+        request_builder = request_builder.bearer_auth(token);
+    }
+
+    for (name, value) in headers {
+        request_builder = request_builder.header(name, value);
+    }
+
+    let response = http::send(client, request_builder)
         .await
         .with_context(|| format!("Failed to download ICS file. URL: {}", url))?;
 
@@ -35,19 +49,42 @@ pub async fn fetch_and_parse_calendar(
     url: &str,
     username: Option<String>,
     password: Option<String>,
+    bearer_token: Option<String>,
+    headers: &[(String, String)],
+    compat_modes: &[CompatMode],
 ) -> Result<Calendar> {
-    let response = fetch_ics_data(client, url, username, password).await?;
+    let response = fetch_ics_data(client, url, username, password, bearer_token, headers).await?;
+
+    parse_response(response, url, compat_modes).await
+}
 
+pub(crate) async fn parse_response(
+    response: Response,
+    url: &str,
+    compat_modes: &[CompatMode],
+) -> Result<Calendar> {
     let ics_content = response
         .bytes()
         .await
         .with_context(|| format!("Failed to read ICS content. URL: {}", url))?;
 
-    let ics_text = std::str::from_utf8(&ics_content)
+    parse_calendar(&ics_content, url, compat_modes)
+}
+
+pub(crate) fn parse_calendar(
+    ics_content: &[u8],
+    url: &str,
+    compat_modes: &[CompatMode],
+) -> Result<Calendar> {
+    let ics_text = std::str::from_utf8(ics_content)
         .with_context(|| format!("Invalid UTF-8 in ICS content. URL: {}", url))?;
 
-    ics_text
+    let ics_text = compat::preprocess(ics_text, compat_modes);
+
+    let calendar = ics_text
         .parse::<Calendar>()
         .map_err(|e: String| anyhow!(e))
-        .with_context(|| format!("Failed to parse iCalendar content. URL: {}", url))
+        .with_context(|| format!("Failed to parse iCalendar content. URL: {}", url))?;
+
+    Ok(compat::postprocess(calendar, compat_modes))
 }
@@ -0,0 +1,277 @@
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use anyhow::{Context, Result};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Runs whose journals are kept by default, so recent interrupted runs can still be resumed.
+pub const DEFAULT_JOURNAL_KEEP: usize = 20;
+
+/// An action confirmed by the server during a sync run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    Uploaded,
+    Deleted,
+    /// Not synced because it duplicates another event, see `Journal::record_duplicate`.
+    Suppressed,
+}
+
+type ActionSet = HashSet<(String, Action)>;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    uid: String,
+    action: Action,
+    /// The UID of the copy that was kept instead of a suppressed event.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    duplicate_of: Option<String>,
+    /// Written before the request is sent, the action is done once a confirmed entry follows.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pending: bool,
+}
+
+/// Append-only record of a sync run, stored as `<run-id>.jsonl` in the journal directory.
+///
+/// Works as a write-ahead log: every upload and deletion is written as pending before its
+/// request is sent and again as soon as the server confirmed it, each line synced to disk. An
+/// interrupted run can be resumed without repeating the confirmed actions, and the pending
+/// ones without a confirmation are reconciled with the server, see [`Journal::unconfirmed`].
+#[derive(Debug)]
+pub struct Journal {
+    run_id: String,
+    path: PathBuf,
+    file: Mutex<File>,
+    confirmed: ActionSet,
+    unconfirmed: ActionSet,
+}
+
+impl Journal {
+    /// Starts a new run with a random ID.
+    pub fn start(dir: &Path) -> Result<Self> {
+        let run_id = Uuid::new_v4().to_string();
+        fs::create_dir_all(dir).with_context(|| {
+            format!(
+                "Failed to create journal directory. Path: {}",
+                dir.display()
+            )
+        })?;
+        Self::open(
+            journal_path(dir, &run_id),
+            run_id,
+            HashSet::new(),
+            HashSet::new(),
+        )
+    }
+
+    /// Continues an interrupted run, loading the actions it already completed.
+    pub fn resume(dir: &Path, run_id: &str) -> Result<Self> {
+        Uuid::parse_str(run_id).with_context(|| format!("Invalid run ID: {}", run_id))?;
+
+        let path = journal_path(dir, run_id);
+        let content = fs::read_to_string(&path).with_context(|| {
+            format!(
+                "No journal found for run {}. Path: {}",
+                run_id,
+                path.display()
+            )
+        })?;
+        let (confirmed, unconfirmed) = read_entries(run_id, &content);
+
+        Self::open(path, run_id.to_string(), confirmed, unconfirmed)
+    }
+
+    /// The journal of this run for another target calendar, stored as
+    /// `<run-id>.<calendar-id>.jsonl` next to this one. Each target has its own state, as the
+    /// same event is uploaded to and deleted from every target independently.
+    pub fn for_target(&self, calendar_url: &str) -> Result<Self> {
+        let calendar_id: String = calendar_url
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+                _ => '_',
+            })
+            .collect();
+        let path = self
+            .path
+            .with_file_name(format!("{}.{}.jsonl", self.run_id, calendar_id));
+
+        // Exists when an interrupted run is resumed
+        let (confirmed, unconfirmed) = match fs::read_to_string(&path) {
+            Ok(content) => read_entries(&self.run_id, &content),
+            Err(_) => Default::default(),
+        };
+        Self::open(path, self.run_id.clone(), confirmed, unconfirmed)
+    }
+
+    /// Deletes the journals of all but the `keep` most recent runs in the directory, including
+    /// their per-target journals, returning how many runs were removed. Older runs can't be
+    /// resumed afterwards.
+    pub fn prune(dir: &Path, keep: usize) -> Result<usize> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return Ok(0),
+        };
+
+        // The files of each run, and when the run last wrote to one of them
+        let mut runs: HashMap<String, (SystemTime, Vec<PathBuf>)> = HashMap::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let Some(run_id) = name
+                .strip_suffix(".jsonl")
+                .and_then(|stem| stem.split('.').next())
+                .filter(|run_id| Uuid::parse_str(run_id).is_ok())
+            else {
+                continue;
+            };
+            let modified = entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            let run = runs
+                .entry(run_id.to_string())
+                .or_insert((SystemTime::UNIX_EPOCH, Vec::new()));
+            run.0 = run.0.max(modified);
+            run.1.push(path);
+        }
+
+        let mut runs: Vec<_> = runs.into_values().collect();
+        runs.sort_by_key(|(modified, _)| Reverse(*modified));
+        let pruned = runs.len().saturating_sub(keep);
+        for (_, paths) in runs.into_iter().skip(keep) {
+            for path in paths {
+                fs::remove_file(&path).with_context(|| {
+                    format!("Failed to delete old journal. Path: {}", path.display())
+                })?;
+            }
+        }
+        Ok(pruned)
+    }
+
+    fn open(
+        path: PathBuf,
+        run_id: String,
+        confirmed: ActionSet,
+        unconfirmed: ActionSet,
+    ) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open journal. Path: {}", path.display()))?;
+
+        Ok(Self {
+            run_id,
+            path,
+            file: Mutex::new(file),
+            confirmed,
+            unconfirmed,
+        })
+    }
+
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether a previous attempt of this run already completed the action.
+    pub fn is_confirmed(&self, uid: &str, action: Action) -> bool {
+        self.confirmed.contains(&(uid.to_string(), action))
+    }
+
+    /// Actions a previous attempt of this run sent without getting a confirmation, so the
+    /// server may or may not have applied them.
+    pub fn unconfirmed(&self) -> impl Iterator<Item = (&str, Action)> {
+        self.unconfirmed
+            .iter()
+            .map(|(uid, action)| (uid.as_str(), *action))
+    }
+
+    /// Records that the request for an action is about to be sent.
+    pub fn begin(&self, uid: &str, action: Action) -> Result<()> {
+        self.write(Entry {
+            uid: uid.to_string(),
+            action,
+            duplicate_of: None,
+            pending: true,
+        })
+    }
+
+    pub fn record(&self, uid: &str, action: Action) -> Result<()> {
+        self.write(Entry {
+            uid: uid.to_string(),
+            action,
+            duplicate_of: None,
+            pending: false,
+        })
+    }
+
+    /// Records that the event `uid` was left out in favor of `kept_uid`.
+    pub fn record_duplicate(&self, uid: &str, kept_uid: &str) -> Result<()> {
+        self.write(Entry {
+            uid: uid.to_string(),
+            action: Action::Suppressed,
+            duplicate_of: Some(kept_uid.to_string()),
+            pending: false,
+        })
+    }
+
+    fn write(&self, entry: Entry) -> Result<()> {
+        let line = serde_json::to_string(&entry)?;
+
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        // Synced, so a confirmation survives a crash of the whole machine
+        writeln!(file, "{}", line)
+            .and_then(|_| file.sync_data())
+            .with_context(|| format!("Failed to write journal. Path: {}", self.path.display()))
+    }
+}
+
+fn journal_path(dir: &Path, run_id: &str) -> PathBuf {
+    dir.join(format!("{}.jsonl", run_id))
+}
+
+/// The confirmed and the unconfirmed actions of a journal.
+fn read_entries(run_id: &str, content: &str) -> (ActionSet, ActionSet) {
+    let (pending, confirmed): (Vec<Entry>, Vec<Entry>) = content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str::<Entry>(line) {
+            Ok(entry) => Some(entry),
+            // The last line may be cut off if the run was killed while writing it
+            Err(e) => {
+                warn!("Skipping unreadable journal line of run {}: {}", run_id, e);
+                None
+            }
+        })
+        .partition(|entry| entry.pending);
+    let confirmed: ActionSet = confirmed
+        .into_iter()
+        .map(|entry| (entry.uid, entry.action))
+        .collect();
+    let unconfirmed = pending
+        .into_iter()
+        .map(|entry| (entry.uid, entry.action))
+        .filter(|action| !confirmed.contains(action))
+        .collect();
+    (confirmed, unconfirmed)
+}
@@ -1,14 +1,145 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
 
-use anyhow::{Context, Ok, Result};
+use anyhow::{Context, Ok, Result, bail};
 
+use explain::SkipReason;
+use http::Client;
 use icalendar::{Calendar, Component, Event};
-use log::{debug, info};
-use reqwest::Client;
+use log::{debug, info, warn};
+use nextcloud::api::{CalendarObject, TimeRange, Transfer};
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod compat;
 pub mod config;
+pub mod dedupe;
+pub mod digest;
+pub mod dns;
+pub mod explain;
+pub mod freebusy;
+pub mod geo;
+pub mod hooks;
+pub mod hours;
+pub mod http;
 pub mod ics_parser;
+pub mod journal;
+pub mod lock;
 pub mod nextcloud;
+pub mod notify;
+pub mod oauth;
+pub mod ownership;
+pub mod paths;
+pub mod pipeline;
+pub mod progress;
+pub mod recording;
+pub mod recurrence;
+pub mod redact;
+pub mod report;
+pub mod routing;
+#[cfg(feature = "scripting")]
+pub mod script;
+mod shell;
+pub mod source;
+pub mod state;
+pub mod syncback;
+pub mod tasks;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+pub mod timezone;
+pub mod transform;
+pub mod urls;
+pub mod validate;
+pub mod verify;
+pub mod window;
+
+/// Which stale events a sync deletes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Deletions {
+    /// Never delete anything, new events are only added.
+    Disabled,
+    /// Delete synced events that are no longer in the source.
+    #[default]
+    SyncedOnly,
+    /// Like `SyncedOnly`, but only for events inside the sync window.
+    WindowOnly,
+}
+
+impl FromStr for Deletions {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "disabled" => Ok(Self::Disabled),
+            "synced-only" => Ok(Self::SyncedOnly),
+            "window-only" => Ok(Self::WindowOnly),
+            other => bail!("Unknown deletions mode: {}", other),
+        }
+    }
+}
+
+/// Uploads in batches with a pause in between, to not overwhelm small instances.
+#[derive(Debug, Clone, Copy)]
+pub struct UploadBatching {
+    pub size: usize,
+    pub pause: Duration,
+}
+
+/// Options changing how `sync_calendar` applies the diff.
+#[derive(Debug, Clone, Default)]
+pub struct SyncOptions {
+    pub hooks: hooks::Hooks,
+    /// Collect a per-event explanation of the diff into `SyncReport::explanations`.
+    pub explain: bool,
+    /// Add a unified diff of the stored and the new event to the explanation of every update.
+    pub explain_diff: bool,
+    /// Journal of the run, confirmed actions are recorded and skipped when resuming.
+    pub journal: Option<Arc<journal::Journal>>,
+    pub deletions: Deletions,
+    /// The sync window, used by `Deletions::WindowOnly`.
+    pub time_range: TimeRange,
+    /// Stale events are moved to this calendar instead of being deleted.
+    pub archive_calendar_url: Option<String>,
+    /// Upload in batches instead of all at once, e.g. for the initial import.
+    pub upload_batching: Option<UploadBatching>,
+    /// Match new source UIDs to stale synced events with the same summary and times, for
+    /// feeds that regenerate their UIDs on every export.
+    pub relink_renamed_uids: bool,
+    /// Abort before uploading if the calendar would hold more events than this.
+    pub max_calendar_items: Option<usize>,
+    /// Abort before uploading if the new events don't fit into the reported DAV quota.
+    pub check_quota: bool,
+    /// LAST-MODIFIED stamps this close together count as unchanged if the content matches.
+    pub timestamp_tolerance: Duration,
+    /// Fetch the calendar again after the sync and fail if an uploaded event is missing or
+    /// was stored with different content.
+    pub verify: bool,
+    /// Which properties are written by the sync and which are left to the calendar.
+    pub ownership: ownership::PropertyOwnership,
+    /// Leave out source events that duplicate another source event or an event already in
+    /// the calendar, see `dedupe::remove_duplicates`.
+    pub dedupe: bool,
+    /// Properties kept from the Nextcloud copies for events that are uploaded anew, they
+    /// should also be `target_owned` in `ownership`.
+    pub sync_back: Option<Arc<syncback::SyncBack>>,
+    /// PRODID, CALSCALE and calendar name of the uploaded resources.
+    pub calendar_metadata: nextcloud::api::CalendarMetadata,
+    /// Receives the progress of the sync, see `progress::SyncEngine` for a stream of it.
+    pub progress: progress::Progress,
+}
+
+/// Summary of the changes made by a sync.
+#[derive(Debug, Clone, Default)]
+pub struct SyncReport {
+    pub uploaded: usize,
+    pub deleted: usize,
+    pub unchanged: usize,
+    pub explanations: Vec<String>,
+}
 
 fn get_synced_uids(events: &HashMap<String, Event>) -> HashSet<String> {
     events
@@ -18,90 +149,558 @@ fn get_synced_uids(events: &HashMap<String, Event>) -> HashSet<String> {
         .collect()
 }
 
-fn calculate_diff<'a>(
+/// Splits the source events into the ones to upload and the synced UIDs to delete.
+pub fn calculate_diff<'a>(
     source_events: &'a HashMap<String, Event>,
     nextcloud_events: &HashMap<String, Event>,
+    options: &SyncOptions,
 ) -> (Vec<&'a Event>, HashSet<String>) {
     let mut events_to_upload = Vec::new();
-    let mut uids_to_delete: HashSet<String> = get_synced_uids(nextcloud_events);
+    let mut uids_to_delete: HashSet<String> = match options.deletions {
+        Deletions::Disabled => HashSet::new(),
+        Deletions::SyncedOnly => get_synced_uids(nextcloud_events),
+        Deletions::WindowOnly => get_synced_uids(nextcloud_events)
+            .into_iter()
+            .filter(|uid| window::overlaps(&nextcloud_events[uid], &options.time_range))
+            .collect(),
+    };
 
     debug!("Calculating sync diff...");
     for (uid, source_event) in source_events {
         uids_to_delete.remove(uid);
 
-        if let Some(existing_event) = nextcloud_events.get(uid) {
-            if nextcloud::api::should_skip(source_event, existing_event) {
-                debug!("Skipping unchanged event with UID: {}", uid);
-                continue;
-            }
+        if let Some(existing_event) = nextcloud_events.get(uid)
+            && skip_reason(source_event, existing_event, options).is_some()
+        {
+            debug!("Skipping unchanged event with UID: {}", uid);
+            continue;
         }
         events_to_upload.push(source_event);
     }
     (events_to_upload, uids_to_delete)
 }
 
+/// Why the existing copy of an event is up to date, if it is.
+fn skip_reason(
+    source_event: &Event,
+    existing_event: &Event,
+    options: &SyncOptions,
+) -> Option<SkipReason> {
+    let last_modified = source_event.get_last_modified();
+    if last_modified.is_some() && last_modified == existing_event.get_last_modified() {
+        Some(SkipReason::Unchanged)
+    } else if nextcloud::api::should_skip(source_event, existing_event, options.timestamp_tolerance)
+    {
+        Some(SkipReason::WithinTolerance)
+    } else if options.ownership.is_configured()
+        && options
+            .ownership
+            .same_owned_content(source_event, existing_event)
+    {
+        Some(SkipReason::OwnedUnchanged)
+    } else {
+        None
+    }
+}
+
+/// What identifies an event when its UID can't be trusted.
+fn event_fingerprint(event: &Event) -> Option<(String, String, String)> {
+    Some((
+        event.get_summary()?.to_string(),
+        event.property_value("DTSTART")?.to_string(),
+        event
+            .property_value("DTEND")
+            .or_else(|| event.property_value("DURATION"))
+            .unwrap_or_default()
+            .to_string(),
+    ))
+}
+
+/// Gives source events the UID of the synced event they replace, so it is updated in place
+/// instead of deleted and created again.
+///
+/// Only fingerprints that are unique on both sides are matched.
+fn relink_renamed_uids(
+    source_events: &mut HashMap<String, Event>,
+    nextcloud_events: &HashMap<String, Event>,
+) {
+    let unique_fingerprints = |events: Vec<(&String, &Event)>| {
+        let mut by_fingerprint: HashMap<(String, String, String), Option<String>> = HashMap::new();
+        for (uid, event) in events {
+            if let Some(fingerprint) = event_fingerprint(event) {
+                by_fingerprint
+                    .entry(fingerprint)
+                    .and_modify(|uid| *uid = None)
+                    .or_insert_with(|| Some(uid.clone()));
+            }
+        }
+        by_fingerprint
+            .into_iter()
+            .filter_map(|(fingerprint, uid)| Some((fingerprint, uid?)))
+            .collect::<HashMap<_, _>>()
+    };
+
+    let new_source_events = unique_fingerprints(
+        source_events
+            .iter()
+            .filter(|(uid, _)| !nextcloud_events.contains_key(*uid))
+            .collect(),
+    );
+    let orphaned_nextcloud_events = unique_fingerprints(
+        nextcloud_events
+            .iter()
+            .filter(|(uid, event)| {
+                !source_events.contains_key(*uid) && event.property_value("X-SYNCED").is_some()
+            })
+            .collect(),
+    );
+
+    for (fingerprint, source_uid) in new_source_events {
+        let Some(nextcloud_uid) = orphaned_nextcloud_events.get(&fingerprint) else {
+            continue;
+        };
+        if let Some(mut event) = source_events.remove(&source_uid) {
+            debug!("Relinking renamed UID {} to {}", source_uid, nextcloud_uid);
+            event.uid(nextcloud_uid);
+            source_events.insert(nextcloud_uid.clone(), event);
+        }
+    }
+}
+
+/// Sets SEQUENCE above the one of the existing copy, so clients recognize the update.
+///
+/// Many feeds never increase SEQUENCE themselves, a higher source value is kept.
+fn bump_sequence(event: &mut Event, existing_event: Option<&Event>) {
+    let Some(existing_sequence) = existing_event.map(|e| e.get_sequence().unwrap_or_default())
+    else {
+        return;
+    };
+
+    let sequence = event.get_sequence().unwrap_or_default();
+    if sequence <= existing_sequence {
+        event.sequence(existing_sequence + 1);
+    }
+}
+
+// Warn once a sync leaves less than this share of the quota free
+const QUOTA_WARNING_SHARE: f64 = 0.1;
+
+/// Fails before anything is uploaded if the upload would exceed the item limit or the quota,
+/// instead of leaving a half-completed import behind.
+#[allow(clippy::too_many_arguments)]
+async fn check_capacity(
+    client: &Client,
+    nextcloud_username: &str,
+    nextcloud_password: &str,
+    nextcloud_calendar_url: &str,
+    nextcloud_events: &HashMap<String, Event>,
+    events_to_upload: &[&Event],
+    deleted: usize,
+    options: &SyncOptions,
+) -> Result<()> {
+    let new_events: Vec<&&Event> = events_to_upload
+        .iter()
+        .filter(|event| {
+            event
+                .get_uid()
+                .is_none_or(|uid| !nextcloud_events.contains_key(uid))
+        })
+        .collect();
+
+    if let Some(max_items) = options.max_calendar_items {
+        let items = (nextcloud_events.len() + new_events.len()).saturating_sub(deleted);
+        if items > max_items {
+            bail!(
+                "The calendar would hold {} events after the sync, more than MAX_CALENDAR_ITEMS={}",
+                items,
+                max_items
+            );
+        }
+    }
+
+    if !options.check_quota || new_events.is_empty() {
+        return Ok(());
+    }
+    let quota = match nextcloud::api::get_quota(
+        client,
+        nextcloud_calendar_url,
+        nextcloud_username,
+        nextcloud_password,
+    )
+    .await
+    {
+        Result::Ok(quota) => quota,
+        Err(e) => {
+            warn!("Could not check the quota, uploading anyway: {:#}", e);
+            return Ok(());
+        }
+    };
+    let Some(available) = quota.available_bytes else {
+        debug!("The server reports no quota for the calendar");
+        return Ok(());
+    };
+
+    let required: u64 = new_events
+        .iter()
+        .map(|event| {
+            let calendar = options.calendar_metadata.calendar((**event).clone());
+            calendar.to_string().len() as u64
+        })
+        .sum();
+    if required > available {
+        bail!(
+            "Uploading {} new events needs about {} bytes, but only {} bytes of quota are left",
+            new_events.len(),
+            required,
+            available
+        );
+    }
+    let total = available + quota.used_bytes.unwrap_or_default();
+    if ((available - required) as f64) < total as f64 * QUOTA_WARNING_SHARE {
+        warn!(
+            "The sync leaves only {} of {} bytes of quota free",
+            available - required,
+            total
+        );
+    }
+    Ok(())
+}
+
+fn explain_diff(
+    source_events: &HashMap<String, Event>,
+    nextcloud_events: &HashMap<String, Event>,
+    events_to_upload: &[&Event],
+    uids_to_delete: &HashSet<String>,
+    options: &SyncOptions,
+) -> Vec<String> {
+    let uploaded: HashSet<&str> = events_to_upload
+        .iter()
+        .filter_map(|event| event.get_uid())
+        .collect();
+
+    let mut uids: Vec<&String> = source_events.keys().collect();
+    uids.sort();
+
+    let mut explanations: Vec<String> = uids
+        .into_iter()
+        .map(|uid| {
+            let source_event = &source_events[uid];
+            if uploaded.contains(uid.as_str()) {
+                let existing_event = nextcloud_events.get(uid);
+                let explanation = explain::explain_upload(uid, source_event, existing_event);
+                match existing_event {
+                    Some(existing_event) if options.explain_diff => format!(
+                        "{}\n{}",
+                        explanation,
+                        explain::unified_diff(existing_event, source_event)
+                    ),
+                    _ => explanation,
+                }
+            } else {
+                let existing_event = nextcloud_events.get(uid);
+                // Events the diff would upload are only left out when the journal confirmed them
+                let reason = existing_event
+                    .and_then(|existing_event| skip_reason(source_event, existing_event, options))
+                    .unwrap_or(SkipReason::JournalConfirmed);
+                explain::explain_skip(uid, source_event, existing_event, reason)
+            }
+        })
+        .collect();
+
+    let mut deleted: Vec<&String> = uids_to_delete.iter().collect();
+    deleted.sort();
+    explanations.extend(deleted.into_iter().map(|uid| explain::explain_delete(uid)));
+    explanations
+}
+
+/// Confirms the uploads an interrupted attempt of the run sent without getting an answer, if
+/// the calendar holds the copy they uploaded. All other unconfirmed actions are left to the
+/// diff against the fresh calendar, so they are only repeated if they didn't reach the server.
+fn reconcile_journal(
+    journal: &journal::Journal,
+    nextcloud_events: &HashMap<String, Event>,
+) -> Result<()> {
+    for (uid, action) in journal.unconfirmed() {
+        let stored_by_run = nextcloud_events
+            .get(uid)
+            .and_then(|event| event.property_value("X-SYNC-RUN"))
+            == Some(journal.run_id());
+        if action == journal::Action::Uploaded && stored_by_run {
+            debug!(
+                "Upload of {} reached the server before the interruption",
+                uid
+            );
+            journal.record(uid, action)?;
+        }
+    }
+    Ok(())
+}
+
 pub async fn sync_calendar(
     client: &Client,
     nextcloud_username: &str,
     nextcloud_password: &str,
     nextcloud_calendar_url: &str,
     source_calendar: Calendar,
-    nextcloud_calendar: Calendar,
-) -> Result<()> {
-    let source_events = nextcloud::api::extract_events(source_calendar, true);
-    let nextcloud_events = nextcloud::api::extract_events(nextcloud_calendar, false);
+    nextcloud_objects: Vec<CalendarObject>,
+    options: &SyncOptions,
+) -> Result<SyncReport> {
+    let hrefs = nextcloud::api::hrefs_by_uid(&nextcloud_objects);
+    let mut source_events = nextcloud::api::extract_events(source_calendar, true);
+    let nextcloud_events =
+        nextcloud::api::extract_events(nextcloud::api::merge_objects(nextcloud_objects), false);
+
+    if let Some(journal) = &options.journal {
+        reconcile_journal(journal, &nextcloud_events)?;
+    }
+
+    if let Some(sync_back) = &options.sync_back {
+        sync_back.collect(&nextcloud_events);
+        sync_back.save()?;
+    }
 
-    let (events_to_upload, uids_to_delete) = calculate_diff(&source_events, &nextcloud_events);
+    if options.relink_renamed_uids {
+        relink_renamed_uids(&mut source_events, &nextcloud_events);
+    }
+
+    if options.dedupe {
+        for duplicate in dedupe::remove_duplicates(&mut source_events, &nextcloud_events) {
+            info!(
+                "Skipping {}, a duplicate of {}",
+                duplicate.uid, duplicate.kept_uid
+            );
+            if let Some(journal) = &options.journal {
+                journal.record_duplicate(&duplicate.uid, &duplicate.kept_uid)?;
+            }
+        }
+    }
+
+    let (mut events_to_upload, mut uids_to_delete) =
+        calculate_diff(&source_events, &nextcloud_events, options);
+
+    if let Some(journal) = &options.journal {
+        events_to_upload.retain(|event| {
+            let uid = event.get_uid().unwrap_or_default();
+            !journal.is_confirmed(uid, journal::Action::Uploaded)
+        });
+        uids_to_delete.retain(|uid| !journal.is_confirmed(uid, journal::Action::Deleted));
+    }
+    options.progress.emit(progress::SyncEvent::Diffed {
+        uploads: events_to_upload.len(),
+        deletions: uids_to_delete.len(),
+    });
+
+    let explanations = if options.explain {
+        explain_diff(
+            &source_events,
+            &nextcloud_events,
+            &events_to_upload,
+            &uids_to_delete,
+            options,
+        )
+    } else {
+        Vec::new()
+    };
+
+    let report = SyncReport {
+        uploaded: events_to_upload.len(),
+        deleted: uids_to_delete.len(),
+        unchanged: source_events.len() - events_to_upload.len(),
+        explanations,
+    };
+
+    check_capacity(
+        client,
+        nextcloud_username,
+        nextcloud_password,
+        nextcloud_calendar_url,
+        &nextcloud_events,
+        &events_to_upload,
+        uids_to_delete.len(),
+        options,
+    )
+    .await?;
 
+    let mut uploaded_events = Vec::new();
     if !events_to_upload.is_empty() {
         info!(
             "Uploading {} new/modified events...",
             events_to_upload.len()
         );
 
-        let owned_events_to_upload = events_to_upload.clone().into_iter().cloned().collect();
-        nextcloud::api::handle_uploads(
+        let mut owned_events_to_upload: Vec<Event> = events_to_upload
+            .iter()
+            .map(|event| {
+                let existing_event = event.get_uid().and_then(|uid| nextcloud_events.get(uid));
+                let mut event = options.ownership.merge(event, existing_event);
+                if let (None, Some(sync_back)) = (existing_event, &options.sync_back) {
+                    event = sync_back.restore(&event);
+                }
+                bump_sequence(&mut event, existing_event);
+                if let Some(journal) = &options.journal {
+                    event.add_property("X-SYNC-RUN", journal.run_id());
+                }
+                event
+            })
+            .collect();
+        if options.verify {
+            uploaded_events = owned_events_to_upload.clone();
+        }
+
+        let total = owned_events_to_upload.len();
+        let batch_size = options
+            .upload_batching
+            .map_or(total, |batching| batching.size.max(1));
+        while !owned_events_to_upload.is_empty() {
+            let rest =
+                owned_events_to_upload.split_off(batch_size.min(owned_events_to_upload.len()));
+            nextcloud::api::handle_uploads(
+                client,
+                nextcloud_username,
+                nextcloud_password,
+                nextcloud_calendar_url,
+                owned_events_to_upload,
+                &hrefs,
+                &options.calendar_metadata,
+                options.journal.as_ref(),
+                &options.progress,
+            )
+            .await
+            .context("Failed to upload events")?;
+            owned_events_to_upload = rest;
+
+            if let (false, Some(batching)) =
+                (owned_events_to_upload.is_empty(), options.upload_batching)
+            {
+                info!(
+                    "Uploaded {}/{} events, pausing for {:?}...",
+                    total - owned_events_to_upload.len(),
+                    total,
+                    batching.pause
+                );
+                tokio::time::sleep(batching.pause).await;
+            }
+        }
+
+        for event in &events_to_upload {
+            options.hooks.on_event_upload(event).await;
+        }
+    } else {
+        info!("No new or modified events to upload.");
+    }
+
+    if let (false, Some(archive_calendar_url)) =
+        (uids_to_delete.is_empty(), &options.archive_calendar_url)
+    {
+        info!("Archiving {} stale events...", uids_to_delete.len());
+        nextcloud::api::handle_transfers(
             client,
             nextcloud_username,
             nextcloud_password,
             nextcloud_calendar_url,
-            owned_events_to_upload,
+            archive_calendar_url,
+            uids_to_delete.clone(),
+            &hrefs,
+            Transfer::Move,
+            options.journal.as_ref(),
+            &options.progress,
         )
         .await
-        .context("Failed to upload events")?;
-    } else {
-        info!("No new or modified events to upload.");
-    }
+        .context("Failed to archive events")?;
 
-    if !uids_to_delete.is_empty() {
+        for uid in &uids_to_delete {
+            options
+                .hooks
+                .on_event_delete(uid, nextcloud_events.get(uid))
+                .await;
+        }
+    } else if !uids_to_delete.is_empty() {
         info!("Deleting {} stale events...", uids_to_delete.len());
         nextcloud::api::handle_deletes(
             client,
             nextcloud_username,
             nextcloud_password,
             nextcloud_calendar_url,
-            uids_to_delete,
+            uids_to_delete.clone(),
+            &hrefs,
+            options.journal.as_ref(),
+            &options.progress,
         )
         .await
         .context("Failed to delete events")?;
+
+        for uid in &uids_to_delete {
+            options
+                .hooks
+                .on_event_delete(uid, nextcloud_events.get(uid))
+                .await;
+        }
     } else {
         info!("No stale events to delete.");
     }
 
+    if !uploaded_events.is_empty() {
+        verify_uploads(
+            client,
+            nextcloud_username,
+            nextcloud_password,
+            nextcloud_calendar_url,
+            &uploaded_events,
+        )
+        .await?;
+    }
+
     info!("Calendar sync complete. ✅");
-    Ok(())
+    Ok(report)
+}
+
+/// Fails if the calendar doesn't hold the uploaded events as they were sent, for servers that
+/// acknowledge uploads but mangle the data.
+async fn verify_uploads(
+    client: &Client,
+    nextcloud_username: &str,
+    nextcloud_password: &str,
+    nextcloud_calendar_url: &str,
+    uploaded_events: &[Event],
+) -> Result<()> {
+    info!("Verifying {} uploaded events...", uploaded_events.len());
+    let nextcloud_objects = nextcloud::api::get_calendar_objects(
+        client,
+        nextcloud_calendar_url,
+        nextcloud_username,
+        nextcloud_password,
+        TimeRange::default(),
+    )
+    .await
+    .context("Failed to fetch the calendar for verification")?;
+
+    let discrepancies = verify::verify_uploads(uploaded_events, nextcloud_objects);
+    if discrepancies.is_empty() {
+        info!("All uploaded events verified.");
+        return Ok(());
+    }
+    for discrepancy in &discrepancies {
+        warn!("Verification failed for {}", discrepancy);
+    }
+    bail!(
+        "{} of {} uploaded events failed verification",
+        discrepancies.len(),
+        uploaded_events.len()
+    )
 }
 
 pub async fn delete_synced_events(
     client: &Client,
-    nextcloud_calendar: Calendar,
+    nextcloud_objects: Vec<CalendarObject>,
     nextcloud_calendar_url: &str,
     username: &str,
     password: &str,
 ) -> Result<()> {
     info!("Deleting all synced events...");
 
-    let nextcloud_events = nextcloud::api::extract_events(nextcloud_calendar, false);
+    let hrefs = nextcloud::api::hrefs_by_uid(&nextcloud_objects);
+    let nextcloud_events =
+        nextcloud::api::extract_events(nextcloud::api::merge_objects(nextcloud_objects), false);
     let uids_to_delete: HashSet<String> = get_synced_uids(&nextcloud_events);
 
     nextcloud::api::handle_deletes(
@@ -110,6 +709,47 @@ pub async fn delete_synced_events(
         password,
         nextcloud_calendar_url,
         uids_to_delete,
+        &hrefs,
+        None,
+        &progress::Progress::default(),
     )
     .await
 }
+
+/// Moves or copies all synced events into another calendar, keeping them unchanged.
+pub async fn migrate_synced_events(
+    client: &Client,
+    nextcloud_objects: Vec<CalendarObject>,
+    nextcloud_calendar_url: &str,
+    destination_calendar_url: &str,
+    username: &str,
+    password: &str,
+    transfer: Transfer,
+) -> Result<usize> {
+    info!(
+        "Migrating all synced events to {}...",
+        destination_calendar_url
+    );
+
+    let hrefs = nextcloud::api::hrefs_by_uid(&nextcloud_objects);
+    let nextcloud_events =
+        nextcloud::api::extract_events(nextcloud::api::merge_objects(nextcloud_objects), false);
+    let uids_to_move: HashSet<String> = get_synced_uids(&nextcloud_events);
+    let count = uids_to_move.len();
+
+    nextcloud::api::handle_transfers(
+        client,
+        username,
+        password,
+        nextcloud_calendar_url,
+        destination_calendar_url,
+        uids_to_move,
+        &hrefs,
+        transfer,
+        None,
+        &progress::Progress::default(),
+    )
+    .await?;
+
+    Ok(count)
+}
@@ -0,0 +1,160 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    process,
+    time::Duration,
+};
+
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{nextcloud::utils::stable_hash, paths};
+
+// How often a waiting run checks whether the lock was released
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+pub const DEFAULT_STALE_AFTER: Duration = Duration::from_secs(6 * 60 * 60);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Owner {
+    pid: u32,
+    /// Unix timestamp
+    started: i64,
+}
+
+impl Owner {
+    fn started(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp(self.started, 0).unwrap_or_default()
+    }
+}
+
+enum Attempt {
+    Acquired(SyncLock),
+    Held(Owner),
+}
+
+/// Keeps other runs from syncing into the same calendar until it is dropped.
+///
+/// The lock is a file created exclusively in the state directory. A lock left behind by a
+/// killed run is taken over once its process is gone or it is older than `stale_after`.
+#[derive(Debug)]
+pub struct SyncLock {
+    path: PathBuf,
+}
+
+impl SyncLock {
+    /// The lock file of a target calendar, so runs of different configurations for the same
+    /// calendar exclude each other.
+    pub fn path_for(calendar_url: &str) -> PathBuf {
+        paths::state_dir()
+            .join("locks")
+            .join(format!("{}.lock", stable_hash(calendar_url)))
+    }
+
+    /// Takes the lock, failing if another run holds it unless `wait` is set.
+    pub async fn acquire(path: &Path, wait: bool, stale_after: Duration) -> Result<Self> {
+        let mut waiting = false;
+        loop {
+            match Self::try_acquire(path, stale_after)? {
+                Attempt::Acquired(lock) => return Ok(lock),
+                Attempt::Held(owner) if !wait => bail!(
+                    "Another sync of this calendar is running since {} (PID {}), use --wait to \
+                     wait for it. Lock file: {}",
+                    owner.started(),
+                    owner.pid,
+                    path.display()
+                ),
+                Attempt::Held(owner) => {
+                    if !waiting {
+                        info!(
+                            "Waiting for the sync running since {} (PID {})...",
+                            owner.started(),
+                            owner.pid
+                        );
+                        waiting = true;
+                    }
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                }
+            }
+        }
+    }
+
+    fn try_acquire(path: &Path, stale_after: Duration) -> Result<Attempt> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).with_context(|| {
+                format!("Failed to create lock directory. Path: {}", dir.display())
+            })?;
+        }
+
+        match OpenOptions::new().write(true).create_new(true).open(path) {
+            Ok(mut file) => {
+                let owner = Owner {
+                    pid: process::id(),
+                    started: Utc::now().timestamp(),
+                };
+                serde_json::to_writer(&mut file, &owner)?;
+                file.flush()?;
+                Ok(Attempt::Acquired(Self {
+                    path: path.to_path_buf(),
+                }))
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                let owner = fs::read_to_string(path)
+                    .ok()
+                    .and_then(|content| serde_json::from_str::<Owner>(&content).ok());
+                match owner {
+                    Some(owner) if !is_stale(&owner, stale_after) => Ok(Attempt::Held(owner)),
+                    // The owner may not have written its PID yet
+                    None if is_fresh(path) => Ok(Attempt::Held(Owner {
+                        pid: 0,
+                        started: Utc::now().timestamp(),
+                    })),
+                    _ => {
+                        warn!("Removing stale lock file {}", path.display());
+                        fs::remove_file(path).with_context(|| {
+                            format!("Failed to remove stale lock. Path: {}", path.display())
+                        })?;
+                        Self::try_acquire(path, stale_after)
+                    }
+                }
+            }
+            Err(e) => Err(e)
+                .with_context(|| format!("Failed to create lock file. Path: {}", path.display())),
+        }
+    }
+}
+
+impl Drop for SyncLock {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            warn!("Failed to remove lock file {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+fn is_stale(owner: &Owner, stale_after: Duration) -> bool {
+    let age = (Utc::now() - owner.started()).to_std().unwrap_or_default();
+    age > stale_after || !process_exists(owner.pid)
+}
+
+fn is_fresh(path: &Path) -> bool {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age < POLL_INTERVAL)
+}
+
+// Only Linux offers a cheap check without extra dependencies, elsewhere the age decides
+#[cfg(target_os = "linux")]
+fn process_exists(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn process_exists(_pid: u32) -> bool {
+    true
+}
@@ -1,12 +1,28 @@
 use anyhow::{Context, Ok, Result};
+use chrono::{DateTime, NaiveDate, NaiveTime, SecondsFormat, Utc};
 use clap::{Parser, Subcommand};
 use dotenv::dotenv;
-use log::info;
+use log::{LevelFilter, info};
 use nextcloud_ics_sync::{
+    Deletions, SyncOptions,
     config::{self, Config},
-    ics_parser, nextcloud, sync_calendar,
+    digest, freebusy,
+    http::{self, Client, HttpConfig},
+    journal::Journal,
+    lock::SyncLock,
+    nextcloud::{self, api::Transfer, credentials::PasswordStore},
+    oauth, paths, pipeline,
+    progress::SyncEngine,
+    recording::Recorder,
+    redact, report, state, sync_calendar,
+    syncback::SyncBack,
+};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::Arc,
 };
-use reqwest::Client;
 
 // TODO: Merge Calenders (internal and external)
 
@@ -16,43 +32,279 @@ use reqwest::Client;
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+
+    /// Print for every event why it is uploaded, skipped or deleted
+    #[arg(long, global = true)]
+    explain: bool,
+
+    /// Like --explain, with a unified diff of the stored and the new ICS text of every
+    /// updated event
+    #[arg(long, global = true)]
+    explain_diff: bool,
+
+    /// Save all HTTP requests and responses with scrubbed credentials to this directory
+    #[arg(long, global = true, value_name = "DIR", conflicts_with = "replay")]
+    record: Option<PathBuf>,
+
+    /// Answer all HTTP requests from a directory created with `--record`
+    #[arg(long, global = true, value_name = "DIR")]
+    replay: Option<PathBuf>,
+
+    /// Load the configuration from this file instead of `.env` and the default config file
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Log every HTTP request with status and timing, and up to BODY_BYTES of the bodies
+    #[arg(
+        long,
+        global = true,
+        value_name = "BODY_BYTES",
+        num_args = 0..=1,
+        default_missing_value = "2048"
+    )]
+    trace_http: Option<usize>,
+
+    /// Wait for a running sync of the same calendar to finish instead of failing
+    #[arg(long, global = true, overrides_with = "no_wait")]
+    wait: bool,
+
+    /// Fail immediately if another sync of the same calendar is running [DEFAULT]
+    #[arg(long, global = true)]
+    no_wait: bool,
+
+    /// Directory for journals and tokens, instead of the platform state directory
+    #[arg(long, global = true, value_name = "DIR")]
+    state_dir: Option<PathBuf>,
 }
 
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Synchronizes events with the calendar provider [DEFAULT]
-    Sync,
+    Sync {
+        /// Resume an interrupted run, skipping the events it already uploaded or deleted
+        #[arg(long, value_name = "RUN_ID")]
+        resume: Option<String>,
+
+        /// Upload in batches with pauses (IMPORT_BATCH_SIZE, IMPORT_BATCH_PAUSE_SECS), for the
+        /// first sync of a huge calendar
+        #[arg(long)]
+        initial_import: bool,
+        /// Fetch the calendar again afterwards and check that every uploaded event was stored
+        /// unchanged (VERIFY_UPLOADS)
+        #[arg(long)]
+        verify: bool,
+        /// Sync the last successfully fetched copy of the source if it can't be fetched
+        /// (USE_SOURCE_CACHE_ON_FAILURE, SOURCE_CACHE_MAX_AGE_SECS)
+        #[arg(long)]
+        use_cache_on_failure: bool,
+    },
     /// Fetch available calendar ids (alias `fetch`)
     #[clap(alias = "fetch")]
     FetchCalendars,
     /// Delete all synced events (alias `delete`)
     #[clap(alias = "delete")]
     DeleteSyncedEvents,
+    /// Move all synced events to another calendar, e.g. after renaming the target calendar
+    Migrate {
+        /// ID of the calendar to move the events to
+        #[arg(long, value_name = "CALENDAR_ID")]
+        to: String,
+        /// Copy the events instead, keeping them in the current calendar as well
+        #[arg(long)]
+        copy: bool,
+    },
+    /// Compare the source with the Nextcloud calendar without changing anything and print
+    /// drift statistics
+    Report,
+    /// Email the synced events of the next days (DIGEST_DAYS, default 7)
+    Digest {
+        /// Print the digest instead of sending it
+        #[arg(long)]
+        print: bool,
+    },
+    /// Publish the combined busy times of AVAILABILITY_ATTENDEES into
+    /// AVAILABILITY_CALENDAR_ID
+    MirrorAvailability,
+    /// Print the busy times of the calendar, one `<start> <end> <type>` line per period
+    Freebusy {
+        /// Start of the range, a date like `2024-03-04` or a time like `2024-03-04T09:00:00Z`
+        #[arg(value_parser = parse_time)]
+        start: DateTime<Utc>,
+        /// End of the range, exclusive
+        #[arg(value_parser = parse_time)]
+        end: DateTime<Utc>,
+    },
+    /// Authorize access to an OAuth protected source feed (alias `login`)
+    #[clap(alias = "login")]
+    OauthLogin,
+    /// Replace the Nextcloud app password by a new one and store it where the old one was
+    /// configured
+    RotatePassword,
+    /// Move the journals and tokens to another machine without a full re-sync
+    State {
+        #[command(subcommand)]
+        command: StateCommands,
+    },
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    dotenv().ok();
-    env_logger::init();
+#[derive(Subcommand, Debug)]
+enum StateCommands {
+    /// Write the state into a portable archive
+    Export {
+        /// File to write the archive to
+        path: PathBuf,
+    },
+    /// Restore the state from an archive written by `state export`
+    Import {
+        /// Archive to restore
+        path: PathBuf,
+        /// Replace state files that already exist
+        #[arg(long)]
+        force: bool,
+    },
+}
 
+#[tokio::main]
+async fn main() -> ExitCode {
     let cli = Cli::parse();
-    let client = Client::new();
+
+    match run(cli).await {
+        Result::Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            // Error contexts contain URLs, which may carry tokens
+            eprintln!("Error: {}", redact::text(&format!("{:?}", e)));
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    match &cli.config {
+        Some(path) => dotenv::from_path(path)
+            .with_context(|| format!("Failed to load config file. Path: {}", path.display()))?,
+        None => {
+            // Variables of the working directory's `.env` win over the config file
+            dotenv().ok();
+            dotenv::from_path(paths::config_file()).ok();
+        }
+    }
+    redact::set_enabled(config::load_redact_secrets()?);
+    let mut logger = env_logger::Builder::from_default_env();
+    if cli.trace_http.is_some() {
+        logger.filter_module("nextcloud_ics_sync::http", LevelFilter::Trace);
+    }
+    logger
+        .format(|buf, record| {
+            writeln!(
+                buf,
+                "[{} {:<5} {}] {}",
+                buf.timestamp(),
+                record.level(),
+                record.target(),
+                redact::text(&record.args().to_string())
+            )
+        })
+        .init();
+
+    if let Some(dir) = &cli.state_dir {
+        paths::set_state_dir(dir);
+    }
+    let mut client = http::init(&HttpConfig {
+        trace_body_bytes: cli.trace_http,
+        ..config::load_http_config()?
+    })?;
+
+    if let Some(dir) = &cli.record {
+        let recorder = Recorder::record(dir, client.clone())?;
+        client = client.with_transport(Arc::new(recorder));
+    }
+    if let Some(dir) = &cli.replay {
+        client = client.with_transport(Arc::new(Recorder::replay(dir)?));
+    }
 
     match cli.command {
         Some(Commands::FetchCalendars) => print_available_calendar_ids(&client).await,
-        Some(Commands::DeleteSyncedEvents) => delete_synced_events(&client).await,
-        None | Some(Commands::Sync) => sync_calendars(&client).await,
+        Some(Commands::DeleteSyncedEvents) => delete_synced_events(&client, cli.wait).await,
+        Some(Commands::Migrate { to, copy }) => {
+            let transfer = match copy {
+                true => Transfer::Copy,
+                false => Transfer::Move,
+            };
+            migrate_synced_events(&client, &to, transfer, cli.wait).await
+        }
+        Some(Commands::Freebusy { start, end }) => print_free_busy(&client, start, end).await,
+        Some(Commands::MirrorAvailability) => mirror_availability(&client, cli.wait).await,
+        Some(Commands::OauthLogin) => oauth_login(&client).await,
+        Some(Commands::Report) => print_drift_report(&client).await,
+        Some(Commands::Digest { print }) => send_digest(&client, print).await,
+        Some(Commands::State { command }) => transfer_state(command),
+        Some(Commands::RotatePassword) => rotate_password(&client, cli.config.as_deref()).await,
+        None => {
+            sync_calendars(
+                &client,
+                cli.explain,
+                cli.explain_diff,
+                cli.wait,
+                None,
+                false,
+                false,
+                false,
+            )
+            .await
+        }
+        Some(Commands::Sync {
+            resume,
+            initial_import,
+            verify,
+            use_cache_on_failure,
+        }) => {
+            sync_calendars(
+                &client,
+                cli.explain,
+                cli.explain_diff,
+                cli.wait,
+                resume,
+                initial_import,
+                verify,
+                use_cache_on_failure,
+            )
+            .await
+        }
     }
 }
 
-async fn delete_synced_events(client: &Client) -> Result<()> {
+/// Keeps overlapping runs, e.g. of a slow sync and the next cron invocation, from changing
+/// the calendar at the same time.
+async fn lock_calendar(config: &Config, calendar_url: &str, wait: bool) -> Result<SyncLock> {
+    SyncLock::acquire(
+        &SyncLock::path_for(calendar_url),
+        wait,
+        config.lock_stale_after,
+    )
+    .await
+}
+
+async fn delete_synced_events(client: &Client, wait: bool) -> Result<()> {
     let config = Config::from_env()?;
+    let _lock = lock_calendar(&config, &config.nextcloud_calendar_url, wait).await?;
 
-    let nextcloud_calendar = get_nextcloud_calendar(client, &config).await?;
+    let event_index = nextcloud::api::get_event_index(
+        client,
+        &config.nextcloud_calendar_url,
+        &config.nextcloud_username,
+        &config.nextcloud_password,
+    )
+    .await
+    .with_context(|| {
+        format!(
+            "Failed to look up events of current calendar. URL: {}",
+            &config.nextcloud_calendar_url
+        )
+    })?;
 
     nextcloud_ics_sync::delete_synced_events(
         client,
-        nextcloud_calendar,
+        event_index,
         &config.nextcloud_calendar_url,
         &config.nextcloud_username,
         &config.nextcloud_password,
@@ -60,46 +312,409 @@ async fn delete_synced_events(client: &Client) -> Result<()> {
     .await
 }
 
-async fn sync_calendars(client: &Client) -> Result<()> {
+async fn migrate_synced_events(
+    client: &Client,
+    calendar_id: &str,
+    transfer: Transfer,
+    wait: bool,
+) -> Result<()> {
     let config = Config::from_env()?;
+    let _lock = lock_calendar(&config, &config.nextcloud_calendar_url, wait).await?;
+    let destination_url = config::calendar_url(
+        &config.nextcloud_url,
+        &config.nextcloud_username,
+        calendar_id,
+    );
 
-    info!("Downloading source calendar from {}...", config.ics_url);
-
-    let source_calendar = ics_parser::fetch_and_parse_calendar(
-        &client,
-        &config.ics_url,
-        config.ics_username.clone(),
-        config.ics_password.clone(),
+    let event_index = nextcloud::api::get_event_index(
+        client,
+        &config.nextcloud_calendar_url,
+        &config.nextcloud_username,
+        &config.nextcloud_password,
     )
     .await
     .with_context(|| {
         format!(
-            "Failed to fetch and parse source calendar. URL: {}",
-            config.ics_url
+            "Failed to look up events of current calendar. URL: {}",
+            &config.nextcloud_calendar_url
         )
     })?;
 
+    let count = nextcloud_ics_sync::migrate_synced_events(
+        client,
+        event_index,
+        &config.nextcloud_calendar_url,
+        &destination_url,
+        &config.nextcloud_username,
+        &config.nextcloud_password,
+        transfer,
+    )
+    .await?;
+
+    let verb = match transfer {
+        Transfer::Move => "Moved",
+        Transfer::Copy => "Copied",
+    };
+    println!(
+        "\n{} {} events. Set CALENDAR_ID={} to keep syncing into the new calendar.\n",
+        verb, count, calendar_id
+    );
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn sync_calendars(
+    client: &Client,
+    explain: bool,
+    explain_diff: bool,
+    wait: bool,
+    resume: Option<String>,
+    initial_import: bool,
+    verify: bool,
+    use_cache_on_failure: bool,
+) -> Result<()> {
+    let mut config = Config::from_env()?;
+    config.use_source_cache_on_failure |= use_cache_on_failure;
+    let mut _locks = vec![lock_calendar(&config, &config.nextcloud_calendar_url, wait).await?];
+    for calendar_url in config
+        .calendar_routes
+        .calendar_urls()
+        .into_iter()
+        .chain(config.additional_calendar_urls.iter().map(String::as_str))
+    {
+        if calendar_url != config.nextcloud_calendar_url {
+            _locks.push(lock_calendar(&config, calendar_url, wait).await?);
+        }
+    }
+
+    let journal = match &resume {
+        Some(run_id) => Journal::resume(&config.journal_dir, run_id)?,
+        None => {
+            // Keeps this run, which was just started, as well
+            let journal = Journal::start(&config.journal_dir)?;
+            let pruned = Journal::prune(&config.journal_dir, config.journal_keep.max(1))?;
+            if pruned > 0 {
+                info!("Deleted the journals of {} old runs", pruned);
+            }
+            journal
+        }
+    };
+    let run_id = journal.run_id().to_string();
     info!(
-        "Downloading nextcloud calendar  {}...",
-        config.nextcloud_calendar_url
+        "Sync run {} (journal: {})",
+        run_id,
+        journal.path().display()
     );
+    let unconfirmed = journal.unconfirmed().count();
+    if unconfirmed > 0 {
+        info!(
+            "{} actions were sent without a confirmation, they are checked against the calendar",
+            unconfirmed
+        );
+    }
+
+    let sync_back = if config.sync_back_properties.is_empty() {
+        None
+    } else {
+        let sync_back =
+            SyncBack::load(&config.sync_back_file, config.sync_back_properties.clone())?;
+        Some(Arc::new(sync_back))
+    };
 
-    let nextcloud_calendar = get_nextcloud_calendar(client, &config).await?;
+    let options = SyncOptions {
+        hooks: config.hooks.clone(),
+        explain: explain || explain_diff,
+        explain_diff,
+        journal: Some(Arc::new(journal)),
+        deletions: config.deletions,
+        archive_calendar_url: config.archive_calendar_url.clone(),
+        upload_batching: initial_import.then_some(config.import_batching),
+        relink_renamed_uids: config.relink_renamed_uids,
+        dedupe: config.dedupe_events,
+        max_calendar_items: config.max_calendar_items,
+        check_quota: config.check_quota,
+        verify: verify || config.verify_uploads,
+        timestamp_tolerance: config.timestamp_tolerance,
+        ownership: config.ownership.clone(),
+        sync_back,
+        calendar_metadata: config.calendar_metadata.clone(),
+        progress: Default::default(),
+        time_range: config
+            .sync_window
+            .map(|sync_window| sync_window.time_range(Utc::now()))
+            .unwrap_or_default(),
+    };
 
-    info!("Syncing calendars...");
+    let report = SyncEngine::from_config(client.clone(), &config, options)
+        .run(&config)
+        .await?;
+    for explanation in &report.explanations {
+        println!("{}", explanation);
+    }
+    info!("Sync process completed.");
+    Ok(())
+}
+
+async fn print_drift_report(client: &Client) -> Result<()> {
+    let config = Config::from_env()?;
+    let time_range = config
+        .sync_window
+        .map(|sync_window| sync_window.time_range(Utc::now()))
+        .unwrap_or_default();
 
-    sync_calendar(
-        &client,
+    let compatibility = nextcloud::status::negotiate(
+        client,
+        &config.nextcloud_url,
         &config.nextcloud_username,
         &config.nextcloud_password,
-        &config.nextcloud_calendar_url,
-        source_calendar,
+    )
+    .await?;
+
+    let (source_calendar, nextcloud_calendar) = tokio::try_join!(
+        pipeline::source_calendar(client, &config, time_range),
+        pipeline::nextcloud_calendar(
+            client,
+            &config.nextcloud_calendar_url,
+            &config.nextcloud_username,
+            &config.nextcloud_password,
+            time_range,
+            compatibility,
+        ),
+    )?;
+
+    println!(
+        "\n{}",
+        report::drift_report(source_calendar, nextcloud_calendar)
+    );
+    Ok(())
+}
+
+async fn send_digest(client: &Client, print: bool) -> Result<()> {
+    let config = Config::from_env()?;
+    let options = config::load_digest()?;
+    let now = Utc::now();
+
+    let compatibility = nextcloud::status::negotiate(
+        client,
+        &config.nextcloud_url,
+        &config.nextcloud_username,
+        &config.nextcloud_password,
+    )
+    .await?;
+    let time_range = digest::time_range(now, options.days);
+    let mut objects = Vec::new();
+    for calendar_url in std::iter::once(config.nextcloud_calendar_url.as_str())
+        .chain(config.calendar_routes.calendar_urls())
+    {
+        objects.extend(
+            pipeline::nextcloud_calendar(
+                client,
+                calendar_url,
+                &config.nextcloud_username,
+                &config.nextcloud_password,
+                time_range,
+                compatibility,
+            )
+            .await?,
+        );
+    }
+
+    let events = digest::upcoming_events(&objects, now, options.days);
+    let text = digest::format_digest(&events, options.days);
+    if print {
+        println!("\n{}", text);
+        return Ok(());
+    }
+
+    digest::send_email(&options, &text).await?;
+    info!(
+        "Sent the digest of {} events to {}",
+        events.len(),
+        options.to.join(", ")
+    );
+    Ok(())
+}
+
+async fn print_free_busy(client: &Client, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<()> {
+    if start >= end {
+        anyhow::bail!("The start {} is not before the end {}", start, end);
+    }
+    let nextcloud_url = config::load_nextcloud_url()?;
+    let nextcloud_username = config::load_nextcloud_username()?;
+    let nextcloud_password = config::load_nextcloud_password()?;
+    let calendar_url = config::load_nextcloud_calendar_url(&nextcloud_url, &nextcloud_username)?;
+
+    let periods = nextcloud::api::get_free_busy(
+        client,
+        &calendar_url,
+        &nextcloud_username,
+        &nextcloud_password,
+        start,
+        end,
+    )
+    .await?;
+    for period in periods {
+        println!(
+            "{}\t{}\t{}",
+            period.start.to_rfc3339_opts(SecondsFormat::Secs, true),
+            period.end.to_rfc3339_opts(SecondsFormat::Secs, true),
+            period.kind
+        );
+    }
+    Ok(())
+}
+
+/// A date, taken as midnight UTC, or an RFC 3339 time.
+fn parse_time(value: &str) -> std::result::Result<DateTime<Utc>, String> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(|date| date.and_time(NaiveTime::MIN).and_utc())
+        .or_else(|_| DateTime::parse_from_rfc3339(value).map(|time| time.to_utc()))
+        .map_err(|_| {
+            format!(
+                "expected a date like 2024-03-04 or a time like 2024-03-04T09:00:00Z, got {}",
+                value
+            )
+        })
+}
+
+async fn mirror_availability(client: &Client, wait: bool) -> Result<()> {
+    let nextcloud_url = config::load_nextcloud_url()?;
+    let nextcloud_username = config::load_nextcloud_username()?;
+    let nextcloud_password = config::load_nextcloud_password()?;
+    let options = config::load_availability(&nextcloud_url, &nextcloud_username)?;
+    let _lock = SyncLock::acquire(
+        &SyncLock::path_for(&options.calendar_url),
+        wait,
+        config::load_lock_stale_after()?,
+    )
+    .await?;
+
+    let time_range = options.time_range(Utc::now());
+    let (Some(start), Some(end)) = (time_range.start, time_range.end) else {
+        anyhow::bail!("AVAILABILITY_DAYS is too large");
+    };
+    let busy_times = nextcloud::scheduling::query_free_busy(
+        client,
+        &nextcloud_url,
+        &nextcloud_username,
+        &nextcloud_password,
+        &options.organizer,
+        &options.attendees,
+        start,
+        end,
+    )
+    .await?;
+    let blocks = freebusy::combine(&busy_times);
+
+    let nextcloud_calendar = nextcloud::api::get_calendar_objects(
+        client,
+        &options.calendar_url,
+        &nextcloud_username,
+        &nextcloud_password,
+        time_range,
+    )
+    .await?;
+    // Past blocks are kept, they end up outside of the queried range
+    let sync_options = SyncOptions {
+        deletions: Deletions::WindowOnly,
+        time_range,
+        ..Default::default()
+    };
+    let report = sync_calendar(
+        client,
+        &nextcloud_username,
+        &nextcloud_password,
+        &options.calendar_url,
+        freebusy::availability_calendar(&blocks, &options.summary),
         nextcloud_calendar,
+        &sync_options,
     )
     .await
-    .context("Failed to sync calendars.")?;
+    .with_context(|| format!("Failed to sync calendar {}.", options.calendar_url))?;
+    info!(
+        "Published {} busy blocks of {} attendees ({} uploaded, {} deleted)",
+        blocks.len(),
+        options.attendees.len(),
+        report.uploaded,
+        report.deleted
+    );
+    Ok(())
+}
 
-    info!("Sync process completed.");
+async fn rotate_password(client: &Client, config_file: Option<&Path>) -> Result<()> {
+    let nextcloud_url = config::load_nextcloud_url()?;
+    let nextcloud_username = config::load_nextcloud_username()?;
+    let nextcloud_password = config::load_nextcloud_password()?;
+    let env_files = match config_file {
+        Some(path) => vec![path.to_path_buf()],
+        None => vec![PathBuf::from(".env"), paths::config_file()],
+    };
+    // Found before rotating, the old password is revoked by the rotation
+    let store = PasswordStore::find(
+        config::load_nextcloud_password_file().as_deref(),
+        &env_files,
+    )?;
+
+    let new_password = nextcloud::credentials::rotate_app_password(
+        client,
+        &nextcloud_url,
+        &nextcloud_username,
+        &nextcloud_password,
+    )
+    .await?;
+    if let Err(e) = store.store(&new_password) {
+        eprintln!(
+            "\nThe old app password is revoked, store the new one manually: {}\n",
+            new_password
+        );
+        return Err(e);
+    }
+
+    nextcloud::api::get_calendar_ids(client, &nextcloud_url, &nextcloud_username, &new_password)
+        .await
+        .context("The new app password was stored, but does not work")?;
+    println!(
+        "\nRotated the app password and stored it in {}\n",
+        store.path().display()
+    );
+    Ok(())
+}
+
+fn transfer_state(command: StateCommands) -> Result<()> {
+    let locations = config::load_state_locations();
+    match command {
+        StateCommands::Export { path } => {
+            let files = state::export(&locations, &path)?;
+            println!("\nExported {} state files to {}\n", files, path.display());
+        }
+        StateCommands::Import { path, force } => {
+            let files = state::import(&locations, &path, force)?;
+            println!("\nImported {} state files from {}\n", files, path.display());
+        }
+    }
+    Ok(())
+}
+
+async fn oauth_login(client: &Client) -> Result<()> {
+    let oauth_config =
+        config::load_ics_oauth()?.context("ICS_OAUTH_TOKEN_URL environment variable not set")?;
+
+    println!(
+        "\nOpen the following URL and authorize access:\n\n{}\n",
+        oauth::authorization_url(&oauth_config)?
+    );
+    println!("Paste the `code` parameter of the redirect URL:");
+
+    let mut code = String::new();
+    std::io::stdin()
+        .read_line(&mut code)
+        .context("Failed to read authorization code")?;
+
+    oauth::exchange_code(client, &oauth_config, code.trim())
+        .await
+        .context("Failed to exchange authorization code")?;
+
+    println!("\nToken stored in {}\n", oauth_config.token_cache.display());
     Ok(())
 }
 
@@ -108,7 +723,7 @@ async fn print_available_calendar_ids(client: &Client) -> Result<()> {
     let nextcloud_username = config::load_nextcloud_username()?;
     let nextcloud_password = config::load_nextcloud_password()?;
     let available_calendars = nextcloud::api::get_calendar_ids(
-        &client,
+        client,
         &nextcloud_url,
         &nextcloud_username,
         &nextcloud_password,
@@ -120,19 +735,3 @@ async fn print_available_calendar_ids(client: &Client) -> Result<()> {
     );
     Ok(())
 }
-
-async fn get_nextcloud_calendar(client: &Client, config: &Config) -> Result<icalendar::Calendar> {
-    ics_parser::fetch_and_parse_calendar(
-        &client,
-        &format!("{}?export", &config.nextcloud_calendar_url),
-        Some(config.nextcloud_username.clone()),
-        Some(config.nextcloud_password.clone()),
-    )
-    .await
-    .with_context(|| {
-        format!(
-            "Failed to fetch and parse current calendar. URL: {}?export",
-            &config.nextcloud_calendar_url
-        )
-    })
-}
@@ -1,14 +1,29 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
 
-use anyhow::{Context, Result};
-use reqwest::{Client, StatusCode};
-use serde_xml_rs::from_str;
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use reqwest::{StatusCode, Url};
 
-use futures::future::try_join_all;
+use futures::future::join_all;
 use icalendar::{Calendar, CalendarComponent, Component, Event};
 use log::{debug, info};
+use tokio::io::BufReader;
+use tokio_util::io::StreamReader;
 
 use super::{models::Multistatus, utils};
+use crate::{
+    freebusy::{self, BusyPeriod},
+    http::{self, Client},
+    ics_parser,
+    journal::{Action, Journal},
+    progress::Progress,
+    validate, verify,
+};
 
 pub async fn get_calendar_ids(
     client: &Client,
@@ -28,13 +43,11 @@ pub async fn get_calendar_ids(
 
     let url = format!("{}/remote.php/dav/calendars/{}/", nextcloud_url, username);
 
-    let response = client
-        .request(reqwest::Method::from_bytes(b"PROPFIND").unwrap(), &url)
-        .basic_auth(username, Some(password))
+    let request = http::dav_request(client, "PROPFIND", &url, username, password)
         .header("Depth", "1")
         .header("Content-Type", "application/xml")
-        .body(propfind_body)
-        .send()
+        .body(propfind_body);
+    let response = http::send(client, request)
         .await
         .context("Failed to send PROPFIND request to get calendar IDs")?;
 
@@ -43,7 +56,7 @@ pub async fn get_calendar_ids(
         .await
         .context("Failed to read PROPFIND response body for calendar IDs")?;
 
-    let multistatus = from_str::<Multistatus>(&xml_data)?;
+    let multistatus = Multistatus::parse(&xml_data)?;
     let ids = multistatus
         .responses
         .iter()
@@ -52,7 +65,7 @@ pub async fn get_calendar_ids(
                 p.prop
                     .resourcetype
                     .as_ref()
-                    .is_some_and(|t| t.calendar_deleted.is_none())
+                    .is_some_and(|t| !t.calendar_deleted)
             })
         })
         .filter_map(|r| utils::get_calendar_id_after_username(&r.href, username))
@@ -60,77 +73,540 @@ pub async fn get_calendar_ids(
     Ok(ids)
 }
 
+/// Privileges that allow creating and changing events.
+const WRITE_PRIVILEGES: &[&str] = &["all", "write", "write-content", "bind"];
+
+/// Refuses read-only calendars and subscriptions, whose PUTs would fail with confusing
+/// errors, and names the calendars that are writable instead.
+///
+/// Calendars that are not in the listing or don't report their privileges are assumed
+/// to be writable.
+pub async fn ensure_writable(
+    client: &Client,
+    nextcloud_url: &str,
+    nextcloud_calendar_url: &str,
+    username: &str,
+    password: &str,
+) -> Result<()> {
+    let propfind_body = r#"<?xml version="1.0" encoding="UTF-8"?>
+  <d:propfind xmlns:d="DAV:">
+    <d:prop>
+      <d:resourcetype/>
+      <d:current-user-privilege-set/>
+    </d:prop>
+  </d:propfind>"#;
+
+    let url = format!("{}/remote.php/dav/calendars/{}/", nextcloud_url, username);
+    let request = http::dav_request(client, "PROPFIND", &url, username, password)
+        .header("Depth", "1")
+        .header("Content-Type", "application/xml")
+        .body(propfind_body);
+    let xml_data = http::send(client, request)
+        .await
+        .context("Failed to send PROPFIND request for calendar privileges")?
+        .text()
+        .await
+        .context("Failed to read PROPFIND response body for calendar privileges")?;
+    let multistatus = Multistatus::parse(&xml_data)?;
+
+    let is_writable = |response: &super::models::Response| {
+        response.propstats.iter().all(|propstat| {
+            let prop = &propstat.prop;
+            !prop.resourcetype.as_ref().is_some_and(|t| t.subscribed)
+                && prop.privileges.as_ref().is_none_or(|privileges| {
+                    privileges
+                        .iter()
+                        .any(|privilege| WRITE_PRIVILEGES.contains(&privilege.as_str()))
+                })
+        })
+    };
+    let is_calendar = |response: &super::models::Response| {
+        response.propstats.iter().any(|propstat| {
+            propstat
+                .prop
+                .resourcetype
+                .as_ref()
+                .is_some_and(|t| (t.calendar || t.subscribed) && !t.calendar_deleted)
+        })
+    };
+
+    let calendar_path = Url::parse(nextcloud_calendar_url)
+        .map(|url| url.path().trim_end_matches('/').to_string())
+        .unwrap_or_default();
+    let Some(target) = multistatus
+        .responses
+        .iter()
+        .find(|response| response.href.trim_end_matches('/') == calendar_path)
+    else {
+        return Ok(());
+    };
+    if is_writable(target) {
+        return Ok(());
+    }
+
+    let writable: Vec<String> = multistatus
+        .responses
+        .iter()
+        .filter(|response| is_calendar(response) && is_writable(response))
+        .filter_map(|response| utils::get_calendar_id_after_username(&response.href, username))
+        .collect();
+    bail!(
+        "{} is read-only (a subscription or shared without write access). Writable calendars: [{}]",
+        nextcloud_calendar_url,
+        writable.join(", ")
+    )
+}
+
+/// Storage quota of a collection (RFC 4331), `None` where the server doesn't report it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Quota {
+    pub used_bytes: Option<u64>,
+    pub available_bytes: Option<u64>,
+}
+
+/// Reads the quota properties of the calendar with a Depth 0 PROPFIND.
+pub async fn get_quota(
+    client: &Client,
+    nextcloud_calendar_url: &str,
+    username: &str,
+    password: &str,
+) -> Result<Quota> {
+    let propfind_body = r#"<?xml version="1.0" encoding="UTF-8"?>
+  <d:propfind xmlns:d="DAV:">
+    <d:prop>
+      <d:quota-available-bytes/>
+      <d:quota-used-bytes/>
+    </d:prop>
+  </d:propfind>"#;
+
+    let request = http::dav_request(
+        client,
+        "PROPFIND",
+        nextcloud_calendar_url,
+        username,
+        password,
+    )
+    .header("Depth", "0")
+    .header("Content-Type", "application/xml")
+    .body(propfind_body);
+    let response = http::send(client, request)
+        .await
+        .context("Failed to send PROPFIND request for the quota")?;
+    if response.status() != StatusCode::MULTI_STATUS {
+        bail!(
+            "PROPFIND for the quota failed with status {}",
+            response.status()
+        );
+    }
+
+    let xml_data = response
+        .text()
+        .await
+        .context("Failed to read PROPFIND response body for the quota")?;
+    let multistatus = Multistatus::parse(&xml_data)?;
+
+    // Negative values mean "unlimited" or "unknown" on some servers
+    let parse = |value: &Option<String>| value.as_deref().and_then(|v| v.parse::<u64>().ok());
+    let mut quota = Quota::default();
+    for propstat in multistatus.responses.iter().flat_map(|r| &r.propstats) {
+        quota.available_bytes = quota
+            .available_bytes
+            .or(parse(&propstat.prop.quota_available_bytes));
+        quota.used_bytes = quota.used_bytes.or(parse(&propstat.prop.quota_used_bytes));
+    }
+    Ok(quota)
+}
+
+/// A calendar resource as returned by a calendar-query REPORT.
+#[derive(Debug)]
+pub struct CalendarObject {
+    pub href: String,
+    pub etag: Option<String>,
+    pub calendar: Calendar,
+}
+
+/// Limits a calendar-query to events overlapping the range, a missing bound is unlimited.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimeRange {
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+}
+
+impl TimeRange {
+    fn to_filter(self) -> String {
+        if self.start.is_none() && self.end.is_none() {
+            return String::new();
+        }
+
+        let format = |bound: Option<DateTime<Utc>>, name: &str| {
+            bound
+                .map(|time| format!(r#" {}="{}""#, name, time.format("%Y%m%dT%H%M%SZ")))
+                .unwrap_or_default()
+        };
+        format!(
+            "<c:time-range{}{}/>",
+            format(self.start, "start"),
+            format(self.end, "end")
+        )
+    }
+}
+
+/// Fetches all event resources of a calendar with a single calendar-query REPORT.
+pub async fn get_calendar_objects(
+    client: &Client,
+    nextcloud_calendar_url: &str,
+    username: &str,
+    password: &str,
+    time_range: TimeRange,
+) -> Result<Vec<CalendarObject>> {
+    calendar_query(
+        client,
+        nextcloud_calendar_url,
+        username,
+        password,
+        "<c:calendar-data/>",
+        time_range,
+    )
+    .await
+}
+
+/// Fetches the href of every event with a single calendar-query REPORT.
+///
+/// Only `UID` and `X-SYNCED` of the events are requested, which is all deletions need
+/// and much smaller than the full calendar.
+pub async fn get_event_index(
+    client: &Client,
+    nextcloud_calendar_url: &str,
+    username: &str,
+    password: &str,
+) -> Result<Vec<CalendarObject>> {
+    calendar_query(
+        client,
+        nextcloud_calendar_url,
+        username,
+        password,
+        r#"<c:calendar-data>
+        <c:comp name="VCALENDAR">
+          <c:comp name="VEVENT">
+            <c:prop name="UID"/>
+            <c:prop name="X-SYNCED"/>
+          </c:comp>
+        </c:comp>
+      </c:calendar-data>"#,
+        TimeRange::default(),
+    )
+    .await
+}
+
+/// The busy times in the calendar between `start` and `end`, with a free-busy-query REPORT
+/// (RFC 4791). Only needs the free/busy privilege, not read access to the events.
+pub async fn get_free_busy(
+    client: &Client,
+    nextcloud_calendar_url: &str,
+    username: &str,
+    password: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<BusyPeriod>> {
+    let time_range = TimeRange {
+        start: Some(start),
+        end: Some(end),
+    };
+    let report_body = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+  <c:free-busy-query xmlns:c="urn:ietf:params:xml:ns:caldav">
+    {}
+  </c:free-busy-query>"#,
+        time_range.to_filter()
+    );
+
+    let request = http::dav_request(client, "REPORT", nextcloud_calendar_url, username, password)
+        .header("Depth", "1")
+        .header("Content-Type", "application/xml")
+        .body(report_body);
+    let response = http::send(client, request)
+        .await
+        .context("Failed to send free-busy-query REPORT")?;
+
+    let status = response.status();
+    if status != StatusCode::OK {
+        let body = response.text().await.unwrap_or_default();
+        bail!(
+            "Free-busy-query REPORT failed with status {} and body of:\n{}",
+            status,
+            body
+        );
+    }
+    let calendar_data = response
+        .text()
+        .await
+        .context("Failed to read free-busy-query REPORT response body")?;
+    freebusy::busy_periods(&calendar_data)
+}
+
+async fn calendar_query(
+    client: &Client,
+    nextcloud_calendar_url: &str,
+    username: &str,
+    password: &str,
+    calendar_data: &str,
+    time_range: TimeRange,
+) -> Result<Vec<CalendarObject>> {
+    let report_body = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+  <c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+    <d:prop>
+      <d:getetag/>
+      {}
+    </d:prop>
+    <c:filter>
+      <c:comp-filter name="VCALENDAR">
+        <c:comp-filter name="VEVENT">{}</c:comp-filter>
+      </c:comp-filter>
+    </c:filter>
+  </c:calendar-query>"#,
+        calendar_data,
+        time_range.to_filter()
+    );
+
+    let request = http::dav_request(client, "REPORT", nextcloud_calendar_url, username, password)
+        .header("Depth", "1")
+        .header("Content-Type", "application/xml")
+        .body(report_body);
+    let response = http::send(client, request)
+        .await
+        .context("Failed to send calendar-query REPORT")?;
+
+    let status = response.status();
+    if status != StatusCode::MULTI_STATUS {
+        let body = response.text().await.unwrap_or_default();
+        bail!(
+            "Calendar-query REPORT failed with status {} and body of:\n{}",
+            status,
+            body
+        );
+    }
+
+    // Large calendars can have responses of many MB, so events are parsed while the body is read
+    let body = StreamReader::new(response.bytes_stream().map_err(std::io::Error::other));
+    let mut objects = Vec::new();
+    Multistatus::parse_stream(BufReader::new(body), |response| {
+        let href = response.href;
+        let Some(prop) = response
+            .propstats
+            .into_iter()
+            .map(|propstat| propstat.prop)
+            .find(|prop| prop.calendar_data.is_some())
+        else {
+            return Ok(());
+        };
+
+        let calendar_data = prop.calendar_data.unwrap_or_default();
+        let calendar = ics_parser::parse_calendar(calendar_data.as_bytes(), &href, &[])?;
+        objects.push(CalendarObject {
+            href,
+            etag: prop.getetag,
+            calendar,
+        });
+        Ok(())
+    })
+    .await
+    .context("Failed to read calendar-query REPORT response")?;
+
+    Ok(objects)
+}
+
+/// Fetches all events of a calendar, merged into one calendar.
+pub async fn get_calendar(
+    client: &Client,
+    nextcloud_calendar_url: &str,
+    username: &str,
+    password: &str,
+) -> Result<Calendar> {
+    let objects = get_calendar_objects(
+        client,
+        nextcloud_calendar_url,
+        username,
+        password,
+        TimeRange::default(),
+    )
+    .await?;
+    Ok(merge_objects(objects))
+}
+
+/// Merges the events of all resources into one calendar.
+pub fn merge_objects(objects: Vec<CalendarObject>) -> Calendar {
+    let mut calendar = Calendar::new();
+    for object in objects {
+        calendar.extend(object.calendar.components);
+    }
+    calendar
+}
+
+/// Maps the UID of every event to the href of the resource containing it.
+pub fn hrefs_by_uid(objects: &[CalendarObject]) -> HashMap<String, String> {
+    objects
+        .iter()
+        .flat_map(|object| {
+            object
+                .calendar
+                .components
+                .iter()
+                .filter_map(|component| component.as_event()?.get_uid())
+                .map(|uid| (uid.to_string(), object.href.clone()))
+        })
+        .collect()
+}
+
+/// URL of the resource holding the event, a name derived from the UID for events that don't
+/// exist yet.
+fn resource_url(base_url: &str, uid: &str, hrefs: &HashMap<String, String>) -> String {
+    hrefs
+        .get(uid)
+        .and_then(|href| Url::parse(base_url).ok()?.join(href).ok())
+        .map(String::from)
+        .unwrap_or_else(|| format!("{}{}", base_url, utils::resource_name(uid)))
+}
+
+/// Calendar-level properties of the uploaded resources, so admins can trace where synced
+/// events came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CalendarMetadata {
+    pub prodid: String,
+    pub calscale: String,
+    /// Written as X-WR-CALNAME.
+    pub calendar_name: Option<String>,
+}
+
+impl CalendarMetadata {
+    pub const DEFAULT_PRODID: &str = concat!(
+        "-//nextcloud-ics-sync//nextcloud-ics-sync ",
+        env!("CARGO_PKG_VERSION"),
+        "//EN"
+    );
+
+    /// The calendar resource holding a single event.
+    pub fn calendar(&self, event: Event) -> Calendar {
+        let mut calendar = Calendar {
+            properties: Vec::new(),
+            components: vec![event.into()],
+        };
+        calendar
+            .append_property(("VERSION", "2.0"))
+            .append_property(("PRODID", self.prodid.as_str()))
+            .append_property(("CALSCALE", self.calscale.as_str()));
+        if let Some(name) = &self.calendar_name {
+            calendar.name(name);
+        }
+        calendar
+    }
+}
+
+impl Default for CalendarMetadata {
+    fn default() -> Self {
+        Self {
+            prodid: Self::DEFAULT_PRODID.to_string(),
+            calscale: "GREGORIAN".to_string(),
+            calendar_name: None,
+        }
+    }
+}
+
 /// Handles the concurrent upload of multiple events to Nextcloud.
+#[allow(clippy::too_many_arguments)]
 pub async fn handle_uploads(
     client: &Client,
     username: &str,
     password: &str,
     base_url: &str,
     events: Vec<Event>,
+    hrefs: &HashMap<String, String>,
+    metadata: &CalendarMetadata,
+    journal: Option<&Arc<Journal>>,
+    progress: &Progress,
 ) -> Result<()> {
-    let tasks = events.into_iter().map(|event| {
+    // Everything is validated first, so a broken event fails the sync before any upload
+    let payloads = events
+        .into_iter()
+        .map(|event| {
+            let uid = event
+                .get_uid()
+                .map(str::to_string)
+                .context("Event is missing a UID, cannot upload.")?;
+            // The event is moved into its calendar and serialized once, the body is not copied
+            let event_content = validate::normalize_payload(&metadata.calendar(event).to_string());
+            validate::validate_payload(&event_content)
+                .with_context(|| format!("Invalid event with UID: {}", uid))?;
+            Ok((uid, event_content))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let tasks = payloads.into_iter().map(|(uid, event_content)| {
         let client = client.clone();
+        let journal = journal.cloned();
+        let progress = progress.clone();
         let username = username.to_string();
         let password = password.to_string();
-        let base_url = base_url.to_string();
+        // Existing events are updated in place, whatever their resource name
+        let upload_url = resource_url(base_url, &uid, hrefs);
 
-        tokio::spawn(async move {
-            let uid = event
-                .get_uid()
-                .context("Event is missing a UID, cannot upload.")?;
-            // URL-encode the UID for the path segment.
-            let upload_url = format!("{}{}.ics", base_url, uid);
-
-            let event_calendar = Calendar::new().push(event.clone()).done();
-            let event_content = event_calendar.to_string();
-
-            let request = client
-                .put(&upload_url)
-                .basic_auth(&username, Some(&password))
-                .header("Content-Type", "text/calendar")
-                .body(event_content.clone())
-                .build()?;
-
-            let response = client
-                .execute(request)
-                .await
-                .with_context(|| format!("Failed to upload event with UID: {}", uid))?;
-
-            match response.status() {
-                StatusCode::OK | StatusCode::CREATED | StatusCode::NO_CONTENT => {
-                    debug!("-> Upload successful for UID: {}", uid);
-                    Ok(())
+        async move {
+            let result = async {
+                if let Some(journal) = &journal {
+                    journal.begin(&uid, Action::Uploaded)?;
                 }
-                _ => {
-                    let status = response.status();
-                    let body = response.text().await.unwrap_or_default();
-
-                    Err(anyhow::anyhow!(
-                        "Upload failed for UID {} with status {} and body of:\n{}",
-                        uid,
-                        status,
-                        body
-                    ))
+                let request = http::dav_request(&client, "PUT", &upload_url, &username, &password)
+                    .header("Content-Type", "text/calendar")
+                    .body(event_content);
+
+                let response = http::send(&client, request)
+                    .await
+                    .with_context(|| format!("Failed to upload event with UID: {}", uid))?;
+
+                match response.status() {
+                    StatusCode::OK | StatusCode::CREATED | StatusCode::NO_CONTENT => {
+                        debug!("-> Upload successful for UID: {}", uid);
+                        match journal {
+                            Some(journal) => journal.record(&uid, Action::Uploaded),
+                            None => Ok(()),
+                        }
+                    }
+                    _ => {
+                        let status = response.status();
+                        let body = response.text().await.unwrap_or_default();
+
+                        Err(anyhow::anyhow!(
+                            "Upload failed for UID {} with status {} and body of:\n{}",
+                            uid,
+                            status,
+                            body
+                        ))
+                    }
                 }
             }
-        })
+            .await;
+            progress.finish(&uid, Action::Uploaded, &result);
+            result
+        }
     });
 
-    try_join_all(tasks)
-        .await?
-        .into_iter()
-        .collect::<Result<()>>()?;
+    // Every request runs to completion, so the journal knows the outcome of all of them
+    join_all(tasks).await.into_iter().collect::<Result<()>>()?;
 
     Ok(())
 }
 
 /// Handles the concurrent deletion of multiple events from Nextcloud.
+#[allow(clippy::too_many_arguments)]
 pub async fn handle_deletes(
     client: &Client,
     username: &str,
     password: &str,
     nextcloud_calendar_url: &str,
     uids: HashSet<String>,
+    hrefs: &HashMap<String, String>,
+    journal: Option<&Arc<Journal>>,
+    progress: &Progress,
 ) -> Result<()> {
     if uids.is_empty() {
         info!("No events to delete.");
@@ -143,54 +619,251 @@ pub async fn handle_deletes(
         let client = client.clone();
         let username = username.to_string();
         let password = password.to_string();
-        let nextcloud_calendar_url = nextcloud_calendar_url.to_string();
-
-        tokio::spawn(async move {
-            let delete_url = format!("{}{}.ics", nextcloud_calendar_url, uid);
-
-            let response = client
-                .delete(&delete_url)
-                .basic_auth(&username, Some(&password))
-                .send()
-                .await
-                .context(format!("Failed to delete event with UID: {}", uid))?;
-
-            match response.status() {
-                StatusCode::OK | StatusCode::NO_CONTENT => {
-                    debug!("-> Deletion successful for UID: {}", uid);
-                    Ok(())
+        let delete_url = resource_url(nextcloud_calendar_url, &uid, hrefs);
+        let journal = journal.cloned();
+        let progress = progress.clone();
+
+        async move {
+            let result = async {
+                if let Some(journal) = &journal {
+                    journal.begin(&uid, Action::Deleted)?;
                 }
-                _ => {
-                    let status = response.status();
-                    let body = response.text().await.unwrap_or_default();
-
-                    Err(anyhow::anyhow!(
-                        "Deletion failed for UID {} with status {} and body of:\n{}",
-                        uid,
-                        status,
-                        body
-                    ))
+                let request =
+                    http::dav_request(&client, "DELETE", &delete_url, &username, &password);
+                let response = http::send(&client, request)
+                    .await
+                    .context(format!("Failed to delete event with UID: {}", uid))?;
+
+                match response.status() {
+                    // Gone already, e.g. deleted by an attempt that was killed before confirming it
+                    StatusCode::OK | StatusCode::NO_CONTENT | StatusCode::NOT_FOUND => {
+                        debug!("-> Deletion successful for UID: {}", uid);
+                        match journal {
+                            Some(journal) => journal.record(&uid, Action::Deleted),
+                            None => Ok(()),
+                        }
+                    }
+                    _ => {
+                        let status = response.status();
+                        let body = response.text().await.unwrap_or_default();
+
+                        Err(anyhow::anyhow!(
+                            "Deletion failed for UID {} with status {} and body of:\n{}",
+                            uid,
+                            status,
+                            body
+                        ))
+                    }
                 }
             }
-        })
+            .await;
+            progress.finish(&uid, Action::Deleted, &result);
+            result
+        }
     });
 
-    try_join_all(tasks)
-        .await?
-        .into_iter()
-        .collect::<Result<()>>()?;
+    join_all(tasks).await.into_iter().collect::<Result<()>>()?;
 
     info!("Deleted!");
 
     Ok(())
 }
 
-pub fn should_skip(source_event: &Event, existing_event: &Event) -> bool {
+/// Moves an event resource to another URL, usually in another calendar, with WebDAV MOVE.
+///
+/// Unlike delete and re-upload, this keeps the event as it is, e.g. with the attendee responses.
+/// An existing destination is only replaced if `overwrite` is set.
+pub async fn move_event(
+    client: &Client,
+    username: &str,
+    password: &str,
+    source_url: &str,
+    destination_url: &str,
+    overwrite: bool,
+) -> Result<()> {
+    transfer_event(
+        client,
+        "MOVE",
+        username,
+        password,
+        source_url,
+        destination_url,
+        overwrite,
+    )
+    .await
+}
+
+/// Copies an event resource to another URL with WebDAV COPY.
+pub async fn copy_event(
+    client: &Client,
+    username: &str,
+    password: &str,
+    source_url: &str,
+    destination_url: &str,
+    overwrite: bool,
+) -> Result<()> {
+    transfer_event(
+        client,
+        "COPY",
+        username,
+        password,
+        source_url,
+        destination_url,
+        overwrite,
+    )
+    .await
+}
+
+async fn transfer_event(
+    client: &Client,
+    method: &str,
+    username: &str,
+    password: &str,
+    source_url: &str,
+    destination_url: &str,
+    overwrite: bool,
+) -> Result<()> {
+    let request = http::dav_request(client, method, source_url, username, password)
+        .header("Destination", destination_url)
+        .header("Overwrite", if overwrite { "T" } else { "F" });
+    let response = http::send(client, request)
+        .await
+        .with_context(|| format!("Failed to send {} request. URL: {}", method, source_url))?;
+
+    match response.status() {
+        StatusCode::CREATED | StatusCode::NO_CONTENT => Ok(()),
+        StatusCode::PRECONDITION_FAILED => bail!(
+            "{} failed because the destination already exists. URL: {}",
+            method,
+            destination_url
+        ),
+        status => {
+            let body = response.text().await.unwrap_or_default();
+            bail!(
+                "{} failed with status {} and body of:\n{}",
+                method,
+                status,
+                body
+            )
+        }
+    }
+}
+
+/// How events get into another calendar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Transfer {
+    /// With WebDAV MOVE, removing them from their calendar.
+    #[default]
+    Move,
+    /// With WebDAV COPY, keeping them in their calendar as well.
+    Copy,
+}
+
+/// Handles the concurrent move or copy of multiple events into another calendar.
+///
+/// Moves are journaled as deletions and copies as uploads.
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_transfers(
+    client: &Client,
+    username: &str,
+    password: &str,
+    nextcloud_calendar_url: &str,
+    destination_calendar_url: &str,
+    uids: HashSet<String>,
+    hrefs: &HashMap<String, String>,
+    transfer: Transfer,
+    journal: Option<&Arc<Journal>>,
+    progress: &Progress,
+) -> Result<()> {
+    let (verb, progressive, past, action) = match transfer {
+        Transfer::Move => ("move", "Moving", "Moved", Action::Deleted),
+        Transfer::Copy => ("copy", "Copying", "Copied", Action::Uploaded),
+    };
+    if uids.is_empty() {
+        info!("No events to {}.", verb);
+        return Ok(());
+    }
+
+    info!("{} {} events...", progressive, uids.len());
+
+    let tasks = uids.into_iter().map(|uid| {
+        let client = client.clone();
+        let username = username.to_string();
+        let password = password.to_string();
+        let source_url = resource_url(nextcloud_calendar_url, &uid, hrefs);
+        // Keep the resource name, so the event can be found under the same name in the archive
+        let resource_name = source_url.rsplit('/').next().unwrap_or_default();
+        let destination_url = format!("{}{}", destination_calendar_url, resource_name);
+        let journal = journal.cloned();
+        let progress = progress.clone();
+
+        async move {
+            let result = async {
+                if let Some(journal) = &journal {
+                    journal.begin(&uid, action)?;
+                }
+                match transfer {
+                    Transfer::Move => {
+                        move_event(
+                            &client,
+                            &username,
+                            &password,
+                            &source_url,
+                            &destination_url,
+                            true,
+                        )
+                        .await
+                    }
+                    Transfer::Copy => {
+                        copy_event(
+                            &client,
+                            &username,
+                            &password,
+                            &source_url,
+                            &destination_url,
+                            true,
+                        )
+                        .await
+                    }
+                }
+                .with_context(|| format!("Failed to {} event with UID: {}", verb, uid))?;
+
+                debug!("-> {} UID: {}", past, uid);
+                match journal {
+                    Some(journal) => journal.record(&uid, action),
+                    None => Ok(()),
+                }
+            }
+            .await;
+            progress.finish(&uid, action, &result);
+            result
+        }
+    });
+
+    join_all(tasks).await.into_iter().collect::<Result<()>>()?;
+
+    info!("{}!", past);
+
+    Ok(())
+}
+
+/// Whether the existing copy is up to date, judged by LAST-MODIFIED.
+///
+/// Timestamps at most `tolerance` apart count as equal if the events have the same content,
+/// for feeds that regenerate them with some jitter or servers that rewrite them.
+pub fn should_skip(source_event: &Event, existing_event: &Event, tolerance: Duration) -> bool {
     match (
         source_event.get_last_modified(),
         existing_event.get_last_modified(),
     ) {
-        (Some(source_ts), Some(existing_ts)) => source_ts == existing_ts,
+        (Some(source_ts), Some(existing_ts)) if source_ts == existing_ts => true,
+        (Some(source_ts), Some(existing_ts)) => {
+            (source_ts - existing_ts)
+                .abs()
+                .to_std()
+                .is_ok_and(|difference| difference <= tolerance)
+                && verify::same_content(source_event, existing_event)
+        }
         _ => false,
     }
 }
@@ -0,0 +1,132 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+
+use crate::http::{self, Client};
+
+#[derive(Deserialize)]
+struct RotateResponse {
+    ocs: Ocs,
+}
+
+#[derive(Deserialize)]
+struct Ocs {
+    data: RotateData,
+}
+
+#[derive(Deserialize)]
+struct RotateData {
+    apppassword: String,
+}
+
+/// Replaces the app password the request is authenticated with by a new one, the old one
+/// stops working immediately.
+pub async fn rotate_app_password(
+    client: &Client,
+    nextcloud_url: &str,
+    username: &str,
+    password: &str,
+) -> Result<String> {
+    let url = format!(
+        "{}/ocs/v2.php/core/apppassword/rotate?format=json",
+        nextcloud_url
+    );
+    let request = client
+        .post(&url)
+        .basic_auth(username, Some(password))
+        .header("OCS-APIRequest", "true");
+    let response: RotateResponse = http::send(client, request)
+        .await
+        .context("Failed to request a new app password")?
+        .error_for_status()
+        .with_context(|| {
+            format!(
+                "App password rotation failed, NEXTCLOUD_PASSWORD must be an app password. \
+                 URL: {}",
+                url
+            )
+        })?
+        .json()
+        .await
+        .context("Failed to parse the app password response")?;
+    Ok(response.ocs.data.apppassword)
+}
+
+/// Where the Nextcloud password is configured, so it can be replaced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PasswordStore {
+    /// The file given by `NEXTCLOUD_PASSWORD_FILE`, holding only the password.
+    File(PathBuf),
+    /// A `.env` style file with a `NEXTCLOUD_PASSWORD=` line.
+    EnvFile(PathBuf),
+}
+
+impl PasswordStore {
+    /// Finds the store of the password: the `NEXTCLOUD_PASSWORD_FILE` if the password is
+    /// read from it, otherwise the first of `env_files` that sets it.
+    pub fn find(password_file: Option<&Path>, env_files: &[PathBuf]) -> Result<Self> {
+        if let Some(path) = password_file {
+            return Ok(Self::File(path.to_path_buf()));
+        }
+        env_files
+            .iter()
+            .find(|path| {
+                fs::read_to_string(path)
+                    .is_ok_and(|content| content.lines().any(|line| password_line(line).is_some()))
+            })
+            .map(|path| Self::EnvFile(path.clone()))
+            .context(
+                "NEXTCLOUD_PASSWORD is not set in NEXTCLOUD_PASSWORD_FILE or a config file, so \
+                 a new password could not be stored",
+            )
+    }
+
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::File(path) | Self::EnvFile(path) => path,
+        }
+    }
+
+    /// Replaces the password, writing a copy first so the file is never left half written.
+    pub fn store(&self, password: &str) -> Result<()> {
+        let content = match self {
+            Self::File(_) => format!("{}\n", password),
+            Self::EnvFile(path) => {
+                let content = fs::read_to_string(path).with_context(|| {
+                    format!("Failed to read config file. Path: {}", path.display())
+                })?;
+                let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+                let Some(line) = lines.iter_mut().find(|line| password_line(line).is_some()) else {
+                    bail!("NEXTCLOUD_PASSWORD is no longer set in {}", path.display());
+                };
+                let prefix = password_line(line).unwrap_or_default().to_string();
+                *line = format!("{}NEXTCLOUD_PASSWORD={}", prefix, password);
+                lines.join("\n") + "\n"
+            }
+        };
+
+        let path = self.path();
+        let partial = path.with_extension("partial");
+        fs::write(&partial, content)
+            .and_then(|_| match fs::metadata(path) {
+                // The password file is usually only readable by its owner
+                Ok(metadata) => fs::set_permissions(&partial, metadata.permissions()),
+                Err(_) => Ok(()),
+            })
+            .and_then(|_| fs::rename(&partial, path))
+            .with_context(|| format!("Failed to store the new password. Path: {}", path.display()))
+    }
+}
+
+/// The `export ` prefix, possibly empty, of a line setting the password.
+fn password_line(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start();
+    let rest = trimmed.strip_prefix("export ").unwrap_or(trimmed);
+    rest.trim_start()
+        .starts_with("NEXTCLOUD_PASSWORD=")
+        .then(|| &trimmed[..trimmed.len() - rest.len()])
+}
@@ -1,3 +1,6 @@
 pub mod api;
+pub mod credentials;
 mod models;
-mod utils;
+pub mod scheduling;
+pub mod status;
+pub(crate) mod utils;
@@ -1,62 +1,363 @@
-use serde::{Deserialize, Serialize};
+use anyhow::{Context, Result, anyhow};
+use quick_xml::{
+    NsReader,
+    escape::resolve_predefined_entity,
+    events::{BytesStart, Event},
+    name::ResolveResult,
+};
+use tokio::io::AsyncBufRead;
 
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(rename = "multistatus")]
+const DAV: &str = "DAV:";
+const CALDAV: &str = "urn:ietf:params:xml:ns:caldav";
+const NEXTCLOUD: &str = "http://nextcloud.com/ns";
+const CALENDARSERVER: &str = "http://calendarserver.org/ns/";
+
+/// A `DAV:multistatus` response body.
+///
+/// Elements are matched by namespace and local name, so the prefixes chosen by the server
+/// (`d:`, `D:`, a default namespace, ...) don't matter. Unknown elements are ignored.
+#[derive(Debug, Default)]
 pub struct Multistatus {
-    #[serde(rename = "d:response", default)]
     pub responses: Vec<Response>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(rename = "d:response")]
+#[derive(Debug, Default)]
 pub struct Response {
-    #[serde(rename = "d:href")]
     pub href: String,
-
-    #[serde(rename = "d:propstat", default)]
     pub propstats: Vec<Propstat>,
 }
 
 // --------------------------------------------------
 
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(rename = "d:propstat")]
+#[derive(Debug, Default)]
 pub struct Propstat {
-    #[serde(rename = "d:prop")]
     pub prop: Prop,
-
-    #[serde(rename = "d:status")]
     pub status: String,
 }
 
 // --------------------------------------------------
 
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(rename = "d:prop")]
+#[derive(Debug, Default)]
 pub struct Prop {
-    #[serde(rename = "d:resourcetype", default)]
     pub resourcetype: Option<ResourceType>,
-
-    #[serde(rename = "d:owner", default)]
     pub owner: Option<Owner>,
-
-    #[serde(rename = "d:displayname", default)]
     pub displayname: Option<String>,
+    pub getetag: Option<String>,
+    pub calendar_data: Option<String>,
+    pub quota_available_bytes: Option<String>,
+    pub quota_used_bytes: Option<String>,
+    /// Local names of the privileges in `current-user-privilege-set`, like `write` or `read`.
+    pub privileges: Option<Vec<String>>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(rename = "d:resourcetype")]
+#[derive(Debug, Default)]
 pub struct ResourceType {
-    #[serde(rename = "x1:deleted-calendar")]
-    pub calendar_deleted: Option<Empty>,
+    pub calendar: bool,
+    pub calendar_deleted: bool,
+    /// A read-only calendar subscription.
+    pub subscribed: bool,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(rename = "d:owner")]
+#[derive(Debug, Default)]
 pub struct Owner {
-    #[serde(rename = "d:href")]
     pub href: String,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct Empty;
+/// An element name resolved to its namespace URI.
+type Name = (String, String);
+
+impl Multistatus {
+    pub fn parse(xml: &str) -> Result<Self> {
+        let mut reader = NsReader::from_str(xml);
+        let mut parser = MultistatusParser::default();
+        let mut multistatus = Multistatus::default();
+
+        loop {
+            let (namespace, event) = reader
+                .read_resolved_event()
+                .context("Failed to parse multistatus XML")?;
+            if let Event::Eof = event {
+                break;
+            }
+            if let Some(response) = parser.handle(namespace, event)? {
+                multistatus.responses.push(response);
+            }
+        }
+
+        Ok(multistatus)
+    }
+
+    /// Parses a multistatus body while it is read, passing every `response` on as soon as it
+    /// is complete, so the raw XML never has to be held in memory as a whole.
+    pub async fn parse_stream<R: AsyncBufRead + Unpin>(
+        reader: R,
+        mut on_response: impl FnMut(Response) -> Result<()>,
+    ) -> Result<()> {
+        let mut reader = NsReader::from_reader(reader);
+        let mut parser = MultistatusParser::default();
+        let mut buf = Vec::new();
+
+        loop {
+            let (namespace, event) = reader
+                .read_resolved_event_into_async(&mut buf)
+                .await
+                .context("Failed to parse multistatus XML")?;
+            if let Event::Eof = event {
+                break;
+            }
+            if let Some(response) = parser.handle(namespace, event)? {
+                on_response(response)?;
+            }
+            buf.clear();
+        }
+
+        Ok(())
+    }
+}
+
+/// Builds `Response`s from the events of a namespace resolving reader.
+#[derive(Default)]
+struct MultistatusParser {
+    stack: Vec<Name>,
+    text: String,
+    response: Option<Response>,
+}
+
+impl MultistatusParser {
+    /// Handles one event, returning the response it completes.
+    fn handle(&mut self, namespace: ResolveResult, event: Event) -> Result<Option<Response>> {
+        match event {
+            Event::Start(start) => {
+                let name = resolve_name(namespace, &start);
+                self.start(&name);
+                self.stack.push(name);
+                self.text.clear();
+            }
+            Event::Empty(start) => {
+                let name = resolve_name(namespace, &start);
+                self.start(&name);
+                return Ok(self.end(&name, ""));
+            }
+            Event::End(_) => {
+                let name = self
+                    .stack
+                    .pop()
+                    .ok_or_else(|| anyhow!("Unbalanced multistatus XML"))?;
+                let text = std::mem::take(&mut self.text);
+                return Ok(self.end(&name, text.trim()));
+            }
+            Event::Text(t) => self.text.push_str(&t.xml_content()?),
+            Event::CData(t) => self.text.push_str(&t.decode()?),
+            Event::GeneralRef(r) => {
+                if let Some(c) = r.resolve_char_ref()? {
+                    self.text.push(c);
+                } else if let Some(s) = resolve_predefined_entity(&r.decode()?) {
+                    self.text.push_str(s);
+                }
+            }
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn start(&mut self, name: &Name) {
+        match (name.0.as_str(), name.1.as_str()) {
+            (DAV, "response") => self.response = Some(Response::default()),
+            (DAV, "propstat") => {
+                if let Some(response) = self.response.as_mut() {
+                    response.propstats.push(Propstat::default());
+                }
+            }
+            (DAV, "resourcetype") => {
+                if let Some(prop) = self.current_prop() {
+                    prop.resourcetype = Some(ResourceType::default());
+                }
+            }
+            (DAV, "owner") => {
+                if let Some(prop) = self.current_prop() {
+                    prop.owner = Some(Owner::default());
+                }
+            }
+            (DAV, "current-user-privilege-set") => {
+                if let Some(prop) = self.current_prop() {
+                    prop.privileges = Some(Vec::new());
+                }
+            }
+            (_, privilege) if self.parent_is(DAV, "privilege") => {
+                let privilege = privilege.to_string();
+                if let Some(privileges) = self.current_prop().and_then(|p| p.privileges.as_mut()) {
+                    privileges.push(privilege);
+                }
+            }
+            (CALENDARSERVER, "subscribed") if self.parent_is(DAV, "resourcetype") => {
+                if let Some(resourcetype) = self.current_resourcetype() {
+                    resourcetype.subscribed = true;
+                }
+            }
+            (CALDAV, "calendar") if self.parent_is(DAV, "resourcetype") => {
+                if let Some(resourcetype) = self.current_resourcetype() {
+                    resourcetype.calendar = true;
+                }
+            }
+            (NEXTCLOUD, "deleted-calendar") if self.parent_is(DAV, "resourcetype") => {
+                if let Some(resourcetype) = self.current_resourcetype() {
+                    resourcetype.calendar_deleted = true;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, name: &Name, text: &str) -> Option<Response> {
+        match (name.0.as_str(), name.1.as_str()) {
+            (DAV, "response") => return self.response.take(),
+            (DAV, "href") if self.parent_is(DAV, "response") => {
+                if let Some(response) = self.response.as_mut() {
+                    response.href = text.to_string();
+                }
+            }
+            (DAV, "href") if self.parent_is(DAV, "owner") => {
+                if let Some(owner) = self.current_prop().and_then(|p| p.owner.as_mut()) {
+                    owner.href = text.to_string();
+                }
+            }
+            (DAV, "status") if self.parent_is(DAV, "propstat") => {
+                if let Some(propstat) = self.current_propstat() {
+                    propstat.status = text.to_string();
+                }
+            }
+            (DAV, "displayname") if self.parent_is(DAV, "prop") => {
+                if let Some(prop) = self.current_prop() {
+                    prop.displayname = Some(text.to_string());
+                }
+            }
+            (DAV, "getetag") if self.parent_is(DAV, "prop") => {
+                if let Some(prop) = self.current_prop() {
+                    prop.getetag = Some(text.to_string());
+                }
+            }
+            (CALDAV, "calendar-data") if self.parent_is(DAV, "prop") => {
+                if let Some(prop) = self.current_prop() {
+                    prop.calendar_data = Some(text.to_string());
+                }
+            }
+            (DAV, "quota-available-bytes") if self.parent_is(DAV, "prop") => {
+                if let Some(prop) = self.current_prop() {
+                    prop.quota_available_bytes = Some(text.to_string());
+                }
+            }
+            (DAV, "quota-used-bytes") if self.parent_is(DAV, "prop") => {
+                if let Some(prop) = self.current_prop() {
+                    prop.quota_used_bytes = Some(text.to_string());
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn parent_is(&self, namespace: &str, local_name: &str) -> bool {
+        self.stack
+            .last()
+            .is_some_and(|(ns, local)| ns == namespace && local == local_name)
+    }
+
+    fn current_propstat(&mut self) -> Option<&mut Propstat> {
+        self.response.as_mut()?.propstats.last_mut()
+    }
+
+    fn current_prop(&mut self) -> Option<&mut Prop> {
+        self.current_propstat().map(|propstat| &mut propstat.prop)
+    }
+
+    fn current_resourcetype(&mut self) -> Option<&mut ResourceType> {
+        self.current_prop()?.resourcetype.as_mut()
+    }
+}
+
+/// A `schedule-response` body of a POST to the scheduling outbox (RFC 6638).
+#[derive(Debug, Default)]
+pub struct ScheduleResponse {
+    pub responses: Vec<RecipientResponse>,
+}
+
+/// The answer for one recipient of a scheduling request.
+#[derive(Debug, Default)]
+pub struct RecipientResponse {
+    /// e.g. `mailto:alice@example.com`
+    pub recipient: String,
+    /// e.g. `2.0;Success`
+    pub request_status: String,
+    pub calendar_data: Option<String>,
+}
+
+impl ScheduleResponse {
+    pub fn parse(xml: &str) -> Result<Self> {
+        let mut reader = NsReader::from_str(xml);
+        let mut stack: Vec<Name> = Vec::new();
+        let mut text = String::new();
+        let mut schedule_response = ScheduleResponse::default();
+
+        loop {
+            let (namespace, event) = reader
+                .read_resolved_event()
+                .context("Failed to parse schedule-response XML")?;
+            match event {
+                Event::Eof => break,
+                Event::Start(start) => {
+                    let name = resolve_name(namespace, &start);
+                    if name.0 == CALDAV && name.1 == "response" {
+                        schedule_response
+                            .responses
+                            .push(RecipientResponse::default());
+                    }
+                    stack.push(name);
+                    text.clear();
+                }
+                Event::End(_) => {
+                    let name = stack
+                        .pop()
+                        .ok_or_else(|| anyhow!("Unbalanced schedule-response XML"))?;
+                    let text = std::mem::take(&mut text);
+                    let parent = stack
+                        .last()
+                        .map(|(ns, local)| (ns.as_str(), local.as_str()));
+                    let Some(response) = schedule_response.responses.last_mut() else {
+                        continue;
+                    };
+                    match (name.0.as_str(), name.1.as_str(), parent) {
+                        (DAV, "href", Some((CALDAV, "recipient"))) => {
+                            response.recipient = text.trim().to_string()
+                        }
+                        (CALDAV, "request-status", _) => {
+                            response.request_status = text.trim().to_string()
+                        }
+                        (CALDAV, "calendar-data", _) => response.calendar_data = Some(text),
+                        _ => {}
+                    }
+                }
+                Event::Text(t) => text.push_str(&t.xml_content()?),
+                Event::CData(t) => text.push_str(&t.decode()?),
+                Event::GeneralRef(r) => {
+                    if let Some(c) = r.resolve_char_ref()? {
+                        text.push(c);
+                    } else if let Some(s) = resolve_predefined_entity(&r.decode()?) {
+                        text.push_str(s);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(schedule_response)
+    }
+}
+
+fn resolve_name(namespace: ResolveResult, start: &BytesStart) -> Name {
+    let namespace = match namespace {
+        ResolveResult::Bound(ns) => String::from_utf8_lossy(ns.as_ref()).into_owned(),
+        _ => String::new(),
+    };
+    let local_name = String::from_utf8_lossy(start.local_name().as_ref()).into_owned();
+    (namespace, local_name)
+}
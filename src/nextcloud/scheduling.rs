@@ -0,0 +1,101 @@
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use uuid::Uuid;
+
+use super::models::ScheduleResponse;
+use crate::{
+    freebusy::{self, BusyPeriod},
+    http::{self, Client},
+};
+
+/// Asks the scheduling outbox of the account for the busy times of the attendees (RFC 6638
+/// free/busy request), returning them per attendee in the given order.
+///
+/// The organizer must be an email address of the account. Fails if the server can't answer
+/// for one of the attendees, as their busy times would silently be missing otherwise.
+#[allow(clippy::too_many_arguments)]
+pub async fn query_free_busy(
+    client: &Client,
+    nextcloud_url: &str,
+    username: &str,
+    password: &str,
+    organizer: &str,
+    attendees: &[String],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<(String, Vec<BusyPeriod>)>> {
+    let format = |time: DateTime<Utc>| time.format("%Y%m%dT%H%M%SZ").to_string();
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!(
+            "PRODID:-//nextcloud-ics-sync//nextcloud-ics-sync {}//EN",
+            env!("CARGO_PKG_VERSION")
+        ),
+        "METHOD:REQUEST".to_string(),
+        "BEGIN:VFREEBUSY".to_string(),
+        format!("UID:{}", Uuid::new_v4()),
+        format!("DTSTAMP:{}", format(Utc::now())),
+        format!("DTSTART:{}", format(start)),
+        format!("DTEND:{}", format(end)),
+        format!("ORGANIZER:mailto:{}", organizer),
+    ];
+    lines.extend(
+        attendees
+            .iter()
+            .map(|attendee| format!("ATTENDEE:mailto:{}", attendee)),
+    );
+    lines.extend(["END:VFREEBUSY".to_string(), "END:VCALENDAR".to_string()]);
+    let body = lines.join("\r\n") + "\r\n";
+
+    let url = format!(
+        "{}/remote.php/dav/calendars/{}/outbox/",
+        nextcloud_url, username
+    );
+    let request = http::dav_request(client, "POST", &url, username, password)
+        .header("Content-Type", "text/calendar; charset=utf-8")
+        .body(body);
+    let response = http::send(client, request)
+        .await
+        .context("Failed to send the free/busy request")?;
+    match response.status() {
+        StatusCode::OK => {}
+        StatusCode::FORBIDDEN => bail!(
+            "The free/busy request was refused, the organizer {} must be an email address of \
+             the Nextcloud account {}",
+            organizer,
+            username
+        ),
+        status => bail!("The free/busy request failed with status {}", status),
+    }
+    let xml_data = response
+        .text()
+        .await
+        .context("Failed to read the free/busy response")?;
+    let schedule_response = ScheduleResponse::parse(&xml_data)?;
+
+    let mut busy_times = Vec::new();
+    for attendee in attendees {
+        let Some(response) = schedule_response.responses.iter().find(|response| {
+            let recipient = response.recipient.trim_start_matches("mailto:");
+            recipient.eq_ignore_ascii_case(attendee)
+        }) else {
+            bail!("The free/busy response has no answer for {}", attendee);
+        };
+        if !response.request_status.starts_with("2.") {
+            bail!(
+                "No free/busy information for {}: {}",
+                attendee,
+                response.request_status
+            );
+        }
+        let periods = match &response.calendar_data {
+            Some(calendar_data) => freebusy::busy_periods(calendar_data)
+                .with_context(|| format!("Invalid free/busy information for {}", attendee))?,
+            None => Vec::new(),
+        };
+        busy_times.push((attendee.clone(), periods));
+    }
+    Ok(busy_times)
+}
@@ -0,0 +1,222 @@
+use std::fmt;
+
+use anyhow::{Context, Result, bail};
+use log::{debug, info, warn};
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+use crate::http::{self, Client};
+
+/// The public `status.php` of a Nextcloud instance.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServerStatus {
+    #[serde(default)]
+    pub installed: bool,
+    #[serde(default)]
+    pub maintenance: bool,
+    /// Like `28.0.1.1`.
+    #[serde(default)]
+    pub version: String,
+}
+
+pub async fn get_status(client: &Client, nextcloud_url: &str) -> Result<ServerStatus> {
+    let url = format!("{}/status.php", nextcloud_url);
+    let response = http::send(client, client.get(&url))
+        .await
+        .context("Failed to request status.php")?;
+    // Instances in maintenance mode answer every request, status.php included, with 503
+    if response.status() == StatusCode::SERVICE_UNAVAILABLE {
+        return Ok(ServerStatus {
+            installed: true,
+            maintenance: true,
+            version: String::new(),
+        });
+    }
+
+    response
+        .error_for_status()
+        .with_context(|| format!("status.php request failed. URL: {}", url))?
+        .json()
+        .await
+        .context("Failed to parse status.php response")
+}
+
+/// Fails early with a clear message if the instance is in maintenance mode or the calendar
+/// doesn't support CalDAV, instead of failing every single request.
+///
+/// Probes that can't be answered, e.g. because a proxy blocks `status.php`, are only logged.
+pub async fn ensure_available(
+    client: &Client,
+    nextcloud_url: &str,
+    nextcloud_calendar_url: &str,
+    username: &str,
+    password: &str,
+) -> Result<()> {
+    match get_status(client, nextcloud_url).await {
+        Ok(status) if status.maintenance => bail!(
+            "Nextcloud at {} is in maintenance mode, try again later",
+            nextcloud_url
+        ),
+        Ok(status) if !status.installed => {
+            bail!("Nextcloud at {} is not installed yet", nextcloud_url)
+        }
+        Ok(status) => debug!("Nextcloud version: {}", status.version),
+        Err(e) => warn!("Could not check the Nextcloud status: {:#}", e),
+    }
+
+    let request = http::dav_request(
+        client,
+        "OPTIONS",
+        nextcloud_calendar_url,
+        username,
+        password,
+    );
+    let dav = match http::send(client, request).await {
+        Ok(response) => response
+            .headers()
+            .get_all("DAV")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .collect::<Vec<_>>()
+            .join(","),
+        Err(e) => {
+            warn!("Could not check the calendar capabilities: {:#}", e);
+            return Ok(());
+        }
+    };
+    if !dav.is_empty()
+        && !dav
+            .split(',')
+            .any(|class| class.trim() == "calendar-access")
+    {
+        bail!(
+            "{} does not advertise CalDAV (calendar-access), check NEXTCLOUD_URL and CALENDAR_ID",
+            nextcloud_calendar_url
+        );
+    }
+    Ok(())
+}
+
+/// Older releases aren't tested and lack the CalDAV features the sync relies on.
+pub const MIN_SUPPORTED_MAJOR: u32 = 20;
+
+// Open-ended time-range filters in calendar-queries are rejected or ignored before this
+const TIME_RANGE_FILTER_MAJOR: u32 = 24;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+pub struct Version {
+    pub major: u32,
+    #[serde(default)]
+    pub minor: u32,
+    #[serde(default)]
+    pub micro: u32,
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.micro)
+    }
+}
+
+/// Workarounds for quirks of the detected server version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Compatibility {
+    /// `None` if the version couldn't be detected, all features are assumed then.
+    pub version: Option<Version>,
+    /// Whether calendar-queries may carry a time-range filter, otherwise the sync window is
+    /// applied to the downloaded events.
+    pub time_range_filter: bool,
+}
+
+impl Default for Compatibility {
+    fn default() -> Self {
+        Self {
+            version: None,
+            time_range_filter: true,
+        }
+    }
+}
+
+impl Compatibility {
+    pub fn for_version(version: Version) -> Self {
+        Self {
+            version: Some(version),
+            time_range_filter: version.major >= TIME_RANGE_FILTER_MAJOR,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct CapabilitiesResponse {
+    ocs: Ocs,
+}
+
+#[derive(Deserialize)]
+struct Ocs {
+    data: CapabilitiesData,
+}
+
+#[derive(Deserialize)]
+struct CapabilitiesData {
+    version: Version,
+}
+
+/// Reads the server version from the OCS capabilities endpoint.
+pub async fn get_version(
+    client: &Client,
+    nextcloud_url: &str,
+    username: &str,
+    password: &str,
+) -> Result<Version> {
+    let url = format!(
+        "{}/ocs/v1.php/cloud/capabilities?format=json",
+        nextcloud_url
+    );
+    let request = client
+        .get(&url)
+        .basic_auth(username, Some(password))
+        .header("OCS-APIRequest", "true");
+    let capabilities: CapabilitiesResponse = http::send(client, request)
+        .await
+        .context("Failed to request the capabilities")?
+        .error_for_status()
+        .with_context(|| format!("Capabilities request failed. URL: {}", url))?
+        .json()
+        .await
+        .context("Failed to parse the capabilities response")?;
+    Ok(capabilities.ocs.data.version)
+}
+
+/// Detects the server version and the workarounds it needs, failing for unsupported versions.
+pub async fn negotiate(
+    client: &Client,
+    nextcloud_url: &str,
+    username: &str,
+    password: &str,
+) -> Result<Compatibility> {
+    let version = match get_version(client, nextcloud_url, username, password).await {
+        Ok(version) => version,
+        Err(e) => {
+            warn!("Could not detect the Nextcloud version: {:#}", e);
+            return Ok(Compatibility::default());
+        }
+    };
+    if version.major < MIN_SUPPORTED_MAJOR {
+        bail!(
+            "Nextcloud {} is not supported, at least version {} is required",
+            version,
+            MIN_SUPPORTED_MAJOR
+        );
+    }
+
+    let compatibility = Compatibility::for_version(version);
+    if !compatibility.time_range_filter {
+        info!(
+            "Compatibility mode for Nextcloud {}: the sync window is applied locally",
+            version
+        );
+    } else {
+        debug!("Nextcloud {} needs no workarounds", version);
+    }
+    Ok(compatibility)
+}
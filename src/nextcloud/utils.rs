@@ -1,17 +1,58 @@
 use icalendar::{Component, Event};
-use urlencoding::encode;
+use sha2::{Digest, Sha256};
 
-pub fn get_calendar_id_after_username<'a>(s: &'a str, username: &str) -> Option<String> {
+pub fn get_calendar_id_after_username(s: &str, username: &str) -> Option<String> {
     s.split_once(&format!("/{}/", username))
         .map(|(_, remainder)| remainder.trim_matches('/').to_string())
         .filter(|remainder| !remainder.is_empty())
 }
 
 pub fn process_event(mut event: Event) -> Event {
-    if let Some(uid) = event.get_uid() {
-        let encoded_uid = encode(uid).into_owned().replace("%2F", "-");
-        event.uid(&encoded_uid);
+    if event.get_uid().is_some() {
         event.add_property("X-SYNCED", "TRUE");
     }
     event
 }
+
+/// File name for a new event, the SHA-256 of the UID in hex. Distinct UIDs never share a name
+/// and names never contain `%`, `/` or other reserved characters, however long the UID is.
+pub fn resource_name(uid: &str) -> String {
+    let digest = Sha256::digest(uid.as_bytes());
+    let hex: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("{}.ics", hex)
+}
+
+/// A short hash that stays the same across runs, platforms and Rust versions.
+pub(crate) fn stable_hash(text: &str) -> String {
+    base32(&fnv1a_128(text.as_bytes()).to_be_bytes())
+}
+
+// FNV-1a is stable across platforms and Rust versions, unlike the std hashers
+fn fnv1a_128(bytes: &[u8]) -> u128 {
+    const OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+    const PRIME: u128 = 0x0000000001000000000000000000013b;
+
+    bytes.iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u128::from(*byte)).wrapping_mul(PRIME)
+    })
+}
+
+const BASE32_ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+fn base32(bytes: &[u8]) -> String {
+    let mut result = String::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for byte in bytes {
+        buffer = (buffer << 8) | u32::from(*byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            result.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        result.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    result
+}
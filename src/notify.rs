@@ -0,0 +1,293 @@
+use std::{fmt, str::FromStr, sync::Arc};
+
+use anyhow::{Context, Result, bail};
+use futures::future::BoxFuture;
+use log::{debug, warn};
+use serde_json::json;
+use urlencoding::encode;
+use uuid::Uuid;
+
+use crate::{
+    SyncReport,
+    http::{self, Client},
+    redact,
+};
+
+// Limits of the admin notifications API
+const MAX_SUBJECT_CHARS: usize = 255;
+const MAX_MESSAGE_CHARS: usize = 4000;
+
+pub const TELEGRAM_API_URL: &str = "https://api.telegram.org";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// A sync run that changed the calendar.
+    Summary,
+    /// A sync run that failed.
+    Failure,
+}
+
+impl Severity {
+    pub const ALL: [Severity; 2] = [Severity::Summary, Severity::Failure];
+}
+
+impl FromStr for Severity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "summary" => Ok(Self::Summary),
+            "failure" => Ok(Self::Failure),
+            _ => bail!("Invalid severity: {} (expected summary or failure)", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub severity: Severity,
+    pub subject: String,
+    pub message: String,
+}
+
+impl Notification {
+    /// Summarizes a successful run, `None` if it changed nothing.
+    pub fn sync_succeeded(report: &SyncReport) -> Option<Self> {
+        if report.uploaded == 0 && report.deleted == 0 {
+            return None;
+        }
+        Some(Self {
+            severity: Severity::Summary,
+            subject: format!(
+                "Calendar sync: {} uploaded, {} deleted",
+                report.uploaded, report.deleted
+            ),
+            message: format!(
+                "{} events were uploaded, {} deleted and {} unchanged.",
+                report.uploaded, report.deleted, report.unchanged
+            ),
+        })
+    }
+
+    pub fn sync_failed(error: &anyhow::Error) -> Self {
+        Self {
+            severity: Severity::Failure,
+            subject: "Calendar sync failed".to_string(),
+            message: redact::text(&format!("{:#}", error)),
+        }
+    }
+}
+
+/// Delivers notifications about sync runs.
+pub trait Notifier: Send + Sync {
+    fn notify<'a>(
+        &'a self,
+        client: &'a Client,
+        notification: &'a Notification,
+    ) -> BoxFuture<'a, Result<()>>;
+}
+
+/// A notification in the Nextcloud web interface and apps of `recipient`, sent through the
+/// admin notifications API, which requires the credentials of an admin.
+pub struct NextcloudNotifier {
+    pub nextcloud_url: String,
+    pub username: String,
+    pub password: String,
+    pub recipient: String,
+}
+
+impl Notifier for NextcloudNotifier {
+    fn notify<'a>(
+        &'a self,
+        client: &'a Client,
+        notification: &'a Notification,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let url = format!(
+                "{}/ocs/v2.php/apps/notifications/api/v2/admin_notifications/{}",
+                self.nextcloud_url,
+                encode(&self.recipient)
+            );
+            let body = json!({
+                "shortMessage": truncate(&notification.subject, MAX_SUBJECT_CHARS),
+                "longMessage": truncate(&notification.message, MAX_MESSAGE_CHARS),
+            });
+            send_ocs(client, &url, &self.username, &self.password, body)
+                .await
+                .with_context(|| format!("Failed to notify Nextcloud user {}", self.recipient))
+        })
+    }
+}
+
+/// A message in a Nextcloud Talk conversation, posted by the syncing account.
+pub struct TalkNotifier {
+    pub nextcloud_url: String,
+    pub username: String,
+    pub password: String,
+    /// The token at the end of the conversation's URL.
+    pub room_token: String,
+}
+
+impl Notifier for TalkNotifier {
+    fn notify<'a>(
+        &'a self,
+        client: &'a Client,
+        notification: &'a Notification,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let url = format!(
+                "{}/ocs/v2.php/apps/spreed/api/v1/chat/{}",
+                self.nextcloud_url,
+                encode(&self.room_token)
+            );
+            let body = json!({
+                "message": format!("{}\n\n{}", notification.subject, notification.message),
+            });
+            send_ocs(client, &url, &self.username, &self.password, body)
+                .await
+                .context("Failed to post to the Talk conversation")
+        })
+    }
+}
+
+/// A message in a Matrix room, sent with the access token of a bot or user in the room.
+pub struct MatrixNotifier {
+    pub homeserver_url: String,
+    pub access_token: String,
+    /// The internal room ID like `!abc:example.org`, not an alias.
+    pub room_id: String,
+}
+
+impl Notifier for MatrixNotifier {
+    fn notify<'a>(
+        &'a self,
+        client: &'a Client,
+        notification: &'a Notification,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            // The transaction ID makes retried requests idempotent
+            let url = format!(
+                "{}/_matrix/client/v3/rooms/{}/send/m.room.message/{}",
+                self.homeserver_url.trim_end_matches('/'),
+                encode(&self.room_id),
+                Uuid::new_v4()
+            );
+            let body = json!({
+                "msgtype": "m.text",
+                "body": format!("{}\n\n{}", notification.subject, notification.message),
+            });
+            let request = client.put(&url).bearer_auth(&self.access_token).json(&body);
+            http::send(client, request)
+                .await?
+                .error_for_status()
+                .with_context(|| format!("Failed to post to Matrix room {}", self.room_id))?;
+            Ok(())
+        })
+    }
+}
+
+/// A message from a Telegram bot to a chat.
+pub struct TelegramNotifier {
+    /// [`TELEGRAM_API_URL`] unless a local Bot API server is used.
+    pub api_url: String,
+    pub bot_token: String,
+    pub chat_id: String,
+}
+
+impl Notifier for TelegramNotifier {
+    fn notify<'a>(
+        &'a self,
+        client: &'a Client,
+        notification: &'a Notification,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let url = format!(
+                "{}/bot{}/sendMessage",
+                self.api_url.trim_end_matches('/'),
+                self.bot_token
+            );
+            let body = json!({
+                "chat_id": self.chat_id,
+                "text": format!("{}\n\n{}", notification.subject, notification.message),
+            });
+            // The URL contains the bot token, so it is left out of the error
+            let status = http::send(client, client.post(&url).json(&body))
+                .await
+                .map_err(|_| anyhow::anyhow!("Failed to reach the Telegram Bot API"))?
+                .status();
+            if !status.is_success() {
+                bail!(
+                    "Failed to message Telegram chat {}: {}",
+                    self.chat_id,
+                    status
+                );
+            }
+            Ok(())
+        })
+    }
+}
+
+/// The configured notifiers, failures to notify are logged and never fail the sync.
+#[derive(Clone, Default)]
+pub struct Notifiers {
+    notifiers: Vec<(Arc<dyn Notifier>, Vec<Severity>)>,
+}
+
+impl Notifiers {
+    /// Adds a notifier that receives the notifications of the given severities.
+    pub fn push(&mut self, notifier: impl Notifier + 'static, severities: Vec<Severity>) {
+        self.notifiers.push((Arc::new(notifier), severities));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.notifiers.is_empty()
+    }
+
+    pub async fn send(&self, client: &Client, notification: &Notification) {
+        let notifiers = self
+            .notifiers
+            .iter()
+            .filter(|(_, severities)| severities.contains(&notification.severity));
+        for (notifier, _) in notifiers {
+            debug!("Sending notification: {}", notification.subject);
+            if let Err(e) = notifier.notify(client, notification).await {
+                warn!("{:#}", e);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Notifiers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Notifiers")
+            .field("count", &self.notifiers.len())
+            .finish()
+    }
+}
+
+async fn send_ocs(
+    client: &Client,
+    url: &str,
+    username: &str,
+    password: &str,
+    body: serde_json::Value,
+) -> Result<()> {
+    let request = client
+        .post(url)
+        .basic_auth(username, Some(password))
+        .header("OCS-APIRequest", "true")
+        .header("Accept", "application/json")
+        .json(&body);
+    http::send(client, request)
+        .await?
+        .error_for_status()
+        .with_context(|| format!("OCS request failed. URL: {}", url))?;
+    Ok(())
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => text[..end].to_string(),
+        None => text.to_string(),
+    }
+}
@@ -0,0 +1,208 @@
+use std::{
+    fs,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result, bail};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use urlencoding::encode;
+
+use crate::{
+    http::{self, Client},
+    paths,
+};
+
+// Tokens expiring within this many seconds are refreshed ahead of time
+const EXPIRY_MARGIN_SECS: u64 = 60;
+
+#[derive(Debug, Clone)]
+pub struct OAuthConfig {
+    pub token_url: String,
+    /// Only set for the authorization-code flow, otherwise client credentials are used.
+    pub auth_url: Option<String>,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub scope: Option<String>,
+    pub redirect_uri: String,
+    pub token_cache: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedToken {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_at: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_in: Option<u64>,
+}
+
+impl CachedToken {
+    fn is_valid(&self) -> bool {
+        self.expires_at
+            .is_none_or(|expires_at| expires_at > now() + EXPIRY_MARGIN_SECS)
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Returns a valid access token, refreshing or requesting a new one if needed.
+pub async fn get_access_token(client: &Client, config: &OAuthConfig) -> Result<String> {
+    let cached = load_cached_token(config);
+
+    if let Some(token) = &cached
+        && token.is_valid()
+    {
+        debug!("Using cached OAuth access token");
+        return Ok(token.access_token.clone());
+    }
+
+    let mut refreshed = None;
+    if let Some(refresh_token) = cached.and_then(|token| token.refresh_token) {
+        info!("Refreshing OAuth access token...");
+        match request_token(
+            client,
+            config,
+            &[
+                ("grant_type", "refresh_token"),
+                ("refresh_token", &refresh_token),
+            ],
+        )
+        .await
+        {
+            Ok(token) => {
+                refreshed = Some(CachedToken {
+                    // Providers may omit the refresh token when it stays the same
+                    refresh_token: token.refresh_token.or(Some(refresh_token)),
+                    ..token
+                })
+            }
+            // The refresh token was revoked or expired, start over
+            Err(e) => warn!("Failed to refresh OAuth access token: {:#}", e),
+        }
+    }
+
+    let token = match refreshed {
+        Some(token) => token,
+        None if config.auth_url.is_none() => {
+            info!("Requesting OAuth access token via client credentials...");
+            let mut params = vec![("grant_type", "client_credentials")];
+            if let Some(scope) = &config.scope {
+                params.push(("scope", scope));
+            }
+            request_token(client, config, &params).await?
+        }
+        None => bail!("No valid OAuth token cached. Run `nextcloud-ics-sync oauth-login` first."),
+    };
+
+    save_cached_token(config, &token)?;
+    Ok(token.access_token)
+}
+
+/// Builds the URL the user has to open to authorize this tool.
+pub fn authorization_url(config: &OAuthConfig) -> Result<String> {
+    let auth_url = config
+        .auth_url
+        .as_ref()
+        .context("ICS_OAUTH_AUTH_URL environment variable not set")?;
+    let separator = if auth_url.contains('?') { '&' } else { '?' };
+
+    let mut url = format!(
+        "{}{}response_type=code&client_id={}&redirect_uri={}",
+        auth_url,
+        separator,
+        encode(&config.client_id),
+        encode(&config.redirect_uri)
+    );
+    if let Some(scope) = &config.scope {
+        url.push_str(&format!("&scope={}", encode(scope)));
+    }
+    Ok(url)
+}
+
+/// Exchanges an authorization code for tokens and stores them in the token cache.
+pub async fn exchange_code(client: &Client, config: &OAuthConfig, code: &str) -> Result<()> {
+    let token = request_token(
+        client,
+        config,
+        &[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &config.redirect_uri),
+        ],
+    )
+    .await?;
+
+    save_cached_token(config, &token)
+}
+
+async fn request_token(
+    client: &Client,
+    config: &OAuthConfig,
+    params: &[(&str, &str)],
+) -> Result<CachedToken> {
+    let mut params = params.to_vec();
+    params.push(("client_id", &config.client_id));
+    if let Some(secret) = &config.client_secret {
+        params.push(("client_secret", secret));
+    }
+
+    let response = http::send(client, client.post(&config.token_url).form(&params))
+        .await
+        .with_context(|| format!("Failed to request OAuth token. URL: {}", config.token_url))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        bail!(
+            "OAuth token request failed with status {} and body of:\n{}",
+            status,
+            body
+        );
+    }
+
+    let token: TokenResponse = response
+        .json()
+        .await
+        .context("Failed to parse OAuth token response")?;
+
+    Ok(CachedToken {
+        access_token: token.access_token,
+        refresh_token: token.refresh_token,
+        expires_at: token.expires_in.map(|expires_in| now() + expires_in),
+    })
+}
+
+fn load_cached_token(config: &OAuthConfig) -> Option<CachedToken> {
+    let content = fs::read_to_string(&config.token_cache).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn save_cached_token(config: &OAuthConfig, token: &CachedToken) -> Result<()> {
+    let content = serde_json::to_string_pretty(token)?;
+    if let Some(dir) = config.token_cache.parent() {
+        fs::create_dir_all(dir).with_context(|| {
+            format!(
+                "Failed to create OAuth token cache directory. Path: {}",
+                dir.display()
+            )
+        })?;
+    }
+    paths::write_private(&config.token_cache, content).with_context(|| {
+        format!(
+            "Failed to write OAuth token cache. Path: {}",
+            config.token_cache.display()
+        )
+    })
+}
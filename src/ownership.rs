@@ -0,0 +1,88 @@
+use icalendar::{Component, Event};
+
+use crate::explain;
+
+/// Properties the sync always writes, they identify the event and track its changes.
+const BOOKKEEPING: &[&str] = &[
+    "UID",
+    "RECURRENCE-ID",
+    "DTSTAMP",
+    "LAST-MODIFIED",
+    "SEQUENCE",
+    "X-SYNCED",
+    "X-SYNC-RUN",
+];
+
+/// Which properties of a synced event come from the source and which belong to the Nextcloud
+/// calendar, e.g. alarms or categories added there. Subcomponents like `VALARM` are named by
+/// their kind.
+#[derive(Debug, Clone, Default)]
+pub struct PropertyOwnership {
+    /// Only these are taken from the source, all if empty.
+    pub owned: Vec<String>,
+    /// Kept as they are in the calendar, even if the source has them.
+    pub target_owned: Vec<String>,
+}
+
+impl PropertyOwnership {
+    pub fn is_configured(&self) -> bool {
+        !self.owned.is_empty() || !self.target_owned.is_empty()
+    }
+
+    pub fn is_owned(&self, name: &str) -> bool {
+        let listed = |names: &[String]| names.iter().any(|owned| owned.eq_ignore_ascii_case(name));
+        BOOKKEEPING.contains(&name)
+            || (!listed(&self.target_owned) && (self.owned.is_empty() || listed(&self.owned)))
+    }
+
+    /// The event to upload, with the owned parts of the source event and everything else of
+    /// the existing copy.
+    pub fn merge(&self, source_event: &Event, existing_event: Option<&Event>) -> Event {
+        if !self.is_configured() {
+            return source_event.clone();
+        }
+
+        let mut merged = Event::new();
+        self.copy(source_event, &mut merged, true);
+        if let Some(existing_event) = existing_event {
+            self.copy(existing_event, &mut merged, false);
+        }
+        merged
+    }
+
+    /// Whether the owned parts of both events are the same, so the upload can be skipped.
+    pub fn same_owned_content(&self, source_event: &Event, existing_event: &Event) -> bool {
+        let owned_values = |event: &Event| {
+            let mut values = explain::property_values(event);
+            values.retain(|name, _| self.is_owned(name) && !BOOKKEEPING.contains(&name.as_str()));
+            let components: Vec<_> = event
+                .components()
+                .iter()
+                .filter(|component| self.is_owned(&component.component_kind()))
+                .map(explain::property_values)
+                .collect();
+            (values, components)
+        };
+        owned_values(source_event) == owned_values(existing_event)
+    }
+
+    fn copy(&self, from: &Event, to: &mut Event, owned: bool) {
+        for (name, property) in from.properties() {
+            if self.is_owned(name) == owned {
+                to.append_property(property.clone());
+            }
+        }
+        for (name, properties) in from.multi_properties() {
+            if self.is_owned(name) == owned {
+                for property in properties {
+                    to.append_multi_property(property.clone());
+                }
+            }
+        }
+        for component in from.components() {
+            if self.is_owned(&component.component_kind()) == owned {
+                to.append_component(component.clone());
+            }
+        }
+    }
+}
@@ -0,0 +1,102 @@
+#[cfg(unix)]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+const APP_NAME: &str = "nextcloud-ics-sync";
+
+static STATE_DIR_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Uses `dir` instead of the platform state directory, e.g. for `--state-dir`.
+///
+/// Only the first call has an effect.
+pub fn set_state_dir(dir: &Path) {
+    let _ = STATE_DIR_OVERRIDE.set(dir.to_path_buf());
+}
+
+/// `$XDG_CONFIG_HOME/nextcloud-ics-sync`, `%APPDATA%\nextcloud-ics-sync` on Windows.
+pub fn config_dir() -> PathBuf {
+    base_dir("XDG_CONFIG_HOME", "APPDATA", ".config").join(APP_NAME)
+}
+
+/// The `.env` style configuration file loaded in addition to a `.env` in the working directory.
+pub fn config_file() -> PathBuf {
+    config_dir().join("config.env")
+}
+
+/// Journals and tokens, `$XDG_STATE_HOME/nextcloud-ics-sync` or
+/// `%LOCALAPPDATA%\nextcloud-ics-sync\state` on Windows.
+pub fn state_dir() -> PathBuf {
+    if let Some(dir) = STATE_DIR_OVERRIDE.get() {
+        return dir.clone();
+    }
+    match xdg_dir("XDG_STATE_HOME") {
+        Some(dir) => dir.join(APP_NAME),
+        _ if cfg!(windows) => base_dir("", "LOCALAPPDATA", "")
+            .join(APP_NAME)
+            .join("state"),
+        _ => home_dir().join(".local/state").join(APP_NAME),
+    }
+}
+
+/// Data that can be recomputed, `$XDG_CACHE_HOME/nextcloud-ics-sync` or
+/// `%LOCALAPPDATA%\nextcloud-ics-sync\cache` on Windows.
+pub fn cache_dir() -> PathBuf {
+    match xdg_dir("XDG_CACHE_HOME") {
+        Some(dir) => dir.join(APP_NAME),
+        _ if cfg!(windows) => base_dir("", "LOCALAPPDATA", "")
+            .join(APP_NAME)
+            .join("cache"),
+        _ => home_dir().join(".cache").join(APP_NAME),
+    }
+}
+
+/// Writes a file only the user can read, for tokens and other secrets. Existing files are
+/// truncated and get their permissions tightened as well.
+pub fn write_private(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(path)?;
+    #[cfg(unix)]
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    file.write_all(contents.as_ref())?;
+    file.sync_all()
+}
+
+fn base_dir(xdg_var: &str, windows_var: &str, home_fallback: &str) -> PathBuf {
+    if !xdg_var.is_empty()
+        && let Some(dir) = xdg_dir(xdg_var)
+    {
+        return dir;
+    }
+    if cfg!(windows)
+        && let Some(dir) = env::var_os(windows_var).filter(|dir| !dir.is_empty())
+    {
+        return PathBuf::from(dir);
+    }
+    home_dir().join(home_fallback)
+}
+
+// The XDG Base Directory Specification requires absolute paths and says relative ones are
+// invalid and should be ignored
+fn xdg_dir(var: &str) -> Option<PathBuf> {
+    env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+}
+
+// Without a home directory everything ends up in the working directory like before
+fn home_dir() -> PathBuf {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."))
+}
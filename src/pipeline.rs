@@ -0,0 +1,272 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use icalendar::Calendar;
+use log::{info, warn};
+
+use crate::{
+    SyncOptions, SyncReport,
+    config::Config,
+    geo::GeoEnricher,
+    http::Client,
+    nextcloud::{
+        self,
+        api::{CalendarObject, TimeRange},
+        status::Compatibility,
+    },
+    progress::SyncEngine,
+    recurrence, source, sync_calendar, tasks, timezone, window,
+};
+
+/// Checks the instance, fetches the source and the calendars and syncs the source into the
+/// calendar of the engine, the routed calendars and the additional calendars.
+pub(crate) async fn run(engine: &SyncEngine, config: &Config) -> Result<SyncReport> {
+    let SyncEngine {
+        client,
+        username,
+        password,
+        calendar_url,
+        options,
+    } = engine;
+
+    nextcloud::status::ensure_available(
+        client,
+        &config.nextcloud_url,
+        calendar_url,
+        username,
+        password,
+    )
+    .await?;
+    for target_url in std::iter::once(calendar_url.as_str())
+        .chain(config.calendar_routes.calendar_urls())
+        .chain(config.additional_calendar_urls.iter().map(String::as_str))
+    {
+        nextcloud::api::ensure_writable(
+            client,
+            &config.nextcloud_url,
+            target_url,
+            username,
+            password,
+        )
+        .await?;
+    }
+    let compatibility =
+        nextcloud::status::negotiate(client, &config.nextcloud_url, username, password).await?;
+
+    // The downloads are independent, so the slower one determines the wait instead of both
+    let (source_calendar, nextcloud_calendar) = tokio::try_join!(
+        source_calendar(client, config, options.time_range),
+        nextcloud_calendar(
+            client,
+            calendar_url,
+            username,
+            password,
+            options.time_range,
+            compatibility
+        ),
+    )?;
+
+    let source_calendar = match &config.task_routing {
+        Some(routing) => {
+            let (source_calendar, tasks) = routing.split(source_calendar);
+            tasks::create_tasks(
+                client,
+                username,
+                password,
+                &routing.tasks_calendar_url,
+                tasks,
+            )
+            .await
+            .context("Failed to create tasks.")?;
+            source_calendar
+        }
+        None => source_calendar,
+    };
+
+    info!("Syncing calendars...");
+
+    // Every additional calendar receives the whole source, diffed against its own journal
+    let mut targets = Vec::new();
+    for target_url in &config.additional_calendar_urls {
+        let journal = match &options.journal {
+            Some(journal) => Some(Arc::new(journal.for_target(target_url)?)),
+            None => None,
+        };
+        let target_options = SyncOptions {
+            journal,
+            ..options.clone()
+        };
+        let target_calendar = Calendar {
+            properties: source_calendar.properties.clone(),
+            components: source_calendar.components.clone(),
+        };
+        targets.push((target_url.clone(), target_calendar, target_options));
+    }
+
+    // Every routed calendar gets its own diff, the default one was fetched already
+    let mut nextcloud_calendar = Some(nextcloud_calendar);
+    let routed = config
+        .calendar_routes
+        .split(source_calendar, calendar_url)
+        .into_iter()
+        .map(|(target_url, source_calendar)| (target_url, source_calendar, options.clone()));
+    let mut report = SyncReport::default();
+    for (target_url, source_calendar, options) in routed.chain(targets) {
+        let nextcloud_calendar = match nextcloud_calendar.take() {
+            Some(nextcloud_calendar) => nextcloud_calendar,
+            None => {
+                self::nextcloud_calendar(
+                    client,
+                    &target_url,
+                    username,
+                    password,
+                    options.time_range,
+                    compatibility,
+                )
+                .await?
+            }
+        };
+
+        let calendar_report = sync_calendar(
+            client,
+            username,
+            password,
+            &target_url,
+            source_calendar,
+            nextcloud_calendar,
+            &options,
+        )
+        .await
+        .with_context(|| format!("Failed to sync calendar {}.", target_url))?;
+
+        report.uploaded += calendar_report.uploaded;
+        report.deleted += calendar_report.deleted;
+        report.unchanged += calendar_report.unchanged;
+        report.explanations.extend(calendar_report.explanations);
+    }
+    Ok(report)
+}
+
+/// Fetches the configured source, falling back to its cached copy if enabled, and applies the
+/// timezone fixes, the script, the transforms, the enrichment, the recurrence handling and
+/// the sync window to it.
+pub async fn source_calendar(
+    client: &Client,
+    config: &Config,
+    time_range: TimeRange,
+) -> Result<Calendar> {
+    info!("Downloading source calendar from {}...", config.ics_url);
+
+    let fetched = source::from_config(config)?
+        .fetch_calendar(client)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to fetch and parse source calendar. URL: {}",
+                config.ics_url
+            )
+        });
+    let source_calendar = match fetched {
+        Ok(source_calendar) if config.use_source_cache_on_failure => {
+            if let Err(e) = config.source_cache.store(&source_calendar) {
+                warn!("{:#}", e);
+            }
+            source_calendar
+        }
+        Err(e) if config.use_source_cache_on_failure => match config.source_cache.load() {
+            Ok((source_calendar, age)) => {
+                warn!(
+                    "Syncing the cached source calendar from {} minutes ago: {:#}",
+                    age.as_secs() / 60,
+                    e
+                );
+                source_calendar
+            }
+            Err(cache_error) => {
+                return Err(e.context(format!("No usable cached copy: {:#}", cache_error)));
+            }
+        },
+        fetched => fetched?,
+    };
+
+    let source_calendar = timezone::check_tzids(source_calendar, &config.timezones);
+    let source_calendar =
+        timezone::convert_floating_times(source_calendar, &config.timezones.floating);
+
+    let source_calendar = match &config.script_path {
+        Some(script_path) => apply_script(script_path, source_calendar)?,
+        None => source_calendar,
+    };
+
+    let source_calendar = config.hooks.translate_summaries(source_calendar).await;
+    let source_calendar = config.transforms.apply(source_calendar);
+
+    let source_calendar = match &config.geo {
+        Some(geo) => GeoEnricher::from_options(geo)
+            .enrich(client, source_calendar)
+            .await
+            .context("Failed to enrich event locations.")?,
+        None => source_calendar,
+    };
+
+    let source_calendar = match (config.expand_recurrences, config.detect_series) {
+        (true, _) => recurrence::expand_calendar(source_calendar, &time_range),
+        (_, true) => recurrence::collapse_series(source_calendar),
+        _ => source_calendar,
+    };
+
+    Ok(match config.sync_window {
+        Some(_) => window::filter_calendar(source_calendar, &time_range),
+        None => source_calendar,
+    })
+}
+
+#[cfg(feature = "scripting")]
+fn apply_script(script_path: &std::path::Path, calendar: Calendar) -> Result<Calendar> {
+    info!("Applying script {}...", script_path.display());
+    crate::script::EventScript::load(script_path)?.apply_to_calendar(calendar)
+}
+
+#[cfg(not(feature = "scripting"))]
+fn apply_script(_script_path: &std::path::Path, _calendar: Calendar) -> Result<Calendar> {
+    anyhow::bail!(
+        "SCRIPT_PATH is set, but this build has no scripting support. Rebuild with `--features scripting`."
+    )
+}
+
+/// Fetches the events of the calendar in the time range, filtering them locally for servers
+/// whose calendar-query can't.
+pub async fn nextcloud_calendar(
+    client: &Client,
+    calendar_url: &str,
+    username: &str,
+    password: &str,
+    time_range: TimeRange,
+    compatibility: Compatibility,
+) -> Result<Vec<CalendarObject>> {
+    info!("Downloading nextcloud calendar  {}...", calendar_url);
+
+    let objects = nextcloud::api::get_calendar_objects(
+        client,
+        calendar_url,
+        username,
+        password,
+        if compatibility.time_range_filter {
+            time_range
+        } else {
+            TimeRange::default()
+        },
+    )
+    .await
+    .with_context(|| {
+        format!(
+            "Failed to fetch and parse current calendar. URL: {}",
+            calendar_url
+        )
+    })?;
+
+    Ok(match compatibility.time_range_filter {
+        true => objects,
+        false => window::filter_objects(objects, &time_range),
+    })
+}
@@ -0,0 +1,175 @@
+use anyhow::{Context, Result};
+use futures::{Stream, stream};
+use icalendar::Calendar;
+use tokio::sync::mpsc::{self, UnboundedSender};
+
+use crate::{
+    SyncOptions, SyncReport, config::Config, http::Client, journal::Action, nextcloud,
+    notify::Notification, pipeline, sync_calendar,
+};
+
+/// Live progress of a sync, for frontends embedding the crate.
+#[derive(Debug, Clone)]
+pub enum SyncEvent {
+    /// The Nextcloud calendar is being fetched.
+    Fetching,
+    /// The diff is done, this many events are uploaded and deleted.
+    Diffed {
+        uploads: usize,
+        deletions: usize,
+    },
+    Uploaded(String),
+    /// Deleted or moved to the archive calendar.
+    Deleted(String),
+    /// The upload or deletion of the event with this UID failed.
+    Failed(String, String),
+    /// The sync failed as a whole, this is the last event.
+    Aborted(String),
+    /// The sync finished, this is the last event.
+    Done(SyncReport),
+}
+
+/// Where a sync reports its [`SyncEvent`]s, nowhere by default.
+#[derive(Debug, Clone, Default)]
+pub struct Progress {
+    sender: Option<UnboundedSender<SyncEvent>>,
+}
+
+impl Progress {
+    pub fn channel() -> (Self, mpsc::UnboundedReceiver<SyncEvent>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let progress = Self {
+            sender: Some(sender),
+        };
+        (progress, receiver)
+    }
+
+    pub(crate) fn emit(&self, event: SyncEvent) {
+        // A frontend that stopped listening doesn't stop the sync
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(event);
+        }
+    }
+
+    /// Reports the outcome of the request for an action on an event.
+    pub(crate) fn finish<T>(&self, uid: &str, action: Action, result: &Result<T>) {
+        match (result, action) {
+            (Err(e), _) => self.emit(SyncEvent::Failed(uid.to_string(), format!("{:#}", e))),
+            (Ok(_), Action::Uploaded) => self.emit(SyncEvent::Uploaded(uid.to_string())),
+            (Ok(_), Action::Deleted) => self.emit(SyncEvent::Deleted(uid.to_string())),
+            (Ok(_), Action::Suppressed) => {}
+        }
+    }
+}
+
+/// A sync of one calendar, run in the background while its progress is streamed.
+#[derive(Debug, Clone)]
+pub struct SyncEngine {
+    pub client: Client,
+    pub username: String,
+    pub password: String,
+    pub calendar_url: String,
+    pub options: SyncOptions,
+}
+
+impl SyncEngine {
+    /// The sync into the calendar of the configuration.
+    pub fn from_config(client: Client, config: &Config, options: SyncOptions) -> Self {
+        Self {
+            client,
+            username: config.nextcloud_username.clone(),
+            password: config.nextcloud_password.clone(),
+            calendar_url: config.nextcloud_calendar_url.clone(),
+            options,
+        }
+    }
+
+    /// Runs the whole sync of the configuration: checks the instance, fetches and transforms
+    /// the source, and diffs and uploads it into the calendar of the engine and the routed and
+    /// additional calendars of the configuration.
+    ///
+    /// The hooks run around it and the notifiers are told how it went.
+    pub async fn run(&self, config: &Config) -> Result<SyncReport> {
+        config
+            .hooks
+            .pre_sync()
+            .await
+            .context("Aborting sync because the pre_sync hook failed.")?;
+
+        let result = pipeline::run(self, config).await;
+        let result = match &self.options.journal {
+            Some(journal) => result.with_context(|| {
+                format!(
+                    "Sync run {} failed, resume it with `sync --resume {}`",
+                    journal.run_id(),
+                    journal.run_id()
+                )
+            }),
+            None => result,
+        };
+
+        match &result {
+            Ok(report) => {
+                config
+                    .hooks
+                    .post_sync(true, report.uploaded, report.deleted)
+                    .await
+            }
+            Err(_) => config.hooks.post_sync(false, 0, 0).await,
+        }
+
+        let notification = match &result {
+            Ok(report) => Notification::sync_succeeded(report),
+            Err(e) => Some(Notification::sync_failed(e)),
+        };
+        if let Some(notification) = notification {
+            config.notifiers.send(&self.client, &notification).await;
+        }
+        result
+    }
+
+    /// Syncs the source calendar into the calendar, streaming the progress. The stream ends
+    /// with [`SyncEvent::Done`] or [`SyncEvent::Aborted`].
+    ///
+    /// Must be called within a Tokio runtime. The sync keeps running if the stream is dropped.
+    pub fn run_stream(self, source_calendar: Calendar) -> impl Stream<Item = SyncEvent> {
+        let (progress, receiver) = Progress::channel();
+        tokio::spawn(async move {
+            let mut options = self.options;
+            options.progress = progress.clone();
+
+            progress.emit(SyncEvent::Fetching);
+            let result = async {
+                let nextcloud_objects = nextcloud::api::get_calendar_objects(
+                    &self.client,
+                    &self.calendar_url,
+                    &self.username,
+                    &self.password,
+                    options.time_range,
+                )
+                .await?;
+                sync_calendar(
+                    &self.client,
+                    &self.username,
+                    &self.password,
+                    &self.calendar_url,
+                    source_calendar,
+                    nextcloud_objects,
+                    &options,
+                )
+                .await
+            }
+            .await;
+
+            progress.emit(match result {
+                Ok(report) => SyncEvent::Done(report),
+                Err(e) => SyncEvent::Aborted(format!("{:#}", e)),
+            });
+        });
+
+        stream::unfold(receiver, |mut receiver| async move {
+            let event = receiver.recv().await?;
+            Some((event, receiver))
+        })
+    }
+}
@@ -0,0 +1,289 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use anyhow::{Context, Result, anyhow};
+use futures::future::BoxFuture;
+use log::debug;
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    http::{Client, HttpRequest, HttpResponse, HttpTransport},
+    redact::{self, REDACTED},
+};
+
+/// Headers, query parameters and body fields whose values are never written to a recording.
+const SECRET_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    "x-api-key",
+];
+const SECRET_FIELDS: &[&str] = &[
+    "access_token",
+    "refresh_token",
+    "id_token",
+    "client_secret",
+    "code",
+    "code_verifier",
+    "password",
+    "token",
+    "key",
+    "apikey",
+    "api_key",
+    "secret",
+];
+
+/// Headers describing the transfer of the original body, which no longer apply on replay.
+const TRANSFER_HEADERS: &[&str] = &["content-length", "content-encoding", "transfer-encoding"];
+
+/// One recorded HTTP request and its response, stored as `<n>.json` in the recording directory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Exchange {
+    method: String,
+    url: String,
+    request_headers: Vec<(String, String)>,
+    request_body: Option<String>,
+    status: u16,
+    response_headers: Vec<(String, String)>,
+    response_body: String,
+}
+
+enum Mode {
+    Record {
+        dir: PathBuf,
+        count: AtomicUsize,
+    },
+    Replay {
+        exchanges: Mutex<Vec<Option<Exchange>>>,
+    },
+}
+
+/// An [`HttpTransport`] that saves every HTTP exchange with scrubbed credentials to a
+/// directory, or answers requests from such a recording instead of the network.
+///
+/// Installed with [`Client::with_transport`], so retries, Digest authentication and tracing
+/// still apply.
+pub struct Recorder {
+    client: Client,
+    mode: Mode,
+}
+
+impl Recorder {
+    /// Records the exchanges of the requests to `dir`, sending them with `client`.
+    pub fn record(dir: &Path, client: Client) -> Result<Self> {
+        fs::create_dir_all(dir).with_context(|| {
+            format!(
+                "Failed to create recording directory. Path: {}",
+                dir.display()
+            )
+        })?;
+
+        Ok(Self {
+            client,
+            mode: Mode::Record {
+                dir: dir.to_path_buf(),
+                count: AtomicUsize::new(0),
+            },
+        })
+    }
+
+    /// Answers requests from the recording in `dir`.
+    ///
+    /// Requests are matched by method and URL, each recorded response is served once.
+    pub fn replay(dir: &Path) -> Result<Self> {
+        let mut files: Vec<PathBuf> = fs::read_dir(dir)
+            .with_context(|| {
+                format!(
+                    "Failed to read recording directory. Path: {}",
+                    dir.display()
+                )
+            })?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        files.sort();
+
+        let exchanges = files
+            .iter()
+            .map(|path| {
+                let content = fs::read_to_string(path).with_context(|| {
+                    format!("Failed to read recording. Path: {}", path.display())
+                })?;
+                let exchange: Exchange = serde_json::from_str(&content).with_context(|| {
+                    format!("Failed to parse recording. Path: {}", path.display())
+                })?;
+                Ok(Some(exchange))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        debug!("Replaying {} recorded HTTP exchanges", exchanges.len());
+        Ok(Self {
+            client: Client::new(),
+            mode: Mode::Replay {
+                exchanges: Mutex::new(exchanges),
+            },
+        })
+    }
+
+    async fn record_exchange(
+        &self,
+        dir: &Path,
+        count: &AtomicUsize,
+        request: HttpRequest,
+    ) -> Result<HttpResponse> {
+        let method = request.method().to_string();
+        let url = scrub_url(&request.uri().to_string());
+        let request_headers = scrub_headers(request.headers());
+        let request_body = (!request.body().is_empty())
+            .then(|| scrub_body(&String::from_utf8_lossy(request.body())));
+
+        let request = reqwest::Request::try_from(request)?;
+        let origin = request.url().origin().ascii_serialization();
+        let response = self.client.client_for(&origin).execute(request).await?;
+        let status = response.status().as_u16();
+        let headers = response.headers().clone();
+        let body = response.text().await?;
+
+        let exchange = Exchange {
+            method,
+            url,
+            request_headers,
+            request_body,
+            status,
+            response_headers: scrub_headers(&headers),
+            response_body: scrub_body(&body),
+        };
+
+        let path = dir.join(format!("{:04}.json", count.fetch_add(1, Ordering::SeqCst)));
+        fs::write(&path, serde_json::to_string_pretty(&exchange)?)
+            .with_context(|| format!("Failed to write recording. Path: {}", path.display()))?;
+
+        // The scrubbed copy is only for the recording, the caller gets the real response
+        to_response(status, &headers, body)
+    }
+
+    fn replay_exchange(
+        exchanges: &Mutex<Vec<Option<Exchange>>>,
+        request: &HttpRequest,
+    ) -> Result<HttpResponse> {
+        let method = request.method().to_string();
+        let url = scrub_url(&request.uri().to_string());
+        let exchange = {
+            let mut exchanges = exchanges.lock().unwrap_or_else(|e| e.into_inner());
+            exchanges
+                .iter_mut()
+                .find(|e| {
+                    e.as_ref()
+                        .is_some_and(|e| e.method == method && e.url == url)
+                })
+                .and_then(Option::take)
+        };
+        let exchange =
+            exchange.ok_or_else(|| anyhow!("No recorded response for {} {}", method, url))?;
+
+        debug!("Replaying {} {}", exchange.method, exchange.url);
+        let mut headers = HeaderMap::new();
+        for (name, value) in &exchange.response_headers {
+            if let (Ok(name), Ok(value)) = (name.parse(), value.parse()) {
+                headers.append::<reqwest::header::HeaderName>(name, value);
+            }
+        }
+        to_response(exchange.status, &headers, exchange.response_body)
+    }
+}
+
+impl HttpTransport for Recorder {
+    fn execute(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse>> {
+        Box::pin(async move {
+            match &self.mode {
+                Mode::Record { dir, count } => self.record_exchange(dir, count, request).await,
+                Mode::Replay { exchanges } => Self::replay_exchange(exchanges, &request),
+            }
+        })
+    }
+}
+
+fn to_response(status: u16, headers: &HeaderMap, body: String) -> Result<HttpResponse> {
+    let mut response = ::http::Response::builder().status(status);
+    for (name, value) in headers {
+        if !TRANSFER_HEADERS.contains(&name.as_str()) {
+            response = response.header(name, value);
+        }
+    }
+    Ok(response.body(body.into_bytes())?)
+}
+
+/// The URL with credentials, the query and secret path segments masked like in logs.
+fn scrub_url(url: &str) -> String {
+    redact::url(url)
+}
+
+/// The headers with the values of credentials and cookies redacted.
+pub(crate) fn scrub_headers(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if SECRET_HEADERS.contains(&name.as_str()) {
+                REDACTED.to_string()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            };
+            (name.to_string(), value)
+        })
+        .collect()
+}
+
+/// Redacts secret fields of JSON and form encoded bodies, other bodies are kept as they are.
+pub(crate) fn scrub_body(body: &str) -> String {
+    if let Ok(mut json) = serde_json::from_str::<Value>(body)
+        && (json.is_object() || json.is_array())
+    {
+        scrub_json(&mut json);
+        return redact::text(&json.to_string());
+    }
+
+    let is_form = !body.is_empty()
+        && !body.contains(char::is_whitespace)
+        && body.split('&').all(|pair| pair.contains('='));
+    if is_form {
+        return body
+            .split('&')
+            .map(|pair| match pair.split_once('=') {
+                Some((key, _)) if is_secret_field(key) => format!("{}={}", key, REDACTED),
+                _ => pair.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+    }
+
+    redact::text(body)
+}
+
+fn scrub_json(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            for (key, value) in fields {
+                if is_secret_field(key) && !value.is_object() && !value.is_array() {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    scrub_json(value);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(scrub_json),
+        _ => {}
+    }
+}
+
+fn is_secret_field(key: &str) -> bool {
+    SECRET_FIELDS.contains(&key.to_lowercase().as_str())
+}
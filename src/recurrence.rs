@@ -0,0 +1,457 @@
+use std::collections::HashMap;
+
+use chrono::{Datelike, Days, Months, NaiveDate, NaiveDateTime, TimeDelta, Weekday};
+use icalendar::{Calendar, CalendarComponent, Component, Event, Property};
+use log::{debug, warn};
+
+use crate::nextcloud::{api::TimeRange, utils};
+
+/// Series are cut off after this many instances, whatever the window.
+pub const MAX_INSTANCES: usize = 1000;
+
+// Bounds the work for rules whose instances mostly fall before the window
+const MAX_ITERATIONS: usize = 100_000;
+
+/// Replaces recurring events with one event per instance inside the range, for targets that
+/// can't handle unbounded RRULEs.
+///
+/// Instances get the UID `<uid>-<start>`, so they keep their identity across syncs. Modified
+/// instances (RECURRENCE-ID) replace the generated ones. Rules with parts other than FREQ,
+/// INTERVAL, COUNT, UNTIL, WKST and a weekly BYDAY without ordinals are uploaded unchanged.
+pub fn expand_calendar(mut calendar: Calendar, range: &TimeRange) -> Calendar {
+    let mut overrides: HashMap<(String, NaiveDateTime), Event> = HashMap::new();
+    calendar.components.retain(|component| match component {
+        CalendarComponent::Event(event) => {
+            let key = event.get_uid().zip(
+                event
+                    .properties()
+                    .get("RECURRENCE-ID")
+                    .and_then(|id| parse_date_time(id.value())),
+            );
+            match key {
+                Some((uid, recurrence_id)) => {
+                    overrides.insert((uid.to_string(), recurrence_id), event.clone());
+                    false
+                }
+                None => true,
+            }
+        }
+        _ => true,
+    });
+
+    let mut components = Vec::with_capacity(calendar.components.len());
+    for component in calendar.components {
+        let CalendarComponent::Event(event) = component else {
+            components.push(component);
+            continue;
+        };
+        match expand_event(&event, range, &mut overrides) {
+            Some(instances) => components.extend(instances.into_iter().map(Into::into)),
+            None => components.push(event.into()),
+        }
+    }
+
+    // Modified instances of series that were not expanded stay as they are
+    components.extend(overrides.into_values().map(Into::into));
+    calendar.components = components;
+    calendar
+}
+
+fn expand_event(
+    event: &Event,
+    range: &TimeRange,
+    overrides: &mut HashMap<(String, NaiveDateTime), Event>,
+) -> Option<Vec<Event>> {
+    let rrule = event.property_value("RRULE")?;
+    let uid = event.get_uid()?.to_string();
+    let start_property = event.properties().get("DTSTART")?.clone();
+    let start = parse_date_time(start_property.value())?;
+
+    let Some(rule) = Rule::parse(rrule) else {
+        warn!(
+            "Not expanding event {}, its RRULE is not supported: {}",
+            uid, rrule
+        );
+        return None;
+    };
+    if event.multi_properties().contains_key("RDATE") || event.properties().contains_key("RDATE") {
+        warn!("Not expanding event {}, RDATE is not supported", uid);
+        return None;
+    }
+
+    let duration = event
+        .properties()
+        .get("DTEND")
+        .and_then(|end| parse_date_time(end.value()))
+        .map(|end| end - start);
+    let exdates: Vec<NaiveDateTime> = event
+        .multi_properties()
+        .get("EXDATE")
+        .into_iter()
+        .flatten()
+        .chain(event.properties().get("EXDATE"))
+        .flat_map(|exdate| exdate.value().split(','))
+        .filter_map(parse_date_time)
+        .collect();
+
+    let occurrences = rule.occurrences(start, range);
+    debug!(
+        "Expanding event {} into {} instances",
+        uid,
+        occurrences.len()
+    );
+
+    let instances = occurrences
+        .into_iter()
+        .filter(|occurrence| !exdates.contains(occurrence))
+        .map(|occurrence| {
+            let mut instance = overrides
+                .remove(&(uid.clone(), occurrence))
+                .unwrap_or_else(|| {
+                    let mut instance = event.clone();
+                    instance.append_property(with_date(&start_property, occurrence));
+                    if let Some(end) = event.properties().get("DTEND")
+                        && let Some(end_date) =
+                            duration.and_then(|duration| occurrence.checked_add_signed(duration))
+                    {
+                        instance.append_property(with_date(end, end_date));
+                    }
+                    instance
+                });
+            for name in ["RRULE", "EXDATE", "RECURRENCE-ID"] {
+                instance.remove_property(name);
+                instance.remove_multi_property(name);
+            }
+            instance.uid(&format!(
+                "{}-{}",
+                uid,
+                format_like(start_property.value(), occurrence)
+            ));
+            instance
+        })
+        .collect();
+    Some(instances)
+}
+
+/// Series shorter than this are left as separate events.
+pub const MIN_SERIES_INSTANCES: usize = 3;
+
+/// Properties that differ between the instances of an exported series.
+const INSTANCE_PROPERTIES: &[&str] = &[
+    "UID",
+    "DTSTAMP",
+    "DTSTART",
+    "DTEND",
+    "LAST-MODIFIED",
+    "CREATED",
+    "SEQUENCE",
+];
+
+/// Groups identical events on a regular cadence of days or weeks into one recurring event,
+/// for feeds that export every occurrence of a series separately.
+///
+/// Gaps in the cadence, like a cancelled week, become EXDATEs. The series UID is derived
+/// from what the instances share, so it survives old instances dropping out of the feed.
+pub fn collapse_series(mut calendar: Calendar) -> Calendar {
+    let mut groups: HashMap<String, Vec<(NaiveDateTime, Event)>> = HashMap::new();
+    let mut components = Vec::with_capacity(calendar.components.len());
+    for component in calendar.components {
+        match component {
+            CalendarComponent::Event(event) => match series_key(&event) {
+                Some((key, start)) => groups.entry(key).or_default().push((start, event)),
+                None => components.push(event.into()),
+            },
+            other => components.push(other),
+        }
+    }
+
+    let mut keys: Vec<String> = groups.keys().cloned().collect();
+    keys.sort();
+    for key in keys {
+        let mut instances = groups.remove(&key).unwrap_or_default();
+        instances.sort_by_key(|(start, _)| *start);
+        match merge_instances(&key, &instances) {
+            Some(series) => components.push(series.into()),
+            None => components.extend(instances.into_iter().map(|(_, event)| event.into())),
+        }
+    }
+    calendar.components = components;
+    calendar
+}
+
+/// What the instances of a series share: all other properties, the time of day and duration.
+fn series_key(event: &Event) -> Option<(String, NaiveDateTime)> {
+    if event.property_value("RRULE").is_some() || event.property_value("RECURRENCE-ID").is_some() {
+        return None;
+    }
+    let start_property = event.properties().get("DTSTART")?;
+    let start = parse_date_time(start_property.value())?;
+    let duration = event
+        .property_value("DTEND")
+        .and_then(parse_date_time)
+        .map(|end| end - start);
+
+    let mut parts: Vec<String> = event
+        .properties()
+        .iter()
+        .filter(|(name, _)| !INSTANCE_PROPERTIES.contains(&name.as_str()))
+        .map(|(name, property)| format!("{}={}", name, property.value()))
+        .chain(
+            event
+                .multi_properties()
+                .iter()
+                .flat_map(|(name, properties)| {
+                    properties
+                        .iter()
+                        .map(move |property| format!("{}={}", name, property.value()))
+                }),
+        )
+        .collect();
+    parts.sort();
+    parts.push(format!(
+        "{:?}|{}|{:?}",
+        start_property.params().get("TZID").map(|tzid| tzid.value()),
+        format_like(start_property.value(), start)
+            .get(8..)
+            .unwrap_or_default(),
+        duration
+    ));
+    Some((parts.join("\n"), start))
+}
+
+fn merge_instances(key: &str, instances: &[(NaiveDateTime, Event)]) -> Option<Event> {
+    if instances.len() < MIN_SERIES_INSTANCES {
+        return None;
+    }
+    let starts: Vec<NaiveDateTime> = instances.iter().map(|(start, _)| *start).collect();
+    let step = starts.windows(2).map(|pair| pair[1] - pair[0]).min()?;
+    if step.num_days() < 1
+        || step != TimeDelta::days(step.num_days())
+        || starts
+            .windows(2)
+            .any(|pair| (pair[1] - pair[0]).num_days() % step.num_days() != 0)
+    {
+        return None;
+    }
+
+    let (first, template) = &instances[0];
+    let last = starts[starts.len() - 1];
+    let slots = (last - *first).num_days() / step.num_days() + 1;
+    // Instances far apart share a cadence by chance, a series needs no more gaps than instances
+    let gaps = usize::try_from(slots).ok()? - instances.len();
+    if gaps > instances.len() {
+        return None;
+    }
+    let rrule = match step.num_days() % 7 {
+        0 => format!(
+            "FREQ=WEEKLY;INTERVAL={};COUNT={}",
+            step.num_days() / 7,
+            slots
+        ),
+        _ => format!("FREQ=DAILY;INTERVAL={};COUNT={}", step.num_days(), slots),
+    };
+
+    let mut series = template.clone();
+    series.uid(&format!("series-{}", utils::stable_hash(key)));
+    series.add_property("RRULE", rrule);
+
+    let start_property = template.properties().get("DTSTART")?;
+    for slot in 0..slots {
+        let start = first.checked_add_signed(step.checked_mul(i32::try_from(slot).ok()?)?)?;
+        if !starts.contains(&start) {
+            let mut exdate = Property::new("EXDATE", format_like(start_property.value(), start));
+            for parameter in start_property.params().values() {
+                exdate.append_parameter(parameter.clone());
+            }
+            series.append_multi_property(exdate);
+        }
+    }
+
+    // The newest change of any instance counts for the whole series
+    if let Some(last_modified) = instances
+        .iter()
+        .filter_map(|(_, event)| event.property_value("LAST-MODIFIED"))
+        .max()
+    {
+        series.add_property("LAST-MODIFIED", last_modified);
+    }
+    debug!(
+        "Collapsed {} events into series {}",
+        instances.len(),
+        series.get_uid().unwrap_or_default()
+    );
+    Some(series)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+#[derive(Debug)]
+struct Rule {
+    frequency: Frequency,
+    interval: u32,
+    count: Option<usize>,
+    until: Option<NaiveDateTime>,
+    by_day: Vec<Weekday>,
+}
+
+impl Rule {
+    fn parse(rrule: &str) -> Option<Self> {
+        let mut rule = Rule {
+            frequency: Frequency::Daily,
+            interval: 1,
+            count: None,
+            until: None,
+            by_day: Vec::new(),
+        };
+        let mut frequency = None;
+
+        for part in rrule.split(';').filter(|part| !part.is_empty()) {
+            let (name, value) = part.split_once('=')?;
+            match name.to_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.to_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return None,
+                    })
+                }
+                "INTERVAL" => rule.interval = value.parse().ok().filter(|i| *i > 0)?,
+                "COUNT" => rule.count = Some(value.parse().ok()?),
+                "UNTIL" => rule.until = Some(parse_date_time(value)?),
+                "WKST" => {}
+                "BYDAY" => {
+                    rule.by_day = value.split(',').map(parse_weekday).collect::<Option<_>>()?
+                }
+                _ => return None,
+            }
+        }
+
+        rule.frequency = frequency?;
+        // BYDAY of other frequencies means "every Monday of the month" and similar
+        if !rule.by_day.is_empty() && rule.frequency != Frequency::Weekly {
+            return None;
+        }
+        Some(rule)
+    }
+
+    /// Starts of the instances overlapping the range, `start` is the first instance.
+    fn occurrences(&self, start: NaiveDateTime, range: &TimeRange) -> Vec<NaiveDateTime> {
+        let range_start = range.start.map(|start| start.naive_utc());
+        let range_end = range.end.map(|end| end.naive_utc());
+
+        let mut occurrences = Vec::new();
+        let mut count = 0;
+        for period in 0..MAX_ITERATIONS {
+            let Some(candidates) = self.period(start, period) else {
+                break;
+            };
+            for occurrence in candidates {
+                if occurrence < start {
+                    continue;
+                }
+                if self.until.is_some_and(|until| occurrence > until)
+                    || self.count.is_some_and(|max| count >= max)
+                    || range_end.is_some_and(|end| occurrence >= end)
+                    || occurrences.len() >= MAX_INSTANCES
+                {
+                    return occurrences;
+                }
+                count += 1;
+                // The window check is loose by a day, so events spanning its start are kept
+                if range_start.is_none_or(|range_start| {
+                    occurrence
+                        .checked_add_signed(TimeDelta::days(1))
+                        .is_none_or(|loose| loose >= range_start)
+                }) {
+                    occurrences.push(occurrence);
+                }
+            }
+        }
+        occurrences
+    }
+
+    /// The candidate instances of the `period`th interval, `None` once dates overflow.
+    fn period(&self, start: NaiveDateTime, period: usize) -> Option<Vec<NaiveDateTime>> {
+        let steps = u32::try_from(period).ok()?.checked_mul(self.interval)?;
+        Some(match self.frequency {
+            Frequency::Daily => vec![start.checked_add_days(Days::new(steps.into()))?],
+            Frequency::Weekly if self.by_day.is_empty() => {
+                vec![start.checked_add_days(Days::new(u64::from(steps) * 7))?]
+            }
+            Frequency::Weekly => {
+                let week_start = start
+                    .checked_sub_days(Days::new(start.weekday().num_days_from_monday().into()))?
+                    .checked_add_days(Days::new(u64::from(steps) * 7))?;
+                let mut days: Vec<NaiveDateTime> = self
+                    .by_day
+                    .iter()
+                    .filter_map(|day| {
+                        week_start.checked_add_days(Days::new(day.num_days_from_monday().into()))
+                    })
+                    .collect();
+                days.sort();
+                days
+            }
+            // Months without the day, like February 30th, are skipped
+            Frequency::Monthly => start
+                .checked_add_months(Months::new(steps))
+                .filter(|date| date.day() == start.day())
+                .into_iter()
+                .collect(),
+            Frequency::Yearly => start
+                .checked_add_months(Months::new(steps.checked_mul(12)?))
+                .filter(|date| date.day() == start.day())
+                .into_iter()
+                .collect(),
+        })
+    }
+}
+
+fn parse_weekday(day: &str) -> Option<Weekday> {
+    Some(match day.trim().to_uppercase().as_str() {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    })
+}
+
+/// Parses `YYYYMMDD` and `YYYYMMDDTHHMMSS[Z]`, dates are midnight.
+fn parse_date_time(value: &str) -> Option<NaiveDateTime> {
+    let value = value.trim().trim_end_matches('Z');
+    match value.len() {
+        8 => NaiveDate::parse_from_str(value, "%Y%m%d")
+            .ok()?
+            .and_hms_opt(0, 0, 0),
+        _ => NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok(),
+    }
+}
+
+/// Formats the time in the form of `template`, a date, a local or a UTC time.
+fn format_like(template: &str, date_time: NaiveDateTime) -> String {
+    match template.trim() {
+        template if template.len() == 8 => date_time.format("%Y%m%d").to_string(),
+        template if template.ends_with('Z') => date_time.format("%Y%m%dT%H%M%SZ").to_string(),
+        _ => date_time.format("%Y%m%dT%H%M%S").to_string(),
+    }
+}
+
+fn with_date(property: &Property, date_time: NaiveDateTime) -> Property {
+    let mut copy = Property::new(property.key(), format_like(property.value(), date_time));
+    for parameter in property.params().values() {
+        copy.append_parameter(parameter.clone());
+    }
+    copy
+}
@@ -0,0 +1,107 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use reqwest::Url;
+
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Schemes of credentials that follow the name in Authorization headers.
+const AUTH_SCHEMES: &[&str] = &["Basic ", "Bearer ", "Digest "];
+
+const MIN_TOKEN_LEN: usize = 16;
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Turns redaction of logs and error messages on or off, it is on by default.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Masks the credentials, the query string and secret path segments (like the
+/// `private-<token>` of Google secret addresses) of a URL.
+pub fn url(url: &str) -> String {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return url.to_string();
+    }
+    let Ok(mut parsed) = Url::parse(url) else {
+        return match url.split_once('?') {
+            Some((base, _)) => format!("{}?{}", base, REDACTED),
+            None => url.to_string(),
+        };
+    };
+
+    if !parsed.username().is_empty() {
+        let _ = parsed.set_username(REDACTED);
+    }
+    if parsed.password().is_some() {
+        let _ = parsed.set_password(Some(REDACTED));
+    }
+    let has_query = parsed.query().is_some();
+    parsed.set_query(None);
+    parsed.set_fragment(None);
+
+    let path = parsed
+        .path()
+        .split('/')
+        .map(|segment| match segment.strip_prefix("private-") {
+            Some(_) => format!("private-{}", REDACTED),
+            None => segment.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+    parsed.set_path(&path);
+
+    // Set as text, `Url` would percent-encode the brackets of the marker
+    let redacted = parsed.to_string().replace("%5BREDACTED%5D", REDACTED);
+    if has_query {
+        format!("{}?{}", redacted, REDACTED)
+    } else {
+        redacted
+    }
+}
+
+/// Redacts all URLs and Authorization credentials found in a log line or error message.
+pub fn text(text: &str) -> String {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return text.to_string();
+    }
+
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = find_secret(rest) {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(scheme) = AUTH_SCHEMES
+            .iter()
+            .find(|scheme| rest.starts_with(**scheme))
+        {
+            result.push_str(scheme);
+            rest = &rest[scheme.len()..];
+
+            let token_len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && !"-._~+/=".contains(c))
+                .unwrap_or(rest.len());
+            // Shorter words are prose like "Basic authentication", not credentials
+            if token_len >= MIN_TOKEN_LEN {
+                result.push_str(REDACTED);
+                rest = &rest[token_len..];
+            }
+        } else {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || "\"'<>`".contains(c))
+                .unwrap_or(rest.len());
+            result.push_str(&url(&rest[..end]));
+            rest = &rest[end..];
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+fn find_secret(text: &str) -> Option<usize> {
+    ["http://", "https://"]
+        .iter()
+        .chain(AUTH_SCHEMES)
+        .filter_map(|pattern| text.find(pattern))
+        .min()
+}
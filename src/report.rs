@@ -0,0 +1,101 @@
+use std::{collections::BTreeMap, fmt};
+
+use icalendar::{Calendar, Component, Event};
+
+use crate::{
+    explain,
+    nextcloud::{self, api::CalendarObject},
+};
+
+/// Properties a sync changes on upload, so they never match the source.
+const SET_ON_UPLOAD: &[&str] = &["SEQUENCE", "X-SYNC-RUN"];
+
+/// Events of one month, by the month of their DTSTART.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MonthCounts {
+    pub source: usize,
+    pub target: usize,
+}
+
+/// How far the Nextcloud calendar has drifted from the source feed.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DriftReport {
+    pub only_in_source: usize,
+    /// Synced events that are no longer in the source.
+    pub only_in_target: usize,
+    /// Events that exist on both sides with different properties.
+    pub differing: usize,
+    pub in_sync: usize,
+    /// Events in the Nextcloud calendar that were not created by a sync.
+    pub unmanaged: usize,
+    /// Keyed by `YYYY-MM`.
+    pub per_month: BTreeMap<String, MonthCounts>,
+}
+
+/// Compares the source events with the Nextcloud calendar without changing anything.
+pub fn drift_report(
+    source_calendar: Calendar,
+    nextcloud_objects: Vec<CalendarObject>,
+) -> DriftReport {
+    let source_events = nextcloud::api::extract_events(source_calendar, true);
+    let nextcloud_events =
+        nextcloud::api::extract_events(nextcloud::api::merge_objects(nextcloud_objects), false);
+
+    let mut report = DriftReport::default();
+    for (uid, source_event) in &source_events {
+        report.month(source_event).source += 1;
+        match nextcloud_events.get(uid) {
+            None => report.only_in_source += 1,
+            Some(existing_event)
+                if explain::property_changes(source_event, existing_event)
+                    .iter()
+                    .any(|(name, _, _)| !SET_ON_UPLOAD.contains(&name.as_str())) =>
+            {
+                report.differing += 1
+            }
+            Some(_) => report.in_sync += 1,
+        }
+    }
+
+    for (uid, nextcloud_event) in &nextcloud_events {
+        report.month(nextcloud_event).target += 1;
+        if nextcloud_event.property_value("X-SYNCED").is_none() {
+            report.unmanaged += 1;
+        } else if !source_events.contains_key(uid) {
+            report.only_in_target += 1;
+        }
+    }
+    report
+}
+
+impl DriftReport {
+    /// Whether a sync would change anything.
+    pub fn has_drift(&self) -> bool {
+        self.only_in_source + self.only_in_target + self.differing > 0
+    }
+
+    fn month(&mut self, event: &Event) -> &mut MonthCounts {
+        let month = event
+            .property_value("DTSTART")
+            .filter(|start| start.len() >= 6 && start.is_char_boundary(6))
+            .map(|start| format!("{}-{}", &start[..4], &start[4..6]))
+            .unwrap_or_else(|| "unknown".to_string());
+        self.per_month.entry(month).or_default()
+    }
+}
+
+impl fmt::Display for DriftReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Only in source:  {}", self.only_in_source)?;
+        writeln!(f, "Only in target:  {}", self.only_in_target)?;
+        writeln!(f, "Differing:       {}", self.differing)?;
+        writeln!(f, "In sync:         {}", self.in_sync)?;
+        writeln!(f, "Not synced:      {}", self.unmanaged)?;
+        writeln!(f)?;
+        writeln!(f, "{:<8} {:>7} {:>7}", "Month", "Source", "Target")?;
+        for (month, counts) in &self.per_month {
+            writeln!(f, "{:<8} {:>7} {:>7}", month, counts.source, counts.target)?;
+        }
+        Ok(())
+    }
+}
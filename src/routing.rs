@@ -0,0 +1,142 @@
+use std::{collections::HashMap, str::FromStr};
+
+use anyhow::{Context, Result, bail};
+use icalendar::{Calendar, CalendarComponent, Component, Event};
+use regex::Regex;
+
+/// What a routing rule looks at.
+#[derive(Debug, Clone)]
+pub enum RouteMatch {
+    /// One of the CATEGORIES, case-insensitively.
+    Category(String),
+    Summary(Regex),
+}
+
+impl FromStr for RouteMatch {
+    type Err = anyhow::Error;
+
+    /// `category:<name>` or `summary:<regex>`.
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().split_once(':') {
+            Some((kind, category)) if kind.eq_ignore_ascii_case("category") => {
+                Ok(Self::Category(category.trim().to_string()))
+            }
+            Some((kind, pattern)) if kind.eq_ignore_ascii_case("summary") => Ok(Self::Summary(
+                Regex::new(pattern.trim())
+                    .with_context(|| format!("Invalid summary pattern: {}", pattern))?,
+            )),
+            _ => bail!(
+                "Invalid route condition, expected category:<name> or summary:<regex>: {}",
+                s
+            ),
+        }
+    }
+}
+
+impl RouteMatch {
+    fn matches(&self, event: &Event) -> bool {
+        match self {
+            Self::Category(category) => event
+                .properties()
+                .get("CATEGORIES")
+                .into_iter()
+                .chain(
+                    event
+                        .multi_properties()
+                        .get("CATEGORIES")
+                        .into_iter()
+                        .flatten(),
+                )
+                .flat_map(|property| property.value().split(','))
+                .any(|value| value.trim().eq_ignore_ascii_case(category)),
+            Self::Summary(pattern) => event
+                .get_summary()
+                .is_some_and(|summary| pattern.is_match(summary)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Route {
+    pub condition: RouteMatch,
+    pub calendar_url: String,
+}
+
+/// Sends source events into different Nextcloud calendars, the first matching route wins
+/// and events matching none go to the default calendar.
+#[derive(Debug, Clone, Default)]
+pub struct CalendarRoutes {
+    pub routes: Vec<Route>,
+}
+
+impl CalendarRoutes {
+    /// The calendars of all routes, without duplicates.
+    pub fn calendar_urls(&self) -> Vec<&str> {
+        let mut urls: Vec<&str> = Vec::new();
+        for route in &self.routes {
+            if !urls.contains(&route.calendar_url.as_str()) {
+                urls.push(&route.calendar_url);
+            }
+        }
+        urls
+    }
+
+    /// Splits the calendar by target calendar, starting with the default one.
+    ///
+    /// Every target gets a calendar, even an empty one, so events that moved to another route
+    /// are deleted from it. Overrides of a recurring event follow its master, a calendar
+    /// object can't be split. Other components like VTIMEZONE are copied to every calendar.
+    pub fn split(&self, calendar: Calendar, default_calendar_url: &str) -> Vec<(String, Calendar)> {
+        let mut targets: Vec<String> = vec![default_calendar_url.to_string()];
+        for url in self.calendar_urls() {
+            if !targets.iter().any(|target| target == url) {
+                targets.push(url.to_string());
+            }
+        }
+
+        let mut target_by_uid: HashMap<String, usize> = HashMap::new();
+        for event in calendar.components.iter().filter_map(|c| c.as_event()) {
+            let Some(uid) = event.get_uid() else {
+                continue;
+            };
+            let is_master = event.property_value("RECURRENCE-ID").is_none();
+            if is_master || !target_by_uid.contains_key(uid) {
+                target_by_uid.insert(uid.to_string(), self.target_index(event, &targets));
+            }
+        }
+
+        let mut calendars: Vec<Calendar> = targets
+            .iter()
+            .map(|_| Calendar {
+                properties: calendar.properties.clone(),
+                components: Vec::new(),
+            })
+            .collect();
+        for component in calendar.components {
+            match &component {
+                CalendarComponent::Event(event) => {
+                    let index = event
+                        .get_uid()
+                        .and_then(|uid| target_by_uid.get(uid))
+                        .copied()
+                        .unwrap_or(0);
+                    calendars[index].components.push(component);
+                }
+                _ => {
+                    for calendar in &mut calendars {
+                        calendar.components.push(component.clone());
+                    }
+                }
+            }
+        }
+        targets.into_iter().zip(calendars).collect()
+    }
+
+    fn target_index(&self, event: &Event, targets: &[String]) -> usize {
+        self.routes
+            .iter()
+            .find(|route| route.condition.matches(event))
+            .and_then(|route| targets.iter().position(|url| *url == route.calendar_url))
+            .unwrap_or(0)
+    }
+}
@@ -0,0 +1,95 @@
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow, bail};
+use icalendar::{Calendar, CalendarComponent, Component, Event};
+use log::debug;
+use rhai::{AST, Dynamic, Engine, Map, Scope};
+
+/// A Rhai script deciding per event whether it is synced and how it is modified.
+///
+/// The script has to define `fn process(event)`, which receives a map of the event's
+/// properties (e.g. `event.SUMMARY`) and returns `true` to keep the event unchanged,
+/// `false` or `()` to drop it, or a map of properties to keep the event with these
+/// values set. Properties set to `()` in the returned map are removed.
+pub struct EventScript {
+    engine: Engine,
+    ast: AST,
+}
+
+impl EventScript {
+    pub fn load(path: &Path) -> Result<Self> {
+        let engine = Engine::new();
+        let ast = engine
+            .compile_file(path.to_path_buf())
+            .map_err(|e| anyhow!(e.to_string()))
+            .with_context(|| format!("Failed to compile script. Path: {}", path.display()))?;
+
+        Ok(Self { engine, ast })
+    }
+
+    /// Runs the script on every event of the calendar, dropping the rejected ones.
+    pub fn apply_to_calendar(&self, mut calendar: Calendar) -> Result<Calendar> {
+        let mut components = Vec::with_capacity(calendar.components.len());
+
+        for component in calendar.components {
+            match component {
+                CalendarComponent::Event(event) => {
+                    if let Some(event) = self.apply(event)? {
+                        components.push(CalendarComponent::Event(event));
+                    }
+                }
+                other => components.push(other),
+            }
+        }
+
+        calendar.components = components;
+        Ok(calendar)
+    }
+
+    pub fn apply(&self, mut event: Event) -> Result<Option<Event>> {
+        let uid = event.get_uid().unwrap_or_default().to_string();
+        let properties: Map = event
+            .properties()
+            .iter()
+            .map(|(key, property)| (key.as_str().into(), property.value().into()))
+            .collect();
+
+        let result: Dynamic = self
+            .engine
+            .call_fn(&mut Scope::new(), &self.ast, "process", (properties,))
+            .map_err(|e| anyhow!(e.to_string()))
+            .with_context(|| format!("Script failed for event with UID: {}", uid))?;
+
+        if result.is_unit() {
+            debug!("Script dropped event with UID: {}", uid);
+            return Ok(None);
+        }
+        if result.is_bool() {
+            let keep = result.as_bool().unwrap_or_default();
+            if !keep {
+                debug!("Script dropped event with UID: {}", uid);
+            }
+            return Ok(keep.then_some(event));
+        }
+
+        let Some(changes) = result.try_cast::<Map>() else {
+            bail!("Script must return a bool or a map. UID: {}", uid);
+        };
+
+        for (key, value) in changes {
+            let key = key.to_uppercase();
+            if value.is_unit() {
+                event.remove_property(&key);
+                continue;
+            }
+
+            // Only overwrite changed values, so parameters like TZID of untouched properties are kept
+            let value = value.to_string();
+            if event.property_value(&key) != Some(value.as_str()) {
+                event.add_property(key, value);
+            }
+        }
+
+        Ok(Some(event))
+    }
+}
@@ -0,0 +1,17 @@
+use tokio::process::Command;
+
+/// Builds a command that runs `command` through the platform shell.
+#[cfg(unix)]
+pub(crate) fn command(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+/// Builds a command that runs `command` through the platform shell.
+#[cfg(windows)]
+pub(crate) fn command(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
@@ -0,0 +1,76 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context, Result, bail};
+use icalendar::Calendar;
+
+use crate::{ics_parser, nextcloud::utils::stable_hash, paths};
+
+/// The last successfully fetched source calendar, used when the source is unreachable.
+#[derive(Debug, Clone)]
+pub struct SourceCache {
+    pub path: PathBuf,
+    /// Older copies are not used, so a feed that stays down is noticed.
+    pub max_age: Duration,
+}
+
+impl SourceCache {
+    pub const DEFAULT_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+    /// The cache file of a source, so different configurations for the same feed share it.
+    pub fn path_for(source_url: &str) -> PathBuf {
+        paths::cache_dir()
+            .join("sources")
+            .join(format!("{}.ics", stable_hash(source_url)))
+    }
+
+    pub fn store(&self, calendar: &Calendar) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).with_context(|| {
+                format!("Failed to create cache directory. Path: {}", dir.display())
+            })?;
+        }
+        // A run killed while writing must not leave a truncated copy behind. Only the user can
+        // read it, as the events may contain private details.
+        let partial = self.path.with_extension("ics.partial");
+        paths::write_private(&partial, calendar.to_string())
+            .and_then(|_| fs::rename(&partial, &self.path))
+            .with_context(|| {
+                format!(
+                    "Failed to write source cache. Path: {}",
+                    self.path.display()
+                )
+            })
+    }
+
+    /// The cached calendar and its age, failing if there is none or it is too old.
+    pub fn load(&self) -> Result<(Calendar, Duration)> {
+        let age = age(&self.path)
+            .with_context(|| format!("No cached source calendar. Path: {}", self.path.display()))?;
+        if age > self.max_age {
+            bail!(
+                "The cached source calendar is {} hours old, older than allowed by \
+                 SOURCE_CACHE_MAX_AGE_SECS. Path: {}",
+                age.as_secs() / 3600,
+                self.path.display()
+            );
+        }
+
+        let content = fs::read(&self.path).with_context(|| {
+            format!("Failed to read source cache. Path: {}", self.path.display())
+        })?;
+        // The cache holds the calendar after the compatibility fixes
+        let calendar = ics_parser::parse_calendar(&content, &self.path.display().to_string(), &[])?;
+        Ok((calendar, age))
+    }
+}
+
+fn age(path: &Path) -> Result<Duration> {
+    Ok(fs::metadata(path)?
+        .modified()?
+        .elapsed()
+        .unwrap_or_default())
+}
@@ -0,0 +1,186 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{NaiveDate, NaiveDateTime};
+use futures::future::BoxFuture;
+use icalendar::{Calendar, CalendarDateTime, Component, DatePerhapsTime, Event, EventLike};
+use log::{debug, warn};
+
+use super::{CalendarSource, SourceRequest, parse_date_value};
+use crate::http::Client;
+
+/// Column names of the CSV header mapped to event fields.
+#[derive(Debug, Clone)]
+pub struct CsvMapping {
+    pub summary: String,
+    pub start: String,
+    pub end: Option<String>,
+    pub location: Option<String>,
+    pub description: Option<String>,
+    /// Column holding the TZID of the start and end times.
+    pub timezone: Option<String>,
+    /// Template like `{Date}-{Shift}` where each placeholder is replaced by the column value.
+    pub uid_template: Option<String>,
+    /// chrono format string for dates not covered by `parse_date_value`, e.g. `%d.%m.%Y %H:%M`.
+    pub date_format: Option<String>,
+    pub delimiter: u8,
+}
+
+#[derive(Debug, Clone)]
+pub enum CsvLocation {
+    Http(Box<SourceRequest>),
+    File(PathBuf),
+}
+
+/// A CSV file, either local or fetched via HTTP, with one event per row.
+pub struct CsvSource {
+    pub location: CsvLocation,
+    pub mapping: CsvMapping,
+}
+
+impl CalendarSource for CsvSource {
+    fn fetch_calendar<'a>(&'a self, client: &'a Client) -> BoxFuture<'a, Result<Calendar>> {
+        Box::pin(async move {
+            let content = match &self.location {
+                CsvLocation::Http(request) => request
+                    .send(client)
+                    .await?
+                    .bytes()
+                    .await
+                    .with_context(|| format!("Failed to read CSV content. URL: {}", request.url))?
+                    .to_vec(),
+                CsvLocation::File(path) => tokio::fs::read(path).await.with_context(|| {
+                    format!("Failed to read CSV file. Path: {}", path.display())
+                })?,
+            };
+
+            convert_rows(&content, &self.mapping)
+        })
+    }
+}
+
+fn convert_rows(content: &[u8], mapping: &CsvMapping) -> Result<Calendar> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(mapping.delimiter)
+        .flexible(true)
+        .from_reader(content);
+
+    let headers = reader
+        .headers()
+        .context("Failed to read CSV header")?
+        .clone();
+
+    let mut calendar = Calendar::new();
+    for (line, record) in reader.records().enumerate() {
+        let record = record.context("Failed to read CSV row")?;
+        let row: HashMap<&str, &str> = headers
+            .iter()
+            .zip(record.iter())
+            .map(|(header, value)| (header, value.trim()))
+            .collect();
+
+        match convert_row(&row, mapping) {
+            Some(event) => {
+                calendar.push(event);
+            }
+            // The header is the first line, data rows start at line 2
+            None => warn!(
+                "Skipping CSV row {} without summary or valid start",
+                line + 2
+            ),
+        }
+    }
+
+    debug!("Converted {} CSV rows", calendar.components.len());
+    Ok(calendar)
+}
+
+fn convert_row(row: &HashMap<&str, &str>, mapping: &CsvMapping) -> Option<Event> {
+    let column = |name: &Option<String>| {
+        name.as_deref()
+            .and_then(|name| row.get(name).copied())
+            .filter(|value| !value.is_empty())
+    };
+
+    let summary = row
+        .get(mapping.summary.as_str())
+        .filter(|v| !v.is_empty())?;
+    let start_value = row.get(mapping.start.as_str())?;
+    let timezone = column(&mapping.timezone);
+    let start = parse_date(start_value, mapping, timezone)?;
+
+    let uid = match &mapping.uid_template {
+        Some(template) => render_template(template, row),
+        None => format!("{}-{}", start_value, summary),
+    };
+
+    let mut event = Event::new();
+    event.uid(&uid).summary(summary).starts(start);
+
+    if let Some(end) = column(&mapping.end).and_then(|v| parse_date(v, mapping, timezone)) {
+        event.ends(end);
+    }
+    if let Some(location) = column(&mapping.location) {
+        event.location(location);
+    }
+    if let Some(description) = column(&mapping.description) {
+        event.description(description);
+    }
+
+    Some(event.done())
+}
+
+fn parse_date(
+    value: &str,
+    mapping: &CsvMapping,
+    timezone: Option<&str>,
+) -> Option<DatePerhapsTime> {
+    let date = mapping
+        .date_format
+        .as_deref()
+        .and_then(|format| {
+            NaiveDateTime::parse_from_str(value, format)
+                .map(DatePerhapsTime::from)
+                .or_else(|_| NaiveDate::parse_from_str(value, format).map(DatePerhapsTime::from))
+                .ok()
+        })
+        .or_else(|| parse_date_value(value))?;
+
+    // Floating times are anchored to the timezone of the row, if there is one
+    match (date, timezone) {
+        (DatePerhapsTime::DateTime(CalendarDateTime::Floating(date_time)), Some(tzid)) => Some(
+            CalendarDateTime::WithTimezone {
+                date_time,
+                tzid: tzid.to_string(),
+            }
+            .into(),
+        ),
+        (date, _) => Some(date),
+    }
+}
+
+/// Replaces each `{column}` placeholder in one pass, so values containing braces are never
+/// substituted again. Unknown columns are kept as they are.
+fn render_template(template: &str, row: &HashMap<&str, &str>) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let placeholder = &rest[start..];
+        match placeholder
+            .find('}')
+            .and_then(|end| Some((end, row.get(&placeholder[1..end])?)))
+        {
+            Some((end, value)) => {
+                result.push_str(value);
+                rest = &placeholder[end + 1..];
+            }
+            None => {
+                result.push('{');
+                rest = &placeholder[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
@@ -0,0 +1,40 @@
+use anyhow::{Context, Result, bail};
+use futures::future::BoxFuture;
+use icalendar::Calendar;
+use log::{debug, warn};
+
+use super::CalendarSource;
+use crate::{compat::CompatMode, http::Client, ics_parser, shell};
+
+/// A user supplied command printing an ICS calendar to stdout, configured as `exec://<command>`.
+pub struct ExecSource {
+    pub command: String,
+    pub compat_modes: Vec<CompatMode>,
+}
+
+impl CalendarSource for ExecSource {
+    fn fetch_calendar<'a>(&'a self, _client: &'a Client) -> BoxFuture<'a, Result<Calendar>> {
+        Box::pin(async move {
+            debug!("Running source command: {}", self.command);
+
+            let output = shell::command(&self.command)
+                .output()
+                .await
+                .with_context(|| format!("Failed to run source command: {}", self.command))?;
+
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if !output.status.success() {
+                bail!(
+                    "Source command failed with {} and output of:\n{}",
+                    output.status,
+                    stderr
+                );
+            }
+            if !stderr.trim().is_empty() {
+                warn!("Source command wrote to stderr:\n{}", stderr);
+            }
+
+            ics_parser::parse_calendar(&output.stdout, &self.command, &self.compat_modes)
+        })
+    }
+}
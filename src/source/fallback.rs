@@ -0,0 +1,41 @@
+use anyhow::{Result, anyhow};
+use futures::future::BoxFuture;
+use icalendar::Calendar;
+use log::{info, warn};
+
+use super::CalendarSource;
+use crate::{http::Client, redact};
+
+/// The same calendar published at several URLs, tried in order until one returns events.
+pub struct FallbackSource {
+    pub sources: Vec<(String, Box<dyn CalendarSource>)>,
+}
+
+impl CalendarSource for FallbackSource {
+    fn fetch_calendar<'a>(&'a self, client: &'a Client) -> BoxFuture<'a, Result<Calendar>> {
+        Box::pin(async move {
+            let mut last_error = anyhow!("No source URL configured");
+            for (index, (url, source)) in self.sources.iter().enumerate() {
+                let url = redact::url(url);
+                match source.fetch_calendar(client).await {
+                    // An empty feed is more likely a broken mirror than a calendar without events
+                    Ok(calendar) if calendar.components.is_empty() => {
+                        warn!("Source {} returned an empty calendar", url);
+                        last_error = anyhow!("Source returned an empty calendar. URL: {}", url);
+                    }
+                    Ok(calendar) => {
+                        if index > 0 {
+                            info!("Using fallback source {}", url);
+                        }
+                        return Ok(calendar);
+                    }
+                    Err(e) => {
+                        warn!("Failed to fetch source {}: {:#}", url, e);
+                        last_error = e;
+                    }
+                }
+            }
+            Err(last_error.context("All source URLs failed"))
+        })
+    }
+}
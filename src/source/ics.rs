@@ -0,0 +1,21 @@
+use anyhow::Result;
+use futures::future::BoxFuture;
+use icalendar::Calendar;
+
+use super::{CalendarSource, SourceRequest};
+use crate::{compat::CompatMode, http::Client, ics_parser};
+
+/// A remote `.ics` feed.
+pub struct IcsSource {
+    pub request: SourceRequest,
+    pub compat_modes: Vec<CompatMode>,
+}
+
+impl CalendarSource for IcsSource {
+    fn fetch_calendar<'a>(&'a self, client: &'a Client) -> BoxFuture<'a, Result<Calendar>> {
+        Box::pin(async move {
+            let response = self.request.send(client).await?;
+            ics_parser::parse_response(response, &self.request.url, &self.compat_modes).await
+        })
+    }
+}
@@ -0,0 +1,137 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use icalendar::{Calendar, Component, Event, EventLike};
+use log::{debug, warn};
+use serde_json::Value;
+
+use super::{CalendarSource, SourceRequest, parse_date_value};
+use crate::http::Client;
+
+/// Paths into the JSON response, e.g. `.data.items[]` for the events and `.start.dateTime`
+/// relative to each event.
+#[derive(Debug, Clone)]
+pub struct JsonMapping {
+    pub events: String,
+    pub uid: Option<String>,
+    pub summary: String,
+    pub start: String,
+    pub end: Option<String>,
+    pub location: Option<String>,
+    pub description: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+/// A JSON/REST API whose entries are converted into events.
+pub struct JsonApiSource {
+    pub request: SourceRequest,
+    pub mapping: JsonMapping,
+}
+
+impl CalendarSource for JsonApiSource {
+    fn fetch_calendar<'a>(&'a self, client: &'a Client) -> BoxFuture<'a, Result<Calendar>> {
+        Box::pin(async move {
+            let response = self.request.send(client).await?;
+            let json: Value = response.json().await.with_context(|| {
+                format!("Failed to parse JSON content. URL: {}", self.request.url)
+            })?;
+
+            Ok(convert_events(&json, &self.mapping))
+        })
+    }
+}
+
+fn convert_events(json: &Value, mapping: &JsonMapping) -> Calendar {
+    let mut calendar = Calendar::new();
+
+    for entry in select(json, &mapping.events) {
+        match convert_event(entry, mapping) {
+            Some(event) => {
+                calendar.push(event);
+            }
+            None => warn!(
+                "Skipping JSON entry without summary or valid start: {}",
+                entry
+            ),
+        }
+    }
+
+    debug!("Converted {} JSON entries", calendar.components.len());
+    calendar
+}
+
+fn convert_event(entry: &Value, mapping: &JsonMapping) -> Option<Event> {
+    let summary = select_string(entry, &mapping.summary)?;
+    let start_value = select_string(entry, &mapping.start)?;
+    let start = parse_date_value(&start_value)?;
+
+    // Without an explicit ID the entry is identified by its start and summary
+    let uid = match &mapping.uid {
+        Some(path) => select_string(entry, path)?,
+        None => format!("{}-{}", start_value, summary),
+    };
+
+    let mut event = Event::new();
+    event.uid(&uid).summary(&summary).starts(start);
+
+    if let Some(end) = optional_string(entry, &mapping.end).and_then(|v| parse_date_value(&v)) {
+        event.ends(end);
+    }
+    if let Some(location) = optional_string(entry, &mapping.location) {
+        event.location(&location);
+    }
+    if let Some(description) = optional_string(entry, &mapping.description) {
+        event.description(&description);
+    }
+    if let Some(last_modified) = optional_string(entry, &mapping.last_modified)
+        .and_then(|v| DateTime::parse_from_rfc3339(&v).ok())
+    {
+        event.last_modified(last_modified.with_timezone(&Utc));
+    }
+
+    Some(event.done())
+}
+
+fn optional_string(value: &Value, path: &Option<String>) -> Option<String> {
+    path.as_ref().and_then(|path| select_string(value, path))
+}
+
+fn select_string(value: &Value, path: &str) -> Option<String> {
+    match select(value, path).first()? {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Evaluates a jq-like path. Supports object keys (`.a.b`), array indices (`.a[0]`)
+/// and iteration over arrays (`.a[]`).
+pub fn select<'a>(value: &'a Value, path: &str) -> Vec<&'a Value> {
+    let mut current = vec![value];
+
+    for segment in path.split('.').filter(|segment| !segment.is_empty()) {
+        let (key, indices) = segment.split_at(segment.find('[').unwrap_or(segment.len()));
+
+        if !key.is_empty() {
+            current = current.into_iter().filter_map(|v| v.get(key)).collect();
+        }
+
+        for index in indices.split('[').filter(|index| !index.is_empty()) {
+            let index = index.trim_end_matches(']');
+            current = if index.is_empty() {
+                current
+                    .into_iter()
+                    .filter_map(Value::as_array)
+                    .flatten()
+                    .collect()
+            } else if let Ok(index) = index.parse::<usize>() {
+                current.into_iter().filter_map(|v| v.get(index)).collect()
+            } else {
+                Vec::new()
+            };
+        }
+    }
+
+    current
+}
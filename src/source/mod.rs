@@ -0,0 +1,200 @@
+use std::{path::PathBuf, str::FromStr};
+
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use futures::future::BoxFuture;
+use icalendar::{Calendar, DatePerhapsTime};
+use log::debug;
+use reqwest::{Response, Url};
+
+use crate::{config::Config, http::Client, ics_parser, oauth};
+
+mod cache;
+mod csv;
+mod exec;
+mod fallback;
+mod ics;
+mod json_api;
+
+pub use cache::SourceCache;
+pub use csv::{CsvLocation, CsvMapping, CsvSource};
+pub use exec::ExecSource;
+pub use fallback::FallbackSource;
+pub use ics::IcsSource;
+pub use json_api::{JsonApiSource, JsonMapping};
+
+/// A source of events that are fed into the sync engine.
+pub trait CalendarSource: Send + Sync {
+    /// Fetches the source and converts its entries into a calendar.
+    fn fetch_calendar<'a>(&'a self, client: &'a Client) -> BoxFuture<'a, Result<Calendar>>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SourceType {
+    #[default]
+    Ics,
+    JsonApi,
+    Csv,
+}
+
+impl FromStr for SourceType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "ics" => Ok(Self::Ics),
+            "json-api" | "json" => Ok(Self::JsonApi),
+            "csv" => Ok(Self::Csv),
+            other => bail!("Unknown source type: {}", other),
+        }
+    }
+}
+
+/// Builds the source configured via `SOURCE_TYPE`, falling back to the `ICS_FALLBACK_URLS`.
+pub fn from_config(config: &Config) -> Result<Box<dyn CalendarSource>> {
+    if config.ics_fallback_urls.is_empty() {
+        return for_url(config, &config.ics_url);
+    }
+
+    let mut sources = Vec::new();
+    for url in std::iter::once(&config.ics_url).chain(&config.ics_fallback_urls) {
+        sources.push((url.clone(), for_url(config, url)?));
+    }
+    Ok(Box::new(FallbackSource { sources }))
+}
+
+fn for_url(config: &Config, url: &str) -> Result<Box<dyn CalendarSource>> {
+    let request = SourceRequest::from_config(config).for_mirror(url);
+
+    if let Some(command) = url.strip_prefix("exec://") {
+        return Ok(Box::new(ExecSource {
+            command: command.to_string(),
+            compat_modes: config.ics_compat.clone(),
+        }));
+    }
+
+    match config.source_type {
+        SourceType::Ics => Ok(Box::new(IcsSource {
+            request,
+            compat_modes: config.ics_compat.clone(),
+        })),
+        SourceType::JsonApi => Ok(Box::new(JsonApiSource {
+            request,
+            mapping: config
+                .json_mapping
+                .clone()
+                .context("JSON field mapping is required for the json-api source")?,
+        })),
+        SourceType::Csv => Ok(Box::new(CsvSource {
+            location: match url.split_once("://") {
+                Some(("http" | "https", _)) => CsvLocation::Http(Box::new(request)),
+                Some(("file", path)) => CsvLocation::File(PathBuf::from(path)),
+                _ => CsvLocation::File(PathBuf::from(url)),
+            },
+            mapping: config
+                .csv_mapping
+                .clone()
+                .context("CSV column mapping is required for the csv source")?,
+        })),
+    }
+}
+
+/// Location and credentials of a source fetched via HTTP.
+#[derive(Debug, Clone)]
+pub struct SourceRequest {
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub bearer_token: Option<String>,
+    pub headers: Vec<(String, String)>,
+    pub oauth: Option<oauth::OAuthConfig>,
+}
+
+impl SourceRequest {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            url: config.ics_url.clone(),
+            username: config.ics_username.clone(),
+            password: config.ics_password.clone(),
+            bearer_token: config.ics_bearer_token.clone(),
+            headers: config.ics_headers.clone(),
+            oauth: config.ics_oauth.clone(),
+        }
+    }
+
+    /// The request for a mirror of the source at `url`. The credentials and headers are only
+    /// kept if the mirror has the same origin, so they are not sent anywhere else.
+    pub fn for_mirror(&self, url: &str) -> Self {
+        let origin = |url: &str| Url::parse(url).ok().map(|url| url.origin());
+        if url == self.url || origin(url).is_some_and(|mirror| Some(mirror) == origin(&self.url)) {
+            return Self {
+                url: url.to_string(),
+                ..self.clone()
+            };
+        }
+
+        debug!("Fetching {} without the credentials of {}", url, self.url);
+        Self {
+            url: url.to_string(),
+            username: None,
+            password: None,
+            bearer_token: None,
+            headers: Vec::new(),
+            oauth: None,
+        }
+    }
+
+    pub async fn send(&self, client: &Client) -> Result<Response> {
+        let bearer_token = match &self.oauth {
+            Some(oauth_config) => Some(
+                oauth::get_access_token(client, oauth_config)
+                    .await
+                    .context("Failed to obtain OAuth access token for source calendar.")?,
+            ),
+            None => self.bearer_token.clone(),
+        };
+
+        ics_parser::fetch_ics_data(
+            client,
+            &self.url,
+            self.username.clone(),
+            self.password.clone(),
+            bearer_token,
+            &self.headers,
+        )
+        .await
+    }
+}
+
+/// Parses the date formats commonly found in non-ICS sources.
+///
+/// RFC 3339 timestamps and unix timestamps become UTC times, values without an offset
+/// floating times and plain dates all-day dates.
+pub fn parse_date_value(value: &str) -> Option<DatePerhapsTime> {
+    let value = value.trim();
+
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(value) {
+        return Some(date_time.with_timezone(&Utc).into());
+    }
+
+    for format in [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M",
+    ] {
+        if let Ok(date_time) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(date_time.into());
+        }
+    }
+
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Some(date.into());
+    }
+
+    value
+        .parse::<i64>()
+        .ok()
+        .and_then(|timestamp| DateTime::<Utc>::from_timestamp(timestamp, 0))
+        .map(Into::into)
+}
@@ -0,0 +1,165 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::paths;
+
+const FORMAT_VERSION: u32 = 1;
+
+/// Where the state of a sync lives on this machine.
+#[derive(Debug, Clone)]
+pub struct StateLocations {
+    pub journal_dir: PathBuf,
+    pub oauth_token: PathBuf,
+    pub sync_back: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Kind {
+    Journal,
+    OauthToken,
+    SyncBack,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StateFile {
+    kind: Kind,
+    name: String,
+    content: String,
+}
+
+/// A portable copy of the state, written by `state export` and read by `state import`.
+///
+/// Files are stored by kind and name instead of their path, so the archive can be imported
+/// with different directories.
+#[derive(Debug, Serialize, Deserialize)]
+struct StateArchive {
+    version: u32,
+    /// Unix timestamp
+    exported: i64,
+    files: Vec<StateFile>,
+}
+
+/// Writes the journals, the OAuth token and the synced back properties into an archive, returning how many files it holds.
+pub fn export(locations: &StateLocations, archive_path: &Path) -> Result<usize> {
+    let mut files = Vec::new();
+    if locations.journal_dir.is_dir() {
+        let entries = fs::read_dir(&locations.journal_dir).with_context(|| {
+            format!(
+                "Failed to read journal directory. Path: {}",
+                locations.journal_dir.display()
+            )
+        })?;
+        for entry in entries {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "jsonl")
+            {
+                files.push(read_file(Kind::Journal, &path)?);
+            }
+        }
+    }
+    if locations.oauth_token.is_file() {
+        files.push(read_file(Kind::OauthToken, &locations.oauth_token)?);
+    }
+    if locations.sync_back.is_file() {
+        files.push(read_file(Kind::SyncBack, &locations.sync_back)?);
+    }
+    files.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let archive = StateArchive {
+        version: FORMAT_VERSION,
+        exported: Utc::now().timestamp(),
+        files,
+    };
+    // The archive holds the OAuth token
+    paths::write_private(archive_path, serde_json::to_string_pretty(&archive)?).with_context(
+        || {
+            format!(
+                "Failed to write state archive. Path: {}",
+                archive_path.display()
+            )
+        },
+    )?;
+    Ok(archive.files.len())
+}
+
+/// Restores the files of an archive, returning how many were written.
+///
+/// Existing files are only replaced with `overwrite`, so the state of a machine that already
+/// synced is not lost by accident.
+pub fn import(locations: &StateLocations, archive_path: &Path, overwrite: bool) -> Result<usize> {
+    let content = fs::read_to_string(archive_path).with_context(|| {
+        format!(
+            "Failed to read state archive. Path: {}",
+            archive_path.display()
+        )
+    })?;
+    let archive: StateArchive = serde_json::from_str(&content)
+        .with_context(|| format!("Invalid state archive. Path: {}", archive_path.display()))?;
+    if archive.version > FORMAT_VERSION {
+        bail!(
+            "State archive version {} was written by a newer release, this one reads up to version {}",
+            archive.version,
+            FORMAT_VERSION
+        );
+    }
+
+    let targets = archive
+        .files
+        .iter()
+        .map(|file| {
+            let target = match file.kind {
+                Kind::Journal => locations.journal_dir.join(plain_name(&file.name)?),
+                Kind::OauthToken => locations.oauth_token.clone(),
+                Kind::SyncBack => locations.sync_back.clone(),
+            };
+            if target.exists() && !overwrite {
+                bail!(
+                    "{} already exists, use --force to replace it",
+                    target.display()
+                );
+            }
+            Ok(target)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    for (file, target) in archive.files.iter().zip(&targets) {
+        if let Some(dir) = target.parent() {
+            fs::create_dir_all(dir).with_context(|| {
+                format!("Failed to create state directory. Path: {}", dir.display())
+            })?;
+        }
+        paths::write_private(target, &file.content)
+            .with_context(|| format!("Failed to write state file. Path: {}", target.display()))?;
+    }
+    Ok(targets.len())
+}
+
+fn read_file(kind: Kind, path: &Path) -> Result<StateFile> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read state file. Path: {}", path.display()))?;
+    Ok(StateFile {
+        kind,
+        name: path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        content,
+    })
+}
+
+// Archives may come from elsewhere, their names must not point outside the directory
+fn plain_name(name: &str) -> Result<&str> {
+    match Path::new(name).file_name() {
+        Some(file_name) if file_name == name => Ok(name),
+        _ => bail!("Invalid file name in state archive: {}", name),
+    }
+}
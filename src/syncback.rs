@@ -0,0 +1,145 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::{Context, Result, anyhow};
+use icalendar::{Calendar, Component, Event};
+use log::debug;
+
+use crate::{ownership::PropertyOwnership, paths};
+
+/// Properties and components like `VALARM` that are synced back from the Nextcloud copies of
+/// the events into a local file, so changes made in Nextcloud survive even if an event is
+/// uploaded anew, e.g. after it was deleted there, moved to another calendar or the calendar
+/// was migrated.
+///
+/// While a copy exists, the properties are kept as they are in it like the
+/// `target_owned` ones of [`PropertyOwnership`], which they should be part of.
+#[derive(Debug)]
+pub struct SyncBack {
+    properties: Vec<String>,
+    path: PathBuf,
+    /// The synced back parts of the events by UID.
+    events: Mutex<HashMap<String, Event>>,
+}
+
+impl SyncBack {
+    /// Loads the parts synced back by earlier runs, if there are any.
+    pub fn load(path: &Path, properties: Vec<String>) -> Result<Self> {
+        let events = match fs::read_to_string(path) {
+            Ok(content) => content
+                .parse::<Calendar>()
+                .map_err(|e: String| anyhow!(e))
+                .with_context(|| format!("Invalid sync-back file. Path: {}", path.display()))?
+                .components
+                .into_iter()
+                .filter_map(|component| component.as_event().cloned())
+                .filter_map(|event| Some((event.get_uid()?.to_string(), event)))
+                .collect(),
+            Err(_) => HashMap::new(),
+        };
+        Ok(Self {
+            properties,
+            path: path.to_path_buf(),
+            events: Mutex::new(events),
+        })
+    }
+
+    pub fn properties(&self) -> &[String] {
+        &self.properties
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Remembers the synced back parts of the synced copies in the calendar. Copies without
+    /// them had them removed in Nextcloud, so they are forgotten as well.
+    pub fn collect(&self, nextcloud_events: &HashMap<String, Event>) {
+        let ownership = self.ownership();
+        let mut events = self.events.lock().unwrap_or_else(|e| e.into_inner());
+        for (uid, event) in nextcloud_events {
+            if event.property_value("X-SYNCED").is_none() {
+                continue;
+            }
+
+            let mut parts = Event::new();
+            parts.uid(uid);
+            let parts = ownership.merge(&parts, Some(event));
+            let has_parts = parts
+                .properties()
+                .keys()
+                .any(|name| self.is_synced_back(name))
+                || !parts.multi_properties().is_empty()
+                || !parts.components().is_empty();
+            if has_parts {
+                events.insert(uid.clone(), parts);
+            } else {
+                events.remove(uid);
+            }
+        }
+    }
+
+    /// Replaces the synced back parts of an event that is uploaded without an existing copy by
+    /// the remembered ones.
+    pub fn restore(&self, event: &Event) -> Event {
+        let events = self.events.lock().unwrap_or_else(|e| e.into_inner());
+        match event.get_uid().and_then(|uid| events.get(uid)) {
+            Some(parts) => {
+                debug!(
+                    "Restoring {} of {}",
+                    self.properties.join(", "),
+                    event.get_uid().unwrap_or_default()
+                );
+                self.ownership().merge(event, Some(parts))
+            }
+            None => event.clone(),
+        }
+    }
+
+    /// Writes the remembered parts, replacing the file at once so it is never left half
+    /// written. Only the user can read it, as the events may contain private details.
+    pub fn save(&self) -> Result<()> {
+        let calendar: Calendar = {
+            let events = self.events.lock().unwrap_or_else(|e| e.into_inner());
+            let mut uids: Vec<&String> = events.keys().collect();
+            uids.sort();
+            uids.into_iter().map(|uid| events[uid].clone()).collect()
+        };
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).with_context(|| {
+                format!(
+                    "Failed to create sync-back directory. Path: {}",
+                    dir.display()
+                )
+            })?;
+        }
+        let partial = self.path.with_extension("partial");
+        paths::write_private(&partial, calendar.to_string())
+            .and_then(|_| fs::rename(&partial, &self.path))
+            .with_context(|| {
+                format!(
+                    "Failed to write sync-back file. Path: {}",
+                    self.path.display()
+                )
+            })
+    }
+
+    fn is_synced_back(&self, name: &str) -> bool {
+        self.properties
+            .iter()
+            .any(|property| property.eq_ignore_ascii_case(name))
+    }
+
+    /// Takes everything but the synced back parts from the first event.
+    fn ownership(&self) -> PropertyOwnership {
+        PropertyOwnership {
+            owned: Vec::new(),
+            target_owned: self.properties.clone(),
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use std::str::FromStr;
+
+use anyhow::{Context, Result, bail};
+use futures::future::try_join_all;
+use icalendar::{Calendar, CalendarComponent, Component, Event, Property, Todo};
+use log::{debug, info};
+use reqwest::StatusCode;
+
+use crate::{
+    http::{self, Client},
+    nextcloud::utils,
+    validate,
+};
+
+/// Whether events routed to tasks are still synced as events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TaskMode {
+    #[default]
+    Also,
+    Instead,
+}
+
+impl FromStr for TaskMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "also" => Ok(Self::Also),
+            "instead" => Ok(Self::Instead),
+            other => bail!("Unknown task routing mode: {}", other),
+        }
+    }
+}
+
+/// Creates a Nextcloud Task for events whose SUMMARY contains one of the patterns, like
+/// `DEADLINE:`.
+#[derive(Debug, Clone)]
+pub struct TaskRouting {
+    /// Matched case-insensitively.
+    pub patterns: Vec<String>,
+    /// A calendar collection supporting VTODO.
+    pub tasks_calendar_url: String,
+    pub mode: TaskMode,
+}
+
+/// Properties carried over from the event, DTSTART becomes DUE.
+const TASK_PROPERTIES: &[&str] = &[
+    "SUMMARY",
+    "DESCRIPTION",
+    "LOCATION",
+    "URL",
+    "CATEGORIES",
+    "PRIORITY",
+];
+
+impl TaskRouting {
+    fn matches(&self, event: &Event) -> bool {
+        let Some(summary) = event.get_summary().map(str::to_lowercase) else {
+            return false;
+        };
+        self.patterns
+            .iter()
+            .any(|pattern| summary.contains(&pattern.to_lowercase()))
+    }
+
+    /// Takes the matching events out of the calendar (unless the mode is `Also`) and returns
+    /// them converted to tasks.
+    pub fn split(&self, mut calendar: Calendar) -> (Calendar, Vec<Todo>) {
+        let mut tasks = Vec::new();
+        calendar.components.retain(|component| match component {
+            CalendarComponent::Event(event) if self.matches(event) => {
+                tasks.extend(to_task(event));
+                self.mode == TaskMode::Also
+            }
+            _ => true,
+        });
+        (calendar, tasks)
+    }
+}
+
+fn to_task(event: &Event) -> Option<Todo> {
+    let uid = event.get_uid()?;
+    let mut task = Todo::new();
+    task.uid(&format!("task-{}", uid));
+    for name in TASK_PROPERTIES {
+        if let Some(property) = event.properties().get(*name) {
+            task.append_property(property.clone());
+        }
+    }
+    if let Some(start) = event.properties().get("DTSTART") {
+        let mut due = Property::new("DUE", start.value());
+        for parameter in start.params().values() {
+            due.append_parameter(parameter.clone());
+        }
+        task.append_property(due);
+    }
+    task.add_property("X-SYNCED", "TRUE");
+    Some(task.done())
+}
+
+/// Creates the tasks that don't exist yet.
+///
+/// Existing tasks are never overwritten, so tasks completed or edited in Nextcloud stay as
+/// they are. Returns how many were created.
+pub async fn create_tasks(
+    client: &Client,
+    username: &str,
+    password: &str,
+    tasks_calendar_url: &str,
+    tasks: Vec<Todo>,
+) -> Result<usize> {
+    let requests = tasks.into_iter().map(|task| {
+        let uid = task.get_uid().unwrap_or_default().to_string();
+        let url = format!("{}{}", tasks_calendar_url, utils::resource_name(&uid));
+        let body = validate::normalize_payload(&Calendar::from_iter([task]).to_string());
+
+        async move {
+            let request = http::dav_request(client, "PUT", &url, username, password)
+                .header("Content-Type", "text/calendar")
+                .header("If-None-Match", "*")
+                .body(body);
+            let response = http::send(client, request)
+                .await
+                .with_context(|| format!("Failed to create task with UID: {}", uid))?;
+
+            match response.status() {
+                StatusCode::CREATED | StatusCode::NO_CONTENT | StatusCode::OK => {
+                    debug!("-> Created task {}", uid);
+                    Ok(true)
+                }
+                StatusCode::PRECONDITION_FAILED => Ok(false),
+                status => bail!(
+                    "Creating task {} failed with status {} and body of:\n{}",
+                    uid,
+                    status,
+                    response.text().await.unwrap_or_default()
+                ),
+            }
+        }
+    });
+
+    let created = try_join_all(requests)
+        .await?
+        .into_iter()
+        .filter(|created| *created)
+        .count();
+    info!("Created {} new tasks.", created);
+    Ok(created)
+}
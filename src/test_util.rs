@@ -0,0 +1,483 @@
+use digest_auth::{AuthContext, AuthorizationHeader, HttpMethod};
+use icalendar::{Calendar, Component, Event, EventLike};
+use quick_xml::escape::escape;
+use wiremock::{
+    Match, Mock, MockServer, Request, ResponseTemplate,
+    matchers::{any, basic_auth, body_string_contains, header, method, path, path_regex},
+};
+
+const DIGEST_CHALLENGE: &str =
+    r#"Digest realm="Nextcloud", qop="auth", algorithm=MD5, nonce="5f1c0a5e8b7d4c3a""#;
+
+/// A stubbed Nextcloud CalDAV server for testing code built on `nextcloud::api`.
+///
+/// Every `mock_*` method registers the responses of one DAV flow, requests are only
+/// answered when they carry the configured basic auth credentials.
+pub struct MockNextcloud {
+    server: MockServer,
+    pub username: String,
+    pub password: String,
+    digest: bool,
+}
+
+impl MockNextcloud {
+    pub async fn start(username: &str, password: &str) -> Self {
+        Self {
+            server: MockServer::start().await,
+            username: username.to_string(),
+            password: password.to_string(),
+            digest: false,
+        }
+    }
+
+    /// A server that only accepts Digest authentication, requests without a valid answer
+    /// get a `401` with a challenge.
+    pub async fn start_digest(username: &str, password: &str) -> Self {
+        let nextcloud = Self {
+            digest: true,
+            ..Self::start(username, password).await
+        };
+        Mock::given(any())
+            .respond_with(
+                ResponseTemplate::new(401).insert_header("WWW-Authenticate", DIGEST_CHALLENGE),
+            )
+            .with_priority(u8::MAX)
+            .mount(&nextcloud.server)
+            .await;
+        nextcloud
+    }
+
+    pub fn server(&self) -> &MockServer {
+        &self.server
+    }
+
+    /// Base URL, the equivalent of `NEXTCLOUD_URL`.
+    pub fn url(&self) -> String {
+        self.server.uri()
+    }
+
+    pub fn calendar_path(&self, calendar_id: &str) -> String {
+        format!(
+            "/remote.php/dav/calendars/{}/{}/",
+            self.username, calendar_id
+        )
+    }
+
+    /// Full DAV URL of a calendar, the equivalent of `Config::nextcloud_calendar_url`.
+    pub fn calendar_url(&self, calendar_id: &str) -> String {
+        format!("{}{}", self.url(), self.calendar_path(calendar_id))
+    }
+
+    /// Answers the PROPFIND on the calendar home with the given calendars.
+    pub async fn mock_calendar_list(&self, calendar_ids: &[&str]) {
+        let home = format!("/remote.php/dav/calendars/{}/", self.username);
+        let responses: String = calendar_ids
+            .iter()
+            .map(|id| {
+                format!(
+                    "<d:response><d:href>{}{}/</d:href><d:propstat><d:prop>\
+                     <d:displayname>{}</d:displayname>\
+                     <d:resourcetype><d:collection/><cal:calendar/></d:resourcetype>\
+                     </d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>",
+                    home, id, id
+                )
+            })
+            .collect();
+
+        let body = format!(
+            r#"<?xml version="1.0"?>
+<d:multistatus xmlns:d="DAV:" xmlns:cal="urn:ietf:params:xml:ns:caldav" xmlns:cs="http://calendarserver.org/ns/">{}</d:multistatus>"#,
+            responses
+        );
+
+        self.mock_calendar_home(body).await;
+    }
+
+    /// Answers the PROPFIND on the calendar home with a raw multistatus body.
+    pub async fn mock_calendar_home(&self, body: impl Into<String>) {
+        let home = format!("/remote.php/dav/calendars/{}/", self.username);
+        self.register("PROPFIND", &home, multistatus(body.into()))
+            .await;
+    }
+
+    /// Answers the calendar-query REPORT with one `<uid>.ics` resource per event of the calendar.
+    pub async fn mock_calendar_query(&self, calendar_id: &str, calendar: &Calendar) {
+        let responses: String = calendar
+            .components
+            .iter()
+            .filter_map(|component| component.as_event())
+            .map(|event| {
+                let data = Calendar::new().push(event.clone()).done().to_string();
+                format!(
+                    "<d:response><d:href>{}{}.ics</d:href><d:propstat><d:prop>\
+                     <d:getetag>\"{}\"</d:getetag><cal:calendar-data>{}</cal:calendar-data>\
+                     </d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>",
+                    self.calendar_path(calendar_id),
+                    event.get_uid().unwrap_or_default(),
+                    event
+                        .get_last_modified()
+                        .map(|t| t.timestamp())
+                        .unwrap_or_default(),
+                    escape(&data)
+                )
+            })
+            .collect();
+
+        let body = format!(
+            r#"<?xml version="1.0"?>
+<d:multistatus xmlns:d="DAV:" xmlns:cal="urn:ietf:params:xml:ns:caldav">{}</d:multistatus>"#,
+            responses
+        );
+        self.mock_report(calendar_id, body).await;
+    }
+
+    /// Answers REPORT requests on the calendar with a raw multistatus body.
+    pub async fn mock_report(&self, calendar_id: &str, body: impl Into<String>) {
+        self.register(
+            "REPORT",
+            &self.calendar_path(calendar_id),
+            multistatus(body.into()),
+        )
+        .await;
+    }
+
+    /// Answers PROPFIND requests on the calendar itself with a raw multistatus body.
+    pub async fn mock_calendar_properties(&self, calendar_id: &str, body: impl Into<String>) {
+        self.register(
+            "PROPFIND",
+            &self.calendar_path(calendar_id),
+            multistatus(body.into()),
+        )
+        .await;
+    }
+
+    /// Answers `status.php` and the OPTIONS request on the calendar like a CalDAV capable
+    /// instance, optionally in maintenance mode.
+    pub async fn mock_status(&self, calendar_id: &str, maintenance: bool) {
+        let status = format!(
+            r#"{{"installed":true,"maintenance":{},"version":"28.0.1.1"}}"#,
+            maintenance
+        );
+        Mock::given(method("GET"))
+            .and(path("/status.php"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(status, "application/json"))
+            .mount(&self.server)
+            .await;
+        self.register(
+            "OPTIONS",
+            &self.calendar_path(calendar_id),
+            ResponseTemplate::new(200)
+                .insert_header("DAV", "1, 3, access-control, calendar-access"),
+        )
+        .await;
+    }
+
+    /// Answers the OCS capabilities request with the given server version.
+    pub async fn mock_capabilities(&self, major: u32, minor: u32, micro: u32) {
+        let body = format!(
+            r#"{{"ocs":{{"data":{{"version":{{"major":{},"minor":{},"micro":{}}}}}}}}}"#,
+            major, minor, micro
+        );
+        self.register(
+            "GET",
+            "/ocs/v1.php/cloud/capabilities",
+            ResponseTemplate::new(200).set_body_raw(body, "application/json"),
+        )
+        .await;
+    }
+
+    /// Answers an OCS API request, e.g. of a notification, with an empty success response.
+    pub async fn mock_ocs(&self, http_method: &str, request_path: &str) {
+        self.register(
+            http_method,
+            request_path,
+            ResponseTemplate::new(200).set_body_raw(
+                r#"{"ocs":{"meta":{"status":"ok","statuscode":200},"data":[]}}"#,
+                "application/json",
+            ),
+        )
+        .await;
+    }
+
+    /// Answers the rotation of the current app password with `new_password`.
+    pub async fn mock_app_password_rotation(&self, new_password: &str) {
+        self.register(
+            "POST",
+            "/ocs/v2.php/core/apppassword/rotate",
+            ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "ocs": {
+                    "meta": {"status": "ok", "statuscode": 200},
+                    "data": {"apppassword": new_password},
+                },
+            })),
+        )
+        .await;
+    }
+
+    /// Answers free/busy requests to the scheduling outbox with the `FREEBUSY` lines of
+    /// every recipient, like `FREEBUSY:20240101T100000Z/20240101T110000Z`.
+    pub async fn mock_free_busy(&self, recipients: &[(&str, &[&str])]) {
+        let responses: String = recipients
+            .iter()
+            .map(|(recipient, lines)| {
+                let data = free_busy_data(lines);
+                format!(
+                    "<cal:response><cal:recipient><d:href>mailto:{}</d:href></cal:recipient>\
+                     <cal:request-status>2.0;Success</cal:request-status>\
+                     <cal:calendar-data>{}</cal:calendar-data></cal:response>",
+                    recipient,
+                    escape(&data)
+                )
+            })
+            .collect();
+
+        let body = format!(
+            r#"<?xml version="1.0"?>
+<cal:schedule-response xmlns:d="DAV:" xmlns:cal="urn:ietf:params:xml:ns:caldav">{}</cal:schedule-response>"#,
+            responses
+        );
+        let outbox = format!("/remote.php/dav/calendars/{}/outbox/", self.username);
+        self.register(
+            "POST",
+            &outbox,
+            ResponseTemplate::new(200).set_body_raw(body, "application/xml; charset=utf-8"),
+        )
+        .await;
+    }
+
+    /// Answers the free-busy-query REPORT on the calendar with the `FREEBUSY` lines.
+    pub async fn mock_free_busy_query(&self, calendar_id: &str, lines: &[&str]) {
+        self.register(
+            "REPORT",
+            &self.calendar_path(calendar_id),
+            ResponseTemplate::new(200).set_body_raw(free_busy_data(lines), "text/calendar"),
+        )
+        .await;
+    }
+
+    /// Answers every request below `path_prefix` without checking credentials, for third
+    /// party APIs like chat services that are stubbed on the same server.
+    pub async fn mock_external(&self, http_method: &str, path_prefix: &str) {
+        Mock::given(method(http_method))
+            .and(path_regex(format!("^{}", regex_escape(path_prefix))))
+            .respond_with(ResponseTemplate::new(200).set_body_raw("{}", "application/json"))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Serves a source feed at `request_path` without checking credentials.
+    pub async fn mock_feed(&self, request_path: &str, content_type: &str, body: &str) {
+        Mock::given(method("GET"))
+            .and(path(request_path))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, content_type))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Serves a source feed at `request_path` only to requests carrying all the headers.
+    pub async fn mock_protected_feed(
+        &self,
+        request_path: &str,
+        headers: &[(&str, &str)],
+        body: &str,
+    ) {
+        let mut mock = Mock::given(method("GET")).and(path(request_path));
+        for (name, value) in headers {
+            mock = mock.and(header(*name, *value));
+        }
+        mock.respond_with(ResponseTemplate::new(200).set_body_raw(body, "text/calendar"))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Answers the next `times` requests to `request_path` with `503 Service Unavailable`,
+    /// before the mocks registered for it.
+    pub async fn mock_unavailable(&self, request_path: &str, times: u64) {
+        Mock::given(path(request_path))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(times)
+            .with_priority(1)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Answers token requests of the given OAuth grant type at `/oauth/token`.
+    pub async fn mock_oauth_token(&self, grant_type: &str, status: u16, body: &str) {
+        Mock::given(method("POST"))
+            .and(path("/oauth/token"))
+            .and(body_string_contains(format!("grant_type={}", grant_type)))
+            .respond_with(ResponseTemplate::new(status).set_body_raw(body, "application/json"))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Accepts every PUT of an event resource in the calendar.
+    pub async fn mock_uploads(&self, calendar_id: &str) {
+        self.register_resource("PUT", calendar_id, ResponseTemplate::new(201))
+            .await;
+    }
+
+    /// Accepts every DELETE of an event resource in the calendar.
+    pub async fn mock_deletes(&self, calendar_id: &str) {
+        self.register_resource("DELETE", calendar_id, ResponseTemplate::new(204))
+            .await;
+    }
+
+    /// Accepts every MOVE of an event resource out of the calendar.
+    pub async fn mock_moves(&self, calendar_id: &str) {
+        self.register_resource("MOVE", calendar_id, ResponseTemplate::new(201))
+            .await;
+    }
+
+    /// Accepts every COPY of an event resource out of the calendar.
+    pub async fn mock_copies(&self, calendar_id: &str) {
+        self.register_resource("COPY", calendar_id, ResponseTemplate::new(201))
+            .await;
+    }
+
+    /// All requests received with the given method, in order.
+    pub async fn received(&self, http_method: &str) -> Vec<Request> {
+        self.server
+            .received_requests()
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|request| request.method.as_str() == http_method)
+            .collect()
+    }
+
+    /// Paths of all requests received with the given method, sorted.
+    pub async fn received_paths(&self, http_method: &str) -> Vec<String> {
+        let mut paths: Vec<String> = self
+            .received(http_method)
+            .await
+            .into_iter()
+            .map(|request| request.url.path().to_string())
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    async fn register(&self, http_method: &str, request_path: &str, response: ResponseTemplate) {
+        self.authenticated(Mock::given(method(http_method)).and(path(request_path)))
+            .respond_with(response)
+            .mount(&self.server)
+            .await;
+    }
+
+    fn authenticated(&self, mock: wiremock::MockBuilder) -> wiremock::MockBuilder {
+        if self.digest {
+            mock.and(DigestAuth {
+                username: self.username.clone(),
+                password: self.password.clone(),
+            })
+        } else {
+            mock.and(basic_auth(&self.username, &self.password))
+        }
+    }
+
+    async fn register_resource(
+        &self,
+        http_method: &str,
+        calendar_id: &str,
+        response: ResponseTemplate,
+    ) {
+        let resource = format!(
+            "^{}[^/]+\\.ics$",
+            regex_escape(&self.calendar_path(calendar_id))
+        );
+        self.authenticated(Mock::given(method(http_method)).and(path_regex(resource)))
+            .respond_with(response)
+            .mount(&self.server)
+            .await;
+    }
+}
+
+/// A VFREEBUSY object with the `FREEBUSY` lines.
+fn free_busy_data(lines: &[&str]) -> String {
+    let lines: String = lines.iter().map(|line| format!("{}\r\n", line)).collect();
+    format!(
+        "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VFREEBUSY\r\n{}END:VFREEBUSY\r\nEND:VCALENDAR\r\n",
+        lines
+    )
+}
+
+/// Matches requests with a correct answer to [`DIGEST_CHALLENGE`].
+struct DigestAuth {
+    username: String,
+    password: String,
+}
+
+impl Match for DigestAuth {
+    fn matches(&self, request: &Request) -> bool {
+        let Some(answer) = request
+            .headers
+            .get("Authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| AuthorizationHeader::parse(value).ok())
+        else {
+            return false;
+        };
+
+        let Ok(mut challenge) = digest_auth::parse(DIGEST_CHALLENGE) else {
+            return false;
+        };
+        challenge.nc = answer.nc.saturating_sub(1);
+        let mut context = AuthContext::new_with_method(
+            self.username.as_str(),
+            self.password.as_str(),
+            answer.uri.as_str(),
+            Option::<&[u8]>::None,
+            HttpMethod::from(request.method.as_str()),
+        );
+        if let Some(cnonce) = &answer.cnonce {
+            context.set_custom_cnonce(cnonce.as_str());
+        }
+        challenge
+            .respond(&context)
+            .is_ok_and(|expected| expected.response == answer.response)
+    }
+}
+
+/// A calendar of `count` distinct events for benchmarks and load tests.
+///
+/// `revision` goes into LAST-MODIFIED, so calendars generated with different revisions
+/// differ in every event. `synced` marks the events as created by a sync.
+pub fn synthetic_calendar(count: usize, revision: u32, synced: bool) -> Calendar {
+    (0..count)
+        .map(|i| {
+            let mut event = Event::new();
+            event
+                .uid(&format!("synthetic-{}@example.com", i))
+                .summary(&format!("Event {}", i))
+                .location(&format!("Room {}", i % 100))
+                .description("Generated event with a description of typical length.")
+                .add_property(
+                    "DTSTART",
+                    format!("2024{:02}{:02}T100000Z", i % 12 + 1, i % 28 + 1),
+                )
+                .add_property(
+                    "DTEND",
+                    format!("2024{:02}{:02}T110000Z", i % 12 + 1, i % 28 + 1),
+                )
+                .add_property("LAST-MODIFIED", format!("2024010{}T000000Z", revision % 10));
+            if synced {
+                event.add_property("X-SYNCED", "TRUE");
+            }
+            event.done()
+        })
+        .collect()
+}
+
+fn multistatus(body: String) -> ResponseTemplate {
+    ResponseTemplate::new(207).set_body_raw(body, "application/xml; charset=utf-8")
+}
+
+fn regex_escape(s: &str) -> String {
+    s.chars()
+        .flat_map(|c| {
+            let escape = "\\.+*?()|[]{}^$".contains(c).then_some('\\');
+            escape.into_iter().chain(std::iter::once(c))
+        })
+        .collect()
+}
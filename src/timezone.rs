@@ -0,0 +1,233 @@
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
+
+use anyhow::{Result, bail};
+use icalendar::{Calendar, CalendarComponent, Component, Event, Property};
+use log::{debug, warn};
+
+use crate::compat;
+
+/// How TZIDs the timezone database doesn't know are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TzidValidation {
+    Off,
+    /// Log every unknown TZID once.
+    #[default]
+    Report,
+    /// Replace unknown TZIDs with their IANA name from the alias table where possible, and
+    /// report the rest.
+    Repair,
+}
+
+impl FromStr for TzidValidation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "report" => Ok(Self::Report),
+            "repair" => Ok(Self::Repair),
+            other => bail!("Unknown TZID validation mode: {}", other),
+        }
+    }
+}
+
+/// What happens to floating times (without TZID or `Z`), which every client shows in its
+/// own timezone.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum FloatingTimes {
+    #[default]
+    Keep,
+    /// Add this TZID to them.
+    Anchor(String),
+    /// Read them as UTC times and mark them with `Z`.
+    Utc,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TimezoneOptions {
+    pub validation: TzidValidation,
+    /// Own mappings from TZIDs to IANA names, checked before the Windows names.
+    pub aliases: HashMap<String, String>,
+    pub floating: FloatingTimes,
+}
+
+/// Properties whose TZID parameter is checked.
+const DATE_PROPERTIES: &[&str] = &["DTSTART", "DTEND", "DUE", "RECURRENCE-ID"];
+const DATE_LIST_PROPERTIES: &[&str] = &["EXDATE", "RDATE"];
+
+/// Checks all TZIDs of the events against the bundled IANA timezone database, so the check
+/// works the same on every machine.
+///
+/// A TZID the server doesn't know makes it fall back to UTC or floating time, which silently
+/// shifts the event.
+pub fn check_tzids(mut calendar: Calendar, options: &TimezoneOptions) -> Calendar {
+    if options.validation == TzidValidation::Off {
+        return calendar;
+    }
+    let is_known = |tzid: &str| tzid.parse::<chrono_tz::Tz>().is_ok();
+
+    let mut reported: HashSet<String> = HashSet::new();
+    let mut replacements: HashMap<String, Option<String>> = HashMap::new();
+    for component in &mut calendar.components {
+        let CalendarComponent::Event(event) = component else {
+            continue;
+        };
+
+        for tzid in event_tzids(event) {
+            if is_known(&tzid) {
+                continue;
+            }
+            let replacement = replacements
+                .entry(tzid.clone())
+                .or_insert_with(|| match options.validation {
+                    TzidValidation::Repair => repair(&tzid, options).filter(|r| is_known(r)),
+                    _ => None,
+                })
+                .clone();
+
+            match replacement {
+                Some(replacement) => replace_tzid(event, &tzid, &replacement),
+                None if reported.insert(tzid.clone()) => warn!(
+                    "Unknown TZID {} (first seen in event {}), its times may be wrong on the server",
+                    tzid,
+                    event.get_uid().unwrap_or_default()
+                ),
+                None => {}
+            }
+        }
+    }
+
+    for (tzid, replacement) in replacements {
+        if let Some(replacement) = replacement {
+            debug!("Replaced TZID {} with {}", tzid, replacement);
+        }
+    }
+    calendar
+}
+
+fn event_tzids(event: &Event) -> Vec<String> {
+    let single = DATE_PROPERTIES
+        .iter()
+        .filter_map(|name| event.properties().get(*name));
+    let lists = DATE_LIST_PROPERTIES
+        .iter()
+        .filter_map(|name| event.multi_properties().get(*name))
+        .flatten();
+
+    let mut tzids: Vec<String> = single
+        .chain(lists)
+        .filter_map(|property| property.params().get("TZID"))
+        .map(|tzid| tzid.value().to_string())
+        .collect();
+    tzids.sort();
+    tzids.dedup();
+    tzids
+}
+
+fn repair(tzid: &str, options: &TimezoneOptions) -> Option<String> {
+    let trimmed = tzid.trim_matches(|c: char| c == '"' || c.is_whitespace());
+    if let Some(alias) = options.aliases.get(trimmed) {
+        return Some(alias.clone());
+    }
+    if let Some(iana) = compat::windows_to_iana(trimmed) {
+        return Some(iana.to_string());
+    }
+    // Prefixed IDs like `/mozilla.org/20050126_1/Europe/Berlin` end in the IANA name
+    let parts: Vec<&str> = trimmed.split('/').filter(|part| !part.is_empty()).collect();
+    (parts.len() > 2).then(|| parts[parts.len() - 2..].join("/"))
+}
+
+fn replace_tzid(event: &mut Event, tzid: &str, replacement: &str) {
+    let has_tzid = |property: &Property| {
+        property
+            .params()
+            .get("TZID")
+            .is_some_and(|p| p.value() == tzid)
+    };
+
+    for name in DATE_PROPERTIES {
+        if let Some(property) = event.properties().get(*name).filter(|p| has_tzid(p)) {
+            let mut property = property.clone();
+            property.add_parameter("TZID", replacement);
+            event.append_property(property);
+        }
+    }
+    for name in DATE_LIST_PROPERTIES {
+        let Some(properties) = event.multi_properties().get(*name).cloned() else {
+            continue;
+        };
+        event.remove_multi_property(name);
+        for mut property in properties {
+            if has_tzid(&property) {
+                property.add_parameter("TZID", replacement);
+            }
+            event.append_multi_property(property);
+        }
+    }
+}
+
+/// Applies the floating time policy to all date-times of the events, dates of all-day events
+/// are left alone.
+pub fn convert_floating_times(mut calendar: Calendar, policy: &FloatingTimes) -> Calendar {
+    if *policy == FloatingTimes::Keep {
+        return calendar;
+    }
+
+    for component in &mut calendar.components {
+        let CalendarComponent::Event(event) = component else {
+            continue;
+        };
+
+        for name in DATE_PROPERTIES {
+            if let Some(property) = event.properties().get(*name).filter(|p| is_floating(p)) {
+                let property = anchor(property, policy);
+                event.append_property(property);
+            }
+        }
+        for name in DATE_LIST_PROPERTIES {
+            let Some(properties) = event.multi_properties().get(*name).cloned() else {
+                continue;
+            };
+            event.remove_multi_property(name);
+            for property in properties {
+                let property = match is_floating(&property) {
+                    true => anchor(&property, policy),
+                    false => property,
+                };
+                event.append_multi_property(property);
+            }
+        }
+    }
+    calendar
+}
+
+// `20240101T100000`, not `20240101T100000Z` or a date
+fn is_floating(property: &Property) -> bool {
+    property.params().get("TZID").is_none()
+        && property
+            .value()
+            .split(',')
+            .all(|value| value.len() == 15 && value.as_bytes()[8] == b'T')
+}
+
+fn anchor(property: &Property, policy: &FloatingTimes) -> Property {
+    match policy {
+        FloatingTimes::Keep => property.clone(),
+        FloatingTimes::Anchor(tzid) => property.clone().add_parameter("TZID", tzid).done(),
+        FloatingTimes::Utc => {
+            let values: Vec<String> = property
+                .value()
+                .split(',')
+                .map(|value| format!("{}Z", value))
+                .collect();
+            let mut utc = Property::new(property.key(), values.join(","));
+            for parameter in property.params().values() {
+                utc.append_parameter(parameter.clone());
+            }
+            utc
+        }
+    }
+}
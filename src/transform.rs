@@ -0,0 +1,829 @@
+use std::{collections::HashMap, str::FromStr};
+
+use anyhow::{Result, bail};
+use chrono::{Datelike, NaiveDate, NaiveDateTime, TimeDelta};
+use icalendar::{
+    Calendar, CalendarComponent, CalendarDateTime, Component, DatePerhapsTime, Event, EventLike,
+    Property,
+};
+use log::{debug, warn};
+
+use crate::hours::WeeklyHours;
+
+/// What happens to ORGANIZER and ATTENDEE properties before events are uploaded.
+///
+/// Nextcloud's scheduling engine sends invitation emails for events with attendees, which is
+/// rarely wanted for a mirrored feed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SchedulingPolicy {
+    /// Remove ORGANIZER and ATTENDEE.
+    #[default]
+    Strip,
+    /// Keep them, but mark them with `SCHEDULE-AGENT=CLIENT` so the server sends nothing.
+    Client,
+    /// Upload them unchanged, Nextcloud sends real invitations.
+    Keep,
+}
+
+impl FromStr for SchedulingPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "strip" => Ok(Self::Strip),
+            "client" => Ok(Self::Client),
+            "keep" => Ok(Self::Keep),
+            other => bail!("Unknown scheduling policy: {}", other),
+        }
+    }
+}
+
+/// Which ATTACH properties are uploaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AttachmentPolicy {
+    #[default]
+    Keep,
+    /// Drop inline (base64) attachments, keep links.
+    UrlsOnly,
+    Strip,
+}
+
+impl FromStr for AttachmentPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "keep" => Ok(Self::Keep),
+            "urls-only" => Ok(Self::UrlsOnly),
+            "strip" => Ok(Self::Strip),
+            other => bail!("Unknown attachment policy: {}", other),
+        }
+    }
+}
+
+/// Scripts whose letters are replaced with Latin ones in SUMMARY.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transliteration {
+    /// Russian and Ukrainian Cyrillic.
+    Cyrillic,
+}
+
+impl FromStr for Transliteration {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "cyrillic" => Ok(Self::Cyrillic),
+            other => bail!("Unknown transliteration: {}", other),
+        }
+    }
+}
+
+/// What happens to an event exceeding one of the `EventLimits`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LimitPolicy {
+    /// Cut long texts and drop the oldest EXDATEs, events still too large are skipped.
+    #[default]
+    Truncate,
+    /// Leave the event out of the sync.
+    Skip,
+}
+
+impl FromStr for LimitPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "truncate" => Ok(Self::Truncate),
+            "skip" => Ok(Self::Skip),
+            other => bail!("Unknown event limit policy: {}", other),
+        }
+    }
+}
+
+/// Bounds for pathological feed entries, so one absurd event can't stall or fail a sync.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EventLimits {
+    /// Applies to SUMMARY, DESCRIPTION and LOCATION.
+    pub max_text_bytes: Option<usize>,
+    pub max_exdates: Option<usize>,
+    /// Size of the serialized event.
+    pub max_event_bytes: Option<usize>,
+    pub policy: LimitPolicy,
+}
+
+const LIMITED_TEXT_PROPERTIES: &[&str] = &["SUMMARY", "DESCRIPTION", "LOCATION"];
+
+const TRUNCATION_MARKER: &str = "…";
+
+impl EventLimits {
+    /// Enforces the limits on the event, returns whether it is kept.
+    pub fn enforce(&self, event: &mut Event) -> bool {
+        let uid = event.get_uid().unwrap_or_default().to_string();
+
+        let long_texts: Vec<Property> = LIMITED_TEXT_PROPERTIES
+            .iter()
+            .filter_map(|name| event.properties().get(*name))
+            .filter(|property| {
+                self.max_text_bytes
+                    .is_some_and(|max| property.value().len() > max)
+            })
+            .cloned()
+            .collect();
+        let exdates = exdate_values(event);
+        let too_many_exdates = self.max_exdates.is_some_and(|max| exdates.len() > max);
+
+        if !long_texts.is_empty() || too_many_exdates {
+            if self.policy == LimitPolicy::Skip {
+                warn!(
+                    "Skipping event {}, it exceeds the text or EXDATE limits",
+                    uid
+                );
+                return false;
+            }
+            warn!(
+                "Truncating event {}, it exceeds the text or EXDATE limits",
+                uid
+            );
+            for property in long_texts {
+                let max = self.max_text_bytes.unwrap_or_default();
+                event.append_property(with_value(&property, &truncate(property.value(), max)));
+            }
+            if too_many_exdates {
+                // The oldest exceptions are the least likely to still matter
+                let keep = exdates.len() - self.max_exdates.unwrap_or_default();
+                event.remove_multi_property("EXDATE");
+                for (property, value) in exdates.into_iter().skip(keep) {
+                    event.append_multi_property(with_value(&property, &value));
+                }
+            }
+        }
+
+        if let Some(max) = self.max_event_bytes {
+            let size = Calendar::from_iter([event.clone()]).to_string().len();
+            if size > max {
+                warn!(
+                    "Skipping event {}, it has {} bytes (at most {})",
+                    uid, size, max
+                );
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Every EXDATE value with the property it came from, one property may list several.
+fn exdate_values(event: &Event) -> Vec<(Property, String)> {
+    event
+        .multi_properties()
+        .get("EXDATE")
+        .into_iter()
+        .flatten()
+        .flat_map(|property| {
+            property
+                .value()
+                .split(',')
+                .map(|value| (property.clone(), value.to_string()))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+fn with_value(property: &Property, value: &str) -> Property {
+    let mut copy = Property::new(property.key(), value);
+    for parameter in property.params().values() {
+        copy.append_parameter(parameter.clone());
+    }
+    copy
+}
+
+fn truncate(text: &str, max_bytes: usize) -> String {
+    let mut end = max_bytes.saturating_sub(TRUNCATION_MARKER.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{}", &text[..end], TRUNCATION_MARKER)
+}
+
+const CYRILLIC: &[(char, &str)] = &[
+    ('а', "a"),
+    ('б', "b"),
+    ('в', "v"),
+    ('г', "g"),
+    ('ґ', "g"),
+    ('д', "d"),
+    ('е', "e"),
+    ('є', "ye"),
+    ('ё', "yo"),
+    ('ж', "zh"),
+    ('з', "z"),
+    ('и', "i"),
+    ('і', "i"),
+    ('ї', "yi"),
+    ('й', "y"),
+    ('к', "k"),
+    ('л', "l"),
+    ('м', "m"),
+    ('н', "n"),
+    ('о', "o"),
+    ('п', "p"),
+    ('р', "r"),
+    ('с', "s"),
+    ('т', "t"),
+    ('у', "u"),
+    ('ф', "f"),
+    ('х', "kh"),
+    ('ц', "ts"),
+    ('ч', "ch"),
+    ('ш', "sh"),
+    ('щ', "shch"),
+    ('ъ', ""),
+    ('ы', "y"),
+    ('ь', ""),
+    ('э', "e"),
+    ('ю', "yu"),
+    ('я', "ya"),
+];
+
+pub fn transliterate(text: &str, transliteration: Transliteration) -> String {
+    let table = match transliteration {
+        Transliteration::Cyrillic => CYRILLIC,
+    };
+
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        let lower = c.to_lowercase().next().unwrap_or(c);
+        match table.iter().find(|(letter, _)| *letter == lower) {
+            Some((_, latin)) if lower != c => {
+                let mut latin = latin.chars();
+                result.extend(latin.next().map(|first| first.to_ascii_uppercase()));
+                result.extend(latin);
+            }
+            Some((_, latin)) => result.push_str(latin),
+            None => result.push(c),
+        }
+    }
+    result
+}
+
+/// Which X- properties of the source are uploaded, and under which name.
+///
+/// Names are matched case-insensitively, a trailing `*` matches any suffix. By default all are
+/// uploaded unchanged.
+#[derive(Debug, Clone, Default)]
+pub struct XPropertyRules {
+    /// Only these are kept, all if empty.
+    pub keep: Vec<String>,
+    pub drop: Vec<String>,
+    /// `(from, to)`, renaming to TRANSP maps Microsoft busy states to OPAQUE/TRANSPARENT.
+    pub rename: Vec<(String, String)>,
+}
+
+impl XPropertyRules {
+    pub fn apply_to_event(&self, event: &mut Event) {
+        let names: Vec<String> = event
+            .properties()
+            .keys()
+            .chain(event.multi_properties().keys())
+            .filter(|name| name.to_uppercase().starts_with("X-"))
+            .cloned()
+            .collect();
+
+        for name in names {
+            let rename = self
+                .rename
+                .iter()
+                .find(|(from, _)| matches_pattern(&name, from))
+                .map(|(_, to)| to.clone());
+            let keep = (self.keep.is_empty()
+                || self.keep.iter().any(|p| matches_pattern(&name, p)))
+                && !self.drop.iter().any(|p| matches_pattern(&name, p));
+            if keep && rename.is_none() {
+                continue;
+            }
+
+            let single = event.properties().get(&name).cloned();
+            let multi = event.multi_properties().get(&name).cloned();
+            event.remove_property(&name);
+            event.remove_multi_property(&name);
+            let Some(to) = rename.filter(|_| keep) else {
+                continue;
+            };
+
+            for property in single.into_iter().chain(multi.into_iter().flatten()) {
+                if to.eq_ignore_ascii_case("TRANSP") {
+                    // An explicit TRANSP of the source wins
+                    if event.property_value("TRANSP").is_none()
+                        && let Some(transp) = busy_status_to_transp(property.value())
+                    {
+                        event.add_property("TRANSP", transp);
+                    }
+                } else {
+                    let mut renamed = Property::new(&to, property.value());
+                    for parameter in property.params().values() {
+                        renamed.append_parameter(parameter.clone());
+                    }
+                    event.append_property(renamed);
+                }
+            }
+        }
+    }
+}
+
+fn matches_pattern(name: &str, pattern: &str) -> bool {
+    let (name, pattern) = (name.to_uppercase(), pattern.trim().to_uppercase());
+    match pattern.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => name == pattern,
+    }
+}
+
+fn busy_status_to_transp(status: &str) -> Option<&'static str> {
+    match status.trim().to_uppercase().as_str() {
+        "FREE" => Some("TRANSPARENT"),
+        "BUSY" | "OOF" | "TENTATIVE" | "WORKINGELSEWHERE" => Some("OPAQUE"),
+        _ => None,
+    }
+}
+
+/// Changes applied to every source event before it is compared and uploaded.
+#[derive(Debug, Clone, Default)]
+pub struct Transforms {
+    pub scheduling: SchedulingPolicy,
+    pub attachments: AttachmentPolicy,
+    /// Inline attachments larger than this many (decoded) bytes are dropped.
+    pub max_attachment_bytes: Option<usize>,
+    pub transliteration: Option<Transliteration>,
+    pub limits: EventLimits,
+    /// Replace DURATION and missing DTENDs with a computed DTEND.
+    pub normalize_end: bool,
+    pub x_properties: XPropertyRules,
+    /// Upload only the time blocks with this SUMMARY, for mirroring a calendar purely to
+    /// block availability.
+    pub busy_shadow: Option<String>,
+    /// Appended to DESCRIPTION, see `annotate`.
+    pub annotation: Option<String>,
+    /// Keep only events with one of these addresses as ATTENDEE or ORGANIZER, for shared
+    /// feeds of a whole team.
+    pub participants: Vec<String>,
+    /// Keep only events overlapping these hours, e.g. to trim a shift plan.
+    pub busy_hours: Option<WeeklyHours>,
+    /// Drop shorter events, e.g. zero-length markers.
+    pub min_duration: Option<TimeDelta>,
+    /// Drop longer events, e.g. banners spanning a whole semester.
+    pub max_duration: Option<TimeDelta>,
+}
+
+impl Transforms {
+    pub fn apply(&self, mut calendar: Calendar) -> Calendar {
+        let method = strip_method(&mut calendar);
+        if let Some(template) = &self.annotation {
+            annotate(&mut calendar, template);
+        }
+        calendar.components.retain_mut(|component| match component {
+            CalendarComponent::Event(event) => {
+                // Before the scheduling properties are scrubbed
+                if !self.involves_participants(event) {
+                    return false;
+                }
+                if let Some(busy_hours) = &self.busy_hours
+                    && !busy_hours.overlaps(event)
+                {
+                    return false;
+                }
+                if let Some(method) = &method {
+                    adjust_for_method(event, method);
+                }
+                self.apply_to_event(event);
+                // After apply_to_event, so a normalized DTEND counts
+                self.has_allowed_duration(event) && self.limits.enforce(event)
+            }
+            _ => true,
+        });
+        calendar
+    }
+
+    /// Whether one of the `participants` attends or organizes the event, always true
+    /// without a participant filter.
+    pub fn involves_participants(&self, event: &Event) -> bool {
+        if self.participants.is_empty() {
+            return true;
+        }
+        let scheduling = ["ORGANIZER", "ATTENDEE"];
+        let mut properties = scheduling
+            .iter()
+            .filter_map(|name| event.properties().get(*name))
+            .chain(
+                scheduling
+                    .iter()
+                    .flat_map(|name| event.multi_properties().get(*name))
+                    .flatten(),
+            );
+
+        properties.any(|property| {
+            let addresses = [
+                Some(property.value()),
+                property.params().get("EMAIL").map(|email| email.value()),
+            ];
+            addresses.into_iter().flatten().any(|address| {
+                let address = address.trim();
+                let address = address
+                    .get(..7)
+                    .filter(|scheme| scheme.eq_ignore_ascii_case("mailto:"))
+                    .map_or(address, |_| &address[7..]);
+                self.participants
+                    .iter()
+                    .any(|participant| participant.eq_ignore_ascii_case(address))
+            })
+        })
+    }
+
+    /// Whether the event lasts between `min_duration` and `max_duration`. Events whose
+    /// duration can't be determined are kept.
+    pub fn has_allowed_duration(&self, event: &Event) -> bool {
+        let Some(duration) = event_duration(event) else {
+            return true;
+        };
+        let allowed = self.min_duration.is_none_or(|min| duration >= min)
+            && self.max_duration.is_none_or(|max| duration <= max);
+        if !allowed {
+            debug!(
+                "Dropping event {} lasting {}",
+                event.get_uid().unwrap_or_default(),
+                duration
+            );
+        }
+        allowed
+    }
+
+    pub fn apply_to_event(&self, event: &mut Event) {
+        scrub_scheduling(event, self.scheduling);
+        self.filter_attachments(event);
+        self.x_properties.apply_to_event(event);
+        if self.normalize_end {
+            normalize_end(event);
+        }
+        if let Some(summary) = &self.busy_shadow {
+            *event = busy_shadow(event, summary);
+        }
+
+        if let Some(transliteration) = self.transliteration
+            && let Some(summary) = event.get_summary()
+        {
+            let summary = transliterate(summary, transliteration);
+            event.summary(&summary);
+        }
+    }
+
+    fn filter_attachments(&self, event: &mut Event) {
+        let Some(attachments) = event.multi_properties().get("ATTACH").cloned() else {
+            return;
+        };
+
+        event.remove_multi_property("ATTACH");
+        for attachment in attachments {
+            let inline = attachment
+                .params()
+                .get("ENCODING")
+                .is_some_and(|encoding| encoding.value().eq_ignore_ascii_case("BASE64"));
+            let keep = match self.attachments {
+                AttachmentPolicy::Keep => true,
+                AttachmentPolicy::UrlsOnly => !inline,
+                AttachmentPolicy::Strip => false,
+            };
+            // Base64 encodes 3 bytes in 4 characters
+            let too_large = inline
+                && self
+                    .max_attachment_bytes
+                    .is_some_and(|max| attachment.value().len() / 4 * 3 > max);
+
+            if keep && !too_large {
+                event.append_multi_property(attachment);
+            }
+        }
+    }
+}
+
+/// Appends the template to the DESCRIPTION of every event, with these placeholders:
+///
+/// - `{week}`: ISO week number of the start, `{year}` its ISO week-numbering year
+/// - `{day}` and `{days}`: position in a multi-day event, or in a run of events with the same
+///   SUMMARY on consecutive days, like "Day {day} of {days}"
+pub fn annotate(calendar: &mut Calendar, template: &str) {
+    let mut by_summary: HashMap<String, Vec<NaiveDate>> = HashMap::new();
+    for event in calendar.components.iter().filter_map(|c| c.as_event()) {
+        if let (Some(summary), Some(date)) = (event.get_summary(), start_date(event)) {
+            by_summary
+                .entry(summary.to_string())
+                .or_default()
+                .push(date);
+        }
+    }
+    for dates in by_summary.values_mut() {
+        dates.sort();
+        dates.dedup();
+    }
+
+    for component in &mut calendar.components {
+        let CalendarComponent::Event(event) = component else {
+            continue;
+        };
+        let Some(date) = start_date(event) else {
+            continue;
+        };
+
+        let (day, days) = event
+            .get_summary()
+            .and_then(|summary| by_summary.get(summary))
+            .map(|dates| run_position(dates, date))
+            .filter(|(_, days)| *days > 1)
+            .unwrap_or_else(|| (1, span_days(event, date)));
+        let week = date.iso_week();
+        let annotation = template
+            .replace("{week}", &week.week().to_string())
+            .replace("{year}", &week.year().to_string())
+            .replace("{day}", &day.to_string())
+            .replace("{days}", &days.to_string());
+
+        let description = match event.get_description() {
+            Some(description) => format!("{}\n\n{}", description, annotation),
+            None => annotation,
+        };
+        event.description(&description);
+    }
+}
+
+fn start_date(event: &Event) -> Option<NaiveDate> {
+    event.get_start().map(date_of)
+}
+
+fn date_of(date: DatePerhapsTime) -> NaiveDate {
+    match date {
+        DatePerhapsTime::Date(date) => date,
+        DatePerhapsTime::DateTime(CalendarDateTime::Utc(date_time)) => date_time.date_naive(),
+        DatePerhapsTime::DateTime(CalendarDateTime::Floating(date_time)) => date_time.date(),
+        DatePerhapsTime::DateTime(CalendarDateTime::WithTimezone { date_time, .. }) => {
+            date_time.date()
+        }
+    }
+}
+
+/// Position of `date` in its run of consecutive dates, 1-based, and the length of the run.
+fn run_position(dates: &[NaiveDate], date: NaiveDate) -> (usize, usize) {
+    let Some(index) = dates.iter().position(|d| *d == date) else {
+        return (1, 1);
+    };
+    let consecutive = |pair: &[NaiveDate]| pair[1] - pair[0] == TimeDelta::days(1);
+    let before = dates[..=index]
+        .windows(2)
+        .rev()
+        .take_while(|pair| consecutive(pair))
+        .count();
+    let after = dates[index..]
+        .windows(2)
+        .take_while(|pair| consecutive(pair))
+        .count();
+    (before + 1, before + after + 1)
+}
+
+// All-day events end on the exclusive next day, timed events on the day they end
+fn span_days(event: &Event, start: NaiveDate) -> usize {
+    let end = match event.get_end() {
+        Some(DatePerhapsTime::Date(end)) => end.pred_opt().unwrap_or(end),
+        Some(end) => date_of(end),
+        None => start,
+    };
+    usize::try_from((end - start).num_days()).unwrap_or(0) + 1
+}
+
+/// Properties that describe when an event blocks time, everything else is private.
+const SHADOW_PROPERTIES: &[&str] = &[
+    "UID",
+    "DTSTAMP",
+    "DTSTART",
+    "DTEND",
+    "DURATION",
+    "RRULE",
+    "RDATE",
+    "EXDATE",
+    "RECURRENCE-ID",
+    "LAST-MODIFIED",
+    "SEQUENCE",
+    "STATUS",
+    "TRANSP",
+];
+
+/// A copy of the event with only its times and the given SUMMARY, without alarms or any
+/// other detail.
+fn busy_shadow(event: &Event, summary: &str) -> Event {
+    let mut shadow = Event::new();
+    for name in SHADOW_PROPERTIES {
+        if let Some(property) = event.properties().get(*name) {
+            shadow.append_property(property.clone());
+        }
+        for property in event.multi_properties().get(*name).into_iter().flatten() {
+            shadow.append_multi_property(property.clone());
+        }
+    }
+    shadow.summary(summary);
+    shadow
+}
+
+/// Removes the iTIP METHOD of feeds exported from scheduling systems, CalDAV servers reject
+/// it on PUT.
+fn strip_method(calendar: &mut Calendar) -> Option<String> {
+    let index = calendar
+        .properties
+        .iter()
+        .position(|property| property.key().eq_ignore_ascii_case("METHOD"))?;
+    let method = calendar.properties.remove(index).value().to_uppercase();
+    debug!("Removed METHOD:{} from the source calendar", method);
+    Some(method)
+}
+
+/// Carries over what the METHOD said about the events, which is lost with it.
+fn adjust_for_method(event: &mut Event, method: &str) {
+    event.remove_property("METHOD");
+
+    // A cancellation message cancels the events it contains
+    if method == "CANCEL" && event.property_value("STATUS") != Some("CANCELLED") {
+        debug!(
+            "Marking event {} as cancelled",
+            event.get_uid().unwrap_or_default()
+        );
+        event.add_property("STATUS", "CANCELLED");
+    }
+
+    // RSVP asks for a reply to the invitation, which a stored event can't send
+    let Some(attendees) = event.multi_properties().get("ATTENDEE").cloned() else {
+        return;
+    };
+    if !attendees.iter().any(|a| a.params().contains_key("RSVP")) {
+        return;
+    }
+    event.remove_multi_property("ATTENDEE");
+    for attendee in attendees {
+        let mut copy = Property::new(attendee.key(), attendee.value());
+        for (name, parameter) in attendee.params() {
+            if name != "RSVP" {
+                copy.append_parameter(parameter.clone());
+            }
+        }
+        event.append_multi_property(copy);
+    }
+}
+
+fn scrub_scheduling(event: &mut Event, policy: SchedulingPolicy) {
+    match policy {
+        SchedulingPolicy::Keep => {}
+        SchedulingPolicy::Strip => {
+            event.remove_property("ORGANIZER");
+            event.remove_multi_property("ATTENDEE");
+        }
+        SchedulingPolicy::Client => {
+            if let Some(mut organizer) = event.properties().get("ORGANIZER").cloned() {
+                organizer.add_parameter("SCHEDULE-AGENT", "CLIENT");
+                event.append_property(organizer);
+            }
+            if let Some(attendees) = event.multi_properties().get("ATTENDEE").cloned() {
+                event.remove_multi_property("ATTENDEE");
+                for mut attendee in attendees {
+                    attendee.add_parameter("SCHEDULE-AGENT", "CLIENT");
+                    event.append_multi_property(attendee);
+                }
+            }
+        }
+    }
+}
+
+/// Writes a DTEND computed from DURATION, or the RFC 5545 default of one day for all-day and
+/// no time for timed events, and flags events ending before they start.
+fn normalize_end(event: &mut Event) {
+    let Some(start) = event.get_start() else {
+        return;
+    };
+    let uid = event.get_uid().unwrap_or_default().to_string();
+
+    if event.property_value("DTEND").is_none() {
+        let duration = match event.property_value("DURATION") {
+            Some(duration) => match parse_duration(duration) {
+                Some(duration) => duration,
+                None => {
+                    warn!("Event {} has an invalid DURATION: {}", uid, duration);
+                    return;
+                }
+            },
+            None if matches!(start, DatePerhapsTime::Date(_)) => TimeDelta::days(1),
+            None => TimeDelta::zero(),
+        };
+        let Some(end) = add_duration(start.clone(), duration) else {
+            warn!("Event {} has an out of range DURATION", uid);
+            return;
+        };
+        event.remove_property("DURATION");
+        event.ends(end);
+    }
+
+    if let Some(end) = event.get_end()
+        && let (Some((start_zone, start)), Some((end_zone, end))) =
+            (comparable(&start), comparable(&end))
+        && start_zone == end_zone
+        && end < start
+    {
+        warn!("Event {} ends before it starts ({} < {})", uid, end, start);
+    }
+}
+
+/// The end of an event with the duration, `None` if it lies outside of the supported dates.
+fn add_duration(start: DatePerhapsTime, duration: TimeDelta) -> Option<DatePerhapsTime> {
+    Some(match start {
+        DatePerhapsTime::Date(date) => DatePerhapsTime::Date(
+            date.checked_add_signed(TimeDelta::try_days(duration.num_days())?)?,
+        ),
+        DatePerhapsTime::DateTime(CalendarDateTime::Utc(date_time)) => {
+            CalendarDateTime::Utc(date_time.checked_add_signed(duration)?).into()
+        }
+        DatePerhapsTime::DateTime(CalendarDateTime::Floating(date_time)) => {
+            CalendarDateTime::Floating(date_time.checked_add_signed(duration)?).into()
+        }
+        // Added to the local time, so a day stays a day across DST changes
+        DatePerhapsTime::DateTime(CalendarDateTime::WithTimezone { date_time, tzid }) => {
+            CalendarDateTime::WithTimezone {
+                date_time: date_time.checked_add_signed(duration)?,
+                tzid,
+            }
+            .into()
+        }
+    })
+}
+
+/// The time between DTSTART and DTEND or the DURATION, with the RFC 5545 defaults of one
+/// day for all-day and none for timed events.
+fn event_duration(event: &Event) -> Option<TimeDelta> {
+    let start = event.get_start()?;
+    match (event.get_end(), event.property_value("DURATION")) {
+        (Some(end), _) => {
+            let ((start_zone, start), (end_zone, end)) = (comparable(&start)?, comparable(&end)?);
+            // Times in different zones can't be compared without a timezone database
+            (start_zone == end_zone).then_some(end - start)
+        }
+        (None, Some(duration)) => parse_duration(duration),
+        (None, None) if matches!(start, DatePerhapsTime::Date(_)) => Some(TimeDelta::days(1)),
+        (None, None) => Some(TimeDelta::zero()),
+    }
+}
+
+/// The local time and its timezone, if any.
+fn comparable(date: &DatePerhapsTime) -> Option<(Option<&str>, NaiveDateTime)> {
+    Some(match date {
+        DatePerhapsTime::Date(date) => (None, date.and_hms_opt(0, 0, 0)?),
+        DatePerhapsTime::DateTime(CalendarDateTime::Utc(date_time)) => {
+            (Some("UTC"), date_time.naive_utc())
+        }
+        DatePerhapsTime::DateTime(CalendarDateTime::Floating(date_time)) => (None, *date_time),
+        DatePerhapsTime::DateTime(CalendarDateTime::WithTimezone { date_time, tzid }) => {
+            (Some(tzid.as_str()), *date_time)
+        }
+    })
+}
+
+/// Parses an RFC 5545 duration like `P1W`, `P1DT2H` or `-PT15M`, `None` if it is invalid or
+/// too long to represent.
+pub fn parse_duration(value: &str) -> Option<TimeDelta> {
+    let value = value.trim();
+    let (sign, value) = match value.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let value = value.strip_prefix('P')?;
+    let (date, time) = value.split_once('T').unwrap_or((value, ""));
+
+    let mut duration = TimeDelta::zero();
+    let mut number = String::new();
+    let mut has_part = false;
+    for (part, units) in [(date, "WD"), (time, "HMS")] {
+        for c in part.chars() {
+            if c.is_ascii_digit() {
+                number.push(c);
+                continue;
+            }
+            let amount: i64 = number.parse().ok()?;
+            number.clear();
+            has_part = true;
+            let part = match c {
+                'W' if units == "WD" => TimeDelta::try_weeks(amount),
+                'D' if units == "WD" => TimeDelta::try_days(amount),
+                'H' if units == "HMS" => TimeDelta::try_hours(amount),
+                'M' if units == "HMS" => TimeDelta::try_minutes(amount),
+                'S' if units == "HMS" => TimeDelta::try_seconds(amount),
+                _ => return None,
+            };
+            duration = duration.checked_add(&part?)?;
+        }
+        if !number.is_empty() {
+            return None;
+        }
+    }
+    has_part.then_some(duration * sign)
+}
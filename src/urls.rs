@@ -0,0 +1,108 @@
+use anyhow::{Context, Result, bail};
+use log::warn;
+use reqwest::Url;
+
+/// The instance URL of `NEXTCLOUD_URL`, fixing links to the web interface or the DAV
+/// endpoint, a missing scheme and trailing slashes.
+pub fn nextcloud_url(input: &str) -> Result<String> {
+    let mut url = parse_http_url("NEXTCLOUD_URL", input)?;
+
+    let path = url.path().to_string();
+    let base_path = ["/index.php/apps/", "/apps/", "/remote.php/"]
+        .iter()
+        .find_map(|marker| path.find(marker).map(|start| &path[..start]));
+    if let Some(base_path) = base_path {
+        warn!(
+            "NEXTCLOUD_URL {} is a link into Nextcloud, using the instance URL {}{} instead. \
+             The calendar is chosen with CALENDAR_ID.",
+            input,
+            url.origin().ascii_serialization(),
+            base_path
+        );
+        url.set_path(base_path);
+    }
+    url.set_query(None);
+    url.set_fragment(None);
+
+    Ok(url.as_str().trim_end_matches('/').to_string())
+}
+
+/// The DAV collection URL of `NEXTCLOUD_CALENDAR_URL`, ending with a slash.
+pub fn calendar_collection_url(input: &str) -> Result<String> {
+    let url = parse_http_url("NEXTCLOUD_CALENDAR_URL", input)?;
+    if !url.path().contains("/remote.php/") {
+        warn!(
+            "NEXTCLOUD_CALENDAR_URL {} is not a DAV URL, copy it from the calendar's \
+             \"Copy internal link\" action",
+            input
+        );
+    }
+    // Resource URLs are built by appending to the collection
+    let url = url.as_str().trim_end_matches('/');
+    Ok(format!("{}/", url))
+}
+
+/// The calendar ID of `CALENDAR_ID`, taken from the end of a pasted calendar URL.
+pub fn calendar_id(input: &str) -> String {
+    let trimmed = input.trim().trim_matches('/');
+    let Some((_, rest)) = trimmed.split_once("/remote.php/dav/calendars/") else {
+        return trimmed.to_string();
+    };
+    // `<user>/<calendar>`, the user comes from NEXTCLOUD_USERNAME
+    let calendar_id = rest.rsplit('/').next().unwrap_or(rest).to_string();
+    warn!(
+        "CALENDAR_ID {} is a URL, using its calendar ID {} instead",
+        input, calendar_id
+    );
+    calendar_id
+}
+
+/// The URL of the source feed, with `webcal://` links turned into HTTPS.
+pub fn source_url(input: &str) -> String {
+    let trimmed = input.trim();
+    for scheme in ["webcal://", "webcals://"] {
+        if let Some(rest) = trimmed.strip_prefix(scheme) {
+            warn!("{} is a calendar app link, fetching it with HTTPS", input);
+            return format!("https://{}", rest);
+        }
+    }
+    trimmed.to_string()
+}
+
+fn parse_http_url(key: &str, input: &str) -> Result<Url> {
+    let trimmed = input.trim();
+    let with_scheme = if has_scheme(trimmed) {
+        trimmed.to_string()
+    } else {
+        warn!("{} {} has no scheme, assuming https://", key, trimmed);
+        format!("https://{}", trimmed)
+    };
+
+    let url = Url::parse(&with_scheme).with_context(|| format!("Invalid {}: {}", key, input))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        bail!(
+            "Invalid {}: {} (expected an http:// or https:// URL)",
+            key,
+            input
+        );
+    }
+    if url.host_str().is_none_or(str::is_empty) {
+        bail!("Invalid {}: {} (the host is missing)", key, input);
+    }
+    Ok(url)
+}
+
+/// Whether the input starts with a URL scheme like `https:` or `mailto:`, which `<host>:<port>`
+/// looks like as well.
+fn has_scheme(input: &str) -> bool {
+    let Some((scheme, rest)) = input.split_once(':') else {
+        return false;
+    };
+    let is_scheme = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    let port = rest.split('/').next().unwrap_or_default();
+    let is_port = !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit());
+    is_scheme && !is_port
+}
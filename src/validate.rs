@@ -0,0 +1,134 @@
+use anyhow::{Result, bail};
+
+// Content lines are folded at 75 octets, RFC 5545 section 3.1
+const MAX_LINE_OCTETS: usize = 75;
+
+/// Properties every VEVENT of an uploaded calendar must carry.
+const REQUIRED_EVENT_PROPERTIES: &[&str] = &["UID", "DTSTAMP", "DTSTART"];
+
+/// Rewrites a serialized calendar with CRLF line endings and content lines folded at 75
+/// octets on character boundaries.
+///
+/// Line breaks inside values, which the serializer leaves unescaped for unknown
+/// properties, are escaped as `\n`.
+pub fn normalize_payload(ics: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for line in ics.split("\r\n") {
+        let line = line.replace("\r\n", "\\n").replace(['\r', '\n'], "\\n");
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ if line.is_empty() => {}
+            _ => lines.push(line),
+        }
+    }
+
+    let mut normalized = String::with_capacity(ics.len() + ics.len() / MAX_LINE_OCTETS * 3);
+    for line in &lines {
+        fold(line, &mut normalized);
+    }
+    normalized
+}
+
+fn fold(line: &str, out: &mut String) {
+    let mut rest = line;
+    // Continuation lines start with a space, which counts towards their length
+    let mut limit = MAX_LINE_OCTETS;
+    while rest.len() > limit {
+        let mut end = limit;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        out.push_str(&rest[..end]);
+        out.push_str("\r\n ");
+        rest = &rest[end..];
+        limit = MAX_LINE_OCTETS - 1;
+    }
+    out.push_str(rest);
+    out.push_str("\r\n");
+}
+
+/// Checks a serialized calendar before it is uploaded, so broken events fail with the name of
+/// the offending property instead of the server's generic `400 Bad Request`.
+///
+/// Checked are CRLF line endings, folding, unescaped control characters, the syntax of
+/// property names and the mandatory properties of events.
+pub fn validate_payload(ics: &str) -> Result<()> {
+    let Some(ics) = ics.strip_suffix("\r\n") else {
+        bail!("Calendar does not end with CRLF");
+    };
+
+    let mut lines: Vec<String> = Vec::new();
+    for line in ics.split("\r\n") {
+        if line.contains(['\r', '\n']) {
+            bail!(
+                "{} contains a line break that is not escaped",
+                property_name(line)
+            );
+        }
+        if line.chars().any(|c| c.is_control() && c != '\t') {
+            bail!("{} contains a control character", property_name(line));
+        }
+        if line.len() > MAX_LINE_OCTETS {
+            bail!(
+                "{} is not folded, one of its lines has {} octets (at most {} allowed)",
+                property_name(line),
+                line.len(),
+                MAX_LINE_OCTETS
+            );
+        }
+
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            (Some(_), None) => bail!("Calendar starts with a folded line"),
+            (None, _) => lines.push(line.to_string()),
+        }
+    }
+
+    let mut components: Vec<String> = Vec::new();
+    let mut event_properties: Vec<String> = Vec::new();
+    for line in &lines {
+        let name = property_name(line);
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            bail!("Invalid property name in line: {}", line);
+        }
+        let Some((_, value)) = line.split_once(':') else {
+            bail!("{} has no value", name);
+        };
+
+        match name.to_uppercase().as_str() {
+            "BEGIN" => components.push(value.to_uppercase()),
+            "END" => {
+                if components.pop().as_deref() != Some(&value.to_uppercase()) {
+                    bail!("END:{} does not match the open component", value);
+                }
+                if value.eq_ignore_ascii_case("VEVENT") {
+                    check_event(&event_properties)?;
+                    event_properties.clear();
+                }
+            }
+            // Properties of nested components like VALARM don't count for the event
+            property if components.last().is_some_and(|c| c == "VEVENT") => {
+                event_properties.push(property.to_string());
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(component) = components.last() {
+        bail!("{} is never closed", component);
+    }
+    Ok(())
+}
+
+fn check_event(properties: &[String]) -> Result<()> {
+    for required in REQUIRED_EVENT_PROPERTIES {
+        if !properties.iter().any(|property| property == required) {
+            bail!("Event is missing the mandatory {} property", required);
+        }
+    }
+    Ok(())
+}
+
+fn property_name(line: &str) -> &str {
+    line.split([';', ':']).next().unwrap_or_default()
+}
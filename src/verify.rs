@@ -0,0 +1,91 @@
+use std::fmt;
+
+use icalendar::{Component, Event};
+
+use crate::{
+    explain,
+    nextcloud::{self, api::CalendarObject, utils::stable_hash},
+};
+
+/// Properties a server may rewrite without changing the event.
+const VOLATILE_PROPERTIES: &[&str] = &["DTSTAMP"];
+
+/// An uploaded event that didn't arrive as it was sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Discrepancy {
+    /// The server accepted the upload, but the event is not in the calendar.
+    Missing { uid: String },
+    /// The stored event differs in these properties.
+    Changed {
+        uid: String,
+        properties: Vec<String>,
+    },
+}
+
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing { uid } => write!(f, "{}: missing after upload", uid),
+            Self::Changed { uid, properties } => {
+                write!(
+                    f,
+                    "{}: stored with different {}",
+                    uid,
+                    properties.join(", ")
+                )
+            }
+        }
+    }
+}
+
+/// Properties that change whenever an event is exported or synced, not when it is edited.
+const SYNC_METADATA: &[&str] = &["DTSTAMP", "LAST-MODIFIED", "SEQUENCE", "X-SYNC-RUN"];
+
+/// Hash of all properties of an event except the [`VOLATILE_PROPERTIES`], independent of
+/// their order and folding.
+pub fn content_hash(event: &Event) -> String {
+    hash_without(event, VOLATILE_PROPERTIES)
+}
+
+/// Whether a source event and its synced copy only differ in their sync metadata.
+pub(crate) fn same_content(source_event: &Event, existing_event: &Event) -> bool {
+    hash_without(source_event, SYNC_METADATA) == hash_without(existing_event, SYNC_METADATA)
+}
+
+fn hash_without(event: &Event, ignored: &[&str]) -> String {
+    let content: String = explain::property_values(event)
+        .into_iter()
+        .filter(|(name, _)| !ignored.contains(&name.as_str()))
+        .map(|(name, value)| format!("{}:{}\n", name, value))
+        .collect();
+    stable_hash(&content)
+}
+
+/// Compares the uploaded events with the calendar fetched after the sync.
+pub fn verify_uploads(
+    uploaded: &[Event],
+    nextcloud_objects: Vec<CalendarObject>,
+) -> Vec<Discrepancy> {
+    let nextcloud_events =
+        nextcloud::api::extract_events(nextcloud::api::merge_objects(nextcloud_objects), false);
+
+    let mut discrepancies: Vec<Discrepancy> = uploaded
+        .iter()
+        .filter_map(|event| {
+            let uid = event.get_uid()?.to_string();
+            let Some(stored) = nextcloud_events.get(&uid) else {
+                return Some(Discrepancy::Missing { uid });
+            };
+            if content_hash(event) == content_hash(stored) {
+                return None;
+            }
+            let properties = explain::property_changes(event, stored)
+                .into_iter()
+                .map(|(name, _, _)| name)
+                .collect();
+            Some(Discrepancy::Changed { uid, properties })
+        })
+        .collect();
+    discrepancies.sort_by_key(|discrepancy| discrepancy.to_string());
+    discrepancies
+}
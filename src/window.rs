@@ -0,0 +1,82 @@
+use chrono::{DateTime, Days, Utc};
+use icalendar::{Calendar, CalendarComponent, CalendarDateTime, Component, DatePerhapsTime, Event};
+
+use crate::nextcloud::api::{CalendarObject, TimeRange};
+
+/// Limits a sync to the events from `past_days` before until `future_days` after now.
+///
+/// Events outside the window are neither uploaded nor deleted.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SyncWindow {
+    pub past_days: Option<u64>,
+    pub future_days: Option<u64>,
+}
+
+impl SyncWindow {
+    pub fn time_range(&self, now: DateTime<Utc>) -> TimeRange {
+        TimeRange {
+            start: self
+                .past_days
+                .and_then(|days| now.checked_sub_days(Days::new(days))),
+            end: self
+                .future_days
+                .and_then(|days| now.checked_add_days(Days::new(days))),
+        }
+    }
+}
+
+/// Drops all events not overlapping the range.
+pub fn filter_calendar(mut calendar: Calendar, range: &TimeRange) -> Calendar {
+    calendar.components.retain(|component| match component {
+        CalendarComponent::Event(event) => overlaps(event, range),
+        _ => true,
+    });
+    calendar
+}
+
+/// Drops all resources without an event overlapping the range, for servers whose
+/// calendar-query can't filter by time range.
+pub fn filter_objects(mut objects: Vec<CalendarObject>, range: &TimeRange) -> Vec<CalendarObject> {
+    objects.retain(|object| {
+        object
+            .calendar
+            .components
+            .iter()
+            .filter_map(|component| component.as_event())
+            .any(|event| overlaps(event, range))
+    });
+    objects
+}
+
+pub(crate) fn overlaps(event: &Event, range: &TimeRange) -> bool {
+    let Some(start) = event.get_start().and_then(to_utc) else {
+        return true;
+    };
+    let starts_before_end = range.end.is_none_or(|end| start < end);
+
+    // Instances of recurring events can lie anywhere after the first one
+    if event.property_value("RRULE").is_some() {
+        return starts_before_end;
+    }
+
+    // As in a CalDAV time-range, an event ending when the range starts is outside of it,
+    // an event without a duration is inside if it starts with the range
+    let end = event.get_end().and_then(to_utc).filter(|end| *end > start);
+    starts_before_end
+        && range.start.is_none_or(|range_start| match end {
+            Some(end) => end > range_start,
+            None => start >= range_start,
+        })
+}
+
+/// Converts to UTC, times with a timezone are treated as UTC which is precise enough for a
+/// window measured in days.
+pub(crate) fn to_utc(date: DatePerhapsTime) -> Option<DateTime<Utc>> {
+    let naive = match date {
+        DatePerhapsTime::DateTime(CalendarDateTime::Utc(date_time)) => return Some(date_time),
+        DatePerhapsTime::DateTime(CalendarDateTime::Floating(date_time)) => date_time,
+        DatePerhapsTime::DateTime(CalendarDateTime::WithTimezone { date_time, .. }) => date_time,
+        DatePerhapsTime::Date(date) => date.and_hms_opt(0, 0, 0)?,
+    };
+    Some(naive.and_utc())
+}
@@ -0,0 +1,74 @@
+#![cfg(feature = "blocking")]
+
+use icalendar::Calendar;
+use nextcloud_ics_sync::{
+    SyncOptions, blocking,
+    http::Client,
+    nextcloud::api::TimeRange,
+    source::{IcsSource, SourceRequest},
+    test_util::MockNextcloud,
+};
+
+const USERNAME: &str = "alice";
+const PASSWORD: &str = "app-password";
+const CALENDAR_ID: &str = "work";
+
+#[test]
+fn syncs_without_an_async_runtime() {
+    // Only the mock server needs a runtime, the sync itself is called from plain code
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let nextcloud = runtime.block_on(async {
+        let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+        nextcloud
+            .mock_calendar_query(CALENDAR_ID, &Calendar::new())
+            .await;
+        nextcloud.mock_uploads(CALENDAR_ID).await;
+        nextcloud
+            .mock_feed(
+                "/feed.ics",
+                "text/calendar",
+                "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VEVENT\r\nUID:meeting\r\n\
+                 SUMMARY:Meeting\r\nDTSTART:20240101T100000Z\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n",
+            )
+            .await;
+        nextcloud
+    });
+
+    let client = Client::new();
+    let calendar_url = nextcloud.calendar_url(CALENDAR_ID);
+    let objects = blocking::get_calendar_objects(
+        &client,
+        &calendar_url,
+        USERNAME,
+        PASSWORD,
+        TimeRange::default(),
+    )
+    .expect("calendar should be fetched");
+
+    let source = IcsSource {
+        request: SourceRequest {
+            url: format!("{}/feed.ics", nextcloud.url()),
+            username: None,
+            password: None,
+            bearer_token: None,
+            headers: Vec::new(),
+            oauth: None,
+        },
+        compat_modes: Vec::new(),
+    };
+    let source = blocking::fetch_calendar(&client, &source).expect("source should be fetched");
+    assert_eq!(source.components.len(), 1);
+    let report = blocking::sync_calendar(
+        &client,
+        USERNAME,
+        PASSWORD,
+        &calendar_url,
+        source,
+        objects,
+        &SyncOptions::default(),
+    )
+    .expect("sync should succeed");
+
+    assert_eq!(report.uploaded, 1);
+    assert_eq!(runtime.block_on(nextcloud.received("PUT")).len(), 1);
+}
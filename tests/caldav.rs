@@ -0,0 +1,1339 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::TimeDelta;
+use icalendar::{Alarm, Calendar, Component, Event, EventLike};
+use nextcloud_ics_sync::{
+    Deletions, SyncOptions, UploadBatching, delete_synced_events,
+    http::Client,
+    journal::{Action, Journal},
+    migrate_synced_events,
+    nextcloud::{
+        self,
+        api::{CalendarMetadata, CalendarObject, TimeRange, Transfer},
+    },
+    ownership::PropertyOwnership,
+    sync_calendar,
+    syncback::SyncBack,
+    test_util::MockNextcloud,
+};
+use sha2::{Digest, Sha256};
+
+const USERNAME: &str = "alice";
+const PASSWORD: &str = "app-password";
+const CALENDAR_ID: &str = "work";
+
+/// Name of the resource a new event is uploaded to.
+fn resource_name(uid: &str) -> String {
+    let hex: String = Sha256::digest(uid.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("{}.ics", hex)
+}
+
+fn event(uid: &str, last_modified: &str, synced: bool) -> Event {
+    let mut event = Event::new();
+    event
+        .uid(uid)
+        .summary(uid)
+        .add_property("DTSTART", "20240101T100000Z")
+        .add_property("LAST-MODIFIED", last_modified);
+    if synced {
+        event.add_property("X-SYNCED", "TRUE");
+    }
+    event.done()
+}
+
+async fn fetch_calendar(client: &Client, nextcloud: &MockNextcloud) -> Vec<CalendarObject> {
+    nextcloud::api::get_calendar_objects(
+        client,
+        &nextcloud.calendar_url(CALENDAR_ID),
+        USERNAME,
+        PASSWORD,
+        TimeRange::default(),
+    )
+    .await
+    .expect("calendar should be fetched")
+}
+
+#[tokio::test]
+async fn propfind_lists_calendar_ids() {
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+    nextcloud.mock_calendar_list(&["personal", "work"]).await;
+
+    let ids =
+        nextcloud::api::get_calendar_ids(&Client::new(), &nextcloud.url(), USERNAME, PASSWORD)
+            .await
+            .expect("calendar ids should be listed");
+
+    assert_eq!(ids, vec!["personal", "work"]);
+}
+
+#[tokio::test]
+async fn propfind_parses_any_namespace_prefix() {
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+    let home = format!("/remote.php/dav/calendars/{}/", USERNAME);
+    nextcloud
+        .mock_calendar_home(format!(
+            r#"<?xml version="1.0"?>
+<multistatus xmlns="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav" xmlns:N="http://nextcloud.com/ns">
+  <response>
+    <href>{home}personal/</href>
+    <propstat><prop><resourcetype><collection/><C:calendar/></resourcetype></prop><status>HTTP/1.1 200 OK</status></propstat>
+  </response>
+  <D:response xmlns:D="DAV:">
+    <D:href>{home}work/</D:href>
+    <D:propstat><D:prop><D:resourcetype><D:collection/><C:calendar/></D:resourcetype></D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat>
+  </D:response>
+  <response>
+    <href>{home}trash/</href>
+    <propstat><prop><resourcetype><collection/><N:deleted-calendar/></resourcetype></prop><status>HTTP/1.1 200 OK</status></propstat>
+  </response>
+</multistatus>"#
+        ))
+        .await;
+
+    let ids =
+        nextcloud::api::get_calendar_ids(&Client::new(), &nextcloud.url(), USERNAME, PASSWORD)
+            .await
+            .expect("calendar ids should be listed");
+
+    assert_eq!(ids, vec!["personal", "work"]);
+}
+
+#[tokio::test]
+async fn sync_puts_new_and_deletes_stale_events() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+
+    let existing: Calendar = [
+        event("stale", "20240101T000000Z", true),
+        event("manual", "20240101T000000Z", false),
+    ]
+    .into_iter()
+    .collect();
+    nextcloud.mock_calendar_query(CALENDAR_ID, &existing).await;
+    nextcloud.mock_uploads(CALENDAR_ID).await;
+    nextcloud.mock_deletes(CALENDAR_ID).await;
+
+    let source: Calendar = [event("new", "20240101T000000Z", false)]
+        .into_iter()
+        .collect();
+
+    let report = sync_calendar(
+        &client,
+        USERNAME,
+        PASSWORD,
+        &nextcloud.calendar_url(CALENDAR_ID),
+        source,
+        fetch_calendar(&client, &nextcloud).await,
+        &SyncOptions::default(),
+    )
+    .await
+    .expect("sync should succeed");
+
+    assert_eq!(report.uploaded, 1);
+    assert_eq!(report.deleted, 1);
+    assert_eq!(
+        nextcloud.received_paths("PUT").await,
+        vec![format!(
+            "{}{}",
+            nextcloud.calendar_path(CALENDAR_ID),
+            resource_name("new")
+        )]
+    );
+    assert_eq!(
+        nextcloud.received_paths("DELETE").await,
+        vec![format!("{}stale.ics", nextcloud.calendar_path(CALENDAR_ID))]
+    );
+}
+
+#[tokio::test]
+async fn sync_updates_events_at_their_existing_href() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+
+    let data = Calendar::new()
+        .push(event("moved", "20240101T000000Z", true))
+        .done()
+        .to_string();
+    nextcloud
+        .mock_report(
+            CALENDAR_ID,
+            format!(
+                r#"<?xml version="1.0"?>
+<d:multistatus xmlns:d="DAV:" xmlns:cal="urn:ietf:params:xml:ns:caldav">
+  <d:response>
+    <d:href>{}4F2A-random-name.ics</d:href>
+    <d:propstat><d:prop><cal:calendar-data>{}</cal:calendar-data></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat>
+  </d:response>
+</d:multistatus>"#,
+                nextcloud.calendar_path(CALENDAR_ID),
+                data
+            ),
+        )
+        .await;
+    nextcloud.mock_uploads(CALENDAR_ID).await;
+
+    let source: Calendar = [event("moved", "20240202T000000Z", false)]
+        .into_iter()
+        .collect();
+
+    sync_calendar(
+        &client,
+        USERNAME,
+        PASSWORD,
+        &nextcloud.calendar_url(CALENDAR_ID),
+        source,
+        fetch_calendar(&client, &nextcloud).await,
+        &SyncOptions::default(),
+    )
+    .await
+    .expect("sync should succeed");
+
+    assert_eq!(
+        nextcloud.received_paths("PUT").await,
+        vec![format!(
+            "{}4F2A-random-name.ics",
+            nextcloud.calendar_path(CALENDAR_ID)
+        )]
+    );
+}
+
+#[tokio::test]
+async fn updates_bump_sequence_above_existing_copy() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+
+    let mut existing_event = event("meeting", "20240101T000000Z", true);
+    existing_event.sequence(3);
+    let existing: Calendar = [existing_event].into_iter().collect();
+    nextcloud.mock_calendar_query(CALENDAR_ID, &existing).await;
+    nextcloud.mock_uploads(CALENDAR_ID).await;
+
+    let mut source_event = event("meeting", "20240202T000000Z", false);
+    source_event.sequence(0);
+    let source: Calendar = [source_event].into_iter().collect();
+
+    sync_calendar(
+        &client,
+        USERNAME,
+        PASSWORD,
+        &nextcloud.calendar_url(CALENDAR_ID),
+        source,
+        fetch_calendar(&client, &nextcloud).await,
+        &SyncOptions::default(),
+    )
+    .await
+    .expect("sync should succeed");
+
+    let uploads = nextcloud.received("PUT").await;
+    assert_eq!(uploads.len(), 1);
+    assert!(String::from_utf8_lossy(&uploads[0].body).contains("SEQUENCE:4"));
+}
+
+#[tokio::test]
+async fn new_events_with_reserved_uid_characters_get_safe_names() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+    nextcloud.mock_uploads(CALENDAR_ID).await;
+
+    let source: Calendar = [
+        event("plain-uid_1.0", "20240101T000000Z", false),
+        event("lecture 1/2 (Äpfel)", "20240101T000000Z", false),
+    ]
+    .into_iter()
+    .collect();
+
+    sync_calendar(
+        &client,
+        USERNAME,
+        PASSWORD,
+        &nextcloud.calendar_url(CALENDAR_ID),
+        source,
+        Vec::new(),
+        &SyncOptions::default(),
+    )
+    .await
+    .expect("sync should succeed");
+
+    let paths = nextcloud.received_paths("PUT").await;
+    let calendar_path = nextcloud.calendar_path(CALENDAR_ID);
+    let names: Vec<&str> = paths
+        .iter()
+        .map(|path| path.strip_prefix(&calendar_path).unwrap())
+        .collect();
+    assert_eq!(names.len(), 2);
+    assert!(names.iter().all(|name| {
+        name.strip_suffix(".ics")
+            .is_some_and(|hash| hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+    }));
+
+    // The UID is uploaded as it is
+    let uploads = nextcloud.received("PUT").await;
+    assert!(
+        uploads
+            .iter()
+            .any(|upload| String::from_utf8_lossy(&upload.body).contains("UID:lecture 1/2 (Äpfel)"))
+    );
+}
+
+#[tokio::test]
+async fn uids_differing_only_in_reserved_characters_are_kept_apart() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+    nextcloud.mock_uploads(CALENDAR_ID).await;
+
+    let source: Calendar = [
+        event("a/b", "20240101T000000Z", false),
+        event("a-b", "20240101T000000Z", false),
+        event("a%2Fb", "20240101T000000Z", false),
+    ]
+    .into_iter()
+    .collect();
+
+    let report = sync_calendar(
+        &client,
+        USERNAME,
+        PASSWORD,
+        &nextcloud.calendar_url(CALENDAR_ID),
+        source,
+        Vec::new(),
+        &SyncOptions::default(),
+    )
+    .await
+    .expect("sync should succeed");
+
+    assert_eq!(report.uploaded, 3);
+    let mut paths = nextcloud.received_paths("PUT").await;
+    paths.sort();
+    paths.dedup();
+    assert_eq!(paths.len(), 3);
+}
+
+#[tokio::test]
+async fn sync_skips_unchanged_events() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+
+    let existing: Calendar = [event("unchanged", "20240101T000000Z", true)]
+        .into_iter()
+        .collect();
+    nextcloud.mock_calendar_query(CALENDAR_ID, &existing).await;
+    nextcloud.mock_uploads(CALENDAR_ID).await;
+
+    let source: Calendar = [event("unchanged", "20240101T000000Z", false)]
+        .into_iter()
+        .collect();
+
+    let report = sync_calendar(
+        &client,
+        USERNAME,
+        PASSWORD,
+        &nextcloud.calendar_url(CALENDAR_ID),
+        source,
+        fetch_calendar(&client, &nextcloud).await,
+        &SyncOptions::default(),
+    )
+    .await
+    .expect("sync should succeed");
+
+    assert_eq!(report.uploaded, 0);
+    assert_eq!(report.unchanged, 1);
+    assert!(nextcloud.received("PUT").await.is_empty());
+}
+
+#[tokio::test]
+async fn delete_removes_only_synced_events() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+
+    let existing: Calendar = [
+        event("synced", "20240101T000000Z", true),
+        event("manual", "20240101T000000Z", false),
+    ]
+    .into_iter()
+    .collect();
+    nextcloud.mock_calendar_query(CALENDAR_ID, &existing).await;
+    nextcloud.mock_deletes(CALENDAR_ID).await;
+
+    let event_index = nextcloud::api::get_event_index(
+        &client,
+        &nextcloud.calendar_url(CALENDAR_ID),
+        USERNAME,
+        PASSWORD,
+    )
+    .await
+    .expect("event index should be fetched");
+
+    delete_synced_events(
+        &client,
+        event_index,
+        &nextcloud.calendar_url(CALENDAR_ID),
+        USERNAME,
+        PASSWORD,
+    )
+    .await
+    .expect("deletion should succeed");
+
+    assert_eq!(
+        nextcloud.received_paths("DELETE").await,
+        vec![format!(
+            "{}synced.ics",
+            nextcloud.calendar_path(CALENDAR_ID)
+        )]
+    );
+}
+
+#[tokio::test]
+async fn explain_lists_changed_properties() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+
+    let existing: Calendar = [
+        event("changed", "20240101T000000Z", true),
+        event("unchanged", "20240101T000000Z", true),
+    ]
+    .into_iter()
+    .collect();
+    nextcloud.mock_calendar_query(CALENDAR_ID, &existing).await;
+    nextcloud.mock_uploads(CALENDAR_ID).await;
+
+    let source: Calendar = [
+        event("changed", "20240202T000000Z", false),
+        event("unchanged", "20240101T000000Z", false),
+    ]
+    .into_iter()
+    .collect();
+
+    let options = SyncOptions {
+        explain: true,
+        ..Default::default()
+    };
+    let report = sync_calendar(
+        &client,
+        USERNAME,
+        PASSWORD,
+        &nextcloud.calendar_url(CALENDAR_ID),
+        source,
+        fetch_calendar(&client, &nextcloud).await,
+        &options,
+    )
+    .await
+    .expect("sync should succeed");
+
+    assert_eq!(
+        report.explanations,
+        vec![
+            "UPLOAD changed: LAST-MODIFIED changed from 20240101T000000Z to 20240202T000000Z\n    \
+             LAST-MODIFIED: 20240101T000000Z -> 20240202T000000Z",
+            "SKIP unchanged: LAST-MODIFIED 20240101T000000Z is unchanged",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn explain_gives_the_reason_events_are_skipped() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+
+    let mut owned = event("owned", "20240101T000000Z", true);
+    owned.description("Notes taken in Nextcloud");
+    let existing: Calendar = [
+        event("unchanged", "20240101T000000Z", true),
+        event("jitter", "20240101T000000Z", true),
+        owned,
+        event("resumed", "20240101T000000Z", true),
+    ]
+    .into_iter()
+    .collect();
+    nextcloud.mock_calendar_query(CALENDAR_ID, &existing).await;
+
+    let mut resumed = event("resumed", "20240303T000000Z", false);
+    resumed.summary("Moved to Friday");
+    let source: Calendar = [
+        event("unchanged", "20240101T000000Z", false),
+        event("jitter", "20240101T000030Z", false),
+        event("owned", "20240303T000000Z", false),
+        resumed,
+    ]
+    .into_iter()
+    .collect();
+
+    let dir = std::env::temp_dir().join(format!("nis-explain-{}", std::process::id()));
+    let interrupted = Journal::start(&dir).expect("journal should be created");
+    interrupted.record("resumed", Action::Uploaded).unwrap();
+    let journal = Journal::resume(&dir, interrupted.run_id()).expect("journal should be resumed");
+
+    let options = SyncOptions {
+        explain: true,
+        timestamp_tolerance: Duration::from_secs(60),
+        ownership: PropertyOwnership {
+            owned: vec!["SUMMARY".to_string(), "DTSTART".to_string()],
+            target_owned: Vec::new(),
+        },
+        journal: Some(Arc::new(journal)),
+        ..Default::default()
+    };
+    let report = sync_calendar(
+        &client,
+        USERNAME,
+        PASSWORD,
+        &nextcloud.calendar_url(CALENDAR_ID),
+        source,
+        fetch_calendar(&client, &nextcloud).await,
+        &options,
+    )
+    .await
+    .expect("sync should succeed");
+
+    assert_eq!(report.uploaded, 0);
+    assert_eq!(
+        report.explanations,
+        vec![
+            "SKIP jitter: LAST-MODIFIED 20240101T000030Z is within the tolerance of \
+             20240101T000000Z and the content is the same",
+            "SKIP owned: the properties owned by the source are unchanged",
+            "SKIP resumed: already uploaded by an earlier attempt of this run",
+            "SKIP unchanged: LAST-MODIFIED 20240101T000000Z is unchanged",
+        ]
+    );
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn calendar_query_sends_time_range_filter() {
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+    nextcloud
+        .mock_calendar_query(CALENDAR_ID, &Calendar::new())
+        .await;
+
+    let start = "2024-01-01T00:00:00Z".parse().unwrap();
+    nextcloud::api::get_calendar_objects(
+        &Client::new(),
+        &nextcloud.calendar_url(CALENDAR_ID),
+        USERNAME,
+        PASSWORD,
+        TimeRange {
+            start: Some(start),
+            end: None,
+        },
+    )
+    .await
+    .expect("calendar should be fetched");
+
+    let requests = nextcloud.received("REPORT").await;
+    let body = String::from_utf8_lossy(&requests[0].body);
+    assert!(body.contains(r#"<c:time-range start="20240101T000000Z"/>"#));
+}
+
+#[tokio::test]
+async fn resumed_run_skips_confirmed_uploads() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+    nextcloud
+        .mock_calendar_query(CALENDAR_ID, &Calendar::new())
+        .await;
+    nextcloud.mock_uploads(CALENDAR_ID).await;
+
+    let dir = std::env::temp_dir().join(format!("nis-journal-{}", std::process::id()));
+    let interrupted = Journal::start(&dir).expect("journal should be created");
+    interrupted
+        .record("done", Action::Uploaded)
+        .expect("journal should be written");
+    let journal = Journal::resume(&dir, interrupted.run_id()).expect("journal should be resumed");
+
+    let source: Calendar = [
+        event("done", "20240101T000000Z", false),
+        event("pending", "20240101T000000Z", false),
+    ]
+    .into_iter()
+    .collect();
+
+    let options = SyncOptions {
+        journal: Some(Arc::new(journal)),
+        ..Default::default()
+    };
+    sync_calendar(
+        &client,
+        USERNAME,
+        PASSWORD,
+        &nextcloud.calendar_url(CALENDAR_ID),
+        source,
+        fetch_calendar(&client, &nextcloud).await,
+        &options,
+    )
+    .await
+    .expect("sync should succeed");
+
+    let puts = nextcloud.received("PUT").await;
+    assert_eq!(puts.len(), 1);
+    assert!(puts[0].url.path().ends_with(&resource_name("pending")));
+    let body = String::from_utf8_lossy(&puts[0].body);
+    assert!(body.contains(&format!("X-SYNC-RUN:{}", interrupted.run_id())));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn unconfirmed_actions_are_reconciled_with_the_calendar_on_resume() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+    nextcloud.mock_uploads(CALENDAR_ID).await;
+    nextcloud.mock_deletes(CALENDAR_ID).await;
+
+    let dir = std::env::temp_dir().join(format!("nis-wal-{}", std::process::id()));
+    let interrupted = Journal::start(&dir).expect("journal should be created");
+    for (uid, action) in [
+        ("stored", Action::Uploaded),
+        ("lost", Action::Uploaded),
+        ("gone", Action::Deleted),
+    ] {
+        interrupted
+            .begin(uid, action)
+            .expect("journal should be written");
+    }
+
+    // The upload of `stored` reached the server before the run was killed
+    let mut stored = event("stored", "20240101T000000Z", true);
+    stored.add_property("X-SYNC-RUN", interrupted.run_id());
+    let existing: Calendar = [stored].into_iter().collect();
+    nextcloud.mock_calendar_query(CALENDAR_ID, &existing).await;
+
+    let journal = Journal::resume(&dir, interrupted.run_id()).expect("journal should be resumed");
+    assert_eq!(journal.unconfirmed().count(), 3);
+
+    let source: Calendar = [
+        event("stored", "20240101T000000Z", false),
+        event("lost", "20240101T000000Z", false),
+    ]
+    .into_iter()
+    .collect();
+    let options = SyncOptions {
+        journal: Some(Arc::new(journal)),
+        ..Default::default()
+    };
+    sync_calendar(
+        &client,
+        USERNAME,
+        PASSWORD,
+        &nextcloud.calendar_url(CALENDAR_ID),
+        source,
+        fetch_calendar(&client, &nextcloud).await,
+        &options,
+    )
+    .await
+    .expect("sync should succeed");
+
+    assert_eq!(
+        nextcloud.received_paths("PUT").await,
+        [format!(
+            "{}{}",
+            nextcloud.calendar_path(CALENDAR_ID),
+            resource_name("lost")
+        )]
+    );
+    assert!(nextcloud.received("DELETE").await.is_empty());
+
+    let journal = Journal::resume(&dir, interrupted.run_id()).expect("journal should be resumed");
+    assert!(journal.is_confirmed("stored", Action::Uploaded));
+    assert!(journal.is_confirmed("lost", Action::Uploaded));
+    assert_eq!(
+        journal.unconfirmed().collect::<Vec<_>>(),
+        [("gone", Action::Deleted)]
+    );
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn every_target_calendar_has_its_own_journal() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+    for calendar_id in [CALENDAR_ID, "family"] {
+        nextcloud
+            .mock_calendar_query(calendar_id, &Calendar::new())
+            .await;
+        nextcloud.mock_uploads(calendar_id).await;
+    }
+
+    let dir = std::env::temp_dir().join(format!("nis-targets-{}", std::process::id()));
+    let journal = Journal::start(&dir).expect("journal should be created");
+    let family_url = nextcloud.calendar_url("family");
+    let family_journal = journal
+        .for_target(&family_url)
+        .expect("target journal should be created");
+    let run_id = journal.run_id().to_string();
+
+    // Only the upload to the family calendar went through before the run was killed
+    let source: Calendar = [event("meeting", "20240101T000000Z", false)]
+        .into_iter()
+        .collect();
+    let options = SyncOptions {
+        journal: Some(Arc::new(family_journal)),
+        ..Default::default()
+    };
+    sync_calendar(
+        &client,
+        USERNAME,
+        PASSWORD,
+        &family_url,
+        source,
+        Vec::new(),
+        &options,
+    )
+    .await
+    .expect("sync should succeed");
+
+    let journal = Journal::resume(&dir, &run_id).expect("journal should be resumed");
+    assert!(!journal.is_confirmed("meeting", Action::Uploaded));
+    let family_journal = journal
+        .for_target(&family_url)
+        .expect("target journal should be resumed");
+    assert!(family_journal.is_confirmed("meeting", Action::Uploaded));
+    assert_eq!(family_journal.run_id(), run_id);
+    assert_eq!(
+        family_journal.path(),
+        dir.join(format!("{}.family.jsonl", run_id))
+    );
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn disabled_deletions_keep_stale_events() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+
+    let existing: Calendar = [event("stale", "20240101T000000Z", true)]
+        .into_iter()
+        .collect();
+    nextcloud.mock_calendar_query(CALENDAR_ID, &existing).await;
+    nextcloud.mock_deletes(CALENDAR_ID).await;
+
+    let options = SyncOptions {
+        deletions: Deletions::Disabled,
+        ..Default::default()
+    };
+    let report = sync_calendar(
+        &client,
+        USERNAME,
+        PASSWORD,
+        &nextcloud.calendar_url(CALENDAR_ID),
+        Calendar::new(),
+        fetch_calendar(&client, &nextcloud).await,
+        &options,
+    )
+    .await
+    .expect("sync should succeed");
+
+    assert_eq!(report.deleted, 0);
+    assert!(nextcloud.received("DELETE").await.is_empty());
+}
+
+#[tokio::test]
+async fn archive_moves_stale_events() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+
+    let existing: Calendar = [event("stale", "20240101T000000Z", true)]
+        .into_iter()
+        .collect();
+    nextcloud.mock_calendar_query(CALENDAR_ID, &existing).await;
+    nextcloud.mock_moves(CALENDAR_ID).await;
+
+    let options = SyncOptions {
+        archive_calendar_url: Some(nextcloud.calendar_url("archive")),
+        ..Default::default()
+    };
+    let report = sync_calendar(
+        &client,
+        USERNAME,
+        PASSWORD,
+        &nextcloud.calendar_url(CALENDAR_ID),
+        Calendar::new(),
+        fetch_calendar(&client, &nextcloud).await,
+        &options,
+    )
+    .await
+    .expect("sync should succeed");
+
+    assert_eq!(report.deleted, 1);
+    let moves = nextcloud.received("MOVE").await;
+    assert_eq!(moves.len(), 1);
+    assert_eq!(
+        moves[0].headers["Destination"],
+        format!("{}stale.ics", nextcloud.calendar_url("archive"))
+    );
+}
+
+#[tokio::test]
+async fn migration_moves_or_copies_synced_events() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+
+    let existing: Calendar = [
+        event("synced", "20240101T000000Z", true),
+        event("own", "20240101T000000Z", false),
+    ]
+    .into_iter()
+    .collect();
+    nextcloud.mock_calendar_query(CALENDAR_ID, &existing).await;
+    nextcloud.mock_moves(CALENDAR_ID).await;
+    nextcloud.mock_copies(CALENDAR_ID).await;
+
+    for (transfer, method) in [(Transfer::Move, "MOVE"), (Transfer::Copy, "COPY")] {
+        let count = migrate_synced_events(
+            &client,
+            fetch_calendar(&client, &nextcloud).await,
+            &nextcloud.calendar_url(CALENDAR_ID),
+            &nextcloud.calendar_url("renamed"),
+            USERNAME,
+            PASSWORD,
+            transfer,
+        )
+        .await
+        .expect("migration should succeed");
+        assert_eq!(count, 1);
+
+        let requests = nextcloud.received(method).await;
+        assert_eq!(requests.len(), 1, "{}", method);
+        assert_eq!(
+            requests[0].headers["Destination"],
+            format!("{}synced.ics", nextcloud.calendar_url("renamed"))
+        );
+        assert_eq!(requests[0].headers["Overwrite"], "T");
+    }
+}
+
+#[tokio::test]
+async fn batched_uploads_send_every_event() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+    nextcloud.mock_uploads(CALENDAR_ID).await;
+
+    let source: Calendar = ["a", "b", "c"]
+        .into_iter()
+        .map(|uid| event(uid, "20240101T000000Z", false))
+        .collect();
+    let options = SyncOptions {
+        upload_batching: Some(UploadBatching {
+            size: 2,
+            pause: Duration::ZERO,
+        }),
+        ..Default::default()
+    };
+
+    let report = sync_calendar(
+        &client,
+        USERNAME,
+        PASSWORD,
+        &nextcloud.calendar_url(CALENDAR_ID),
+        source,
+        Vec::new(),
+        &options,
+    )
+    .await
+    .expect("sync should succeed");
+
+    assert_eq!(report.uploaded, 3);
+    assert_eq!(nextcloud.received("PUT").await.len(), 3);
+}
+
+#[tokio::test]
+async fn renamed_uids_are_updated_in_place() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+
+    let mut existing_event = event("export-1", "20240101T000000Z", true);
+    existing_event.summary("Lecture");
+    let existing: Calendar = [existing_event].into_iter().collect();
+    nextcloud.mock_calendar_query(CALENDAR_ID, &existing).await;
+    nextcloud.mock_uploads(CALENDAR_ID).await;
+    nextcloud.mock_deletes(CALENDAR_ID).await;
+
+    let mut source_event = event("export-2", "20240202T000000Z", false);
+    source_event.summary("Lecture");
+    let source: Calendar = [source_event].into_iter().collect();
+    let options = SyncOptions {
+        relink_renamed_uids: true,
+        ..Default::default()
+    };
+
+    let report = sync_calendar(
+        &client,
+        USERNAME,
+        PASSWORD,
+        &nextcloud.calendar_url(CALENDAR_ID),
+        source,
+        fetch_calendar(&client, &nextcloud).await,
+        &options,
+    )
+    .await
+    .expect("sync should succeed");
+
+    assert_eq!((report.uploaded, report.deleted), (1, 0));
+    assert!(nextcloud.received("DELETE").await.is_empty());
+    let uploads = nextcloud.received("PUT").await;
+    assert!(String::from_utf8_lossy(&uploads[0].body).contains("UID:export-1"));
+}
+
+#[tokio::test]
+async fn uploads_exceeding_the_quota_abort_before_any_put() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+    nextcloud.mock_uploads(CALENDAR_ID).await;
+    nextcloud
+        .mock_calendar_properties(
+            CALENDAR_ID,
+            r#"<?xml version="1.0"?>
+<d:multistatus xmlns:d="DAV:"><d:response><d:href>/</d:href><d:propstat><d:prop>
+<d:quota-available-bytes>100</d:quota-available-bytes><d:quota-used-bytes>900</d:quota-used-bytes>
+</d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response></d:multistatus>"#,
+        )
+        .await;
+
+    let source: Calendar = ["a", "b"]
+        .into_iter()
+        .map(|uid| event(uid, "20240101T000000Z", false))
+        .collect();
+    let options = SyncOptions {
+        check_quota: true,
+        ..Default::default()
+    };
+
+    let error = sync_calendar(
+        &client,
+        USERNAME,
+        PASSWORD,
+        &nextcloud.calendar_url(CALENDAR_ID),
+        source,
+        Vec::new(),
+        &options,
+    )
+    .await
+    .expect_err("sync should abort");
+
+    assert!(error.to_string().contains("quota"), "{}", error);
+    assert!(nextcloud.received("PUT").await.is_empty());
+}
+
+#[tokio::test]
+async fn maintenance_mode_fails_before_syncing() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+    nextcloud.mock_status(CALENDAR_ID, true).await;
+
+    let error = nextcloud::status::ensure_available(
+        &client,
+        &nextcloud.url(),
+        &nextcloud.calendar_url(CALENDAR_ID),
+        USERNAME,
+        PASSWORD,
+    )
+    .await
+    .expect_err("probe should fail");
+
+    assert!(error.to_string().contains("maintenance"), "{}", error);
+}
+
+#[tokio::test]
+async fn available_instance_passes_the_probe() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+    nextcloud.mock_status(CALENDAR_ID, false).await;
+
+    nextcloud::status::ensure_available(
+        &client,
+        &nextcloud.url(),
+        &nextcloud.calendar_url(CALENDAR_ID),
+        USERNAME,
+        PASSWORD,
+    )
+    .await
+    .expect("probe should pass");
+}
+
+#[tokio::test]
+async fn old_servers_get_workarounds_and_unsupported_ones_fail() {
+    let client = Client::new();
+    let old = MockNextcloud::start(USERNAME, PASSWORD).await;
+    old.mock_capabilities(23, 0, 12).await;
+    let unsupported = MockNextcloud::start(USERNAME, PASSWORD).await;
+    unsupported.mock_capabilities(19, 0, 0).await;
+
+    let compatibility = nextcloud::status::negotiate(&client, &old.url(), USERNAME, PASSWORD)
+        .await
+        .expect("version should be supported");
+    assert!(!compatibility.time_range_filter);
+    assert_eq!(compatibility.version.map(|v| v.major), Some(23));
+
+    let error = nextcloud::status::negotiate(&client, &unsupported.url(), USERNAME, PASSWORD)
+        .await
+        .expect_err("version should be rejected");
+    assert!(error.to_string().contains("not supported"), "{}", error);
+}
+
+#[tokio::test]
+async fn read_only_calendars_are_refused_with_writable_alternatives() {
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+    let home = format!("/remote.php/dav/calendars/{}/", USERNAME);
+    let privileges = |names: &[&str]| -> String {
+        names
+            .iter()
+            .map(|name| format!("<d:privilege><d:{}/></d:privilege>", name))
+            .collect()
+    };
+    nextcloud
+        .mock_calendar_home(format!(
+            r#"<?xml version="1.0"?>
+<d:multistatus xmlns:d="DAV:" xmlns:cal="urn:ietf:params:xml:ns:caldav" xmlns:cs="http://calendarserver.org/ns/">
+  <d:response><d:href>{home}personal/</d:href><d:propstat><d:prop>
+    <d:resourcetype><d:collection/><cal:calendar/></d:resourcetype>
+    <d:current-user-privilege-set>{}</d:current-user-privilege-set>
+  </d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>
+  <d:response><d:href>{home}{CALENDAR_ID}/</d:href><d:propstat><d:prop>
+    <d:resourcetype><d:collection/><cal:calendar/></d:resourcetype>
+    <d:current-user-privilege-set>{}</d:current-user-privilege-set>
+  </d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>
+  <d:response><d:href>{home}holidays/</d:href><d:propstat><d:prop>
+    <d:resourcetype><d:collection/><cs:subscribed/></d:resourcetype>
+  </d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>
+</d:multistatus>"#,
+            privileges(&["read", "write", "write-content"]),
+            privileges(&["read"]),
+        ))
+        .await;
+
+    let error = nextcloud::api::ensure_writable(
+        &Client::new(),
+        &nextcloud.url(),
+        &nextcloud.calendar_url(CALENDAR_ID),
+        USERNAME,
+        PASSWORD,
+    )
+    .await
+    .expect_err("read-only calendar should be refused");
+
+    let message = error.to_string();
+    assert!(message.contains("read-only"), "{}", message);
+    assert!(message.contains("[personal]"), "{}", message);
+}
+
+#[tokio::test]
+async fn verification_reports_events_the_server_did_not_store() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+    // The mock keeps serving the old calendar, as if it dropped every upload
+    let stored: Calendar = [event("kept", "20240101T000000Z", true)]
+        .into_iter()
+        .collect();
+    nextcloud.mock_calendar_query(CALENDAR_ID, &stored).await;
+    nextcloud.mock_uploads(CALENDAR_ID).await;
+
+    let source: Calendar = [
+        event("kept", "20240201T000000Z", false),
+        event("new", "20240201T000000Z", false),
+    ]
+    .into_iter()
+    .collect();
+    let options = SyncOptions {
+        verify: true,
+        ..Default::default()
+    };
+
+    let error = sync_calendar(
+        &client,
+        USERNAME,
+        PASSWORD,
+        &nextcloud.calendar_url(CALENDAR_ID),
+        source,
+        fetch_calendar(&client, &nextcloud).await,
+        &options,
+    )
+    .await
+    .expect_err("verification should fail");
+
+    assert_eq!(nextcloud.received("PUT").await.len(), 2);
+    assert!(
+        error
+            .to_string()
+            .contains("2 of 2 uploaded events failed verification"),
+        "{}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn timestamp_jitter_within_the_tolerance_is_ignored_for_unchanged_content() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+    let stored: Calendar = ["jitter", "edited"]
+        .into_iter()
+        .map(|uid| event(uid, "20240101T100000Z", true))
+        .collect();
+    nextcloud.mock_calendar_query(CALENDAR_ID, &stored).await;
+    nextcloud.mock_uploads(CALENDAR_ID).await;
+
+    let mut edited = event("edited", "20240101T100002Z", false);
+    edited.summary("edited (moved)");
+    let source: Calendar = [event("jitter", "20240101T100002Z", false), edited]
+        .into_iter()
+        .collect();
+    let options = SyncOptions {
+        timestamp_tolerance: Duration::from_secs(5),
+        ..Default::default()
+    };
+
+    let report = sync_calendar(
+        &client,
+        USERNAME,
+        PASSWORD,
+        &nextcloud.calendar_url(CALENDAR_ID),
+        source,
+        fetch_calendar(&client, &nextcloud).await,
+        &options,
+    )
+    .await
+    .expect("sync should succeed");
+
+    assert_eq!((report.uploaded, report.unchanged), (1, 1));
+    let uploads = nextcloud.received("PUT").await;
+    assert!(String::from_utf8_lossy(&uploads[0].body).contains("UID:edited"));
+}
+
+#[tokio::test]
+async fn target_owned_properties_are_kept_and_ignored_by_the_diff() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+
+    let mut renamed = event("renamed", "20240101T000000Z", true);
+    renamed
+        .add_property("CATEGORIES", "Personal")
+        .alarm(Alarm::display("Soon", -TimeDelta::minutes(15)));
+    let mut recategorized = event("recategorized", "20240101T000000Z", true);
+    recategorized.add_property("CATEGORIES", "Personal");
+    let existing: Calendar = [renamed.done(), recategorized.done()].into_iter().collect();
+    nextcloud.mock_calendar_query(CALENDAR_ID, &existing).await;
+    nextcloud.mock_uploads(CALENDAR_ID).await;
+
+    let mut renamed = event("renamed", "20240202T000000Z", false);
+    renamed
+        .summary("Renamed")
+        .add_property("CATEGORIES", "Work");
+    let mut recategorized = event("recategorized", "20240202T000000Z", false);
+    recategorized.add_property("CATEGORIES", "Work");
+    let source: Calendar = [renamed.done(), recategorized.done()].into_iter().collect();
+
+    let options = SyncOptions {
+        ownership: PropertyOwnership {
+            target_owned: vec!["CATEGORIES".to_string(), "VALARM".to_string()],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let report = sync_calendar(
+        &client,
+        USERNAME,
+        PASSWORD,
+        &nextcloud.calendar_url(CALENDAR_ID),
+        source,
+        fetch_calendar(&client, &nextcloud).await,
+        &options,
+    )
+    .await
+    .expect("sync should succeed");
+
+    assert_eq!(report.uploaded, 1);
+    let uploads = nextcloud.received("PUT").await;
+    let body = String::from_utf8_lossy(&uploads[0].body);
+    assert!(body.contains("SUMMARY:Renamed"), "{}", body);
+    assert!(body.contains("CATEGORIES:Personal"), "{}", body);
+    assert!(body.contains("BEGIN:VALARM"), "{}", body);
+    assert!(!body.contains("Work"), "{}", body);
+}
+
+#[tokio::test]
+async fn synced_back_alarms_are_restored_when_an_event_is_uploaded_anew() {
+    let client = Client::new();
+    let path = std::env::temp_dir().join(format!("nis-sync-back-{}.ics", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let ownership = PropertyOwnership {
+        target_owned: vec!["VALARM".to_string()],
+        ..Default::default()
+    };
+    let source = || -> Calendar {
+        [event("meeting", "20240101T000000Z", false)]
+            .into_iter()
+            .collect()
+    };
+
+    // The alarm was added in Nextcloud, the next sync writes it back
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+    let mut alarmed = event("meeting", "20240101T000000Z", true);
+    alarmed.alarm(Alarm::display("Soon", -TimeDelta::minutes(15)));
+    let existing: Calendar = [alarmed.done()].into_iter().collect();
+    nextcloud.mock_calendar_query(CALENDAR_ID, &existing).await;
+    let options = SyncOptions {
+        ownership: ownership.clone(),
+        sync_back: Some(Arc::new(
+            SyncBack::load(&path, vec!["VALARM".to_string()]).unwrap(),
+        )),
+        ..Default::default()
+    };
+    sync_calendar(
+        &client,
+        USERNAME,
+        PASSWORD,
+        &nextcloud.calendar_url(CALENDAR_ID),
+        source(),
+        fetch_calendar(&client, &nextcloud).await,
+        &options,
+    )
+    .await
+    .expect("sync should succeed");
+    assert!(nextcloud.received("PUT").await.is_empty());
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    // The copy is gone, e.g. after moving to another calendar, and is uploaded with the alarm
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+    nextcloud
+        .mock_calendar_query(CALENDAR_ID, &Calendar::new())
+        .await;
+    nextcloud.mock_uploads(CALENDAR_ID).await;
+    let options = SyncOptions {
+        ownership,
+        sync_back: Some(Arc::new(
+            SyncBack::load(&path, vec!["VALARM".to_string()]).unwrap(),
+        )),
+        ..Default::default()
+    };
+    sync_calendar(
+        &client,
+        USERNAME,
+        PASSWORD,
+        &nextcloud.calendar_url(CALENDAR_ID),
+        source(),
+        fetch_calendar(&client, &nextcloud).await,
+        &options,
+    )
+    .await
+    .expect("sync should succeed");
+
+    let uploads = nextcloud.received("PUT").await;
+    let body = String::from_utf8_lossy(&uploads[0].body);
+    assert!(body.contains("UID:meeting"), "{}", body);
+    assert!(body.contains("BEGIN:VALARM"), "{}", body);
+    assert!(body.contains("DESCRIPTION:Soon"), "{}", body);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn duplicates_of_other_events_are_suppressed_and_journaled() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+
+    let mut invitation = event("invitation", "20240101T000000Z", false);
+    invitation.summary("Review");
+    let existing: Calendar = [invitation.done()].into_iter().collect();
+    nextcloud.mock_calendar_query(CALENDAR_ID, &existing).await;
+    nextcloud.mock_uploads(CALENDAR_ID).await;
+
+    let mut team = event("team-standup", "20240101T000000Z", false);
+    team.summary("Standup");
+    let mut personal = event("personal-standup", "20240101T000000Z", false);
+    personal.summary("  standup ").location("Room 2");
+    let mut review = event("team-review", "20240101T000000Z", false);
+    review.summary("REVIEW");
+    let source: Calendar = [team.done(), personal.done(), review.done()]
+        .into_iter()
+        .collect();
+
+    let dir = std::env::temp_dir().join(format!("nis-dedupe-{}", std::process::id()));
+    let journal = Arc::new(Journal::start(&dir).expect("journal should be created"));
+    let options = SyncOptions {
+        dedupe: true,
+        journal: Some(journal.clone()),
+        ..Default::default()
+    };
+    let report = sync_calendar(
+        &client,
+        USERNAME,
+        PASSWORD,
+        &nextcloud.calendar_url(CALENDAR_ID),
+        source,
+        fetch_calendar(&client, &nextcloud).await,
+        &options,
+    )
+    .await
+    .expect("sync should succeed");
+
+    assert_eq!(report.uploaded, 1);
+    let puts = nextcloud.received("PUT").await;
+    assert!(
+        puts[0]
+            .url
+            .path()
+            .ends_with(&resource_name("personal-standup"))
+    );
+
+    let entries = std::fs::read_to_string(journal.path()).unwrap();
+    assert!(
+        entries
+            .contains(r#"{"uid":"team-review","action":"suppressed","duplicate_of":"invitation"}"#)
+    );
+    assert!(entries.contains(
+        r#"{"uid":"team-standup","action":"suppressed","duplicate_of":"personal-standup"}"#
+    ));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn uploads_carry_the_configured_calendar_metadata() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+    nextcloud
+        .mock_calendar_query(CALENDAR_ID, &Calendar::new())
+        .await;
+    nextcloud.mock_uploads(CALENDAR_ID).await;
+
+    let source: Calendar = [event("meeting", "20240101T000000Z", false)]
+        .into_iter()
+        .collect();
+    let options = SyncOptions {
+        calendar_metadata: CalendarMetadata {
+            prodid: "-//Example Corp//Room Sync//EN".to_string(),
+            calendar_name: Some("Rooms".to_string()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    sync_calendar(
+        &client,
+        USERNAME,
+        PASSWORD,
+        &nextcloud.calendar_url(CALENDAR_ID),
+        source,
+        fetch_calendar(&client, &nextcloud).await,
+        &options,
+    )
+    .await
+    .expect("sync should succeed");
+
+    let uploads = nextcloud.received("PUT").await;
+    let body = String::from_utf8_lossy(&uploads[0].body);
+    assert!(body.contains("PRODID:-//Example Corp//Room Sync//EN\r\n"));
+    assert!(body.contains("CALSCALE:GREGORIAN\r\n"));
+    assert!(body.contains("X-WR-CALNAME:Rooms\r\n"));
+    assert!(!body.contains("ICALENDAR-RS"));
+    assert!(CalendarMetadata::DEFAULT_PRODID.starts_with("-//nextcloud-ics-sync//"));
+}
@@ -0,0 +1,91 @@
+use icalendar::{Calendar, CalendarComponent, Component};
+use nextcloud_ics_sync::compat::{self, CompatMode};
+
+fn load_fixture(name: &str) -> Calendar {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    let content = std::fs::read_to_string(&path).expect("fixture should exist");
+    content.parse().expect("fixture should parse")
+}
+
+#[test]
+fn google_quirks_drop_cancelled_instances() {
+    let calendar = compat::postprocess(
+        load_fixture("google_secret_feed.ics"),
+        &[CompatMode::Google],
+    );
+
+    let events: Vec<_> = calendar
+        .components
+        .iter()
+        .filter_map(CalendarComponent::as_event)
+        .collect();
+
+    assert_eq!(events.len(), 2);
+    assert!(
+        events
+            .iter()
+            .all(|event| event.get_recurrence_id().is_none())
+    );
+}
+
+#[test]
+fn google_quirks_collapse_exdates() {
+    let calendar = compat::postprocess(
+        load_fixture("google_secret_feed.ics"),
+        &[CompatMode::Google],
+    );
+
+    let series = calendar
+        .components
+        .iter()
+        .filter_map(CalendarComponent::as_event)
+        .find(|event| event.get_uid() == Some("weekly-sync@google.com"))
+        .expect("series should be kept");
+
+    let exdates = &series.multi_properties()["EXDATE"];
+    assert_eq!(exdates.len(), 1);
+    assert_eq!(
+        exdates[0].value(),
+        "20240115T100000,20240122T100000,20240129T100000"
+    );
+    assert_eq!(exdates[0].params()["TZID"].value(), "Europe/Berlin");
+}
+
+#[test]
+fn outlook_quirks_map_windows_timezones() {
+    let feed = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:1\r\nDTSTART;TZID=W. Europe Standard Time:20240101T100000\r\nDESCRIPTION:wrapped\r\nwithout folding\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+
+    let text = compat::preprocess(feed, &[CompatMode::Outlook]);
+
+    assert!(text.contains("DTSTART;TZID=Europe/Berlin:20240101T100000"));
+    assert!(text.contains("DESCRIPTION:wrappedwithout folding"));
+}
+
+#[test]
+fn outlook_quirks_only_map_tzid_parameters() {
+    let feed = [
+        "BEGIN:VCALENDAR",
+        "BEGIN:VTIMEZONE",
+        "TZID:Pacific Standard Time",
+        "END:VTIMEZONE",
+        "BEGIN:VEVENT",
+        "UID:1",
+        r#"DTSTART;X-NOTE="a:b;c";TZID="Pacific Standard Time":20240101T100000"#,
+        "DTEND;tzid=W. Europe Standard Time;VALUE=DATE-TIME:20240101T110000",
+        "EXDATE;TZID=Eastern Standard Time;TZID=Tokyo Standard Time:20240108T100000",
+        "DESCRIPTION:Set TZID=W. Europe Standard Time in Outlook",
+        "LOCATION;TZID=Mars Standard Time:Olympus Mons",
+        "END:VEVENT",
+        "END:VCALENDAR",
+    ]
+    .join("\r\n");
+
+    let text = compat::preprocess(&feed, &[CompatMode::Outlook]);
+
+    assert!(text.contains("TZID:America/Los_Angeles\r\n"));
+    assert!(text.contains(r#"DTSTART;X-NOTE="a:b;c";TZID=America/Los_Angeles:20240101T100000"#));
+    assert!(text.contains("DTEND;tzid=Europe/Berlin;VALUE=DATE-TIME:20240101T110000"));
+    assert!(text.contains("EXDATE;TZID=America/New_York;TZID=Asia/Tokyo:20240108T100000"));
+    assert!(text.contains("DESCRIPTION:Set TZID=W. Europe Standard Time in Outlook"));
+    assert!(text.contains("LOCATION;TZID=Mars Standard Time:Olympus Mons"));
+}
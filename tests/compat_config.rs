@@ -0,0 +1,17 @@
+use nextcloud_ics_sync::{compat::CompatMode, config};
+
+// Changes the environment of the process, so it is the only test of its binary
+#[test]
+fn empty_compat_modes_are_ignored() {
+    unsafe { std::env::set_var("ICS_COMPAT", "") };
+    assert_eq!(config::load_ics_compat().unwrap(), []);
+
+    unsafe { std::env::set_var("ICS_COMPAT", " outlook, ,google,") };
+    assert_eq!(
+        config::load_ics_compat().unwrap(),
+        [CompatMode::Outlook, CompatMode::Google]
+    );
+
+    unsafe { std::env::set_var("ICS_COMPAT", "outlook,yahoo") };
+    assert!(config::load_ics_compat().is_err());
+}
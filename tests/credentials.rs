@@ -0,0 +1,59 @@
+use std::fs;
+
+use nextcloud_ics_sync::{
+    http::Client,
+    nextcloud::credentials::{self, PasswordStore},
+    test_util::MockNextcloud,
+};
+
+#[tokio::test]
+async fn app_password_is_rotated() {
+    let nextcloud = MockNextcloud::start("alice", "old-password").await;
+    nextcloud.mock_app_password_rotation("new-password").await;
+
+    let new_password =
+        credentials::rotate_app_password(&Client::new(), &nextcloud.url(), "alice", "old-password")
+            .await
+            .expect("password should be rotated");
+    assert_eq!(new_password, "new-password");
+    assert!(
+        credentials::rotate_app_password(&Client::new(), &nextcloud.url(), "alice", "wrong")
+            .await
+            .is_err()
+    );
+}
+
+#[test]
+fn new_password_replaces_the_configured_one() {
+    let dir = std::env::temp_dir().join(format!("nis-credentials-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let missing = dir.join("missing.env");
+    let env_file = dir.join("config.env");
+    fs::write(
+        &env_file,
+        "NEXTCLOUD_USERNAME=alice\nexport NEXTCLOUD_PASSWORD=old-password\nCALENDAR_ID=work\n",
+    )
+    .unwrap();
+
+    let store = PasswordStore::find(None, &[missing.clone(), env_file.clone()]).unwrap();
+    assert_eq!(store, PasswordStore::EnvFile(env_file.clone()));
+    store.store("new-password").unwrap();
+    assert_eq!(
+        fs::read_to_string(&env_file).unwrap(),
+        "NEXTCLOUD_USERNAME=alice\nexport NEXTCLOUD_PASSWORD=new-password\nCALENDAR_ID=work\n"
+    );
+
+    // A password file wins, it is what the password is read from
+    let password_file = dir.join("password");
+    fs::write(&password_file, "old-password\n").unwrap();
+    let store = PasswordStore::find(Some(&password_file), &[env_file]).unwrap();
+    store.store("newer-password").unwrap();
+    assert_eq!(
+        fs::read_to_string(&password_file).unwrap(),
+        "newer-password\n"
+    );
+
+    assert!(PasswordStore::find(None, &[missing]).is_err());
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,65 @@
+use chrono::{NaiveDate, TimeZone, Utc};
+use icalendar::{Calendar, Component, Event, EventLike, Property};
+use nextcloud_ics_sync::{
+    digest::{format_digest, upcoming_events},
+    nextcloud::api::CalendarObject,
+};
+
+fn object(event: Event) -> CalendarObject {
+    CalendarObject {
+        href: format!("{}.ics", event.get_uid().unwrap()),
+        etag: None,
+        calendar: Calendar::new().push(event).done(),
+    }
+}
+
+#[test]
+fn digest_lists_upcoming_synced_events_by_day() {
+    let now = Utc.with_ymd_and_hms(2024, 3, 4, 8, 0, 0).unwrap();
+    let objects = [
+        object(
+            Event::new()
+                .uid("seminar")
+                .summary("Seminar")
+                .location("Room 2")
+                .append_property(
+                    Property::new("DTSTART", "20240306T140000")
+                        .add_parameter("TZID", "Europe/Berlin")
+                        .done(),
+                )
+                .add_property("X-SYNCED", "TRUE")
+                .done(),
+        ),
+        object(
+            Event::new()
+                .uid("holiday")
+                .summary("Holiday")
+                .starts(NaiveDate::from_ymd_opt(2024, 3, 5).unwrap())
+                .add_property("X-SYNCED", "TRUE")
+                .done(),
+        ),
+        object(
+            Event::new()
+                .uid("later")
+                .summary("Exam")
+                .starts(NaiveDate::from_ymd_opt(2024, 3, 20).unwrap())
+                .add_property("X-SYNCED", "TRUE")
+                .done(),
+        ),
+        object(
+            Event::new()
+                .uid("manual")
+                .summary("Dentist")
+                .starts(NaiveDate::from_ymd_opt(2024, 3, 5).unwrap())
+                .done(),
+        ),
+    ];
+
+    let events = upcoming_events(&objects, now, 7);
+    let uids: Vec<_> = events.iter().filter_map(|event| event.get_uid()).collect();
+    assert_eq!(uids, ["holiday", "seminar"]);
+
+    let digest = format_digest(&events, 7);
+    assert!(digest.contains("Tuesday, 2024-03-05\n  all day Holiday\n"));
+    assert!(digest.contains("Wednesday, 2024-03-06\n  14:00   Seminar (Room 2)\n"));
+}
@@ -0,0 +1,48 @@
+use icalendar::{Calendar, Component, Event};
+use nextcloud_ics_sync::{
+    http::Client,
+    nextcloud::{self, api::TimeRange},
+    test_util::MockNextcloud,
+};
+
+const USERNAME: &str = "alice";
+const PASSWORD: &str = "app-password";
+const CALENDAR_ID: &str = "work";
+
+#[tokio::test]
+async fn digest_challenges_are_answered_and_reused() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start_digest(USERNAME, PASSWORD).await;
+    let stored = Calendar::new()
+        .push(Event::new().uid("a").summary("a").done())
+        .done();
+    nextcloud.mock_calendar_query(CALENDAR_ID, &stored).await;
+    client.use_digest_auth(&nextcloud.url()).unwrap();
+
+    assert_eq!(fetch(&client, &nextcloud).await, 1);
+    assert_eq!(fetch(&client, &nextcloud).await, 1);
+
+    // Only the first request is challenged, the password is never sent
+    let requests = nextcloud.received("REPORT").await;
+    assert_eq!(requests.len(), 3);
+    assert!(!requests[0].headers.contains_key("Authorization"));
+    assert!(requests[1..].iter().all(|request| {
+        request.headers["Authorization"]
+            .to_str()
+            .unwrap()
+            .starts_with("Digest ")
+    }));
+}
+
+async fn fetch(client: &Client, nextcloud: &MockNextcloud) -> usize {
+    nextcloud::api::get_calendar_objects(
+        client,
+        &nextcloud.calendar_url(CALENDAR_ID),
+        USERNAME,
+        PASSWORD,
+        TimeRange::default(),
+    )
+    .await
+    .expect("calendar should be fetched")
+    .len()
+}
@@ -0,0 +1,29 @@
+use std::net::SocketAddr;
+
+use nextcloud_ics_sync::dns::IpFamily;
+
+#[test]
+fn ip_family_filters_and_orders_addresses() {
+    let addresses: Vec<SocketAddr> = ["[2001:db8::1]:0", "192.0.2.1:0", "[2001:db8::2]:0"]
+        .iter()
+        .map(|address| address.parse().unwrap())
+        .collect();
+    let families = |family: IpFamily| -> Vec<bool> {
+        family
+            .apply(addresses.clone())
+            .iter()
+            .map(SocketAddr::is_ipv4)
+            .collect()
+    };
+
+    assert_eq!(families(IpFamily::Auto), [false, true, false]);
+    assert_eq!(families(IpFamily::PreferIpv4), [true, false, false]);
+    assert_eq!(families(IpFamily::PreferIpv6), [false, false, true]);
+    assert_eq!(families(IpFamily::Ipv4Only), [true]);
+    assert_eq!(families(IpFamily::Ipv6Only), [false, false]);
+    assert_eq!(
+        "Prefer-IPv6".parse::<IpFamily>().unwrap(),
+        IpFamily::PreferIpv6
+    );
+    assert!("dual".parse::<IpFamily>().is_err());
+}
@@ -0,0 +1,70 @@
+use std::sync::Arc;
+
+use icalendar::{Calendar, Component, Event};
+use nextcloud_ics_sync::{
+    SyncOptions,
+    config::Config,
+    http::Client,
+    journal::{Action, Journal},
+    progress::SyncEngine,
+    test_util::MockNextcloud,
+};
+
+// Changes the environment of the process, so it is the only test of its binary
+#[tokio::test]
+async fn configured_sync_runs_from_the_source_to_the_calendar() {
+    let dir = std::env::temp_dir().join(format!("nis-engine-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let nextcloud = MockNextcloud::start("alice", "app-password").await;
+    nextcloud.mock_status("work", false).await;
+    nextcloud.mock_capabilities(28, 0, 1).await;
+    nextcloud.mock_calendar_list(&["work"]).await;
+    nextcloud.mock_uploads("work").await;
+    nextcloud.mock_deletes("work").await;
+    nextcloud
+        .mock_feed(
+            "/feed.ics",
+            "text/calendar",
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VEVENT\r\nUID:lecture\r\n\
+             SUMMARY:Lecture\r\nDTSTART:20240304T090000Z\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n",
+        )
+        .await;
+    let stale = Event::new()
+        .uid("cancelled")
+        .summary("Cancelled")
+        .add_property("DTSTART", "20240305T090000Z")
+        .add_property("X-SYNCED", "true")
+        .done();
+    nextcloud
+        .mock_calendar_query("work", &Calendar::from([stale]))
+        .await;
+
+    for (key, value) in [
+        ("NEXTCLOUD_URL", nextcloud.url()),
+        ("NEXTCLOUD_USERNAME", "alice".to_string()),
+        ("NEXTCLOUD_PASSWORD", "app-password".to_string()),
+        ("CALENDAR_ID", "work".to_string()),
+        ("ICS_URL", format!("{}/feed.ics", nextcloud.url())),
+    ] {
+        unsafe { std::env::set_var(key, value) };
+    }
+    let config = Config::from_env().expect("configuration should load");
+
+    let journal = Arc::new(Journal::start(&dir).unwrap());
+    let options = SyncOptions {
+        journal: Some(journal.clone()),
+        ..Default::default()
+    };
+    let report = SyncEngine::from_config(Client::new(), &config, options)
+        .run(&config)
+        .await
+        .expect("sync should succeed");
+
+    assert_eq!((report.uploaded, report.deleted), (1, 1));
+    assert_eq!(nextcloud.received("PUT").await.len(), 1);
+    let resumed = Journal::resume(&dir, journal.run_id()).unwrap();
+    assert!(resumed.is_confirmed("lecture", Action::Uploaded));
+    assert!(resumed.is_confirmed("cancelled", Action::Deleted));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
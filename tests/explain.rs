@@ -0,0 +1,40 @@
+use icalendar::{Component, Event, EventLike, Property};
+use nextcloud_ics_sync::explain::unified_diff;
+
+#[test]
+fn unified_diff_shows_escaping_and_timezone_changes() {
+    let existing = Event::new()
+        .uid("seminar")
+        .location("Room 1, Building A")
+        .append_property(
+            Property::new("DTSTART", "20240306T140000")
+                .add_parameter("TZID", "Europe/Berlin")
+                .done(),
+        )
+        .add_property("DTSTAMP", "20240101T000000Z")
+        .done();
+    let source = Event::new()
+        .uid("seminar")
+        .location("Room 1; Building A")
+        .append_property(
+            Property::new("DTSTART", "20240306T140000")
+                .add_parameter("TZID", "Europe/London")
+                .done(),
+        )
+        .add_property("DTSTAMP", "20240202T000000Z")
+        .done();
+
+    assert_eq!(
+        unified_diff(&existing, &source).lines().collect::<Vec<_>>(),
+        [
+            "--- nextcloud",
+            "+++ source",
+            "@@ -1,3 +1,3 @@",
+            "-DTSTART;TZID=Europe/Berlin:20240306T140000",
+            r"-LOCATION:Room 1\, Building A",
+            "+DTSTART;TZID=Europe/London:20240306T140000",
+            r"+LOCATION:Room 1\; Building A",
+            " UID:seminar",
+        ]
+    );
+}
@@ -0,0 +1,172 @@
+use chrono::{DateTime, TimeZone, Utc};
+use icalendar::Component;
+use nextcloud_ics_sync::{
+    freebusy::{self, BusyPeriod, BusyType},
+    http::Client,
+    nextcloud::{api::get_free_busy, scheduling::query_free_busy},
+    test_util::MockNextcloud,
+};
+
+const USERNAME: &str = "alice";
+const PASSWORD: &str = "app-password";
+
+fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 3, 4, hour, minute, 0).unwrap()
+}
+
+fn busy(start: DateTime<Utc>, end: DateTime<Utc>, kind: BusyType) -> BusyPeriod {
+    BusyPeriod { start, end, kind }
+}
+
+#[tokio::test]
+async fn busy_times_are_requested_from_the_scheduling_outbox() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+    nextcloud
+        .mock_free_busy(&[
+            (
+                "bob@example.com",
+                &[
+                    "FREEBUSY;FBTYPE=BUSY:20240304T090000Z/20240304T100000Z,20240304T140000Z/PT30M",
+                    "FREEBUSY;FBTYPE=FREE:20240304T100000Z/20240304T120000Z",
+                ],
+            ),
+            (
+                "carol@example.com",
+                &["FREEBUSY;FBTYPE=BUSY-TENTATIVE:20240304T110000Z/20240304T120000Z"],
+            ),
+        ])
+        .await;
+
+    let attendees = vec![
+        "carol@example.com".to_string(),
+        "bob@example.com".to_string(),
+    ];
+    let busy_times = query_free_busy(
+        &client,
+        &nextcloud.url(),
+        USERNAME,
+        PASSWORD,
+        "alice@example.com",
+        &attendees,
+        at(0, 0),
+        at(23, 0),
+    )
+    .await
+    .expect("free/busy should be queried");
+
+    assert_eq!(
+        busy_times,
+        [
+            (
+                "carol@example.com".to_string(),
+                vec![busy(at(11, 0), at(12, 0), BusyType::Tentative)]
+            ),
+            (
+                "bob@example.com".to_string(),
+                vec![
+                    busy(at(9, 0), at(10, 0), BusyType::Busy),
+                    busy(at(14, 0), at(14, 30), BusyType::Busy),
+                ]
+            ),
+        ]
+    );
+
+    let requests = nextcloud.received("POST").await;
+    let body = String::from_utf8_lossy(&requests[0].body);
+    assert!(body.contains("METHOD:REQUEST\r\n"));
+    assert!(body.contains("ORGANIZER:mailto:alice@example.com\r\n"));
+    assert!(body.contains("ATTENDEE:mailto:bob@example.com\r\n"));
+    assert!(body.contains("DTSTART:20240304T000000Z\r\n"));
+}
+
+#[tokio::test]
+async fn busy_times_of_a_calendar_are_queried_with_a_report() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+    nextcloud
+        .mock_free_busy_query(
+            "work",
+            &[
+                "FREEBUSY;FBTYPE=BUSY-UNAVAILABLE:20240304T080000Z/PT1H",
+                "FREEBUSY:20240304T120000Z/20240304T130000Z",
+            ],
+        )
+        .await;
+
+    let periods = get_free_busy(
+        &client,
+        &nextcloud.calendar_url("work"),
+        USERNAME,
+        PASSWORD,
+        at(0, 0),
+        at(23, 0),
+    )
+    .await
+    .expect("free/busy should be queried");
+
+    assert_eq!(
+        periods,
+        [
+            busy(at(8, 0), at(9, 0), BusyType::Unavailable),
+            busy(at(12, 0), at(13, 0), BusyType::Busy),
+        ]
+    );
+    assert_eq!(periods[0].kind.to_string(), "BUSY-UNAVAILABLE");
+
+    let requests = nextcloud.received("REPORT").await;
+    let body = String::from_utf8_lossy(&requests[0].body);
+    assert!(body.contains("<c:free-busy-query"));
+    assert!(body.contains(r#"<c:time-range start="20240304T000000Z" end="20240304T230000Z"/>"#));
+}
+
+#[test]
+fn overlapping_busy_times_are_combined_into_blocks() {
+    let busy_times = vec![
+        (
+            "bob@example.com".to_string(),
+            vec![
+                busy(at(9, 0), at(10, 0), BusyType::Busy),
+                busy(at(14, 0), at(15, 0), BusyType::Tentative),
+            ],
+        ),
+        (
+            "alice@example.com".to_string(),
+            vec![busy(at(9, 30), at(11, 0), BusyType::Tentative)],
+        ),
+    ];
+
+    let blocks = freebusy::combine(&busy_times);
+    assert_eq!(blocks.len(), 2);
+    assert_eq!((blocks[0].start, blocks[0].end), (at(9, 0), at(11, 0)));
+    assert_eq!(
+        blocks[0].attendees,
+        ["alice@example.com", "bob@example.com"]
+    );
+    assert!(!blocks[0].tentative);
+    assert!(blocks[1].tentative);
+
+    let calendar = freebusy::availability_calendar(&blocks, "Team busy");
+    let events: Vec<_> = calendar
+        .components
+        .iter()
+        .filter_map(|component| component.as_event())
+        .collect();
+    assert_eq!(events[0].get_summary(), Some("Team busy"));
+    assert_eq!(
+        events[0].get_description(),
+        Some("Busy: alice@example.com, bob@example.com")
+    );
+    assert_eq!(events[1].property_value("STATUS"), Some("TENTATIVE"));
+
+    // The same blocks keep their UIDs, so unchanged blocks are not uploaded again
+    let again = freebusy::availability_calendar(&blocks, "Team busy");
+    let uids = |calendar: &icalendar::Calendar| -> Vec<String> {
+        calendar
+            .components
+            .iter()
+            .filter_map(|component| component.as_event()?.get_uid().map(str::to_string))
+            .collect()
+    };
+    assert_eq!(uids(&calendar), uids(&again));
+}
@@ -0,0 +1,58 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+
+use futures::future::BoxFuture;
+use icalendar::{Calendar, CalendarComponent, Component, Event, EventLike};
+use nextcloud_ics_sync::{
+    geo::{GeoEnricher, Geocoder},
+    http::Client,
+};
+
+struct FixedGeocoder {
+    lookups: Arc<AtomicUsize>,
+}
+
+impl Geocoder for FixedGeocoder {
+    fn geocode<'a>(
+        &'a self,
+        _client: &'a Client,
+        _location: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<Option<(f64, f64)>>> {
+        self.lookups.fetch_add(1, Ordering::SeqCst);
+        Box::pin(async { Ok(Some((52.52, 13.405))) })
+    }
+}
+
+#[tokio::test]
+async fn enrich_adds_geo_and_map_link_once_per_location() {
+    let lookups = Arc::new(AtomicUsize::new(0));
+    let mut enricher = GeoEnricher::new(
+        Some(Box::new(FixedGeocoder {
+            lookups: lookups.clone(),
+        })),
+        Some("https://maps.example.com/?q={location}".to_string()),
+    );
+
+    let calendar: Calendar = ["a", "b"]
+        .into_iter()
+        .map(|uid| Event::new().uid(uid).location("Alexanderplatz 1").done())
+        .collect();
+    let calendar = enricher
+        .enrich(&Client::new(), calendar)
+        .await
+        .expect("enrichment should succeed");
+
+    assert_eq!(lookups.load(Ordering::SeqCst), 1);
+    for component in &calendar.components {
+        let CalendarComponent::Event(event) = component else {
+            continue;
+        };
+        assert_eq!(event.property_value("GEO"), Some("52.52;13.405"));
+        assert_eq!(
+            event.get_description(),
+            Some("https://maps.example.com/?q=Alexanderplatz%201")
+        );
+    }
+}
@@ -0,0 +1,125 @@
+#![cfg(unix)]
+
+use std::fs;
+
+use icalendar::{Calendar, Component, Event, EventLike};
+use nextcloud_ics_sync::hooks::Hooks;
+
+fn event() -> Event {
+    Event::new()
+        .uid("standup")
+        .summary("Daily standup")
+        .location("Room 1")
+        .done()
+}
+
+#[tokio::test]
+async fn hooks_get_the_event_in_their_environment_and_on_stdin() {
+    let dir = std::env::temp_dir().join(format!("nis-hooks-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let env_file = dir.join("env");
+    let stdin_file = dir.join("stdin");
+
+    let hooks = Hooks {
+        on_event_upload: Some(format!(
+            "echo \"$NIS_HOOK|$NIS_UID|$NIS_SUMMARY|$NIS_LOCATION|${{NIS_DESCRIPTION-unset}}\" > {}; \
+             cat > {}",
+            env_file.display(),
+            stdin_file.display()
+        )),
+        post_sync: Some(format!(
+            "echo \"$NIS_SUCCESS|$NIS_UPLOADED|$NIS_DELETED\" > {}",
+            dir.join("post_sync").display()
+        )),
+        ..Hooks::default()
+    };
+
+    hooks.on_event_upload(&event()).await;
+    assert_eq!(
+        fs::read_to_string(&env_file).unwrap(),
+        "on_event_upload|standup|Daily standup|Room 1|unset\n"
+    );
+    let data: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&stdin_file).unwrap()).unwrap();
+    assert_eq!(data["hook"], "on_event_upload");
+    assert_eq!(data["uid"], "standup");
+    assert_eq!(data["summary"], "Daily standup");
+
+    hooks.post_sync(true, 3, 1).await;
+    assert_eq!(
+        fs::read_to_string(dir.join("post_sync")).unwrap(),
+        "true|3|1\n"
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn only_a_failing_pre_sync_hook_aborts() {
+    let failing = Hooks {
+        pre_sync: Some("exit 1".to_string()),
+        post_sync: Some("exit 1".to_string()),
+        on_event_upload: Some("exit 2".to_string()),
+        on_event_delete: Some("exit 3".to_string()),
+        ..Hooks::default()
+    };
+    let error = failing.pre_sync().await.unwrap_err();
+    assert!(
+        error.to_string().contains("pre_sync hook failed"),
+        "{}",
+        error
+    );
+
+    // The others only log a warning
+    failing.post_sync(false, 0, 0).await;
+    failing.on_event_upload(&event()).await;
+    failing.on_event_delete("standup", None).await;
+
+    assert!(Hooks::default().pre_sync().await.is_ok());
+}
+
+#[tokio::test]
+async fn summaries_are_replaced_by_the_translation_hook_output() {
+    let calendar = || -> Calendar {
+        [
+            event(),
+            Event::new().uid("no-summary").done(),
+            Event::new().uid("skip").summary("skip").done(),
+        ]
+        .into_iter()
+        .collect()
+    };
+    let summaries = |calendar: &Calendar| -> Vec<Option<String>> {
+        calendar
+            .components
+            .iter()
+            .filter_map(|component| component.as_event())
+            .map(|event| event.get_summary().map(str::to_string))
+            .collect()
+    };
+
+    let hooks = Hooks {
+        // Prints nothing for `skip`, so its summary is kept
+        translate_summary: Some(
+            r#"[ "$NIS_SUMMARY" = skip ] || echo "Täglich: $NIS_SUMMARY""#.to_string(),
+        ),
+        ..Hooks::default()
+    };
+    let translated = hooks.translate_summaries(calendar()).await;
+    assert_eq!(
+        summaries(&translated),
+        [
+            Some("Täglich: Daily standup".to_string()),
+            None,
+            Some("skip".to_string())
+        ]
+    );
+
+    let failing = Hooks {
+        translate_summary: Some("echo partial; exit 1".to_string()),
+        ..Hooks::default()
+    };
+    let kept = failing.translate_summaries(calendar()).await;
+    assert_eq!(summaries(&kept), summaries(&calendar()));
+}
@@ -0,0 +1,46 @@
+use chrono::NaiveDate;
+use icalendar::{Event, EventLike};
+use nextcloud_ics_sync::hours::WeeklyHours;
+
+// 2024-03-04 is a Monday
+fn shift(day: u32, start: (u32, u32), end: Option<(u32, u32)>) -> Event {
+    let at = |(hour, minute): (u32, u32)| {
+        NaiveDate::from_ymd_opt(2024, 3, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    };
+    let mut event = Event::new();
+    event.starts(at(start));
+    if let Some(end) = end {
+        event.ends(at(end));
+    }
+    event.done()
+}
+
+#[test]
+fn busy_hours_keep_events_overlapping_the_windows() {
+    let hours: WeeklyHours = "Mon-Fri 07:00-20:00, Sun 22:00-06:00".parse().unwrap();
+
+    assert!(hours.overlaps(&shift(4, (9, 0), Some((17, 0)))));
+    assert!(hours.overlaps(&shift(5, (5, 0), Some((8, 0)))));
+    assert!(!hours.overlaps(&shift(5, (20, 0), Some((23, 0)))));
+    assert!(!hours.overlaps(&shift(9, (10, 0), Some((12, 0)))));
+    // Sunday night runs into Monday morning
+    assert!(hours.overlaps(&shift(4, (1, 0), None)));
+    assert!(hours.overlaps(&shift(10, (23, 0), None)));
+    assert!(!hours.overlaps(&shift(10, (21, 0), None)));
+
+    let all_day = Event::new()
+        .starts(NaiveDate::from_ymd_opt(2024, 3, 9).unwrap())
+        .done();
+    assert!(!hours.overlaps(&all_day));
+    assert!("Sat".parse::<WeeklyHours>().unwrap().overlaps(&all_day));
+}
+
+#[test]
+fn invalid_busy_hours_are_rejected() {
+    assert!("Mon-Fri 7-20".parse::<WeeklyHours>().is_err());
+    assert!("Someday 07:00-20:00".parse::<WeeklyHours>().is_err());
+    assert!("".parse::<WeeklyHours>().is_err());
+}
@@ -0,0 +1,77 @@
+use nextcloud_ics_sync::{
+    http::{self, Client, DEFAULT_USER_AGENT, HttpConfig},
+    ics_parser::fetch_and_parse_calendar,
+    test_util::MockNextcloud,
+};
+
+const FEED: &str = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nEND:VCALENDAR\r\n";
+
+async fn fetch(client: &Client, url: &str) -> anyhow::Result<()> {
+    fetch_and_parse_calendar(client, url, None, None, None, &[], &[])
+        .await
+        .map(|_| ())
+}
+
+#[tokio::test]
+async fn retries_are_settings_of_the_client() {
+    let nextcloud = MockNextcloud::start("alice", "app-password").await;
+    nextcloud
+        .mock_feed("/feed.ics", "text/calendar", FEED)
+        .await;
+    let url = format!("{}/feed.ics", nextcloud.url());
+
+    let retrying = http::init(&HttpConfig {
+        retries: 1,
+        ..Default::default()
+    })
+    .unwrap();
+    nextcloud.mock_unavailable("/feed.ics", 1).await;
+    fetch(&retrying, &url)
+        .await
+        .expect("the failure should be retried");
+    assert_eq!(nextcloud.received("GET").await.len(), 2);
+
+    // Building the retrying client didn't change the settings of others
+    nextcloud.mock_unavailable("/feed.ics", 1).await;
+    let error = fetch(&Client::new(), &url).await.unwrap_err();
+    assert!(error.to_string().contains("503"), "{:#}", error);
+    assert_eq!(nextcloud.received("GET").await.len(), 3);
+}
+
+#[tokio::test]
+async fn requests_identify_themselves_and_keep_their_id_across_retries() {
+    let nextcloud = MockNextcloud::start("alice", "app-password").await;
+    nextcloud
+        .mock_protected_feed("/feed.ics", &[("User-Agent", DEFAULT_USER_AGENT)], FEED)
+        .await;
+    nextcloud.mock_unavailable("/feed.ics", 1).await;
+    let url = format!("{}/feed.ics", nextcloud.url());
+
+    let client = http::init(&HttpConfig {
+        retries: 1,
+        request_ids: true,
+        ..Default::default()
+    })
+    .unwrap();
+    fetch(&client, &url)
+        .await
+        .expect("the feed should be served to the user agent");
+    fetch(&client, &url).await.unwrap();
+
+    let ids: Vec<String> = nextcloud
+        .received("GET")
+        .await
+        .iter()
+        .map(|request| {
+            request.headers["X-Request-Id"]
+                .to_str()
+                .unwrap()
+                .to_string()
+        })
+        .collect();
+    assert_eq!(ids.len(), 3);
+    // The retry of the first request keeps its ID, the next request gets a new one
+    assert_eq!(ids[0], ids[1]);
+    assert_ne!(ids[1], ids[2]);
+    assert!(DEFAULT_USER_AGENT.starts_with("nextcloud-ics-sync/"));
+}
@@ -0,0 +1,51 @@
+use std::{
+    fs::{self, File},
+    time::{Duration, SystemTime},
+};
+
+use nextcloud_ics_sync::journal::{Action, Journal};
+
+#[test]
+fn journals_of_old_runs_are_pruned() {
+    let dir = std::env::temp_dir().join(format!("nis-journal-prune-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+
+    let mut runs = Vec::new();
+    for age in [3, 2, 1] {
+        let journal = Journal::start(&dir).expect("journal should be created");
+        journal.record("meeting", Action::Uploaded).unwrap();
+        let target = journal
+            .for_target("https://cloud.example.com/remote.php/dav/calendars/alice/family/")
+            .unwrap();
+        target.record("meeting", Action::Uploaded).unwrap();
+
+        // Older runs last wrote to their journals earlier
+        let modified = SystemTime::now() - Duration::from_secs(age * 3600);
+        for path in [journal.path(), target.path()] {
+            File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        }
+        runs.push((journal.path().to_path_buf(), target.path().to_path_buf()));
+    }
+    fs::write(dir.join("notes.jsonl"), "").unwrap();
+
+    assert_eq!(Journal::prune(&dir, 2).unwrap(), 1);
+    assert!(!runs[0].0.exists());
+    assert!(!runs[0].1.exists());
+    assert!(
+        runs[1..]
+            .iter()
+            .all(|(run, target)| run.exists() && target.exists())
+    );
+    // Files that aren't journals are left alone
+    assert!(dir.join("notes.jsonl").exists());
+
+    assert_eq!(Journal::prune(&dir, 2).unwrap(), 0);
+    assert_eq!(Journal::prune(&dir.join("missing"), 2).unwrap(), 0);
+
+    let _ = fs::remove_dir_all(&dir);
+}
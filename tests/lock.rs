@@ -0,0 +1,51 @@
+use std::{fs, time::Duration};
+
+use nextcloud_ics_sync::lock::{DEFAULT_STALE_AFTER, SyncLock};
+
+#[tokio::test]
+async fn second_run_is_refused_until_the_lock_is_released() {
+    let dir = std::env::temp_dir().join(format!("nis-lock-{}", std::process::id()));
+    let path = dir.join("calendar.lock");
+    let _ = fs::remove_dir_all(&dir);
+
+    let lock = SyncLock::acquire(&path, false, DEFAULT_STALE_AFTER)
+        .await
+        .expect("first run should get the lock");
+    let error = SyncLock::acquire(&path, false, DEFAULT_STALE_AFTER)
+        .await
+        .expect_err("second run should be refused");
+    assert!(error.to_string().contains("--wait"), "{}", error);
+
+    drop(lock);
+    assert!(!path.exists());
+    let _lock = SyncLock::acquire(&path, false, DEFAULT_STALE_AFTER)
+        .await
+        .expect("lock should be free again");
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn stale_locks_are_taken_over() {
+    let dir = std::env::temp_dir().join(format!("nis-stale-lock-{}", std::process::id()));
+    let path = dir.join("calendar.lock");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    // Left behind by a run of this process that started an hour ago
+    let started = chrono::Utc::now().timestamp() - 3600;
+    fs::write(
+        &path,
+        format!(r#"{{"pid":{},"started":{}}}"#, std::process::id(), started),
+    )
+    .unwrap();
+
+    SyncLock::acquire(&path, false, DEFAULT_STALE_AFTER)
+        .await
+        .expect_err("a recent lock of a running process should hold");
+    let _lock = SyncLock::acquire(&path, false, Duration::from_secs(60))
+        .await
+        .expect("an old lock should be taken over");
+
+    let _ = fs::remove_dir_all(&dir);
+}
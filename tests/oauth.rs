@@ -0,0 +1,131 @@
+use std::{fs, path::PathBuf};
+
+use nextcloud_ics_sync::{
+    http::Client,
+    oauth::{OAuthConfig, get_access_token},
+    test_util::MockNextcloud,
+};
+
+fn config(nextcloud: &MockNextcloud, name: &str, auth_url: Option<&str>) -> OAuthConfig {
+    OAuthConfig {
+        token_url: format!("{}/oauth/token", nextcloud.url()),
+        auth_url: auth_url.map(str::to_string),
+        client_id: "sync".to_string(),
+        client_secret: Some("client-secret".to_string()),
+        scope: None,
+        redirect_uri: "urn:ietf:wg:oauth:2.0:oob".to_string(),
+        token_cache: token_cache(name),
+    }
+}
+
+fn token_cache(name: &str) -> PathBuf {
+    std::env::temp_dir()
+        .join(format!("nis-oauth-{}-{}", name, std::process::id()))
+        .join("oauth-token.json")
+}
+
+fn cache_expired_token(config: &OAuthConfig) {
+    fs::create_dir_all(config.token_cache.parent().unwrap()).unwrap();
+    fs::write(
+        &config.token_cache,
+        r#"{"access_token":"expired","refresh_token":"refresh-1","expires_at":0}"#,
+    )
+    .unwrap();
+}
+
+#[tokio::test]
+async fn fetched_tokens_are_cached_until_they_expire() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start("alice", "app-password").await;
+    nextcloud
+        .mock_oauth_token(
+            "client_credentials",
+            200,
+            r#"{"access_token":"token-1","expires_in":3600}"#,
+        )
+        .await;
+    let config = config(&nextcloud, "fetch", None);
+    let _ = fs::remove_file(&config.token_cache);
+
+    assert_eq!(get_access_token(&client, &config).await.unwrap(), "token-1");
+    assert_eq!(get_access_token(&client, &config).await.unwrap(), "token-1");
+
+    let requests = nextcloud.received("POST").await;
+    assert_eq!(requests.len(), 1);
+    let body = String::from_utf8_lossy(&requests[0].body);
+    assert!(body.contains("client_id=sync"));
+    assert!(body.contains("client_secret=client-secret"));
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&config.token_cache)
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    let _ = fs::remove_dir_all(config.token_cache.parent().unwrap());
+}
+
+#[tokio::test]
+async fn expired_tokens_are_refreshed() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start("alice", "app-password").await;
+    nextcloud
+        .mock_oauth_token(
+            "refresh_token",
+            200,
+            r#"{"access_token":"token-2","expires_in":3600}"#,
+        )
+        .await;
+    let config = config(
+        &nextcloud,
+        "refresh",
+        Some("https://auth.example.com/authorize"),
+    );
+    cache_expired_token(&config);
+
+    assert_eq!(get_access_token(&client, &config).await.unwrap(), "token-2");
+
+    let requests = nextcloud.received("POST").await;
+    assert!(String::from_utf8_lossy(&requests[0].body).contains("refresh_token=refresh-1"));
+    // The provider didn't send a new refresh token, so the old one is kept
+    let cached = fs::read_to_string(&config.token_cache).unwrap();
+    assert!(cached.contains("\"refresh_token\": \"refresh-1\""));
+
+    let _ = fs::remove_dir_all(config.token_cache.parent().unwrap());
+}
+
+#[tokio::test]
+async fn failed_refreshes_fall_back_to_a_new_token() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start("alice", "app-password").await;
+    nextcloud
+        .mock_oauth_token("refresh_token", 400, r#"{"error":"invalid_grant"}"#)
+        .await;
+    nextcloud
+        .mock_oauth_token(
+            "client_credentials",
+            200,
+            r#"{"access_token":"token-3","expires_in":3600}"#,
+        )
+        .await;
+    let config = config(&nextcloud, "fallback", None);
+    cache_expired_token(&config);
+
+    assert_eq!(get_access_token(&client, &config).await.unwrap(), "token-3");
+    assert_eq!(nextcloud.received("POST").await.len(), 2);
+
+    // Tokens of the authorization-code flow can only be replaced by logging in again
+    let config = OAuthConfig {
+        auth_url: Some("https://auth.example.com/authorize".to_string()),
+        ..config
+    };
+    cache_expired_token(&config);
+    let error = get_access_token(&client, &config).await.unwrap_err();
+    assert!(error.to_string().contains("oauth-login"));
+
+    let _ = fs::remove_dir_all(config.token_cache.parent().unwrap());
+}
@@ -0,0 +1,50 @@
+#![cfg(unix)]
+
+use std::path::PathBuf;
+
+use nextcloud_ics_sync::paths;
+
+// Changes the environment of the process, so it is the only test of its binary
+#[test]
+fn xdg_directories_are_used_when_absolute() {
+    unsafe { std::env::set_var("HOME", "/home/user") };
+
+    unsafe { std::env::set_var("XDG_CONFIG_HOME", "/xdg/config") };
+    unsafe { std::env::set_var("XDG_STATE_HOME", "/xdg/state") };
+    assert_eq!(
+        paths::config_file(),
+        PathBuf::from("/xdg/config/nextcloud-ics-sync/config.env")
+    );
+    assert_eq!(
+        paths::state_dir(),
+        PathBuf::from("/xdg/state/nextcloud-ics-sync")
+    );
+
+    unsafe { std::env::remove_var("XDG_CONFIG_HOME") };
+    unsafe { std::env::remove_var("XDG_STATE_HOME") };
+    assert_eq!(
+        paths::config_file(),
+        PathBuf::from("/home/user/.config/nextcloud-ics-sync/config.env")
+    );
+    assert_eq!(
+        paths::state_dir(),
+        PathBuf::from("/home/user/.local/state/nextcloud-ics-sync")
+    );
+
+    // Relative paths are invalid according to the specification and ignored, like empty ones
+    unsafe { std::env::set_var("XDG_CONFIG_HOME", "config") };
+    unsafe { std::env::set_var("XDG_STATE_HOME", "") };
+    unsafe { std::env::set_var("XDG_CACHE_HOME", "./cache") };
+    assert_eq!(
+        paths::config_file(),
+        PathBuf::from("/home/user/.config/nextcloud-ics-sync/config.env")
+    );
+    assert_eq!(
+        paths::state_dir(),
+        PathBuf::from("/home/user/.local/state/nextcloud-ics-sync")
+    );
+    assert_eq!(
+        paths::cache_dir(),
+        PathBuf::from("/home/user/.cache/nextcloud-ics-sync")
+    );
+}
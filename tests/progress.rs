@@ -0,0 +1,82 @@
+use futures::StreamExt;
+use icalendar::{Calendar, Component, Event};
+use nextcloud_ics_sync::{
+    SyncOptions,
+    http::Client,
+    progress::{SyncEngine, SyncEvent},
+    test_util::MockNextcloud,
+};
+
+const USERNAME: &str = "alice";
+const PASSWORD: &str = "app-password";
+const CALENDAR_ID: &str = "work";
+
+fn event(uid: &str, synced: bool) -> Event {
+    let mut event = Event::new();
+    event
+        .uid(uid)
+        .summary(uid)
+        .add_property("DTSTART", "20240101T100000Z");
+    if synced {
+        event.add_property("X-SYNCED", "TRUE");
+    }
+    event.done()
+}
+
+#[tokio::test]
+async fn stream_reports_every_step_of_the_sync() {
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+    let existing: Calendar = [event("stale", true)].into_iter().collect();
+    nextcloud.mock_calendar_query(CALENDAR_ID, &existing).await;
+    nextcloud.mock_uploads(CALENDAR_ID).await;
+    nextcloud.mock_deletes(CALENDAR_ID).await;
+
+    let engine = SyncEngine {
+        client: Client::new(),
+        username: USERNAME.to_string(),
+        password: PASSWORD.to_string(),
+        calendar_url: nextcloud.calendar_url(CALENDAR_ID),
+        options: SyncOptions::default(),
+    };
+    let source: Calendar = [event("new", false)].into_iter().collect();
+    let events: Vec<SyncEvent> = engine.run_stream(source).collect().await;
+
+    let steps: Vec<String> = events
+        .iter()
+        .map(|event| match event {
+            SyncEvent::Done(report) => format!("Done({}, {})", report.uploaded, report.deleted),
+            event => format!("{:?}", event),
+        })
+        .collect();
+    assert_eq!(
+        steps,
+        [
+            "Fetching",
+            "Diffed { uploads: 1, deletions: 1 }",
+            "Uploaded(\"new\")",
+            "Deleted(\"stale\")",
+            "Done(1, 1)",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn stream_ends_with_the_error_of_a_failed_sync() {
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+    nextcloud
+        .mock_calendar_query(CALENDAR_ID, &Calendar::new())
+        .await;
+
+    let engine = SyncEngine {
+        client: Client::new(),
+        username: USERNAME.to_string(),
+        password: PASSWORD.to_string(),
+        calendar_url: nextcloud.calendar_url(CALENDAR_ID),
+        options: SyncOptions::default(),
+    };
+    let source: Calendar = [event("new", false)].into_iter().collect();
+    let events: Vec<SyncEvent> = engine.run_stream(source).collect().await;
+
+    assert!(matches!(&events[2], SyncEvent::Failed(uid, _) if uid == "new"));
+    assert!(matches!(events.last(), Some(SyncEvent::Aborted(_))));
+}
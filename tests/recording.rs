@@ -0,0 +1,66 @@
+use std::{fs, sync::Arc};
+
+use nextcloud_ics_sync::{
+    http::Client, ics_parser::fetch_and_parse_calendar, nextcloud, recording::Recorder,
+    test_util::MockNextcloud,
+};
+
+const USERNAME: &str = "alice";
+const PASSWORD: &str = "app-password";
+
+#[tokio::test]
+async fn replays_recorded_exchanges_without_credentials() {
+    let dir = std::env::temp_dir().join(format!("nis-recording-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+    nextcloud.mock_calendar_list(&["personal", "work"]).await;
+    nextcloud
+        .mock_feed(
+            "/calendar/private-0123456789abcdef/basic.ics",
+            "text/calendar",
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nEND:VCALENDAR\r\n",
+        )
+        .await;
+    let feed_url = format!(
+        "{}/calendar/private-0123456789abcdef/basic.ics?token=feed-secret",
+        nextcloud.url()
+    );
+
+    let client = Client::new().with_transport(Arc::new(
+        Recorder::record(&dir, Client::new()).expect("recording should start"),
+    ));
+    let recorded = nextcloud::api::get_calendar_ids(&client, &nextcloud.url(), USERNAME, PASSWORD)
+        .await
+        .expect("calendar ids should be listed");
+    fetch_and_parse_calendar(&client, &feed_url, None, None, None, &[], &[])
+        .await
+        .expect("the feed should be fetched");
+
+    let url = nextcloud.url();
+    drop(nextcloud);
+
+    for entry in fs::read_dir(&dir).unwrap() {
+        let content = fs::read_to_string(entry.unwrap().path()).unwrap();
+        assert!(content.contains("[REDACTED]"));
+        assert!(!content.contains("Basic "));
+        // Secret addresses are masked like in logs
+        assert!(!content.contains("0123456789abcdef"), "{}", content);
+        assert!(!content.contains("feed-secret"), "{}", content);
+    }
+
+    let client = Client::new().with_transport(Arc::new(
+        Recorder::replay(&dir).expect("replay should start"),
+    ));
+    let replayed = nextcloud::api::get_calendar_ids(&client, &url, USERNAME, PASSWORD)
+        .await
+        .expect("calendar ids should be replayed");
+    fetch_and_parse_calendar(&client, &feed_url, None, None, None, &[], &[])
+        .await
+        .expect("the feed should be replayed");
+
+    assert_eq!(recorded, vec!["personal", "work"]);
+    assert_eq!(replayed, recorded);
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,179 @@
+use chrono::{TimeZone, Utc};
+use icalendar::{Calendar, Component, Event};
+use nextcloud_ics_sync::{
+    nextcloud::api::TimeRange,
+    recurrence::{collapse_series, expand_calendar},
+};
+
+fn range() -> TimeRange {
+    TimeRange {
+        start: Some(Utc.with_ymd_and_hms(2024, 1, 8, 0, 0, 0).unwrap()),
+        end: Some(Utc.with_ymd_and_hms(2024, 1, 22, 0, 0, 0).unwrap()),
+    }
+}
+
+fn starts(calendar: &Calendar) -> Vec<(String, String)> {
+    let mut starts: Vec<(String, String)> = calendar
+        .components
+        .iter()
+        .filter_map(|component| component.as_event())
+        .map(|event| {
+            (
+                event.get_uid().unwrap().to_string(),
+                event.property_value("DTSTART").unwrap().to_string(),
+            )
+        })
+        .collect();
+    starts.sort();
+    starts
+}
+
+#[test]
+fn weekly_series_are_expanded_inside_the_window() {
+    let calendar: Calendar = [
+        Event::new()
+            .uid("lecture")
+            .summary("Lecture")
+            .add_property("DTSTART", "20240101T100000Z")
+            .add_property("DTEND", "20240101T113000Z")
+            .add_property("RRULE", "FREQ=WEEKLY;BYDAY=MO,WE")
+            .add_multi_property("EXDATE", "20240110T100000Z")
+            .done(),
+        Event::new()
+            .uid("lecture")
+            .summary("Moved lecture")
+            .add_property("RECURRENCE-ID", "20240115T100000Z")
+            .add_property("DTSTART", "20240115T140000Z")
+            .done(),
+    ]
+    .into_iter()
+    .collect();
+
+    let expanded = expand_calendar(calendar, &range());
+
+    assert_eq!(
+        starts(&expanded),
+        [
+            (
+                "lecture-20240108T100000Z".to_string(),
+                "20240108T100000Z".to_string()
+            ),
+            (
+                "lecture-20240115T100000Z".to_string(),
+                "20240115T140000Z".to_string()
+            ),
+            (
+                "lecture-20240117T100000Z".to_string(),
+                "20240117T100000Z".to_string()
+            ),
+        ]
+    );
+    let first = expanded.components[0].as_event().unwrap();
+    assert_eq!(first.property_value("DTEND"), Some("20240108T113000Z"));
+    assert_eq!(first.property_value("RRULE"), None);
+}
+
+#[test]
+fn counts_and_unsupported_rules() {
+    let calendar: Calendar = [
+        Event::new()
+            .uid("daily")
+            .add_property("DTSTART", "20240106")
+            .add_property("RRULE", "FREQ=DAILY;INTERVAL=2;COUNT=4")
+            .done(),
+        Event::new()
+            .uid("monthly")
+            .add_property("DTSTART", "20240101T100000Z")
+            .add_property("RRULE", "FREQ=MONTHLY;BYDAY=1MO")
+            .done(),
+    ]
+    .into_iter()
+    .collect();
+
+    let expanded = expand_calendar(calendar, &range());
+
+    assert_eq!(
+        starts(&expanded),
+        [
+            ("daily-20240108".to_string(), "20240108".to_string()),
+            ("daily-20240110".to_string(), "20240110".to_string()),
+            ("daily-20240112".to_string(), "20240112".to_string()),
+            ("monthly".to_string(), "20240101T100000Z".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn regular_instances_are_collapsed_into_a_series() {
+    let instance = |uid: &str, day: u32, summary: &str| {
+        Event::new()
+            .uid(uid)
+            .summary(summary)
+            .add_property("DTSTART", format!("202401{:02}T100000Z", day))
+            .add_property("DTEND", format!("202401{:02}T110000Z", day))
+            .done()
+    };
+    // Weekly on the 1st, 8th, (15th cancelled), 22nd and 29th, plus an unrelated event
+    let calendar: Calendar = [
+        instance("a", 1, "Standup"),
+        instance("b", 8, "Standup"),
+        instance("c", 22, "Standup"),
+        instance("d", 29, "Standup"),
+        instance("e", 8, "Review"),
+    ]
+    .into_iter()
+    .collect();
+
+    let collapsed = collapse_series(calendar);
+
+    let events: Vec<&Event> = collapsed
+        .components
+        .iter()
+        .filter_map(|component| component.as_event())
+        .collect();
+    assert_eq!(events.len(), 2);
+    let series = events
+        .iter()
+        .find(|event| event.get_summary() == Some("Standup"))
+        .unwrap();
+    assert!(series.get_uid().unwrap().starts_with("series-"));
+    assert_eq!(series.property_value("DTSTART"), Some("20240101T100000Z"));
+    assert_eq!(
+        series.property_value("RRULE"),
+        Some("FREQ=WEEKLY;INTERVAL=1;COUNT=5")
+    );
+    assert_eq!(
+        series.multi_properties()["EXDATE"][0].value(),
+        "20240115T100000Z"
+    );
+}
+
+#[test]
+fn scattered_instances_are_not_collapsed() {
+    let instance = |uid: &str, date: &str| {
+        Event::new()
+            .uid(uid)
+            .summary("Board meeting")
+            .add_property("DTSTART", format!("{}T100000Z", date))
+            .add_property("DTEND", format!("{}T110000Z", date))
+            .done()
+    };
+    // Daily by their differences, but a series would need far more EXDATEs than instances
+    let calendar: Calendar = [
+        instance("a", "20240101"),
+        instance("b", "20240102"),
+        instance("c", "20241231"),
+    ]
+    .into_iter()
+    .collect();
+
+    let collapsed = collapse_series(calendar);
+    assert_eq!(
+        starts(&collapsed),
+        [
+            ("a".to_string(), "20240101T100000Z".to_string()),
+            ("b".to_string(), "20240102T100000Z".to_string()),
+            ("c".to_string(), "20241231T100000Z".to_string()),
+        ]
+    );
+}
@@ -0,0 +1,58 @@
+use icalendar::{Calendar, Component, Event};
+use nextcloud_ics_sync::{nextcloud::api::CalendarObject, report::drift_report};
+
+fn event(uid: &str, summary: &str, start: &str) -> Event {
+    Event::new()
+        .uid(uid)
+        .summary(summary)
+        .add_property("DTSTART", start)
+        .done()
+}
+
+fn synced(mut event: Event) -> Event {
+    event.add_property("X-SYNCED", "TRUE");
+    event.add_property("X-SYNC-RUN", "run");
+    event
+}
+
+#[test]
+fn report_counts_drift_per_side_and_month() {
+    let source: Calendar = [
+        event("same", "Same", "20240105T100000Z"),
+        event("changed", "New title", "20240110T100000Z"),
+        event("new", "New", "20240201T100000Z"),
+    ]
+    .into_iter()
+    .collect();
+    let target = [
+        synced(event("same", "Same", "20240105T100000Z")),
+        synced(event("changed", "Old title", "20240110T100000Z")),
+        synced(event("removed", "Removed", "20240115T100000Z")),
+        event("manual", "Manual", "20240301"),
+    ]
+    .into_iter()
+    .map(|event| CalendarObject {
+        href: format!("/{}.ics", event.get_uid().unwrap()),
+        etag: None,
+        calendar: [event].into_iter().collect(),
+    })
+    .collect();
+
+    let report = drift_report(source, target);
+
+    assert_eq!(report.only_in_source, 1);
+    assert_eq!(report.only_in_target, 1);
+    assert_eq!(report.differing, 1);
+    assert_eq!(report.in_sync, 1);
+    assert_eq!(report.unmanaged, 1);
+    assert!(report.has_drift());
+    let months: Vec<_> = report
+        .per_month
+        .iter()
+        .map(|(month, counts)| (month.as_str(), counts.source, counts.target))
+        .collect();
+    assert_eq!(
+        months,
+        [("2024-01", 2, 3), ("2024-02", 1, 0), ("2024-03", 0, 1)]
+    );
+}
@@ -0,0 +1,90 @@
+use std::time::Duration;
+
+use icalendar::{Calendar, Component, Event};
+use nextcloud_ics_sync::{
+    dns::IpFamily,
+    http::{self, HttpConfig},
+    nextcloud::{self, api::TimeRange},
+    test_util::MockNextcloud,
+};
+
+const USERNAME: &str = "alice";
+const PASSWORD: &str = "app-password";
+const CALENDAR_ID: &str = "work";
+
+#[tokio::test]
+async fn host_names_are_resolved_from_the_config() {
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+    let stored = Calendar::new()
+        .push(Event::new().uid("a").summary("a").done())
+        .done();
+    nextcloud.mock_calendar_query(CALENDAR_ID, &stored).await;
+
+    let client = http::init(&HttpConfig {
+        resolve: vec![(
+            "nextcloud.local".to_string(),
+            "127.0.0.1:0".parse().unwrap(),
+        )],
+        ..Default::default()
+    })
+    .unwrap();
+    let port = nextcloud.server().address().port();
+    let calendar_url = nextcloud.calendar_url(CALENDAR_ID).replace(
+        &nextcloud.url(),
+        &format!("http://nextcloud.local:{}", port),
+    );
+
+    let objects = nextcloud::api::get_calendar_objects(
+        &client,
+        &calendar_url,
+        USERNAME,
+        PASSWORD,
+        TimeRange::default(),
+    )
+    .await
+    .expect("calendar should be fetched through the configured address");
+    assert_eq!(objects.len(), 1);
+}
+
+#[test]
+fn unix_sockets_require_a_valid_url() {
+    let config = HttpConfig {
+        unix_sockets: vec![("not a url".into(), "/run/nextcloud.sock".into())],
+        ..Default::default()
+    };
+    let error = http::init(&config).expect_err("invalid URL should be rejected");
+    assert!(error.to_string().contains("Invalid URL"), "{}", error);
+}
+
+#[tokio::test]
+async fn ipv4_only_connects_to_localhost_over_ipv4() {
+    let nextcloud = MockNextcloud::start(USERNAME, PASSWORD).await;
+    let stored = Calendar::new()
+        .push(Event::new().uid("a").summary("a").done())
+        .done();
+    nextcloud.mock_calendar_query(CALENDAR_ID, &stored).await;
+
+    let client = http::init(&HttpConfig {
+        ip_family: IpFamily::Ipv4Only,
+        dns_cache_ttl: Duration::from_secs(60),
+        ..Default::default()
+    })
+    .unwrap();
+    let port = nextcloud.server().address().port();
+    let calendar_url = nextcloud
+        .calendar_url(CALENDAR_ID)
+        .replace(&nextcloud.url(), &format!("http://localhost:{}", port));
+
+    for _ in 0..2 {
+        let objects = nextcloud::api::get_calendar_objects(
+            &client,
+            &calendar_url,
+            USERNAME,
+            PASSWORD,
+            TimeRange::default(),
+        )
+        .await
+        .expect("calendar should be fetched over IPv4");
+        assert_eq!(objects.len(), 1);
+    }
+}
@@ -0,0 +1,95 @@
+use icalendar::{Calendar, Component};
+use nextcloud_ics_sync::routing::{CalendarRoutes, Route, RouteMatch};
+
+const FEED: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VTIMEZONE\r
+TZID:Europe/Berlin\r
+END:VTIMEZONE\r
+BEGIN:VEVENT\r
+UID:lecture\r
+SUMMARY:Algorithms\r
+CATEGORIES:Lecture,CS\r
+DTSTART:20240304T100000Z\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:lecture\r
+RECURRENCE-ID:20240311T100000Z\r
+SUMMARY:Exam Algorithms\r
+DTSTART:20240311T100000Z\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:exam\r
+SUMMARY:EXAM Databases\r
+DTSTART:20240320T100000Z\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:party\r
+SUMMARY:Party\r
+DTSTART:20240322T200000Z\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+fn route(condition: &str, calendar_url: &str) -> Route {
+    Route {
+        condition: condition.parse().unwrap(),
+        calendar_url: calendar_url.to_string(),
+    }
+}
+
+#[test]
+fn events_are_split_by_the_first_matching_route() {
+    let routes = CalendarRoutes {
+        routes: vec![
+            route("category:lecture", "uni/"),
+            route("summary:(?i)^exam", "exams/"),
+            route("category:unused", "empty/"),
+        ],
+    };
+    let calendar: Calendar = FEED.parse().unwrap();
+
+    // Events as UID, overrides with their RECURRENCE-ID
+    let split: Vec<(String, Vec<String>, usize)> = routes
+        .split(calendar, "default/")
+        .into_iter()
+        .map(|(url, calendar)| {
+            let events = calendar
+                .components
+                .iter()
+                .filter_map(|component| component.as_event())
+                .map(|event| match event.property_value("RECURRENCE-ID") {
+                    Some(recurrence_id) => {
+                        format!("{}@{}", event.get_uid().unwrap(), recurrence_id)
+                    }
+                    None => event.get_uid().unwrap().to_string(),
+                })
+                .collect();
+            (url, events, calendar.components.len())
+        })
+        .collect();
+
+    let target = |url: &str, events: &[&str], components| {
+        (
+            url.to_string(),
+            events.iter().map(|event| event.to_string()).collect(),
+            components,
+        )
+    };
+    assert_eq!(
+        split,
+        [
+            target("default/", &["party"], 2),
+            // The override stays with its series although its summary matches exams
+            target("uni/", &["lecture", "lecture@20240311T100000Z"], 3),
+            target("exams/", &["exam"], 2),
+            target("empty/", &[], 1),
+        ]
+    );
+}
+
+#[test]
+fn invalid_route_conditions_are_rejected() {
+    assert!("location:Room 1".parse::<RouteMatch>().is_err());
+    assert!("summary:(".parse::<RouteMatch>().is_err());
+}
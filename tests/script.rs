@@ -0,0 +1,100 @@
+#![cfg(feature = "scripting")]
+
+use std::fs;
+
+use icalendar::{Calendar, Component, Event, EventLike, Todo};
+use nextcloud_ics_sync::script::EventScript;
+
+fn script(name: &str, source: &str) -> EventScript {
+    let path =
+        std::env::temp_dir().join(format!("nis-script-{}-{}.rhai", name, std::process::id()));
+    fs::write(&path, source).unwrap();
+    let script = EventScript::load(&path);
+    let _ = fs::remove_file(&path);
+    script.expect("script should compile")
+}
+
+fn event(uid: &str, summary: &str) -> Event {
+    Event::new()
+        .uid(uid)
+        .summary(summary)
+        .location("Room 1")
+        .done()
+}
+
+#[test]
+fn scripts_modify_and_drop_events() {
+    let script = script(
+        "modify",
+        r#"
+        fn process(event) {
+            if event.SUMMARY.starts_with("Private") {
+                return false;
+            }
+            if event.SUMMARY == "Lunch" {
+                return ();
+            }
+            if event.SUMMARY == "Standup" {
+                return true;
+            }
+            #{ summary: "[Work] " + event.SUMMARY, LOCATION: () }
+        }
+        "#,
+    );
+
+    let mut calendar: Calendar = [
+        event("review", "Review"),
+        event("doctor", "Private: Doctor"),
+        event("lunch", "Lunch"),
+        event("standup", "Standup"),
+    ]
+    .into_iter()
+    .collect();
+    calendar.push(Todo::new().uid("todo").summary("Private: Taxes").done());
+
+    let calendar = script.apply_to_calendar(calendar).unwrap();
+    let events: Vec<&Event> = calendar
+        .components
+        .iter()
+        .filter_map(|component| component.as_event())
+        .collect();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].get_summary(), Some("[Work] Review"));
+    assert_eq!(events[0].get_location(), None);
+    assert_eq!(events[1].get_summary(), Some("Standup"));
+    assert_eq!(events[1].get_location(), Some("Room 1"));
+    // Only events are passed to the script
+    assert_eq!(calendar.components.len(), 3);
+}
+
+#[test]
+fn script_errors_fail_the_sync() {
+    let script = script(
+        "error",
+        r#"fn process(event) { if event.SUMMARY == "Broken" { throw "no room"; } 42 }"#,
+    );
+
+    let error = script.apply(event("broken", "Broken")).unwrap_err();
+    let message = format!("{:#}", error);
+    assert!(message.contains("UID: broken"), "{}", message);
+    assert!(message.contains("no room"), "{}", message);
+
+    let error = script.apply(event("other", "Other")).unwrap_err();
+    assert!(
+        error.to_string().contains("must return a bool or a map"),
+        "{}",
+        error
+    );
+
+    let path = std::env::temp_dir().join(format!("nis-script-invalid-{}.rhai", std::process::id()));
+    fs::write(&path, "fn process(event) {").unwrap();
+    let error = EventScript::load(&path)
+        .err()
+        .expect("script should not compile");
+    assert!(
+        error.to_string().contains("Failed to compile script"),
+        "{}",
+        error
+    );
+    let _ = fs::remove_file(&path);
+}
@@ -0,0 +1,38 @@
+#![cfg(unix)]
+
+use nextcloud_ics_sync::config;
+
+// Changes the environment of the process, so it is the only test of its binary
+#[test]
+fn credentials_are_read_from_commands() {
+    // Only the first line of the output is the secret
+    unsafe {
+        std::env::set_var(
+            "NEXTCLOUD_PASSWORD_CMD",
+            "printf 'app-password\\nuser: me\\n'",
+        )
+    };
+    assert_eq!(config::load_nextcloud_password().unwrap(), "app-password");
+
+    unsafe { std::env::set_var("NEXTCLOUD_PASSWORD_CMD", "echo app-password; exit 3") };
+    let error = config::load_nextcloud_password().unwrap_err();
+    assert!(error.to_string().contains("exited with"), "{:#}", error);
+
+    unsafe { std::env::set_var("NEXTCLOUD_PASSWORD_CMD", "true") };
+    let error = config::load_nextcloud_password().unwrap_err();
+    assert!(error.to_string().contains("printed nothing"), "{:#}", error);
+
+    unsafe { std::env::set_var("NEXTCLOUD_PASSWORD", "other-password") };
+    let error = config::load_nextcloud_password().unwrap_err();
+    assert!(
+        error.to_string().contains("NEXTCLOUD_PASSWORD_CMD"),
+        "{:#}",
+        error
+    );
+    unsafe { std::env::remove_var("NEXTCLOUD_PASSWORD") };
+    unsafe { std::env::remove_var("NEXTCLOUD_PASSWORD_CMD") };
+
+    // Other settings never run commands
+    unsafe { std::env::set_var("CALENDAR_ID_CMD", "echo work") };
+    assert!(config::load_calendar_id().is_err());
+}
@@ -0,0 +1,38 @@
+use std::fs;
+
+use nextcloud_ics_sync::config;
+
+// Changes the environment of the process, so it is the only test of its binary
+#[test]
+fn credentials_are_read_from_files() {
+    let dir = std::env::temp_dir().join(format!("nis-secret-files-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("nextcloud_password");
+    unsafe { std::env::set_var("NEXTCLOUD_PASSWORD_FILE", &path) };
+
+    // A trailing newline, as left by most editors, is not part of the secret
+    fs::write(&path, "app-password\n").unwrap();
+    assert_eq!(config::load_nextcloud_password().unwrap(), "app-password");
+    fs::write(&path, "app-password\r\n").unwrap();
+    assert_eq!(config::load_nextcloud_password().unwrap(), "app-password");
+
+    unsafe { std::env::set_var("NEXTCLOUD_PASSWORD", "other-password") };
+    let error = config::load_nextcloud_password().unwrap_err();
+    assert!(
+        error.to_string().contains("NEXTCLOUD_PASSWORD_FILE"),
+        "{:#}",
+        error
+    );
+    unsafe { std::env::remove_var("NEXTCLOUD_PASSWORD") };
+
+    fs::remove_file(&path).unwrap();
+    assert!(config::load_nextcloud_password().is_err());
+
+    // Other settings are never read from files
+    let calendar_id = dir.join("calendar_id");
+    fs::write(&calendar_id, "work").unwrap();
+    unsafe { std::env::set_var("CALENDAR_ID_FILE", &calendar_id) };
+    assert!(config::load_calendar_id().is_err());
+
+    let _ = fs::remove_dir_all(&dir);
+}
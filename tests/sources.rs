@@ -0,0 +1,416 @@
+use std::{fs, time::Duration};
+
+use anyhow::{Result, anyhow};
+use futures::future::BoxFuture;
+use icalendar::{Calendar, Component, Event, EventLike};
+use nextcloud_ics_sync::{
+    http::Client,
+    source::{
+        CalendarSource, CsvLocation, CsvMapping, CsvSource, ExecSource, FallbackSource, IcsSource,
+        JsonApiSource, JsonMapping, SourceCache, SourceRequest,
+    },
+    test_util::MockNextcloud,
+};
+
+/// Returns a calendar with one event of the given summary, none or an error.
+struct StaticSource(Option<Option<&'static str>>);
+
+impl CalendarSource for StaticSource {
+    fn fetch_calendar<'a>(&'a self, _client: &'a Client) -> BoxFuture<'a, Result<Calendar>> {
+        Box::pin(async move {
+            match self.0 {
+                Some(Some(summary)) => Ok(Calendar::new()
+                    .push(Event::new().uid(summary).summary(summary).done())
+                    .done()),
+                Some(None) => Ok(Calendar::new()),
+                None => Err(anyhow!("connection refused")),
+            }
+        })
+    }
+}
+
+fn fallback(sources: &[Option<Option<&'static str>>]) -> FallbackSource {
+    FallbackSource {
+        sources: sources
+            .iter()
+            .enumerate()
+            .map(|(index, source)| {
+                (
+                    format!("https://mirror{}.example.com/feed.ics", index),
+                    Box::new(StaticSource(*source)) as Box<dyn CalendarSource>,
+                )
+            })
+            .collect(),
+    }
+}
+
+#[tokio::test]
+async fn fallback_urls_are_tried_until_one_returns_events() {
+    let client = Client::new();
+    let summary = |calendar: Calendar| {
+        calendar.components[0]
+            .as_event()
+            .and_then(|event| event.get_summary().map(String::from))
+    };
+
+    let calendar = fallback(&[Some(Some("primary")), Some(Some("mirror"))])
+        .fetch_calendar(&client)
+        .await
+        .unwrap();
+    assert_eq!(summary(calendar).as_deref(), Some("primary"));
+
+    let calendar = fallback(&[None, Some(None), Some(Some("mirror"))])
+        .fetch_calendar(&client)
+        .await
+        .unwrap();
+    assert_eq!(summary(calendar).as_deref(), Some("mirror"));
+
+    let error = fallback(&[None, Some(None)])
+        .fetch_calendar(&client)
+        .await
+        .unwrap_err();
+    assert!(
+        format!("{:#}", error).contains("empty calendar"),
+        "{:#}",
+        error
+    );
+}
+
+#[test]
+fn cached_source_is_loaded_until_it_expires() {
+    let dir = std::env::temp_dir().join(format!("nis-source-cache-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let mut cache = SourceCache {
+        path: dir.join("sources").join("feed.ics"),
+        max_age: Duration::from_secs(60),
+    };
+    assert!(cache.load().is_err());
+
+    let calendar = Calendar::new()
+        .push(Event::new().uid("lecture").summary("Lecture").done())
+        .done();
+    cache.store(&calendar).unwrap();
+    let (cached, _) = cache.load().unwrap();
+    let event = cached.components[0].as_event().unwrap();
+    assert_eq!(event.get_uid(), Some("lecture"));
+    assert_eq!(event.get_summary(), Some("Lecture"));
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&cache.path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    cache.max_age = Duration::ZERO;
+    std::thread::sleep(Duration::from_millis(10));
+    let error = cache.load().unwrap_err();
+    assert!(
+        error.to_string().contains("SOURCE_CACHE_MAX_AGE_SECS"),
+        "{}",
+        error
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+fn source_request(url: String) -> SourceRequest {
+    SourceRequest {
+        url,
+        username: None,
+        password: None,
+        bearer_token: None,
+        headers: Vec::new(),
+        oauth: None,
+    }
+}
+
+fn json_mapping() -> JsonMapping {
+    JsonMapping {
+        events: ".data.items[]".to_string(),
+        uid: Some(".id".to_string()),
+        summary: ".title".to_string(),
+        start: ".start.dateTime".to_string(),
+        end: Some(".end.dateTime".to_string()),
+        location: Some(".place.rooms[0]".to_string()),
+        description: None,
+        last_modified: Some(".updated".to_string()),
+    }
+}
+
+#[tokio::test]
+async fn bearer_tokens_and_custom_headers_are_sent_to_the_feed() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start("alice", "app-password").await;
+    nextcloud
+        .mock_protected_feed(
+            "/feed.ics",
+            &[
+                ("Authorization", "Bearer feed-token"),
+                ("X-Api-Key", "api-key"),
+            ],
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VEVENT\r\nUID:standup\r\n\
+             DTSTART:20240304T090000Z\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n",
+        )
+        .await;
+
+    let source = |bearer_token: Option<&str>| IcsSource {
+        request: SourceRequest {
+            bearer_token: bearer_token.map(str::to_string),
+            headers: vec![("X-Api-Key".to_string(), "api-key".to_string())],
+            ..source_request(format!("{}/feed.ics", nextcloud.url()))
+        },
+        compat_modes: Vec::new(),
+    };
+    let calendar = source(Some("feed-token"))
+        .fetch_calendar(&client)
+        .await
+        .expect("the feed should accept the token");
+    assert_eq!(calendar.components.len(), 1);
+
+    // Without the token the feed isn't served
+    assert!(source(None).fetch_calendar(&client).await.is_err());
+}
+
+#[tokio::test]
+async fn credentials_are_only_sent_to_mirrors_of_the_same_origin() {
+    let client = Client::new();
+    let primary = MockNextcloud::start("alice", "app-password").await;
+    let elsewhere = MockNextcloud::start("alice", "app-password").await;
+    let feed = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VEVENT\r\nUID:standup\r\n\
+                DTSTART:20240304T090000Z\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+    primary
+        .mock_protected_feed(
+            "/mirror.ics",
+            &[("Authorization", "Bearer feed-token")],
+            feed,
+        )
+        .await;
+    elsewhere
+        .mock_feed("/mirror.ics", "text/calendar", feed)
+        .await;
+
+    let request = SourceRequest {
+        bearer_token: Some("feed-token".to_string()),
+        headers: vec![("X-Api-Key".to_string(), "api-key".to_string())],
+        ..source_request(format!("{}/feed.ics", primary.url()))
+    };
+    for mirror in [&primary, &elsewhere] {
+        IcsSource {
+            request: request.for_mirror(&format!("{}/mirror.ics", mirror.url())),
+            compat_modes: Vec::new(),
+        }
+        .fetch_calendar(&client)
+        .await
+        .expect("the mirror should be fetched");
+    }
+
+    let received = elsewhere.received("GET").await;
+    assert_eq!(received.len(), 1);
+    assert!(!received[0].headers.contains_key("authorization"));
+    assert!(!received[0].headers.contains_key("x-api-key"));
+}
+
+#[tokio::test]
+async fn json_api_entries_are_mapped_to_events() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start("alice", "app-password").await;
+    nextcloud
+        .mock_feed(
+            "/api/events",
+            "application/json",
+            r#"{"data": {"items": [
+                {
+                    "id": 42,
+                    "title": "Standup",
+                    "start": {"dateTime": "2024-03-04T09:00:00+01:00"},
+                    "end": {"dateTime": "2024-03-04T09:15:00+01:00"},
+                    "place": {"rooms": ["Room 1", "Room 2"]},
+                    "updated": "2024-03-01T12:00:00Z"
+                },
+                {"id": 43, "start": {"dateTime": "2024-03-05T09:00:00Z"}},
+                {"id": 44, "title": "Broken", "start": {"dateTime": "next tuesday"}},
+                {"id": 45, "title": "Holiday", "start": {"dateTime": "2024-03-08"}}
+            ]}}"#,
+        )
+        .await;
+
+    let source = JsonApiSource {
+        request: source_request(format!("{}/api/events", nextcloud.url())),
+        mapping: json_mapping(),
+    };
+    let calendar = source.fetch_calendar(&client).await.unwrap();
+    let events: Vec<&Event> = calendar
+        .components
+        .iter()
+        .filter_map(|component| component.as_event())
+        .collect();
+
+    // Entries without a summary or a valid start are skipped
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].get_uid(), Some("42"));
+    assert_eq!(events[0].get_summary(), Some("Standup"));
+    assert_eq!(
+        events[0].property_value("DTSTART"),
+        Some("20240304T080000Z")
+    );
+    assert_eq!(events[0].property_value("DTEND"), Some("20240304T081500Z"));
+    assert_eq!(events[0].get_location(), Some("Room 1"));
+    assert_eq!(
+        events[0].property_value("LAST-MODIFIED"),
+        Some("20240301T120000Z")
+    );
+    assert_eq!(events[1].property_value("DTSTART"), Some("20240308"));
+
+    // Without an ID path the entries are identified by their start and summary
+    let source = JsonApiSource {
+        mapping: JsonMapping {
+            uid: None,
+            ..json_mapping()
+        },
+        ..source
+    };
+    let calendar = source.fetch_calendar(&client).await.unwrap();
+    assert_eq!(
+        calendar.components[0].as_event().unwrap().get_uid(),
+        Some("2024-03-04T09:00:00+01:00-Standup")
+    );
+}
+
+#[tokio::test]
+async fn invalid_json_api_responses_fail() {
+    let client = Client::new();
+    let nextcloud = MockNextcloud::start("alice", "app-password").await;
+    nextcloud
+        .mock_feed("/api/events", "text/html", "<html>Login</html>")
+        .await;
+
+    let source = JsonApiSource {
+        request: source_request(format!("{}/api/events", nextcloud.url())),
+        mapping: json_mapping(),
+    };
+    let error = source.fetch_calendar(&client).await.unwrap_err();
+    assert!(
+        error.to_string().contains("Failed to parse JSON content"),
+        "{:#}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn csv_rows_are_mapped_to_events() {
+    let dir = std::env::temp_dir().join(format!("nis-csv-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("shifts.csv");
+    fs::write(
+        &path,
+        "Date;Shift;Start;End;Zone;Ward\n\
+         04.03.2024;{Date};04.03.2024 06:00;04.03.2024 14:00;Europe/Berlin;{Ward} 3\n\
+         05.03.2024;;05.03.2024 06:00;;;\n\
+         06.03.2024;Night;2024-03-06;;;B\n",
+    )
+    .unwrap();
+
+    let source = CsvSource {
+        location: CsvLocation::File(path),
+        mapping: CsvMapping {
+            summary: "Shift".to_string(),
+            start: "Start".to_string(),
+            end: Some("End".to_string()),
+            location: Some("Ward".to_string()),
+            description: None,
+            timezone: Some("Zone".to_string()),
+            uid_template: Some("{Date}/{Shift}/{Missing}/{Ward".to_string()),
+            date_format: Some("%d.%m.%Y %H:%M".to_string()),
+            delimiter: b';',
+        },
+    };
+    let calendar = source.fetch_calendar(&Client::new()).await.unwrap();
+    let events: Vec<&Event> = calendar
+        .components
+        .iter()
+        .filter_map(|component| component.as_event())
+        .collect();
+
+    // The row without a shift is skipped
+    assert_eq!(events.len(), 2);
+    // Placeholders in column values are not replaced again, unknown ones are kept
+    assert_eq!(
+        events[0].get_uid(),
+        Some("04.03.2024/{Date}/{Missing}/{Ward")
+    );
+    assert_eq!(events[0].get_location(), Some("{Ward} 3"));
+    assert_eq!(events[0].property_value("DTSTART"), Some("20240304T060000"));
+    assert_eq!(
+        events[0]
+            .properties()
+            .get("DTSTART")
+            .and_then(|start| start.params().get("TZID"))
+            .map(|tzid| tzid.value()),
+        Some("Europe/Berlin")
+    );
+    assert_eq!(
+        events[1].get_uid(),
+        Some("06.03.2024/Night/{Missing}/{Ward")
+    );
+    assert_eq!(events[1].property_value("DTSTART"), Some("20240306"));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+fn exec(command: &str) -> ExecSource {
+    ExecSource {
+        command: command.to_string(),
+        compat_modes: Vec::new(),
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn exec_sources_print_a_calendar() {
+    let calendar = exec(
+        "printf 'BEGIN:VCALENDAR\\r\\nBEGIN:VEVENT\\r\\nUID:backup\\r\\nSUMMARY:Backup\\r\\n\
+         END:VEVENT\\r\\nEND:VCALENDAR\\r\\n'; echo 'fetched 1 event' >&2",
+    )
+    .fetch_calendar(&Client::new())
+    .await
+    .unwrap();
+    let event = calendar.components[0].as_event().unwrap();
+    assert_eq!(event.get_uid(), Some("backup"));
+    assert_eq!(event.get_summary(), Some("Backup"));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn failing_exec_sources_abort_the_sync() {
+    let client = Client::new();
+
+    let error = exec("echo 'token expired' >&2; exit 3")
+        .fetch_calendar(&client)
+        .await
+        .unwrap_err();
+    let message = format!("{:#}", error);
+    assert!(message.contains("Source command failed"), "{}", message);
+    assert!(message.contains("token expired"), "{}", message);
+
+    let error = exec("printf '\\377\\376'")
+        .fetch_calendar(&client)
+        .await
+        .unwrap_err();
+    assert!(
+        format!("{:#}", error).contains("Invalid UTF-8"),
+        "{:#}",
+        error
+    );
+
+    let error = exec("echo 'BEGIN:VCALENDAR'; echo 'BEGIN:VEVENT'")
+        .fetch_calendar(&client)
+        .await
+        .unwrap_err();
+    assert!(
+        format!("{:#}", error).contains("Failed to parse iCalendar content"),
+        "{:#}",
+        error
+    );
+}
@@ -0,0 +1,110 @@
+use std::fs;
+
+use nextcloud_ics_sync::{
+    journal::{Action, Journal},
+    state::{self, StateLocations},
+};
+
+#[test]
+fn exported_state_is_restored_on_another_machine() {
+    let dir = std::env::temp_dir().join(format!("nis-state-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let old = StateLocations {
+        journal_dir: dir.join("old/journal"),
+        oauth_token: dir.join("old/oauth-token.json"),
+        sync_back: dir.join("old/sync-back.ics"),
+    };
+    let new = StateLocations {
+        journal_dir: dir.join("new/journal"),
+        oauth_token: dir.join("new/token.json"),
+        sync_back: dir.join("new/sync-back.ics"),
+    };
+
+    let journal = Journal::start(&old.journal_dir).expect("journal should be created");
+    journal.record("meeting", Action::Uploaded).unwrap();
+    fs::write(&old.oauth_token, r#"{"access_token":"token"}"#).unwrap();
+
+    let archive = dir.join("state.json");
+    assert_eq!(state::export(&old, &archive).unwrap(), 2);
+    assert_eq!(state::import(&new, &archive, false).unwrap(), 2);
+
+    let resumed = Journal::resume(&new.journal_dir, journal.run_id()).expect("run should resume");
+    assert!(resumed.is_confirmed("meeting", Action::Uploaded));
+    assert_eq!(
+        fs::read_to_string(&new.oauth_token).unwrap(),
+        r#"{"access_token":"token"}"#
+    );
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        for path in [&archive, &new.oauth_token] {
+            let mode = fs::metadata(path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600, "{}", path.display());
+        }
+    }
+
+    // The state of a machine that already synced is kept unless forced
+    assert!(state::import(&new, &archive, false).is_err());
+    assert!(state::import(&new, &archive, true).is_ok());
+
+    // Names escaping the journal directory are refused
+    let content = fs::read_to_string(&archive).unwrap().replacen(
+        &format!("\"{}.jsonl\"", journal.run_id()),
+        "\"../escaped.jsonl\"",
+        1,
+    );
+    fs::write(&archive, content).unwrap();
+    assert!(state::import(&new, &archive, true).is_err());
+    assert!(!dir.join("new/escaped.jsonl").exists());
+}
+
+#[test]
+fn archives_of_earlier_releases_are_imported() {
+    let dir = std::env::temp_dir().join(format!("nis-state-old-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let locations = StateLocations {
+        journal_dir: dir.join("journal"),
+        oauth_token: dir.join("oauth-token.json"),
+        sync_back: dir.join("sync-back.ics"),
+    };
+
+    // Journals written before actions were marked as pending only hold confirmations
+    let run_id = "0b6f3c52-5f1e-4a57-9d0c-6f1b2f6c2b1a";
+    let archive = dir.join("state.json");
+    fs::write(
+        &archive,
+        format!(
+            r#"{{
+  "version": 1,
+  "exported": 1767225600,
+  "files": [
+    {{
+      "kind": "journal",
+      "name": "{run_id}.jsonl",
+      "content": "{{\"uid\":\"meeting\",\"action\":\"uploaded\"}}\n{{\"uid\":\"old\",\"action\":\"deleted\"}}\n"
+    }}
+  ]
+}}"#
+        ),
+    )
+    .unwrap();
+    assert_eq!(state::import(&locations, &archive, false).unwrap(), 1);
+
+    let resumed = Journal::resume(&locations.journal_dir, run_id).expect("run should resume");
+    assert!(resumed.is_confirmed("meeting", Action::Uploaded));
+    assert!(resumed.is_confirmed("old", Action::Deleted));
+    assert_eq!(resumed.unconfirmed().count(), 0);
+
+    // Archives of newer releases may hold files this one doesn't know how to restore
+    let content =
+        fs::read_to_string(&archive)
+            .unwrap()
+            .replacen("\"version\": 1", "\"version\": 2", 1);
+    fs::write(&archive, content).unwrap();
+    let error = state::import(&locations, &archive, true).unwrap_err();
+    assert!(error.to_string().contains("newer release"), "{:#}", error);
+
+    let _ = fs::remove_dir_all(&dir);
+}
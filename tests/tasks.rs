@@ -0,0 +1,68 @@
+use icalendar::{Calendar, Component, Event};
+use nextcloud_ics_sync::{
+    http::Client,
+    tasks::{TaskMode, TaskRouting, create_tasks},
+    test_util::MockNextcloud,
+};
+use sha2::{Digest, Sha256};
+
+const TASKS: &str = "tasks";
+
+/// Name of the resource a new event is uploaded to.
+fn resource_name(uid: &str) -> String {
+    let hex: String = Sha256::digest(uid.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("{}.ics", hex)
+}
+
+#[tokio::test]
+async fn matching_events_become_tasks() {
+    let nextcloud = MockNextcloud::start("alice", "secret").await;
+    let routing = TaskRouting {
+        patterns: vec!["deadline:".to_string()],
+        tasks_calendar_url: nextcloud.calendar_url(TASKS),
+        mode: TaskMode::Instead,
+    };
+    let calendar: Calendar = [
+        Event::new()
+            .uid("report")
+            .summary("DEADLINE: Report")
+            .add_property("DTSTART", "20240301T120000Z")
+            .done(),
+        Event::new().uid("lecture").summary("Lecture").done(),
+    ]
+    .into_iter()
+    .collect();
+
+    let (calendar, tasks) = routing.split(calendar);
+
+    assert_eq!(calendar.components.len(), 1);
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0].get_uid(), Some("task-report"));
+    assert_eq!(tasks[0].property_value("DUE"), Some("20240301T120000Z"));
+
+    nextcloud.mock_uploads(TASKS).await;
+    let created = create_tasks(
+        &Client::new(),
+        "alice",
+        "secret",
+        &routing.tasks_calendar_url,
+        tasks,
+    )
+    .await
+    .expect("tasks should be created");
+
+    assert_eq!(created, 1);
+    let puts = nextcloud.received("PUT").await;
+    assert!(
+        puts[0]
+            .url
+            .path()
+            .ends_with(&format!("/tasks/{}", resource_name("task-report")))
+    );
+    // Existing tasks are never overwritten
+    assert_eq!(puts[0].headers["If-None-Match"], "*");
+    assert!(String::from_utf8_lossy(&puts[0].body).contains("BEGIN:VTODO"));
+}
@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+
+use icalendar::{Calendar, Component, Event};
+use nextcloud_ics_sync::timezone::{
+    FloatingTimes, TimezoneOptions, TzidValidation, check_tzids, convert_floating_times,
+};
+
+fn event_with_tzid(uid: &str, tzid: &str) -> Event {
+    let mut event = Event::new();
+    event.uid(uid).append_property(
+        icalendar::Property::new("DTSTART", "20240101T100000")
+            .add_parameter("TZID", tzid)
+            .done(),
+    );
+    event.done()
+}
+
+fn dtstart_tzid(calendar: &Calendar, index: usize) -> String {
+    calendar.components[index].as_event().unwrap().properties()["DTSTART"]
+        .params()
+        .get("TZID")
+        .unwrap()
+        .value()
+        .to_string()
+}
+
+#[test]
+fn repair_maps_windows_prefixed_and_own_aliases() {
+    let calendar: Calendar = [
+        event_with_tzid("windows", "W. Europe Standard Time"),
+        event_with_tzid("prefixed", "/mozilla.org/20050126_1/Europe/Paris"),
+        event_with_tzid("alias", "Company Time"),
+        event_with_tzid("valid", "America/New_York"),
+        event_with_tzid("unknown", "Nowhere Time"),
+    ]
+    .into_iter()
+    .collect();
+    let options = TimezoneOptions {
+        validation: TzidValidation::Repair,
+        aliases: HashMap::from([("Company Time".to_string(), "Europe/Vienna".to_string())]),
+        ..Default::default()
+    };
+
+    let calendar = check_tzids(calendar, &options);
+
+    assert_eq!(dtstart_tzid(&calendar, 0), "Europe/Berlin");
+    assert_eq!(dtstart_tzid(&calendar, 1), "Europe/Paris");
+    assert_eq!(dtstart_tzid(&calendar, 2), "Europe/Vienna");
+    assert_eq!(dtstart_tzid(&calendar, 3), "America/New_York");
+    assert_eq!(dtstart_tzid(&calendar, 4), "Nowhere Time");
+}
+
+#[test]
+fn floating_times_are_anchored_or_converted() {
+    let floating = || -> Calendar {
+        let event = Event::new()
+            .uid("floating")
+            .add_property("DTSTART", "20240101T100000")
+            .add_property("DTEND", "20240101")
+            .add_multi_property("EXDATE", "20240108T100000,20240115T100000")
+            .done();
+        [event].into_iter().collect()
+    };
+
+    let anchored = convert_floating_times(
+        floating(),
+        &FloatingTimes::Anchor("Europe/Berlin".to_string()),
+    );
+    assert_eq!(dtstart_tzid(&anchored, 0), "Europe/Berlin");
+    let event = anchored.components[0].as_event().unwrap();
+    assert!(event.properties()["DTEND"].params().get("TZID").is_none());
+
+    let utc = convert_floating_times(floating(), &FloatingTimes::Utc);
+    let event = utc.components[0].as_event().unwrap();
+    assert_eq!(event.property_value("DTSTART"), Some("20240101T100000Z"));
+    assert_eq!(
+        event.multi_properties()["EXDATE"][0].value(),
+        "20240108T100000Z,20240115T100000Z"
+    );
+}
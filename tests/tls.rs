@@ -0,0 +1,25 @@
+use std::path::PathBuf;
+
+use nextcloud_ics_sync::http::{self, HttpConfig};
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+#[test]
+fn client_certificates_are_loaded_from_pem() {
+    let config = HttpConfig {
+        client_certificate: Some((fixture("client.crt"), fixture("client.key"))),
+        ..Default::default()
+    };
+    http::init(&config).expect("client should be built with the certificate");
+
+    let swapped = HttpConfig {
+        client_certificate: Some((fixture("client.key"), fixture("client.crt"))),
+        ..Default::default()
+    };
+    let error = http::init(&swapped).expect_err("swapped files should be rejected");
+    assert!(error.to_string().contains("PKCS#8"), "{}", error);
+}
@@ -0,0 +1,90 @@
+//! Installs a logger for the whole process, so these tests run in their own binary.
+
+use std::sync::Mutex;
+
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use log::{LevelFilter, Log, Metadata, Record};
+use nextcloud_ics_sync::{
+    http::{self, Client, HttpConfig},
+    ics_parser::fetch_and_parse_calendar,
+    test_util::MockNextcloud,
+};
+
+const FEED: &str =
+    "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//Example//Feed//EN\r\nEND:VCALENDAR\r\n";
+
+/// Keeps the messages logged by the `http` module.
+struct Capture(Mutex<Vec<String>>);
+
+impl Log for Capture {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.target() == "nextcloud_ics_sync::http"
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            self.0.lock().unwrap().push(record.args().to_string());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGS: Capture = Capture(Mutex::new(Vec::new()));
+
+#[tokio::test]
+async fn traces_are_truncated_and_redacted() {
+    log::set_logger(&LOGS).unwrap();
+    log::set_max_level(LevelFilter::Trace);
+
+    let nextcloud = MockNextcloud::start("alice", "app-password").await;
+    nextcloud
+        .mock_feed("/feed.ics", "text/calendar", FEED)
+        .await;
+    let url = format!("{}/feed.ics", nextcloud.url());
+    let fetch = |client: Client| {
+        let url = url.clone();
+        async move {
+            fetch_and_parse_calendar(
+                &client,
+                &url,
+                Some("alice".to_string()),
+                Some("feed-password".to_string()),
+                None,
+                &[],
+                &[],
+            )
+            .await
+            .expect("the feed should be fetched");
+        }
+    };
+
+    // Only clients built with tracing log their requests
+    fetch(Client::new()).await;
+    assert!(LOGS.0.lock().unwrap().is_empty());
+
+    let client = http::init(&HttpConfig {
+        trace_body_bytes: Some(32),
+        ..Default::default()
+    })
+    .unwrap();
+    fetch(client).await;
+
+    let logs = LOGS.0.lock().unwrap().join("\n");
+    assert!(logs.contains(&format!("GET {} -> 200 OK", url)), "{}", logs);
+    assert!(logs.contains("authorization: [REDACTED]"), "{}", logs);
+    assert!(
+        !logs.contains(&BASE64.encode("alice:feed-password")),
+        "{}",
+        logs
+    );
+    assert!(
+        logs.contains(&format!(
+            "Response body:\n{}... ({} bytes total)",
+            &FEED[..32],
+            FEED.len()
+        )),
+        "{}",
+        logs
+    );
+}
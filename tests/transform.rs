@@ -0,0 +1,410 @@
+use icalendar::{Calendar, CalendarComponent, Component, Event, EventLike};
+use nextcloud_ics_sync::transform::{
+    self, AttachmentPolicy, EventLimits, LimitPolicy, SchedulingPolicy, Transforms,
+    Transliteration, XPropertyRules,
+};
+
+fn meeting() -> Calendar {
+    let mut event = Event::new();
+    event
+        .uid("meeting")
+        .add_property("ORGANIZER", "mailto:boss@example.com")
+        .add_multi_property("ATTENDEE", "mailto:alice@example.com")
+        .add_multi_property("ATTENDEE", "mailto:bob@example.com");
+    [event.done()].into_iter().collect()
+}
+
+fn transform(scheduling: SchedulingPolicy) -> Event {
+    first_event(
+        Transforms {
+            scheduling,
+            ..Default::default()
+        }
+        .apply(meeting()),
+    )
+}
+
+fn first_event(calendar: Calendar) -> Event {
+    calendar
+        .components
+        .into_iter()
+        .find_map(|component| match component {
+            CalendarComponent::Event(event) => Some(event),
+            _ => None,
+        })
+        .expect("event should be kept")
+}
+
+#[test]
+fn participant_filter_keeps_events_of_the_given_addresses() {
+    let kept = |participants: &[&str]| {
+        Transforms {
+            participants: participants.iter().map(|p| p.to_string()).collect(),
+            ..Default::default()
+        }
+        .apply(meeting())
+        .components
+        .len()
+    };
+
+    assert_eq!(kept(&[]), 1);
+    assert_eq!(kept(&["Alice@Example.com"]), 1);
+    assert_eq!(kept(&["carol@example.com", "boss@example.com"]), 1);
+    assert_eq!(kept(&["carol@example.com"]), 0);
+}
+
+#[test]
+fn scheduling_properties_are_stripped_by_default() {
+    let event = transform(SchedulingPolicy::default());
+
+    assert!(event.property_value("ORGANIZER").is_none());
+    assert!(!event.multi_properties().contains_key("ATTENDEE"));
+}
+
+#[test]
+fn client_scheduling_marks_every_property() {
+    let event = transform(SchedulingPolicy::Client);
+
+    let organizer = &event.properties()["ORGANIZER"];
+    let attendees = &event.multi_properties()["ATTENDEE"];
+    assert_eq!(attendees.len(), 2);
+    for property in attendees.iter().chain([organizer]) {
+        assert_eq!(property.params()["SCHEDULE-AGENT"].value(), "CLIENT");
+    }
+}
+
+#[test]
+fn urls_only_drops_inline_attachments() {
+    let mut event = Event::new();
+    event.uid("with-files");
+    event.append_multi_property(icalendar::Property::new(
+        "ATTACH",
+        "https://example.com/agenda.pdf",
+    ));
+    event.append_multi_property(
+        icalendar::Property::new("ATTACH", "SGVsbG8gV29ybGQh")
+            .add_parameter("ENCODING", "BASE64")
+            .add_parameter("VALUE", "BINARY")
+            .done(),
+    );
+    let calendar: Calendar = [event.done()].into_iter().collect();
+
+    let event = first_event(
+        Transforms {
+            attachments: AttachmentPolicy::UrlsOnly,
+            ..Default::default()
+        }
+        .apply(calendar),
+    );
+
+    let attachments = &event.multi_properties()["ATTACH"];
+    assert_eq!(attachments.len(), 1);
+    assert_eq!(attachments[0].value(), "https://example.com/agenda.pdf");
+}
+
+#[test]
+fn transliterates_cyrillic_titles() {
+    assert_eq!(
+        transform::transliterate("Щедрый Вечер в Києві", Transliteration::Cyrillic),
+        "Shchedryy Vecher v Kiyevi"
+    );
+}
+
+#[test]
+fn limits_truncate_or_skip_pathological_events() {
+    let huge = || -> Calendar {
+        let mut event = Event::new();
+        event
+            .uid("huge")
+            .description(&"ä".repeat(1000))
+            .add_multi_property("EXDATE", "20240101T100000Z,20240102T100000Z")
+            .add_multi_property("EXDATE", "20240103T100000Z");
+        [event.done()].into_iter().collect()
+    };
+    let limits = |policy| Transforms {
+        limits: EventLimits {
+            max_text_bytes: Some(100),
+            max_exdates: Some(2),
+            policy,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let truncated = first_event(limits(LimitPolicy::Truncate).apply(huge()));
+    let description = truncated.get_description().unwrap();
+    assert!(description.len() <= 100 && description.ends_with('…'));
+    let exdates: Vec<&str> = truncated.multi_properties()["EXDATE"]
+        .iter()
+        .map(|exdate| exdate.value())
+        .collect();
+    assert_eq!(exdates, ["20240102T100000Z", "20240103T100000Z"]);
+
+    assert!(
+        limits(LimitPolicy::Skip)
+            .apply(huge())
+            .components
+            .is_empty()
+    );
+}
+
+#[test]
+fn end_is_computed_from_duration_or_defaults() {
+    let calendar: Calendar = [
+        Event::new()
+            .uid("duration")
+            .add_property("DTSTART", "20240101T100000Z")
+            .add_property("DURATION", "PT1H30M")
+            .done(),
+        Event::new()
+            .uid("all-day")
+            .starts(chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap())
+            .done(),
+        // Ends after the last date chrono can represent
+        Event::new()
+            .uid("endless")
+            .add_property("DTSTART", "20240101T100000Z")
+            .add_property("DURATION", "P100000000W")
+            .done(),
+    ]
+    .into_iter()
+    .collect();
+
+    let calendar = Transforms {
+        normalize_end: true,
+        ..Default::default()
+    }
+    .apply(calendar);
+
+    let events: Vec<&Event> = calendar
+        .components
+        .iter()
+        .filter_map(|component| component.as_event())
+        .collect();
+    assert_eq!(events[0].property_value("DTEND"), Some("20240101T113000Z"));
+    assert_eq!(events[0].property_value("DURATION"), None);
+    assert_eq!(events[1].property_value("DTEND"), Some("20240102"));
+    assert_eq!(events[2].property_value("DTEND"), None);
+    assert_eq!(events[2].property_value("DURATION"), Some("P100000000W"));
+}
+
+#[test]
+fn durations_are_parsed() {
+    assert_eq!(
+        transform::parse_duration("P1W"),
+        Some(chrono::TimeDelta::weeks(1))
+    );
+    assert_eq!(
+        transform::parse_duration("-P1DT2H"),
+        Some(-chrono::TimeDelta::hours(26))
+    );
+    assert_eq!(transform::parse_duration("P"), None);
+    assert_eq!(transform::parse_duration("PT5X"), None);
+
+    // Too long to represent, instead of overflowing
+    assert_eq!(transform::parse_duration("P99999999999999W"), None);
+    assert_eq!(transform::parse_duration("PT9223372036854775807S"), None);
+    assert_eq!(
+        transform::parse_duration("P106000000000DT100000000000000S"),
+        None
+    );
+}
+
+#[test]
+fn method_is_stripped_and_cancellations_kept() {
+    let mut calendar: Calendar = [Event::new()
+        .uid("cancelled")
+        .append_multi_property(
+            icalendar::Property::new("ATTENDEE", "mailto:alice@example.com")
+                .add_parameter("RSVP", "TRUE")
+                .add_parameter("CN", "Alice")
+                .done(),
+        )
+        .done()]
+    .into_iter()
+    .collect();
+    calendar.append_property(("METHOD", "CANCEL"));
+
+    let calendar = Transforms {
+        scheduling: SchedulingPolicy::Keep,
+        ..Default::default()
+    }
+    .apply(calendar);
+
+    assert!(!calendar.properties.iter().any(|p| p.key() == "METHOD"));
+    let event = first_event(calendar);
+    assert_eq!(event.property_value("STATUS"), Some("CANCELLED"));
+    let attendee = &event.multi_properties()["ATTENDEE"][0];
+    assert!(attendee.params().get("RSVP").is_none());
+    assert!(attendee.params().get("CN").is_some());
+}
+
+#[test]
+fn x_properties_are_kept_dropped_and_renamed() {
+    let source = Event::new()
+        .uid("x-props")
+        .add_property("X-MICROSOFT-CDO-BUSYSTATUS", "FREE")
+        .add_property("X-APPLE-TRAVEL-ADVISORY-BEHAVIOR", "AUTOMATIC")
+        .add_property("X-APPLE-STRUCTURED-LOCATION", "geo:1,2")
+        .add_property("X-FEED-CATEGORY", "lecture")
+        .add_property("X-TRACKING", "abc")
+        .done();
+    // Nothing is changed by default
+    let unchanged = first_event(Transforms::default().apply(Calendar::from([source.clone()])));
+    assert_eq!(unchanged.property_value("TRANSP"), None);
+    assert_eq!(
+        unchanged.property_value("X-MICROSOFT-CDO-BUSYSTATUS"),
+        Some("FREE")
+    );
+
+    let rules = XPropertyRules {
+        keep: Vec::new(),
+        drop: vec!["X-APPLE-*".to_string(), "X-TRACKING".to_string()],
+        rename: vec![
+            (
+                "X-MICROSOFT-CDO-BUSYSTATUS".to_string(),
+                "TRANSP".to_string(),
+            ),
+            ("X-FEED-CATEGORY".to_string(), "X-CATEGORY".to_string()),
+        ],
+    };
+    let event = first_event(
+        Transforms {
+            x_properties: rules,
+            ..Default::default()
+        }
+        .apply(Calendar::from([source])),
+    );
+
+    assert_eq!(event.property_value("TRANSP"), Some("TRANSPARENT"));
+    assert_eq!(event.property_value("X-CATEGORY"), Some("lecture"));
+    for dropped in [
+        "X-MICROSOFT-CDO-BUSYSTATUS",
+        "X-APPLE-TRAVEL-ADVISORY-BEHAVIOR",
+        "X-APPLE-STRUCTURED-LOCATION",
+        "X-FEED-CATEGORY",
+        "X-TRACKING",
+    ] {
+        assert_eq!(event.property_value(dropped), None, "{}", dropped);
+    }
+}
+
+#[test]
+fn busy_shadow_keeps_only_times() {
+    let calendar: Calendar = [Event::new()
+        .uid("private")
+        .summary("Doctor")
+        .description("Bring papers")
+        .location("Clinic")
+        .add_property("DTSTART", "20240101T100000Z")
+        .add_property("DTEND", "20240101T110000Z")
+        .add_property("RRULE", "FREQ=WEEKLY")
+        .alarm(icalendar::Alarm::display(
+            "Reminder",
+            -chrono::Duration::minutes(10),
+        ))
+        .done()]
+    .into_iter()
+    .collect();
+
+    let event = first_event(
+        Transforms {
+            busy_shadow: Some("Busy".to_string()),
+            ..Default::default()
+        }
+        .apply(calendar),
+    );
+
+    assert_eq!(event.get_summary(), Some("Busy"));
+    assert_eq!(event.get_uid(), Some("private"));
+    assert_eq!(event.property_value("RRULE"), Some("FREQ=WEEKLY"));
+    assert_eq!(event.property_value("DTEND"), Some("20240101T110000Z"));
+    assert_eq!(event.get_description(), None);
+    assert_eq!(event.get_location(), None);
+    assert!(event.components().is_empty());
+}
+
+#[test]
+fn annotations_fill_week_and_day_of_runs() {
+    let day = |uid: &str, date: &str| {
+        Event::new()
+            .uid(uid)
+            .summary("Project week")
+            .add_property("DTSTART", format!("{}T080000Z", date))
+            .add_property("DTEND", format!("{}T150000Z", date))
+            .done()
+    };
+    let calendar: Calendar = [
+        day("mon", "20240108"),
+        day("tue", "20240109"),
+        day("wed", "20240110"),
+        Event::new()
+            .uid("trip")
+            .description("Bring boots")
+            .starts(chrono::NaiveDate::from_ymd_opt(2024, 1, 12).unwrap())
+            .ends(chrono::NaiveDate::from_ymd_opt(2024, 1, 14).unwrap())
+            .done(),
+    ]
+    .into_iter()
+    .collect();
+
+    let calendar = Transforms {
+        annotation: Some("Week {week}/{year}: day {day} of {days}".to_string()),
+        ..Default::default()
+    }
+    .apply(calendar);
+
+    let descriptions: Vec<&str> = calendar
+        .components
+        .iter()
+        .filter_map(|component| component.as_event()?.get_description())
+        .collect();
+    assert_eq!(
+        descriptions,
+        [
+            "Week 2/2024: day 1 of 3",
+            "Week 2/2024: day 2 of 3",
+            "Week 2/2024: day 3 of 3",
+            "Bring boots\n\nWeek 2/2024: day 1 of 2",
+        ]
+    );
+}
+
+#[test]
+fn duration_filters_drop_markers_and_banners() {
+    let day = |day| chrono::NaiveDate::from_ymd_opt(2024, 3, day).unwrap();
+    let calendar: Calendar = [
+        Event::new()
+            .uid("marker")
+            .starts(day(4).and_hms_opt(9, 0, 0).unwrap())
+            .done(),
+        Event::new()
+            .uid("lecture")
+            .starts(day(4).and_hms_opt(10, 0, 0).unwrap())
+            .add_property("DURATION", "PT90M")
+            .done(),
+        Event::new().uid("holiday").starts(day(5)).done(),
+        Event::new()
+            .uid("semester")
+            .starts(day(1))
+            .ends(chrono::NaiveDate::from_ymd_opt(2024, 7, 31).unwrap())
+            .done(),
+    ]
+    .into_iter()
+    .collect();
+
+    let calendar = Transforms {
+        normalize_end: true,
+        min_duration: transform::parse_duration("PT1M"),
+        max_duration: transform::parse_duration("P2W"),
+        ..Default::default()
+    }
+    .apply(calendar);
+
+    let uids: Vec<&str> = calendar
+        .components
+        .iter()
+        .filter_map(|component| component.as_event()?.get_uid())
+        .collect();
+    assert_eq!(uids, ["lecture", "holiday"]);
+}
@@ -0,0 +1,92 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use futures::future::BoxFuture;
+use icalendar::{Calendar, Component, Event};
+use nextcloud_ics_sync::{
+    SyncOptions,
+    http::{self, HttpConfig, HttpRequest, HttpResponse, HttpTransport},
+    sync_calendar,
+};
+use sha2::{Digest, Sha256};
+
+/// Name of the resource a new event is uploaded to.
+fn resource_name(uid: &str) -> String {
+    let hex: String = Sha256::digest(uid.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("{}.ics", hex)
+}
+
+/// Answers every request with the next status, recording the requests.
+struct MockTransport {
+    statuses: Mutex<Vec<u16>>,
+    requests: Mutex<Vec<HttpRequest>>,
+}
+
+impl HttpTransport for MockTransport {
+    fn execute(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse>> {
+        Box::pin(async move {
+            let status = self.statuses.lock().unwrap().remove(0);
+            self.requests.lock().unwrap().push(request);
+            Ok(::http::Response::builder()
+                .status(status)
+                .body(Vec::new())?)
+        })
+    }
+}
+
+#[tokio::test]
+async fn requests_are_sent_through_the_transport() {
+    let transport = Arc::new(MockTransport {
+        statuses: Mutex::new(vec![503, 201]),
+        requests: Mutex::new(Vec::new()),
+    });
+    let plain = http::init(&HttpConfig {
+        retries: 1,
+        ..Default::default()
+    })
+    .unwrap();
+    let client = plain.clone().with_transport(transport.clone());
+
+    let source = || -> Calendar {
+        let mut event = Event::new();
+        event
+            .uid("meeting")
+            .summary("Meeting")
+            .add_property("DTSTART", "20240101T100000Z");
+        [event.done()].into_iter().collect()
+    };
+    let options = SyncOptions::default();
+    let sync = |client| {
+        sync_calendar(
+            client,
+            "alice",
+            "app-password",
+            "https://cloud.invalid/remote.php/dav/calendars/alice/work/",
+            source(),
+            Vec::new(),
+            &options,
+        )
+    };
+    let report = sync(&client).await;
+    assert_eq!(report.expect("sync should succeed").uploaded, 1);
+    // Other clients, even clones made before, still connect themselves
+    assert!(sync(&plain).await.is_err());
+
+    // The temporary failure was retried through the transport
+    let requests = transport.requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    let request = &requests[1];
+    assert_eq!(request.method(), "PUT");
+    assert_eq!(
+        request.uri().to_string(),
+        format!(
+            "https://cloud.invalid/remote.php/dav/calendars/alice/work/{}",
+            resource_name("meeting")
+        )
+    );
+    assert!(request.headers().contains_key("authorization"));
+    assert!(String::from_utf8_lossy(request.body()).contains("UID:meeting"));
+}
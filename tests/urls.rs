@@ -0,0 +1,74 @@
+use nextcloud_ics_sync::urls;
+
+#[test]
+fn nextcloud_links_are_reduced_to_the_instance_url() {
+    for input in [
+        "https://cloud.example.com",
+        "https://cloud.example.com/",
+        " cloud.example.com ",
+        "https://cloud.example.com/apps/calendar/dayGridMonth/now",
+        "https://cloud.example.com/index.php/apps/calendar/timeGridWeek/2024-01-01",
+        "https://cloud.example.com/remote.php/dav/calendars/alice/work/",
+    ] {
+        assert_eq!(
+            urls::nextcloud_url(input).unwrap(),
+            "https://cloud.example.com",
+            "{}",
+            input
+        );
+    }
+
+    // Instances in a subdirectory keep it
+    assert_eq!(
+        urls::nextcloud_url("http://home.lan:8080/nextcloud/apps/calendar/").unwrap(),
+        "http://home.lan:8080/nextcloud"
+    );
+    assert!(urls::nextcloud_url("ftp://cloud.example.com").is_err());
+    assert!(urls::nextcloud_url("https://").is_err());
+}
+
+#[test]
+fn calendar_ids_and_source_urls_are_fixed_up() {
+    assert_eq!(urls::calendar_id("work"), "work");
+    assert_eq!(urls::calendar_id("/work/"), "work");
+    assert_eq!(
+        urls::calendar_id("https://cloud.example.com/remote.php/dav/calendars/alice/work/"),
+        "work"
+    );
+
+    assert_eq!(
+        urls::source_url("webcal://example.com/feed.ics"),
+        "https://example.com/feed.ics"
+    );
+    assert_eq!(
+        urls::source_url(" https://example.com/feed.ics\n"),
+        "https://example.com/feed.ics"
+    );
+    assert_eq!(urls::source_url("exec://./feed.sh"), "exec://./feed.sh");
+}
+
+#[test]
+fn calendar_urls_are_collections() {
+    assert_eq!(
+        urls::calendar_collection_url(
+            "https://cloud.example.com/remote.php/dav/calendars/alice/family_shared_by_bob"
+        )
+        .unwrap(),
+        "https://cloud.example.com/remote.php/dav/calendars/alice/family_shared_by_bob/"
+    );
+    assert!(urls::calendar_collection_url("mailto:alice@example.com").is_err());
+}
+
+#[test]
+fn only_urls_without_a_scheme_get_https() {
+    assert_eq!(
+        urls::nextcloud_url("cloud.example.com:8443/nextcloud").unwrap(),
+        "https://cloud.example.com:8443/nextcloud"
+    );
+    assert_eq!(
+        urls::nextcloud_url("localhost:8080").unwrap(),
+        "https://localhost:8080"
+    );
+    assert!(urls::nextcloud_url("mailto:alice@example.com").is_err());
+    assert!(urls::nextcloud_url("webdav:cloud.example.com").is_err());
+}
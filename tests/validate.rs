@@ -0,0 +1,45 @@
+use icalendar::{Calendar, Component, Event};
+use nextcloud_ics_sync::validate::{normalize_payload, validate_payload};
+
+#[test]
+fn serialized_events_are_valid() {
+    let event = Event::new()
+        .uid("valid")
+        .summary(
+            &"A summary long enough to be folded by the serializer, with ; and , in it. ".repeat(5),
+        )
+        .add_property("DTSTART", "20240101T100000Z")
+        .done();
+    let calendar: Calendar = [event].into_iter().collect();
+
+    validate_payload(&calendar.to_string()).expect("payload should be valid");
+}
+
+#[test]
+fn errors_name_the_offending_property() {
+    let missing_start = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:a\r\nDTSTAMP:20240101T000000Z\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+    let error = validate_payload(missing_start).unwrap_err().to_string();
+    assert!(error.contains("DTSTART"), "{}", error);
+
+    let raw_newline = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:a\r\nX-ALT-DESC:first\nsecond\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+    let error = validate_payload(raw_newline).unwrap_err().to_string();
+    assert!(error.contains("X-ALT-DESC"), "{}", error);
+}
+
+#[test]
+fn normalization_refolds_and_escapes_line_breaks() {
+    let long_value = "ä".repeat(100);
+    let payload = format!(
+        "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:a\r\nDTSTAMP:20240101T000000Z\r\nDTSTART:20240101T100000Z\r\nX-NOTE:{}\r\nX-ALT-DESC:first\nsecond\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n",
+        long_value
+    );
+
+    let normalized = normalize_payload(&payload);
+
+    validate_payload(&normalized).expect("normalized payload should be valid");
+    assert!(normalized.contains("X-ALT-DESC:first\\nsecond\r\n"));
+    assert_eq!(
+        normalized.replace("\r\n ", ""),
+        payload.replace("first\nsecond", "first\\nsecond")
+    );
+}
@@ -0,0 +1,67 @@
+use icalendar::{Calendar, Component, Event, Property};
+use nextcloud_ics_sync::{
+    nextcloud::api::CalendarObject,
+    verify::{Discrepancy, content_hash, verify_uploads},
+};
+
+fn stored(events: Vec<Event>) -> Vec<CalendarObject> {
+    events
+        .into_iter()
+        .map(|event| CalendarObject {
+            href: format!("{}.ics", event.get_uid().unwrap()),
+            etag: None,
+            calendar: Calendar::new().push(event).done(),
+        })
+        .collect()
+}
+
+fn attendee(first: (&str, &str), second: (&str, &str)) -> Property {
+    Property::new("ATTENDEE", "mailto:bob@example.com")
+        .add_parameter(first.0, first.1)
+        .add_parameter(second.0, second.1)
+        .done()
+}
+
+#[test]
+fn content_hash_ignores_dtstamp_and_parameter_order() {
+    let uploaded = Event::new()
+        .uid("a")
+        .summary("Lecture")
+        .add_property("DTSTAMP", "20240101T000000Z")
+        .append_property(attendee(("CN", "Bob"), ("ROLE", "CHAIR")))
+        .done();
+    let stored = Event::new()
+        .uid("a")
+        .summary("Lecture")
+        .add_property("DTSTAMP", "20240301T000000Z")
+        .append_property(attendee(("ROLE", "CHAIR"), ("CN", "Bob")))
+        .done();
+
+    assert_eq!(content_hash(&uploaded), content_hash(&stored));
+}
+
+#[test]
+fn mangled_and_missing_uploads_are_reported() {
+    let uploaded = vec![
+        Event::new().uid("ok").summary("Seminar").done(),
+        Event::new().uid("mangled").summary("Café").done(),
+        Event::new().uid("lost").summary("Exam").done(),
+    ];
+    let objects = stored(vec![
+        Event::new().uid("ok").summary("Seminar").done(),
+        Event::new().uid("mangled").summary("Caf?").done(),
+    ]);
+
+    assert_eq!(
+        verify_uploads(&uploaded, objects),
+        vec![
+            Discrepancy::Missing {
+                uid: "lost".to_string()
+            },
+            Discrepancy::Changed {
+                uid: "mangled".to_string(),
+                properties: vec!["SUMMARY".to_string()]
+            },
+        ]
+    );
+}
@@ -0,0 +1,117 @@
+use chrono::{TimeZone, Utc};
+use icalendar::{Calendar, Component, Event};
+use nextcloud_ics_sync::{
+    nextcloud::api::{CalendarObject, TimeRange},
+    window::{self, SyncWindow},
+};
+
+fn range() -> TimeRange {
+    TimeRange {
+        start: Some(Utc.with_ymd_and_hms(2024, 1, 8, 0, 0, 0).unwrap()),
+        end: Some(Utc.with_ymd_and_hms(2024, 1, 22, 0, 0, 0).unwrap()),
+    }
+}
+
+fn event(uid: &str, start: &str, end: &str) -> Event {
+    Event::new()
+        .uid(uid)
+        .add_property("DTSTART", start)
+        .add_property("DTEND", end)
+        .done()
+}
+
+fn uids(calendar: &Calendar) -> Vec<&str> {
+    let mut uids: Vec<&str> = calendar
+        .components
+        .iter()
+        .filter_map(|component| component.as_event())
+        .filter_map(|event| event.get_uid())
+        .collect();
+    uids.sort();
+    uids
+}
+
+#[test]
+fn events_straddling_the_edges_are_kept() {
+    let calendar: Calendar = [
+        event("before", "20240105T100000Z", "20240105T110000Z"),
+        event("into-start", "20240107T230000Z", "20240108T010000Z"),
+        event("ends-at-start", "20240107T230000Z", "20240108T000000Z"),
+        event("inside", "20240110T100000Z", "20240110T110000Z"),
+        event("around", "20240101T000000Z", "20240131T000000Z"),
+        event("over-end", "20240121T230000Z", "20240122T010000Z"),
+        event("starts-at-end", "20240122T000000Z", "20240122T010000Z"),
+        event("reminder-at-start", "20240108T000000Z", "20240108T000000Z"),
+        event("after", "20240125T100000Z", "20240125T110000Z"),
+    ]
+    .into_iter()
+    .collect();
+
+    let filtered = window::filter_calendar(calendar, &range());
+    assert_eq!(
+        uids(&filtered),
+        [
+            "around",
+            "inside",
+            "into-start",
+            "over-end",
+            "reminder-at-start"
+        ]
+    );
+}
+
+#[test]
+fn series_starting_before_the_window_are_kept() {
+    let calendar: Calendar = [
+        event("weekly", "20230102T100000Z", "20230102T110000Z")
+            .add_property("RRULE", "FREQ=WEEKLY")
+            .done(),
+        event("future-series", "20240205T100000Z", "20240205T110000Z")
+            .add_property("RRULE", "FREQ=WEEKLY")
+            .done(),
+        event("single", "20230102T100000Z", "20230102T110000Z"),
+    ]
+    .into_iter()
+    .collect();
+
+    let filtered = window::filter_calendar(calendar, &range());
+    assert_eq!(uids(&filtered), ["weekly"]);
+
+    // Servers without time range filters return the whole calendar
+    let objects = filtered
+        .components
+        .into_iter()
+        .map(|component| CalendarObject {
+            href: "event.ics".to_string(),
+            etag: None,
+            calendar: Calendar::from([component]),
+        })
+        .collect();
+    assert_eq!(window::filter_objects(objects, &range()).len(), 1);
+}
+
+#[test]
+fn unset_bounds_are_unlimited() {
+    let now = Utc.with_ymd_and_hms(2024, 1, 15, 12, 0, 0).unwrap();
+    let range = SyncWindow {
+        past_days: Some(7),
+        future_days: None,
+    }
+    .time_range(now);
+    assert_eq!(
+        range.start,
+        Some(Utc.with_ymd_and_hms(2024, 1, 8, 12, 0, 0).unwrap())
+    );
+    assert_eq!(range.end, None);
+
+    let calendar: Calendar = [
+        event("old", "20240101T100000Z", "20240101T110000Z"),
+        event("far-future", "20990101T100000Z", "20990101T110000Z"),
+    ]
+    .into_iter()
+    .collect();
+    assert_eq!(
+        uids(&window::filter_calendar(calendar, &range)),
+        ["far-future"]
+    );
+}